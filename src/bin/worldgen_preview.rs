@@ -0,0 +1,144 @@
+//! Renders world-gen previews to PNG without starting the client
+//!
+//! ```text
+//! worldgen_preview [--config gen.json] [--compare other.json] [--seed N]
+//!                  [--layer biomes|height|caves] [--x X] [--z Z]
+//!                  [--width W] [--depth D] [--scale S] [--min-y Y] [--max-y Y]
+//!                  [--threads N] [--out preview.png]
+//! ```
+//!
+//! Configs are JSON `WorldGenConfig`s holding only the fields that differ
+//! from the defaults. With `--compare`, the image holds the first config, the
+//! second and a panel marking every changed pixel.
+
+use anyhow::{bail, Context, Result};
+use bloksel::world::blocks_data::BlockRegistry;
+use bloksel::world::generator::preview::{
+    render_comparison, render_preview, PreviewLayer, PreviewRegion,
+};
+use bloksel::world::generator::terrain::{TerrainGenerator, WorldGenConfig};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Default)]
+struct Options {
+    config: Option<PathBuf>,
+    compare: Option<PathBuf>,
+    seed: Option<u64>,
+    layer: Option<PreviewLayer>,
+    region: PreviewRegion,
+    threads: Option<usize>,
+    out: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options> {
+    let mut options = Options::default();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .with_context(|| format!("Missing value for {}", flag))
+        };
+        match flag.as_str() {
+            "--config" => options.config = Some(value()?.into()),
+            "--compare" => options.compare = Some(value()?.into()),
+            "--seed" => options.seed = Some(value()?.parse()?),
+            "--layer" => {
+                options.layer = Some(match value()?.as_str() {
+                    "biomes" => PreviewLayer::Biomes,
+                    "height" => PreviewLayer::Height,
+                    "caves" => PreviewLayer::Caves,
+                    other => bail!("Unknown layer '{}'", other),
+                })
+            }
+            "--x" => options.region.x = value()?.parse()?,
+            "--z" => options.region.z = value()?.parse()?,
+            "--width" => options.region.width = value()?.parse()?,
+            "--depth" => options.region.depth = value()?.parse()?,
+            "--scale" => options.region.scale = value()?.parse()?,
+            "--min-y" => options.region.min_y = value()?.parse()?,
+            "--max-y" => options.region.max_y = value()?.parse()?,
+            "--threads" => options.threads = Some(value()?.parse()?),
+            "--out" => options.out = Some(value()?.into()),
+            other => bail!("Unknown argument '{}'", other),
+        }
+    }
+    Ok(options)
+}
+
+fn load_generator(
+    path: Option<&Path>,
+    seed: Option<u64>,
+    registry: &Arc<BlockRegistry>,
+) -> Result<TerrainGenerator> {
+    let mut config = match path {
+        Some(path) => {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let mut config = serde_json::to_value(WorldGenConfig::default())?;
+            merge(&mut config, serde_json::from_str(&json)?);
+            serde_json::from_value(config)
+                .with_context(|| format!("Invalid world-gen config {}", path.display()))?
+        }
+        None => WorldGenConfig::default(),
+    };
+    if let Some(seed) = seed {
+        config.world_seed = seed;
+    }
+    Ok(TerrainGenerator::new(config, registry.clone()))
+}
+
+/// Overlays the fields of `patch` on `base`, recursing into objects
+fn merge(base: &mut Value, patch: Value) {
+    match (base, patch) {
+        (Value::Object(base), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match base.get_mut(&key) {
+                    Some(field) => merge(field, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
+
+fn run(options: Options) -> Result<()> {
+    let registry = Arc::new(BlockRegistry::default());
+    let generator = load_generator(options.config.as_deref(), options.seed, &registry)?;
+    let layer = options.layer.unwrap_or(PreviewLayer::Height);
+    let out = options.out.unwrap_or_else(|| PathBuf::from("preview.png"));
+
+    let image = match &options.compare {
+        Some(path) => {
+            let other = load_generator(Some(path), options.seed, &registry)?;
+            let comparison = render_comparison(&generator, &other, layer, &options.region)?;
+            println!(
+                "{} of {} pixels changed ({:.1}%)",
+                comparison.changed_pixels,
+                comparison.total_pixels,
+                comparison.changed_pixels as f64 * 100.0 / comparison.total_pixels as f64
+            );
+            comparison.image
+        }
+        None => render_preview(&generator, layer, &options.region)?,
+    };
+    image
+        .save(&out)
+        .with_context(|| format!("Failed to write {}", out.display()))?;
+    println!("Wrote {}", out.display());
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let options = parse_args(std::env::args().skip(1))?;
+    match options.threads {
+        Some(threads) => rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()?
+            .install(|| run(options)),
+        None => run(options),
+    }
+}
@@ -8,6 +8,15 @@ pub struct ChunkSysConfig {
     pub unload_distance: u32,
     pub generation_threads: usize,
     pub io_threads: usize,
+    /// Generated chunks added to the world plus generation stages run on
+    /// their neighbours per frame, bounding how long integration stalls the
+    /// main thread
+    #[serde(default = "default_max_chunks_per_frame")]
+    pub max_chunks_per_frame: usize,
+}
+
+fn default_max_chunks_per_frame() -> usize {
+    4
 }
 
 impl Default for ChunkSysConfig {
@@ -19,6 +28,7 @@ impl Default for ChunkSysConfig {
             unload_distance: 12,
             generation_threads: 4,
             io_threads: 2,
+            max_chunks_per_frame: default_max_chunks_per_frame(),
         }
     }
 }
@@ -25,3 +25,26 @@ pub struct EngineConfig {
     pub chunksys: ChunkSysConfig,
     pub worldgen: WorldGenConfig,
 }
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            world_seed: 12345,
+            render_distance: 8,
+            lod_levels: [4, 8, 16],
+            chunk_size: 32,
+            texture_atlas_size: 1024,
+            max_chunk_pool_size: 1000,
+            vsync: true,
+            async_loading: true,
+            fov: 70.0,
+            view_distance: 1000.0,
+            save_interval: 300.0,
+            terrain: TerrainConfig::default(),
+            gameplay: GameplayConfig::default(),
+            rendering: RenderConfig::default(),
+            chunksys: ChunkSysConfig::default(),
+            worldgen: WorldGenConfig::default(),
+        }
+    }
+}
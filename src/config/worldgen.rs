@@ -1,4 +1,19 @@
+use crate::ui::menu::WorldType;
+use crate::world::block::DEFAULT_SUB_RESOLUTION;
+use crate::world::generator::climate::ClimateConfig;
+use crate::world::generator::density::DensityConfig;
+use crate::world::generator::elevation::ElevationConfig;
+use crate::world::generator::erosion::ErosionConfig;
+use crate::world::generator::landcover::LandCoverConfig;
+use crate::world::generator::ores::{default_ore_features, OreFeature};
+use crate::world::generator::osm::OsmConfig;
+use crate::world::generator::rivers::RiverConfig;
+use crate::world::generator::structures::{default_structure_set, StructureSet};
+use crate::world::generator::superflat::SuperflatPreset;
+use crate::world::generator::water::WaterConfig;
+use crate::world::geo::GeoProjection;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorldGenConfig {
@@ -6,9 +21,65 @@ pub struct WorldGenConfig {
     pub world_seed: u64,
     pub terrain_height: u32,
     pub water_level: u32,
+    /// Width in blocks of climate noise features
     pub biome_scale: f32,
+    /// Width in blocks of terrain noise features
     pub noise_scale: f32,
+    /// Noise layers summed into the terrain height
+    #[serde(default = "default_octaves")]
+    pub octaves: u32,
+    /// Amplitude of each octave relative to the one before
+    #[serde(default = "default_persistence")]
+    pub persistence: f32,
+    /// Frequency of each octave relative to the one before
+    #[serde(default = "default_lacunarity")]
+    pub lacunarity: f32,
+    /// Cheese-cave noise value above which stone is carved out
+    #[serde(default = "default_cave_threshold")]
+    pub cave_threshold: f32,
     pub cave_density: f32,
+    /// Ore veins placed in stone, in placement order
+    #[serde(default = "default_ore_features")]
+    pub ore_features: Vec<OreFeature>,
+    #[serde(default)]
+    pub world_type: WorldType,
+    /// Layers of superflat worlds, validated when the world is created
+    #[serde(default)]
+    pub superflat: SuperflatPreset,
+    /// Real-world elevation tiles; columns without data use noise terrain
+    #[serde(default)]
+    pub elevation: Option<ElevationConfig>,
+    /// Placement of real-world data on the block grid
+    #[serde(default)]
+    pub projection: GeoProjection,
+    /// OpenStreetMap extract drawn over the terrain
+    #[serde(default)]
+    pub osm: Option<OsmConfig>,
+    /// Land-cover rasters overriding noise biomes where they have data
+    #[serde(default)]
+    pub land_cover: Option<LandCoverConfig>,
+    /// Hydraulic and thermal erosion of the noise heightfield
+    #[serde(default)]
+    pub erosion: Option<ErosionConfig>,
+    /// Rivers from flow accumulation on the terrain height
+    #[serde(default)]
+    pub rivers: Option<RiverConfig>,
+    /// Temperature and precipitation model biomes are picked from
+    #[serde(default)]
+    pub climate: ClimateConfig,
+    /// Terrain shape as a graph of density functions, replacing the noise
+    /// heightfield
+    #[serde(default)]
+    pub density: Option<DensityConfig>,
+    /// Fluid block, lakes and aquifers
+    #[serde(default)]
+    pub water: WaterConfig,
+    /// Structure templates, pools and spawn rules
+    #[serde(default = "default_structure_set")]
+    pub structures: StructureSet,
+    /// Directory of extra structure files, merged over `structures`
+    #[serde(default)]
+    pub structure_directory: Option<PathBuf>,
 
     // World Settings
     pub world_name: String,
@@ -24,10 +95,44 @@ impl Default for WorldGenConfig {
             water_level: 50,
             biome_scale: 200.0,
             noise_scale: 10.0,
+            octaves: default_octaves(),
+            persistence: default_persistence(),
+            lacunarity: default_lacunarity(),
+            cave_threshold: default_cave_threshold(),
             cave_density: 0.3,
+            ore_features: default_ore_features(),
+            world_type: WorldType::Normal,
+            superflat: SuperflatPreset::default(),
+            elevation: None,
+            projection: GeoProjection::default(),
+            osm: None,
+            land_cover: None,
+            erosion: None,
+            rivers: None,
+            climate: ClimateConfig::default(),
+            density: None,
+            water: WaterConfig::default(),
+            structures: default_structure_set(),
+            structure_directory: None,
             world_name: String::new(),
             chunk_size: 16,
-            sub_resolution: 1,
+            sub_resolution: DEFAULT_SUB_RESOLUTION as u32,
         }
     }
 }
+
+fn default_octaves() -> u32 {
+    4
+}
+
+fn default_persistence() -> f32 {
+    0.5
+}
+
+fn default_lacunarity() -> f32 {
+    2.0
+}
+
+fn default_cave_threshold() -> f32 {
+    0.5
+}
@@ -1,33 +1,41 @@
 use crate::{
-    config::core::EngineConfig,
-    player::physics::Player,
+    config::{core::EngineConfig, worldgen::WorldGenConfig},
+    player::{mining::BreakEvent, physics::Player},
+    render::core::Camera,
     render::pipeline::ChunkRenderer,
+    ui::{hud::HUD, menu::WorldType},
+    utils::audio::{with_audio_mut, AudioError},
     world::{
+        block_id::BlockId,
         blocks_data::BlockRegistry,
-        chunk::Chunk,
+        chunk::{Chunk, Frustum},
         chunk_coord::ChunkCoord,
+        generator::superflat::SuperflatPreset,
         generator::terrain::{TerrainGenerator, WorldGenConfig as TerrainWorldGenConfig},
+        loading::{ChunkLoader, ChunkStreamer},
         pool::ChunkPool,
         spatial::SpatialPartition,
+        ticks::{MAX_TICKS_PER_FRAME, TICK_INTERVAL},
+        World,
     },
 };
 use anyhow::{Context, Result};
 use ash::vk;
 use crossbeam_channel::{bounded, Receiver, Sender};
-use glam::Vec3;
-use log::warn;
+use glam::{DVec3, I64Vec3, Vec3};
+use log::debug;
 use parking_lot::Mutex;
 use rayon::ThreadPool;
 use rayon::ThreadPoolBuilder;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
-    path::Path,
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, AtomicU64},
         Arc,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 pub struct VoxelEngine {
@@ -36,6 +44,10 @@ pub struct VoxelEngine {
     pub terrain_generator: Arc<TerrainGenerator>,
     pub chunk_renderer: Arc<ChunkRenderer>,
     pub player: Arc<Mutex<Player>>,
+    pub world: World,
+    /// Follows the player; the window sets its aspect ratio
+    pub camera: Camera,
+    pub hud: HUD,
 
     // Chunk management
     active_chunks: Arc<parking_lot::RwLock<HashMap<ChunkCoord, Arc<Chunk>>>>,
@@ -49,12 +61,15 @@ pub struct VoxelEngine {
     unload_queue: Sender<ChunkCoord>,
     load_receiver: Receiver<ChunkCoord>,
     unload_receiver: Receiver<ChunkCoord>,
+    chunk_streamer: ChunkStreamer,
 
     // State
     running: Arc<AtomicBool>,
     frame_counter: Arc<AtomicU64>,
     last_tick: Instant,
     last_save: Instant,
+    tick_accumulator: f32,
+    crack_overlay: Option<(I64Vec3, u8)>,
 
     // Configuration
     pub config: EngineConfig,
@@ -67,26 +82,7 @@ impl VoxelEngine {
     ) -> Result<Self> {
         // Initialize core systems
         let block_registry = Arc::new(BlockRegistry::default());
-        let terrain_config = TerrainWorldGenConfig {
-            world_seed: config.worldgen.world_seed,
-            terrain_height: config.worldgen.terrain_height as i32,
-            water_level: config.worldgen.water_level as i32,
-            biome_scale: config.worldgen.biome_scale as f64,
-            noise_scale: config.worldgen.noise_scale as f64,
-            octaves: 4,
-            persistence: 0.5,
-            lacunarity: 2.0,
-            height_multiplier: 1.0,
-            world_type: crate::world::generator::terrain::WorldType::Normal,
-            terrain_amplitude: 1.0,
-            cave_threshold: config.worldgen.cave_density as f64,
-            flat_world_layers: vec![
-                (crate::world::block_id::BlockId::new(1, 0, 0), 1), // Bedrock
-                (crate::world::block_id::BlockId::new(2, 0, 0), 3), // Stone
-                (crate::world::block_id::BlockId::new(3, 0, 0), 1), // Dirt
-                (crate::world::block_id::BlockId::new(4, 0, 0), 1), // Grass
-            ],
-        };
+        let terrain_config = TerrainWorldGenConfig::from(&config.worldgen);
         let terrain_generator = Arc::new(TerrainGenerator::new(
             terrain_config,
             block_registry.clone(),
@@ -97,31 +93,38 @@ impl VoxelEngine {
         let chunk_renderer = Arc::new(ChunkRenderer::new(
             &vulkan_context.device,
             vulkan_context.physical_device,
-            vulkan_context.as_ref().graphics_queue_family_index(),
+            vulkan_context.graphics_queue_family,
             block_registry.clone(),
         )?);
 
         let player = Arc::new(Mutex::new(Player::default()));
+        let world = World::new(terrain_generator.clone());
+        let camera = Camera::new(config.fov, 16.0 / 9.0, 0.1, config.view_distance);
+        let hud = HUD::new(block_registry.clone());
 
         // Setup threading
         let generation_pool = Arc::new(
             ThreadPoolBuilder::new()
-                .num_threads(4)
+                .num_threads(config.chunksys.generation_threads)
                 .build()
                 .with_context(|| "Failed to create generation pool")?,
         );
 
         let io_pool = Arc::new(
             ThreadPoolBuilder::new()
-                .num_threads(2)
+                .num_threads(config.chunksys.io_threads)
                 .build()
                 .with_context(|| "Failed to create IO pool")?,
         );
+        let chunk_streamer = ChunkStreamer::new(
+            ChunkLoader::new(generation_pool.clone(), world.pipeline().clone())
+                .with_save_dir(Self::world_dir(&config)),
+            config.chunksys.clone(),
+        );
         let (load_sender, load_receiver) = bounded(100);
         let (unload_sender, unload_receiver) = bounded(100);
 
         // Initialize chunk systems
-        let base_chunk = Arc::new(Chunk::empty());
         let chunk_pool = Arc::new(ChunkPool::new(config.max_chunk_pool_size));
         let spatial_partition = Arc::new(Mutex::new(SpatialPartition::new(&config)));
 
@@ -130,6 +133,9 @@ impl VoxelEngine {
             terrain_generator,
             chunk_renderer,
             player,
+            world,
+            camera,
+            hud,
             active_chunks: Arc::new(parking_lot::RwLock::new(HashMap::new())),
             chunk_pool,
             spatial_partition,
@@ -139,10 +145,13 @@ impl VoxelEngine {
             unload_queue: unload_sender,
             load_receiver,
             unload_receiver,
+            chunk_streamer,
             running: Arc::new(AtomicBool::new(true)),
             frame_counter: Arc::new(AtomicU64::new(0)),
             last_tick: Instant::now(),
             last_save: Instant::now(),
+            tick_accumulator: 0.0,
+            crack_overlay: None,
             config,
         })
     }
@@ -166,10 +175,13 @@ impl VoxelEngine {
         Ok(())
     }
 
-    pub fn create_world_config(&mut self, name: String, seed: u64) -> EngineConfig {
-        // Use the name parameter to avoid unused variable warning
-        let _world_name = name; // Assign to a variable to avoid unused parameter warning
-
+    pub fn create_world_config(
+        &mut self,
+        name: String,
+        seed: u64,
+        world_type: WorldType,
+        superflat: SuperflatPreset,
+    ) -> EngineConfig {
         EngineConfig {
             world_seed: seed,
             render_distance: 8,
@@ -186,8 +198,43 @@ impl VoxelEngine {
             gameplay: self.config.gameplay.clone(),
             rendering: self.config.rendering.clone(),
             chunksys: self.config.chunksys.clone(),
-            worldgen: self.config.worldgen.clone(),
+            worldgen: WorldGenConfig {
+                world_seed: seed,
+                world_name: name,
+                world_type,
+                superflat,
+                ..self.config.worldgen.clone()
+            },
+        }
+    }
+
+    /// Replaces the current world with an empty one generated from `config`,
+    /// such as the one returned by `create_world_config`
+    pub fn start_world(&mut self, config: EngineConfig) {
+        // The old world is saved through its own loader first, and the new
+        // loader may read the same directory once the saves have landed
+        self.chunk_streamer.unload_all(&mut self.world);
+        while self.chunk_streamer.loader().is_saving() {
+            std::thread::sleep(Duration::from_millis(1));
         }
+
+        self.terrain_generator = Arc::new(TerrainGenerator::new(
+            TerrainWorldGenConfig::from(&config.worldgen),
+            self.block_registry.clone(),
+        ));
+        self.world = World::new(self.terrain_generator.clone());
+        self.chunk_streamer = ChunkStreamer::new(
+            ChunkLoader::new(self.generation_pool.clone(), self.world.pipeline().clone())
+                .with_save_dir(Self::world_dir(&config)),
+            config.chunksys.clone(),
+        );
+        self.active_chunks.write().clear();
+        self.config = config;
+    }
+
+    /// Directory the chunks of the configured world are saved in
+    fn world_dir(config: &EngineConfig) -> PathBuf {
+        Path::new("worlds").join(&config.worldgen.world_name)
     }
 
     pub fn get_stats(&self) -> EngineStats {
@@ -204,7 +251,9 @@ impl VoxelEngine {
             memory_usage: 0, // TODO: Implement memory tracking
             thread_stats: ThreadPoolStats {
                 active_threads: self.generation_pool.current_num_threads(),
-                queued_tasks: self.load_receiver.len() + self.unload_receiver.len(),
+                queued_tasks: self.load_receiver.len()
+                    + self.unload_receiver.len()
+                    + self.chunk_streamer.loader().pending_count(),
             },
         }
     }
@@ -219,37 +268,107 @@ impl VoxelEngine {
         // We can't call begin_frame() directly on an Arc<ChunkRenderer>
     }
 
-    pub fn update(&mut self, _delta_time: f32) {
-        // For now, we'll just skip updating the player to make the code compile
-        // In a real implementation, we would update the player with the terrain generator and input state
-        /*
+    pub fn update(&mut self, delta_time: f32) {
+        // Block ticks run at a fixed rate independent of the frame rate
+        self.tick_accumulator += delta_time;
+        let mut ticks = 0;
+        while self.tick_accumulator >= TICK_INTERVAL {
+            if ticks == MAX_TICKS_PER_FRAME {
+                self.tick_accumulator %= TICK_INTERVAL;
+                break;
+            }
+            self.tick_accumulator -= TICK_INTERVAL;
+            self.world.tick();
+            self.last_tick = Instant::now();
+            ticks += 1;
+        }
+
         let mut player = self.player.lock();
-        player.update(delta_time, &self.terrain_generator, &input_state);
+        let input = std::mem::take(&mut player.input);
+        player.update(delta_time, &self.world, &input);
+        player.update_mining(delta_time, &mut self.world, &input);
+        player.input = input;
+        player.input.reset_frame_input();
+        let player_position = player.world_position();
+        self.camera.set_world_position(player_position);
+        self.camera.rotation = player.look_rotation();
+        let break_events: Vec<_> = player.mining.drain_events().collect();
+        drop(player);
+
+        for event in break_events {
+            self.handle_break_event(event);
+        }
 
-        // Update chunk loading based on player position
-        self.update_chunk_loading(player.position());
-        */
+        let geo_position = self
+            .world
+            .geo_projection()
+            .map(|projection| projection.to_geo(player_position));
+        self.hud.set_geo_position(geo_position);
+
+        let camera = self.camera.clone();
+        self.update_chunk_loading(player_position, Some(&camera));
+        self.process_chunk_loading();
     }
 
-    fn update_chunk_loading(&self, _player_position: Vec3) {
-        // This is a placeholder implementation to make the code compile
-        // In a real implementation, we would load and unload chunks based on player position
+    /// Block being broken and its crack stage, for the crack overlay
+    pub fn crack_overlay(&self) -> Option<(I64Vec3, u8)> {
+        self.crack_overlay
     }
 
-    fn load_chunk(&self, coord: ChunkCoord) {
-        // Send to load queue
-        if let Err(e) = self.load_queue.send(coord) {
-            warn!("Failed to queue chunk load: {:?}", e);
+    /// Forwards a block breaking event to the crack overlay and dig sounds
+    fn handle_break_event(&mut self, event: BreakEvent) {
+        match event {
+            BreakEvent::Started { position, .. } => {
+                self.crack_overlay = Some((position, 0));
+            }
+            BreakEvent::Progress {
+                position,
+                block,
+                crack_stage,
+                ..
+            } => {
+                self.crack_overlay = Some((position, crack_stage));
+                self.play_block_sound(block, "dig");
+            }
+            BreakEvent::Cancelled { .. } => {
+                self.crack_overlay = None;
+            }
+            BreakEvent::Completed { block, .. } => {
+                self.crack_overlay = None;
+                self.play_block_sound(block, "break");
+            }
         }
     }
 
-    fn unload_chunk(&self, coord: ChunkCoord) {
-        // Send to unload queue
-        if let Err(e) = self.unload_queue.send(coord) {
-            warn!("Failed to queue chunk unload: {:?}", e);
+    /// Plays `sounds/<action>/<block name>.ogg` if the audio system is running
+    fn play_block_sound(&self, block: BlockId, action: &str) {
+        let Some(definition) = self.block_registry.get_by_id(block) else {
+            return;
+        };
+        let path = Path::new("sounds")
+            .join(action)
+            .join(format!("{}.ogg", definition.name));
+        match with_audio_mut(|audio| audio.play_sfx(&path, 1.0)) {
+            Ok(_) | Err(AudioError::NotInitialized) => {}
+            Err(e) => debug!("Failed to play {}: {}", path.display(), e),
         }
     }
 
+    /// Requests the missing chunks within the load distance of the player,
+    /// nearest and visible first, and drops requests and chunks beyond the
+    /// unload distance. Without a camera every direction counts as visible.
+    pub fn update_chunk_loading(&mut self, player_position: DVec3, camera: Option<&Camera>) {
+        let view = camera.map(|camera| {
+            let view_projection = camera.projection_matrix() * camera.view_matrix();
+            (
+                Frustum::from_view_projection(&view_projection),
+                camera.origin,
+            )
+        });
+        self.chunk_streamer
+            .set_focus(&mut self.world, player_position, view);
+    }
+
     pub fn save_world(&self, _path: &Path) -> Result<()> {
         // This is a placeholder implementation to make the code compile
         // In a real implementation, we would save the world data
@@ -262,16 +381,19 @@ impl VoxelEngine {
         Ok(())
     }
 
-    // Modified process_chunk_loading to handle both generation and loading
-    pub fn process_chunk_loading(&self) {
-        // This is a placeholder implementation to make the code compile
-        // In a real implementation, we would process chunk loading and unloading
-    }
-
-    fn try_load_chunk(&self, _coord: ChunkCoord) -> Result<Option<Chunk>> {
-        // This is a placeholder implementation to make the code compile
-        // In a real implementation, we would try to load a chunk from disk
-        Ok(None)
+    /// Hands queued loads to the generation pool, cancels queued unloads
+    /// and adds at most `max_chunks_per_frame` generated chunks to the world
+    pub fn process_chunk_loading(&mut self) {
+        let loader = self.chunk_streamer.loader();
+        for coord in self.load_receiver.try_iter() {
+            if !self.world.has_chunk(&coord) {
+                loader.request(coord, self.chunk_streamer.focus().priority(coord));
+            }
+        }
+        for coord in self.unload_receiver.try_iter() {
+            loader.cancel(coord);
+        }
+        self.chunk_streamer.integrate(&mut self.world);
     }
 }
 
@@ -10,7 +10,7 @@ use bloksel::{
         menu::MenuState,
         egui_render::EguiRenderer,
     },
-    render::vulkan::VulkanContext,
+    render::vulkan::{VulkanContext, VulkanSettings},
 };
 use egui::{ClippedPrimitive, Context as EguiContext, TexturesDelta};
 use egui_winit::State as EguiWinitState;
@@ -50,6 +50,7 @@ struct App {
     current_frame: usize,
     engine: Option<VoxelEngine>,
     loading_start: Instant,
+    last_frame: Instant,
     egui_ctx: EguiContext,
     egui_winit: EguiWinitState,
     egui_renderer: EguiRenderer,
@@ -81,15 +82,14 @@ impl App {
         let window_size = (window.inner_size().width, window.inner_size().height);
 
         // Initialize Vulkan
-        let vulkan_settings = vulkan::VulkanSettings {
+        let vulkan_settings = VulkanSettings {
             application_name: "Bloksel".to_string(),
             engine_name: "Bloksel Engine".to_string(),
             enable_validation: cfg!(debug_assertions),
             ..Default::default()
         };
 
-        let vulkan_context = VulkanContext::new(vulkan_settings)?;
-        let vulkan_context = Arc::new(vulkan_context);
+        let vulkan_context = VulkanContext::new(vulkan_settings, &window)?;
 
         // Create surface
         let surface = vulkan_context.create_surface(&window)?;
@@ -121,12 +121,7 @@ impl App {
         let command_pool = vulkan_context.create_command_pool(vulkan_context.graphics_queue_family)?;
 
         // Allocate command buffers
-        let command_buffers = vulkan_context
-            .allocate_command_buffers(
-                command_pool,
-                vk::CommandBufferLevel::PRIMARY,
-                framebuffers.len() as u32,
-            )?;
+        let command_buffers = allocate_command_buffers(&vulkan_context, command_pool, framebuffers.len())?;
 
         // Create sync objects
         let max_frames_in_flight = vulkan_context.settings.max_frames_in_flight;
@@ -143,15 +138,16 @@ impl App {
         // Initialize egui
         let egui_ctx = EguiContext::default();
         let egui_winit = EguiWinitState::new(
-            event_loop.create_proxy(),
             egui_ctx.clone(),
-            egui::ViewportId::from_hash_of(window.id()),
+            egui::ViewportId::ROOT,
+            &window,
             Some(window.scale_factor() as f32),
             None,
         );
 
         // Initialize egui renderer
         let egui_renderer = EguiRenderer::new(&vulkan_context, render_pass)?;
+        let menu_state = MenuState::new(vulkan_context.clone(), render_pass, &window);
 
         Ok((
             Self {
@@ -173,10 +169,11 @@ impl App {
                 current_frame: 0,
                 engine: None,
                 loading_start: Instant::now(),
+                last_frame: Instant::now(),
                 egui_ctx,
                 egui_winit,
                 egui_renderer,
-                menu_state: MenuState::new(vulkan_context.clone(), render_pass, &window),
+                menu_state,
                 is_loading: true,
                 window_size,
             },
@@ -204,13 +201,19 @@ impl App {
                 }
                 false
             }
+            WindowEvent::MouseInput { state, button, .. } => {
+                if let Some(engine) = &self.engine {
+                    engine.player.lock().input.handle_mouse_button(*button, *state);
+                }
+                false
+            }
             _ => false,
         }
     }
 
     fn recreate_swapchain(&mut self) -> Result<()> {
         unsafe {
-            self.vulkan_context.device.wait_idle()?;
+            self.vulkan_context.device.device_wait_idle()?;
         }
 
         // Cleanup old swapchain resources
@@ -256,13 +259,8 @@ impl App {
             .collect::<Result<Vec<_>>>()?;
 
         // Reallocate command buffers
-        self.command_buffers = self
-            .vulkan_context
-            .allocate_command_buffers(
-                self.command_pool,
-                vk::CommandBufferLevel::PRIMARY,
-                self.framebuffers.len() as u32,
-            )?;
+        self.command_buffers =
+            allocate_command_buffers(&self.vulkan_context, self.command_pool, self.framebuffers.len())?;
 
         Ok(())
     }
@@ -303,11 +301,18 @@ impl App {
         }
 
         // Acquire next image
-        let (image_index, _) = self.vulkan_context.acquire_next_image(
-            self.swapchain,
-            self.image_available_semaphores[self.current_frame],
-            vk::Fence::null(),
-        )?;
+        let (image_index, _) = unsafe {
+            self.vulkan_context
+                .swapchain_loader
+                .as_ref()
+                .context("Swapchain loader not initialized")?
+                .acquire_next_image(
+                    self.swapchain,
+                    u64::MAX,
+                    self.image_available_semaphores[self.current_frame],
+                    vk::Fence::null(),
+                )?
+        };
 
         // Begin command buffer
         let command_buffer = self.command_buffers[image_index as usize];
@@ -360,7 +365,7 @@ impl App {
                     rendering: RenderConfig::default(),
                     chunksys: ChunkSysConfig::default(),
                     worldgen: WorldGenConfig::default(),
-                }) {
+                }, self.vulkan_context.clone()) {
                     Ok(engine) => {
                         info!("Engine initialized successfully");
                         self.engine = Some(engine);
@@ -376,8 +381,15 @@ impl App {
             let raw_input = self.egui_winit.take_egui_input(&self.window);
             self.egui_ctx.begin_frame(raw_input);
 
-            // Show menu
+            let now = Instant::now();
+            let delta_time = now.duration_since(self.last_frame).as_secs_f32();
+            self.last_frame = now;
+
+            // Update the running world, then show the menu or its HUD
             if let Some(engine) = &mut self.engine {
+                if self.menu_state.in_game() {
+                    engine.update(delta_time);
+                }
                 self.menu_state.show(&self.egui_ctx, engine);
             }
 
@@ -434,10 +446,10 @@ impl App {
                 .queue_present(self.vulkan_context.present_queue, &present_info)
         };
 
-        if result == Ok(vk::Result::SUBOPTIMAL_KHR) || result == Err(vk::Result::ERROR_OUT_OF_DATE_KHR) {
-            self.recreate_swapchain()?;
-        } else if let Err(e) = result {
-            return Err(anyhow::anyhow!("Failed to present swapchain image: {:?}", e));
+        match result {
+            Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.recreate_swapchain()?,
+            Ok(false) => {}
+            Err(e) => return Err(anyhow::anyhow!("Failed to present swapchain image: {:?}", e)),
         }
 
         self.current_frame = (self.current_frame + 1) % self.vulkan_context.settings.max_frames_in_flight;
@@ -479,7 +491,7 @@ impl App {
         info!("Cleaning up resources...");
 
         unsafe {
-            self.vulkan_context.device.wait_idle().unwrap();
+            self.vulkan_context.device.device_wait_idle().unwrap();
 
             // Cleanup swapchain resources
             self.cleanup_swapchain();
@@ -525,6 +537,20 @@ impl App {
     }
 }
 
+fn allocate_command_buffers(
+    vulkan_context: &VulkanContext,
+    command_pool: vk::CommandPool,
+    count: usize,
+) -> Result<Vec<vk::CommandBuffer>> {
+    let allocate_info = vk::CommandBufferAllocateInfo::builder()
+        .command_pool(command_pool)
+        .level(vk::CommandBufferLevel::PRIMARY)
+        .command_buffer_count(count as u32);
+
+    unsafe { vulkan_context.device.allocate_command_buffers(&allocate_info) }
+        .context("Failed to allocate command buffers")
+}
+
 fn main() -> Result<()> {
     let (mut app, event_loop) = App::new().context("Failed to initialize application")?;
     info!("Application initialized, starting event loop");
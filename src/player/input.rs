@@ -13,6 +13,7 @@ pub struct InputState {
     pub fly_up: bool,
     pub fly_down: bool,
     pub crouch: bool,
+    pub attack: bool,
     pub mouse_delta: (f32, f32),
     pub mouse_scroll: f32,
 }
@@ -40,6 +41,12 @@ impl InputState {
         self.mouse_delta = delta;
     }
 
+    pub fn handle_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        if button == MouseButton::Left {
+            self.attack = state == ElementState::Pressed;
+        }
+    }
+
     pub fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {
         match delta {
             MouseScrollDelta::LineDelta(_, y) => self.mouse_scroll = y,
@@ -0,0 +1,408 @@
+use crate::ui::menu::GameMode;
+use crate::world::block_id::BlockId;
+use crate::world::blocks_data::BlockRegistry;
+use glam::I64Vec3;
+use serde::{Deserialize, Serialize};
+
+/// Number of crack overlay stages shown while a block is being broken
+pub const CRACK_STAGES: u8 = 10;
+
+/// Break resistance marking a block that can never be broken outside creative mode
+pub const UNBREAKABLE_RESISTANCE: u8 = u8::MAX;
+
+/// Seconds of work per point of break resistance with bare hands
+const SECONDS_PER_RESISTANCE: f32 = 0.1;
+
+/// Time multiplier when the block needs a tool the player is not holding
+const WRONG_TOOL_PENALTY: f32 = 10.0 / 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ToolType {
+    Hand,
+    Pickaxe,
+    Shovel,
+    Axe,
+    Hoe,
+}
+
+impl ToolType {
+    /// Tag naming the blocks this tool mines efficiently
+    pub fn mineable_tag(&self) -> Option<&'static str> {
+        match self {
+            ToolType::Hand => None,
+            ToolType::Pickaxe => Some("mineable/pickaxe"),
+            ToolType::Shovel => Some("mineable/shovel"),
+            ToolType::Axe => Some("mineable/axe"),
+            ToolType::Hoe => Some("mineable/hoe"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ToolTier {
+    Wood,
+    Stone,
+    Iron,
+    Diamond,
+}
+
+impl ToolTier {
+    pub fn speed(&self) -> f32 {
+        match self {
+            ToolTier::Wood => 2.0,
+            ToolTier::Stone => 4.0,
+            ToolTier::Iron => 6.0,
+            ToolTier::Diamond => 8.0,
+        }
+    }
+
+    /// Tag listing blocks that need at least this tier to harvest
+    pub fn requirement_tag(&self) -> Option<&'static str> {
+        match self {
+            ToolTier::Wood => None,
+            ToolTier::Stone => Some("needs_tier/stone"),
+            ToolTier::Iron => Some("needs_tier/iron"),
+            ToolTier::Diamond => Some("needs_tier/diamond"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Tool {
+    pub tool_type: ToolType,
+    pub tier: ToolTier,
+}
+
+impl Tool {
+    pub fn new(tool_type: ToolType, tier: ToolTier) -> Self {
+        Self { tool_type, tier }
+    }
+
+    pub fn hand() -> Self {
+        Self::new(ToolType::Hand, ToolTier::Wood)
+    }
+
+    /// Whether this tool is the preferred tool for the block
+    pub fn is_effective(&self, registry: &BlockRegistry, block: BlockId) -> bool {
+        self.tool_type
+            .mineable_tag()
+            .is_some_and(|tag| registry.has_tag(block, tag))
+    }
+
+    /// Whether the tool is good enough to harvest the block
+    pub fn can_harvest(&self, registry: &BlockRegistry, block: BlockId) -> bool {
+        let tier_met = [ToolTier::Stone, ToolTier::Iron, ToolTier::Diamond]
+            .iter()
+            .filter(|tier| **tier > self.tier)
+            .filter_map(|tier| tier.requirement_tag())
+            .all(|tag| !registry.has_tag(block, tag));
+
+        if registry.has_tag(block, "requires_tool") {
+            self.is_effective(registry, block) && tier_met
+        } else {
+            tier_met
+        }
+    }
+}
+
+impl Default for Tool {
+    fn default() -> Self {
+        Self::hand()
+    }
+}
+
+/// Seconds needed to break `block` with `tool`, or `None` if it cannot be broken
+pub fn break_time(
+    registry: &BlockRegistry,
+    block: BlockId,
+    tool: &Tool,
+    game_mode: GameMode,
+) -> Option<f32> {
+    if game_mode == GameMode::Creative {
+        return Some(0.0);
+    }
+    if game_mode == GameMode::Adventure {
+        return None;
+    }
+
+    let resistance = registry.get_break_resistance(block);
+    if resistance == UNBREAKABLE_RESISTANCE {
+        return None;
+    }
+
+    let speed = if tool.is_effective(registry, block) {
+        tool.tier.speed()
+    } else {
+        1.0
+    };
+    let penalty = if tool.can_harvest(registry, block) {
+        1.0
+    } else {
+        WRONG_TOOL_PENALTY
+    };
+
+    Some(resistance as f32 * SECONDS_PER_RESISTANCE * penalty / speed)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BreakEvent {
+    Started {
+        position: I64Vec3,
+        block: BlockId,
+    },
+    Progress {
+        position: I64Vec3,
+        block: BlockId,
+        progress: f32,
+        crack_stage: u8,
+    },
+    Cancelled {
+        position: I64Vec3,
+        block: BlockId,
+    },
+    Completed {
+        position: I64Vec3,
+        block: BlockId,
+        harvested: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct BreakProgress {
+    position: I64Vec3,
+    block: BlockId,
+    tool: Tool,
+    elapsed: f32,
+    duration: f32,
+    crack_stage: u8,
+    harvested: bool,
+}
+
+/// Tracks the block the player is currently breaking and queues events for
+/// the renderer (crack overlay) and audio (dig sounds)
+#[derive(Debug, Default)]
+pub struct BlockBreaker {
+    current: Option<BreakProgress>,
+    events: Vec<BreakEvent>,
+}
+
+impl BlockBreaker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts breaking a block, cancelling any other break in progress. A
+    /// different block or tool at the same position restarts the break.
+    /// Returns false if the block cannot be broken in this game mode.
+    pub fn start(
+        &mut self,
+        registry: &BlockRegistry,
+        position: I64Vec3,
+        block: BlockId,
+        tool: Tool,
+        game_mode: GameMode,
+    ) -> bool {
+        if let Some(current) = &self.current {
+            if current.position == position && current.block == block && current.tool == tool {
+                return true;
+            }
+        }
+        self.cancel();
+
+        let Some(duration) = break_time(registry, block, &tool, game_mode) else {
+            return false;
+        };
+
+        self.events.push(BreakEvent::Started { position, block });
+        self.current = Some(BreakProgress {
+            position,
+            block,
+            tool,
+            elapsed: 0.0,
+            duration,
+            crack_stage: 0,
+            harvested: game_mode != GameMode::Creative && tool.can_harvest(registry, block),
+        });
+
+        // Creative mode and zero-resistance blocks break on the first frame
+        if duration <= 0.0 {
+            self.update(0.0);
+        }
+        true
+    }
+
+    /// Advances the current break. Returns the position of a block that finished breaking.
+    pub fn update(&mut self, dt: f32) -> Option<I64Vec3> {
+        let current = self.current.as_mut()?;
+        current.elapsed += dt;
+
+        let progress = if current.duration <= 0.0 {
+            1.0
+        } else {
+            (current.elapsed / current.duration).min(1.0)
+        };
+
+        if progress >= 1.0 {
+            let done = self.current.take()?;
+            self.events.push(BreakEvent::Completed {
+                position: done.position,
+                block: done.block,
+                harvested: done.harvested,
+            });
+            return Some(done.position);
+        }
+
+        let crack_stage = (progress * CRACK_STAGES as f32) as u8;
+        if crack_stage != current.crack_stage {
+            current.crack_stage = crack_stage;
+            self.events.push(BreakEvent::Progress {
+                position: current.position,
+                block: current.block,
+                progress,
+                crack_stage,
+            });
+        }
+        None
+    }
+
+    /// Stops breaking, e.g. when the button is released or the target changes
+    pub fn cancel(&mut self) {
+        if let Some(current) = self.current.take() {
+            self.events.push(BreakEvent::Cancelled {
+                position: current.position,
+                block: current.block,
+            });
+        }
+    }
+
+    pub fn target(&self) -> Option<I64Vec3> {
+        self.current.as_ref().map(|current| current.position)
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.current.as_ref().map_or(0.0, |current| {
+            if current.duration <= 0.0 {
+                1.0
+            } else {
+                (current.elapsed / current.duration).min(1.0)
+            }
+        })
+    }
+
+    pub fn crack_stage(&self) -> Option<u8> {
+        self.current.as_ref().map(|current| current.crack_stage)
+    }
+
+    pub fn drain_events(&mut self) -> impl Iterator<Item = BreakEvent> + '_ {
+        self.events.drain(..)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stone(registry: &BlockRegistry) -> BlockId {
+        registry.get_by_name("stone").unwrap().id
+    }
+
+    #[test]
+    fn test_break_time_by_tool() {
+        let registry = BlockRegistry::default();
+        let stone = stone(&registry);
+
+        let hand = break_time(&registry, stone, &Tool::hand(), GameMode::Survival).unwrap();
+        let pickaxe = Tool::new(ToolType::Pickaxe, ToolTier::Stone);
+        let with_pickaxe = break_time(&registry, stone, &pickaxe, GameMode::Survival).unwrap();
+        assert!(with_pickaxe < hand);
+
+        let diamond = Tool::new(ToolType::Pickaxe, ToolTier::Diamond);
+        let with_diamond = break_time(&registry, stone, &diamond, GameMode::Survival).unwrap();
+        assert!(with_diamond < with_pickaxe);
+
+        let water = registry.get_by_name("water").unwrap().id;
+        assert!(break_time(&registry, water, &diamond, GameMode::Survival).is_none());
+    }
+
+    #[test]
+    fn test_breaker_events() {
+        let registry = BlockRegistry::default();
+        let stone = stone(&registry);
+        let pos = I64Vec3::new(1, 2, 3);
+        let tool = Tool::new(ToolType::Pickaxe, ToolTier::Wood);
+        let duration = break_time(&registry, stone, &tool, GameMode::Survival).unwrap();
+
+        let mut breaker = BlockBreaker::new();
+        assert!(breaker.start(&registry, pos, stone, tool, GameMode::Survival));
+        assert!(breaker.update(duration * 0.55).is_none());
+        assert_eq!(breaker.crack_stage(), Some(5));
+        assert_eq!(breaker.update(duration), Some(pos));
+
+        let events: Vec<_> = breaker.drain_events().collect();
+        assert!(matches!(events[0], BreakEvent::Started { .. }));
+        assert!(matches!(
+            events[1],
+            BreakEvent::Progress { crack_stage: 5, .. }
+        ));
+        assert!(matches!(
+            events[2],
+            BreakEvent::Completed {
+                harvested: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_changed_block_restarts_break() {
+        let registry = BlockRegistry::default();
+        let stone = stone(&registry);
+        let dirt = registry.get_by_name("dirt").unwrap().id;
+        let pos = I64Vec3::ZERO;
+        let tool = Tool::hand();
+
+        let mut breaker = BlockBreaker::new();
+        breaker.start(&registry, pos, stone, tool, GameMode::Survival);
+        breaker.update(0.1);
+        assert!(breaker.start(&registry, pos, stone, tool, GameMode::Survival));
+        assert!(breaker.progress() > 0.0);
+
+        breaker.start(&registry, pos, dirt, tool, GameMode::Survival);
+        assert_eq!(breaker.progress(), 0.0);
+        let events: Vec<_> = breaker.drain_events().collect();
+        assert!(matches!(
+            events[events.len() - 2],
+            BreakEvent::Cancelled { block, .. } if block == stone
+        ));
+        assert!(matches!(
+            events[events.len() - 1],
+            BreakEvent::Started { block, .. } if block == dirt
+        ));
+    }
+
+    #[test]
+    fn test_cancel_and_creative() {
+        let registry = BlockRegistry::default();
+        let stone = stone(&registry);
+        let pos = I64Vec3::ZERO;
+
+        let mut breaker = BlockBreaker::new();
+        breaker.start(&registry, pos, stone, Tool::hand(), GameMode::Survival);
+        breaker.cancel();
+        assert!(breaker.target().is_none());
+        assert!(matches!(
+            breaker.drain_events().last(),
+            Some(BreakEvent::Cancelled { .. })
+        ));
+
+        breaker.start(&registry, pos, stone, Tool::hand(), GameMode::Creative);
+        assert!(breaker.target().is_none());
+        assert!(matches!(
+            breaker.drain_events().last(),
+            Some(BreakEvent::Completed {
+                harvested: false,
+                ..
+            })
+        ));
+    }
+}
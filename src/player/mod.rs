@@ -1,6 +1,8 @@
 pub mod input;
+pub mod mining;
 pub mod physics;
 
 pub use input::InputState;
+pub use mining::{BlockBreaker, BreakEvent, Tool, ToolTier, ToolType};
 pub use physics::Player;
 pub use physics::PlayerState;
@@ -1,9 +1,12 @@
 use crate::player::input::InputState;
+use crate::player::mining::{BlockBreaker, Tool};
+use crate::ui::menu::GameMode;
 use crate::utils::math::{AABB, Plane, ViewFrustum};
-use crate::world::block_id::BlockData;
+use crate::world::block_id::{BlockData, BlockId};
 use crate::world::block_tech::BlockPhysics;
-use crate::world::{Chunk, ChunkCoord, TerrainGenerator};
-use glam::{Mat4, Quat, Vec2, Vec3};
+use crate::world::origin::FloatingOrigin;
+use crate::world::{Chunk, ChunkCoord, World};
+use glam::{DVec3, I64Vec3, Mat4, Quat, Vec2, Vec3};
 use log::info;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
@@ -12,6 +15,9 @@ use std::sync::Arc;
 use winit::event::{ElementState, MouseScrollDelta};
 use winit::keyboard::KeyCode;
 
+/// Distance in blocks at which the player can break blocks
+const REACH: f64 = 5.0;
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub enum PlayerState {
     Normal,
@@ -25,7 +31,9 @@ pub enum PlayerState {
 #[derive(Debug)]
 pub struct Player {
     // Core properties
+    /// Position relative to `origin`; see `world_position` for the exact one
     pub position: Vec3,
+    pub origin: FloatingOrigin,
     pub velocity: Vec3,
     pub rotation: Vec2,
     pub size: Vec3,
@@ -53,12 +61,18 @@ pub struct Player {
 
     pub physics: BlockPhysics,
     pub input: InputState,
+
+    // Block breaking
+    pub game_mode: GameMode,
+    pub held_tool: Tool,
+    pub mining: BlockBreaker,
 }
 
 impl Default for Player {
     fn default() -> Self {
         Self {
             position: Vec3::new(0.0, 70.0, 0.0),
+            origin: FloatingOrigin::default(),
             velocity: Vec3::ZERO,
             rotation: Vec2::new(0.0, 0.0),
             size: Vec3::new(0.6, 1.8, 0.6),
@@ -72,24 +86,142 @@ impl Default for Player {
             zoom_level: 1.0,
             max_zoom: 2.5,
             min_zoom: 0.4,
-            chunk_size: 16, // Standard chunk size
+            chunk_size: 32, // Matches the generated chunk size
             collision_enabled: true,
             last_safe_position: Vec3::new(0.0, 70.0, 0.0),
             physics: BlockPhysics::default(),
             input: InputState::default(),
+            game_mode: GameMode::Survival,
+            held_tool: Tool::hand(),
+            mining: BlockBreaker::new(),
         }
     }
 }
 
 impl Player {
-    pub fn update(&mut self, dt: f32, terrain: &TerrainGenerator, input: &InputState) {
+    pub fn update(&mut self, dt: f32, world: &World, input: &InputState) {
         self.handle_rotation(input);
         self.handle_movement(dt, input);
         self.handle_zoom(input);
         self.apply_physics(dt);
-        self.update_position(dt, terrain);
+        self.update_position(dt, world);
         self.clamp_rotation();
         self.update_safe_position();
+        self.rebase();
+    }
+
+    /// Exact world position, valid at any distance from spawn
+    pub fn world_position(&self) -> DVec3 {
+        self.origin.to_world(self.position)
+    }
+
+    /// Teleports the player, moving the floating origin with them
+    pub fn set_world_position(&mut self, world: DVec3) {
+        self.origin = FloatingOrigin::at(world);
+        self.position = self.origin.to_local(world);
+        self.last_safe_position = self.position;
+    }
+
+    /// Breaks the targeted block while the attack button is held and removes
+    /// it from the world once done. Returns the position of the broken block;
+    /// crack and dig events queue in `mining` until the engine drains them.
+    pub fn update_mining(
+        &mut self,
+        dt: f32,
+        world: &mut World,
+        input: &InputState,
+    ) -> Option<I64Vec3> {
+        let target = if input.attack {
+            self.target_block(world)
+        } else {
+            None
+        };
+        let Some(position) = target else {
+            self.mining.cancel();
+            return None;
+        };
+
+        let block = world.block_id_at(position);
+        if !self.mining.start(
+            world.block_registry(),
+            position,
+            block,
+            self.held_tool,
+            self.game_mode,
+        ) {
+            return None;
+        }
+
+        // Creative mode breaks the block as soon as it starts
+        let broken = if self.mining.target().is_none() {
+            Some(position)
+        } else {
+            self.mining.update(dt)
+        };
+        if let Some(position) = broken {
+            world.set_block_at(position, None);
+        }
+        broken
+    }
+
+    /// Orientation of the player's view, yaw then pitch
+    pub fn look_rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.rotation.x) * Quat::from_rotation_x(self.rotation.y)
+    }
+
+    /// Unit vector the player is looking along
+    pub fn look_direction(&self) -> Vec3 {
+        self.look_rotation() * Vec3::NEG_Z
+    }
+
+    /// First non-air block within reach along the look direction
+    pub fn target_block(&self, world: &World) -> Option<I64Vec3> {
+        let origin = self.world_position();
+        let direction = self.look_direction().as_dvec3();
+        let mut block = origin.floor().as_i64vec3();
+
+        // Walk the grid one cell boundary at a time
+        let mut step = I64Vec3::ZERO;
+        let mut t_delta = DVec3::splat(f64::INFINITY);
+        let mut t_max = DVec3::splat(f64::INFINITY);
+        for axis in 0..3 {
+            if direction[axis] > 0.0 {
+                step[axis] = 1;
+                t_delta[axis] = 1.0 / direction[axis];
+                t_max[axis] = (block[axis] as f64 + 1.0 - origin[axis]) * t_delta[axis];
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                t_delta[axis] = -1.0 / direction[axis];
+                t_max[axis] = (origin[axis] - block[axis] as f64) * t_delta[axis];
+            }
+        }
+
+        let mut distance = 0.0;
+        while distance <= REACH {
+            if world.block_id_at(block) != BlockId::AIR {
+                return Some(block);
+            }
+            let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+                0
+            } else if t_max.y < t_max.z {
+                1
+            } else {
+                2
+            };
+            distance = t_max[axis];
+            t_max[axis] += t_delta[axis];
+            block[axis] += step[axis];
+        }
+        None
+    }
+
+    /// Moves the floating origin along with the player so physics and the
+    /// view matrix keep working on small `f32` positions
+    fn rebase(&mut self) {
+        if let Some(shift) = self.origin.recenter(self.position) {
+            self.position -= shift;
+            self.last_safe_position -= shift;
+        }
     }
 
     fn handle_rotation(&mut self, input: &InputState) {
@@ -274,16 +406,16 @@ impl Player {
         self.velocity = self.velocity.clamp_length_max(100.0);
     }
 
-    fn update_position(&mut self, dt: f32, terrain: &TerrainGenerator) {
+    fn update_position(&mut self, dt: f32, world: &World) {
         if self.collision_enabled && self.state != PlayerState::Spectator {
-            self.move_with_collision(dt, terrain);
+            self.move_with_collision(dt, world);
         } else {
             // Spectator mode free movement
             self.position += self.velocity * dt;
         }
     }
 
-    fn move_with_collision(&mut self, dt: f32, terrain: &TerrainGenerator) {
+    fn move_with_collision(&mut self, dt: f32, world: &World) {
         let original_position = self.position;
         let mut new_position = self.position + self.velocity * dt;
 
@@ -292,7 +424,7 @@ impl Player {
             let mut test_position = new_position;
             test_position[axis] = self.position[axis];
 
-            if self.check_collision(test_position, terrain) {
+            if self.check_collision(test_position, world) {
                 self.velocity[axis] = 0.0;
                 new_position[axis] = self.position[axis];
             }
@@ -301,7 +433,7 @@ impl Player {
         // Vertical collision handling
         if self.velocity.y != 0.0 {
             let test_position = Vec3::new(new_position.x, new_position.y, self.position.z);
-            if self.check_collision(test_position, terrain) {
+            if self.check_collision(test_position, world) {
                 self.velocity.y = 0.0;
                 self.on_ground = self.velocity.y < 0.0;
                 new_position.y = self.position.y;
@@ -311,17 +443,19 @@ impl Player {
         self.position = new_position;
 
         // Fallback to last safe position if stuck
-        if self.check_collision(self.position, terrain) {
+        if self.check_collision(self.position, world) {
             self.position = self.last_safe_position;
             self.velocity = Vec3::ZERO;
         }
     }
 
-    fn check_collision(&self, position: Vec3, terrain: &TerrainGenerator) -> bool {
-        let chunk_coord = ChunkCoord::from_world_pos(position, self.chunk_size);
-        if let Some(chunk) = terrain.get_chunk(chunk_coord) {
-            let local_pos = position - chunk_coord.to_world_pos(self.chunk_size);
-            return chunk.is_solid_at(local_pos.x as i32, local_pos.y as i32, local_pos.z as i32);
+    fn check_collision(&self, position: Vec3, world: &World) -> bool {
+        let exact = self.origin.to_world(position);
+        let chunk_coord = ChunkCoord::from_world_pos(exact, self.chunk_size);
+        if let Some(chunk) = world.get_chunk(&chunk_coord) {
+            // Sample at sub-block precision so partial blocks only collide where filled
+            let local = (exact - chunk_coord.to_world_pos(self.chunk_size)).as_vec3();
+            return chunk.is_solid_at_point(local, world.block_registry());
         }
         false
     }
@@ -461,3 +595,82 @@ impl Player {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::mining::break_time;
+    use crate::world::block::Block;
+    use crate::world::blocks_data::BlockRegistry;
+    use crate::world::TerrainGenerator;
+    use winit::event::{ElementState, MouseButton};
+
+    /// A world holding one stone block, and a player standing above it
+    /// looking straight down
+    fn stone_below_player() -> (World, Player, I64Vec3) {
+        let mut world = World::new(Arc::new(TerrainGenerator::new(
+            Default::default(),
+            Arc::new(BlockRegistry::default()),
+        )));
+        let coord = ChunkCoord::new(0, 0, 0);
+        world.set_chunk(coord, Chunk::new(coord));
+        let stone = world.block_registry().get_by_name("stone").unwrap().id;
+        let target = I64Vec3::new(4, 2, 4);
+        world.set_block_at(target, Some(Block::new(stone)));
+
+        let mut player = Player::default();
+        player.set_world_position(DVec3::new(4.5, 4.5, 4.5));
+        player.rotation = Vec2::new(0.0, -FRAC_PI_2);
+        (world, player, target)
+    }
+
+    #[test]
+    fn test_mining_removes_targeted_block() {
+        let (mut world, mut player, target) = stone_below_player();
+        let stone = world.block_id_at(target);
+        assert_eq!(player.target_block(&world), Some(target));
+        let inside = player.origin.to_local(DVec3::new(4.5, 2.5, 4.5));
+        assert!(player.check_collision(inside, &world));
+
+        let input = InputState {
+            attack: true,
+            ..Default::default()
+        };
+        let duration = break_time(
+            world.block_registry(),
+            stone,
+            &player.held_tool,
+            player.game_mode,
+        )
+        .unwrap();
+        assert!(player
+            .update_mining(duration * 0.5, &mut world, &input)
+            .is_none());
+        assert_eq!(
+            player.update_mining(duration, &mut world, &input),
+            Some(target)
+        );
+        assert!(world.block_at(target).is_none());
+        assert!(!player.check_collision(inside, &world));
+    }
+
+    #[test]
+    fn test_mouse_button_mines_in_creative() {
+        let (mut world, mut player, target) = stone_below_player();
+        player.game_mode = GameMode::Creative;
+
+        // The same path the event loop and engine update take
+        player
+            .input
+            .handle_mouse_button(MouseButton::Left, ElementState::Pressed);
+        let input = std::mem::take(&mut player.input);
+        assert_eq!(player.update_mining(0.0, &mut world, &input), Some(target));
+        assert!(world.block_at(target).is_none());
+
+        player.input = input;
+        player
+            .input
+            .handle_mouse_button(MouseButton::Left, ElementState::Released);
+        assert!(!player.input.attack);
+    }
+}
@@ -1,7 +1,11 @@
-use glam::{Mat4, Quat, Vec3};
+use crate::world::origin::FloatingOrigin;
+use glam::{DVec3, Mat4, Quat, Vec3};
 
+#[derive(Debug, Clone)]
 pub struct Camera {
+    /// Position relative to `origin`, which all render transforms share
     pub position: Vec3,
+    pub origin: FloatingOrigin,
     pub rotation: Quat,
     pub fov: f32,
     pub aspect_ratio: f32,
@@ -13,6 +17,7 @@ impl Camera {
     pub fn new(fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
         Self {
             position: Vec3::ZERO,
+            origin: FloatingOrigin::default(),
             rotation: Quat::IDENTITY,
             fov,
             aspect_ratio,
@@ -21,6 +26,16 @@ impl Camera {
         }
     }
 
+    pub fn world_position(&self) -> DVec3 {
+        self.origin.to_world(self.position)
+    }
+
+    /// Places the camera at an exact world position. The origin only moves
+    /// once the camera strays far from it, so chunk transforms stay stable.
+    pub fn set_world_position(&mut self, world: DVec3) {
+        self.position = self.origin.follow(world);
+    }
+
     pub fn view_matrix(&self) -> Mat4 {
         Mat4::from_rotation_translation(self.rotation, self.position).inverse()
     }
@@ -176,6 +176,9 @@ impl ChunkRenderer {
             descriptor_pool,
             descriptor_sets,
             command_pool,
+            draw_call_count: 0,
+            vertex_count: 0,
+            triangle_count: 0,
         })
     }
 
@@ -213,8 +216,8 @@ impl ChunkRenderer {
         };
 
         // Create pipeline layout
-        let layout_create_info =
-            vk::PipelineLayoutCreateInfo::builder().set_layouts(&[descriptor_set_layout]);
+        let set_layouts = [descriptor_set_layout];
+        let layout_create_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(&set_layouts);
 
         let pipeline_layout = unsafe {
             device
@@ -236,12 +239,12 @@ impl ChunkRenderer {
             vk::PipelineShaderStageCreateInfo::builder()
                 .stage(vk::ShaderStageFlags::VERTEX)
                 .module(vert_shader)
-                .name(b"main\0".as_ptr() as *const i8)
+                .name(std::ffi::CStr::from_bytes_with_nul(b"main\0").unwrap())
                 .build(),
             vk::PipelineShaderStageCreateInfo::builder()
                 .stage(vk::ShaderStageFlags::FRAGMENT)
                 .module(frag_shader)
-                .name(b"main\0".as_ptr() as *const i8)
+                .name(std::ffi::CStr::from_bytes_with_nul(b"main\0").unwrap())
                 .build(),
         ];
 
@@ -301,9 +304,11 @@ impl ChunkRenderer {
                 height: 1,
             });
 
+        let viewports = [viewport.build()];
+        let scissors = [scissor.build()];
         let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
-            .viewports(&[viewport.build()])
-            .scissors(&[scissor.build()]);
+            .viewports(&viewports)
+            .scissors(&scissors);
 
         let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
             .depth_clamp_enable(false)
@@ -328,10 +333,11 @@ impl ChunkRenderer {
             .blend_enable(false)
             .build();
 
+        let color_blend_attachments = [color_blend_attachment];
         let color_blending = vk::PipelineColorBlendStateCreateInfo::builder()
             .logic_op_enable(false)
             .logic_op(vk::LogicOp::COPY)
-            .attachments(&[color_blend_attachment])
+            .attachments(&color_blend_attachments)
             .blend_constants([0.0, 0.0, 0.0, 0.0]);
 
         let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::builder()
@@ -460,6 +466,11 @@ impl ChunkRenderer {
         })
     }
 
+    /// Registry whose resolved materials colour chunk meshes
+    pub fn block_registry(&self) -> &BlockRegistry {
+        &self.block_registry
+    }
+
     pub fn load_material(
         &mut self,
         block_id: u16,
@@ -548,6 +559,7 @@ impl ChunkRenderer {
     pub fn upload_textures(
         &mut self,
         device: &ash::Device,
+        instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
         command_pool: vk::CommandPool,
         queue: vk::Queue,
@@ -561,6 +573,7 @@ impl ChunkRenderer {
         let buffer_size = (width * height * 4) as vk::DeviceSize;
         let (staging_buffer, staging_buffer_memory) = Self::create_buffer(
             device,
+            instance,
             physical_device,
             buffer_size,
             vk::BufferUsageFlags::TRANSFER_SRC,
@@ -590,6 +603,7 @@ impl ChunkRenderer {
         // Create Vulkan image
         let (texture_image, texture_image_memory) = Self::create_image(
             device,
+            instance,
             physical_device,
             width,
             height,
@@ -661,6 +675,7 @@ impl ChunkRenderer {
     pub fn upload_chunk_data(
         &mut self,
         device: &ash::Device,
+        instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
         mesh: &mut ChunkMesh,
     ) -> Result<(), RenderError> {
@@ -669,6 +684,7 @@ impl ChunkRenderer {
             (mesh.vertices.len() * std::mem::size_of::<f32>()) as vk::DeviceSize;
         let (vertex_buffer, vertex_buffer_memory) = Self::create_buffer(
             device,
+            instance,
             physical_device,
             vertex_buffer_size,
             vk::BufferUsageFlags::VERTEX_BUFFER,
@@ -699,6 +715,7 @@ impl ChunkRenderer {
         let index_buffer_size = (mesh.indices.len() * std::mem::size_of::<u32>()) as vk::DeviceSize;
         let (index_buffer, index_buffer_memory) = Self::create_buffer(
             device,
+            instance,
             physical_device,
             index_buffer_size,
             vk::BufferUsageFlags::INDEX_BUFFER,
@@ -777,12 +794,12 @@ impl ChunkRenderer {
                 }
 
                 // Update uniform buffer
-                let model = chunk.transform();
+                let model = chunk.transform(&camera.origin);
                 let view = camera.view_matrix();
                 let projection = camera.projection_matrix();
 
                 let ubo = UniformBufferObject {
-                    model: chunk.transform(),
+                    model: chunk.transform(&camera.origin),
 
                     view: camera.view_matrix(),
 
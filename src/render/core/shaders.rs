@@ -398,7 +398,7 @@ impl Drop for ShaderProgram {
     fn drop(&mut self) {
         unsafe {
             for memory in &self.uniform_memories {
-                if !memory.is_null() {
+                if *memory != vk::DeviceMemory::null() {
                     self.device.unmap_memory(*memory);
                 }
             }
@@ -408,7 +408,7 @@ impl Drop for ShaderProgram {
                 self.device.free_memory(*memory, None);
             }
 
-            if !self.pipeline.is_null() {
+            if self.pipeline != vk::Pipeline::null() {
                 self.device.destroy_pipeline(self.pipeline, None);
             }
 
@@ -313,6 +313,9 @@ impl VulkanContext {
                 .collect(),
         );
 
+        let surface_loader = Surface::new(&entry, &instance);
+        let swapchain_loader = Swapchain::new(&instance, &device);
+
         Ok(Arc::new(Self {
             entry,
             instance,
@@ -326,8 +329,8 @@ impl VulkanContext {
             present_queue_family: queue_families.present,
             transfer_queue_family: queue_families.transfer,
             compute_queue_family: queue_families.compute,
-            surface_loader: None,
-            swapchain_loader: None,
+            surface_loader: Some(surface_loader),
+            swapchain_loader: Some(swapchain_loader),
             debug_utils,
             settings,
             memory_properties,
@@ -343,7 +346,7 @@ impl VulkanContext {
     }
 
     pub fn create_surface<W: HasRawWindowHandle + HasRawDisplayHandle>(
-        &mut self,
+        &self,
         window: &W,
     ) -> Result<vk::SurfaceKHR> {
         let surface = unsafe {
@@ -356,11 +359,6 @@ impl VulkanContext {
             )?
         };
 
-        // Initialize surface loader if not already initialized
-        if self.surface_loader.is_none() {
-            self.surface_loader = Some(Surface::new(&self.entry, &self.instance));
-        }
-
         // Verify surface support
         let surface_loader = self.surface_loader.as_ref().unwrap();
         let supported = unsafe {
@@ -379,7 +377,7 @@ impl VulkanContext {
     }
 
     pub fn create_swapchain(
-        &mut self,
+        &self,
         surface: vk::SurfaceKHR,
         width: u32,
         height: u32,
@@ -463,10 +461,6 @@ impl VulkanContext {
             swapchain_create_info = swapchain_create_info.queue_family_indices(&indices);
         }
 
-        // Initialize swapchain loader if not already initialized
-        if self.swapchain_loader.is_none() {
-            self.swapchain_loader = Some(Swapchain::new(&self.instance, &self.device));
-        }
         let swapchain_loader = self.swapchain_loader.as_ref().unwrap();
 
         let swapchain =
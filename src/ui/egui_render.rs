@@ -1,7 +1,7 @@
 use crate::render::vulkan::VulkanContext;
 use anyhow::{Context, Result};
 use ash::{vk, Device};
-use egui::{ClippedPrimitive, Context as EguiContext, TexturesDelta, Widget};
+use egui::{epaint::Primitive, ClippedPrimitive, Context as EguiContext, ImageData, TexturesDelta, Widget};
 use egui_winit::State as EguiWinitState;
 use log::{debug, error, info, warn};
 use std::sync::Arc;
@@ -15,8 +15,10 @@ pub struct EguiRenderer {
     index_buffer: Option<Buffer>,
     descriptor_pool: vk::DescriptorPool,
     descriptor_set_layout: vk::DescriptorSetLayout,
+    command_pool: vk::CommandPool,
 }
 
+#[derive(Debug)]
 struct FontTexture {
     image: vk::Image,
     image_view: vk::ImageView,
@@ -24,6 +26,7 @@ struct FontTexture {
     sampler: vk::Sampler,
 }
 
+#[derive(Debug)]
 struct Buffer {
     buffer: vk::Buffer,
     memory: vk::DeviceMemory,
@@ -104,6 +107,11 @@ impl EguiRenderer {
             vulkan_context.device.destroy_shader_module(frag_shader, None);
         }
 
+        // Command pool for the one-off font texture uploads
+        let command_pool = vulkan_context
+            .create_command_pool(vulkan_context.graphics_queue_family)
+            .context("Failed to create egui command pool")?;
+
         Ok(Self {
             pipeline,
             pipeline_layout,
@@ -112,6 +120,7 @@ impl EguiRenderer {
             index_buffer: None,
             descriptor_pool,
             descriptor_set_layout,
+            command_pool,
         })
     }
 
@@ -165,8 +174,9 @@ impl EguiRenderer {
                 },
             ];
 
+            let vertex_bindings = [vertex_binding];
             let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
-                .vertex_binding_descriptions(&[vertex_binding])
+                .vertex_binding_descriptions(&vertex_bindings)
                 .vertex_attribute_descriptions(&vertex_attributes);
 
             // Input assembly state
@@ -208,9 +218,10 @@ impl EguiRenderer {
                 .build();
 
             // Color blend state
+            let color_blend_attachments = [color_blend_attachment];
             let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
                 .logic_op_enable(false)
-                .attachments(&[color_blend_attachment]);
+                .attachments(&color_blend_attachments);
 
             // Dynamic state
             let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
@@ -235,6 +246,7 @@ impl EguiRenderer {
             let pipelines = vulkan_context
                 .device
                 .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
+                .map_err(|(_, e)| e)
                 .context("Failed to create graphics pipeline")?;
 
             Ok(pipelines[0])
@@ -244,7 +256,7 @@ impl EguiRenderer {
     fn upload_font_texture(
         &mut self,
         vulkan_context: &Arc<VulkanContext>,
-        font_texture: &egui::FontImage,
+        font_texture: &ImageData,
     ) -> Result<()> {
         // Cleanup old texture if exists
         if let Some(texture) = self.font_texture.take() {
@@ -258,6 +270,10 @@ impl EguiRenderer {
 
         // Create new texture
         let (width, height) = (font_texture.width() as u32, font_texture.height() as u32);
+        let pixels: Vec<egui::Color32> = match font_texture {
+            ImageData::Color(image) => image.pixels.clone(),
+            ImageData::Font(image) => image.srgba_pixels(None).collect(),
+        };
         let (image, memory) = vulkan_context
             .create_image(
                 width,
@@ -293,7 +309,7 @@ impl EguiRenderer {
 
             // Copy pixels to mapped memory
             std::ptr::copy_nonoverlapping(
-                font_texture.pixels.as_ptr() as *const u8,
+                pixels.as_ptr() as *const u8,
                 data_ptr as *mut u8,
                 (width * height * 4) as usize,
             );
@@ -304,6 +320,8 @@ impl EguiRenderer {
         // Transition image layout and copy buffer to image
         vulkan_context
             .transition_image_layout(
+                self.command_pool,
+                vulkan_context.graphics_queue,
                 image,
                 vk::Format::R8G8B8A8_UNORM,
                 vk::ImageLayout::UNDEFINED,
@@ -312,11 +330,20 @@ impl EguiRenderer {
             .context("Failed to transition image layout to TRANSFER_DST_OPTIMAL")?;
 
         vulkan_context
-            .copy_buffer_to_image(staging_buffer.0, image, width, height)
+            .copy_buffer_to_image(
+                self.command_pool,
+                vulkan_context.graphics_queue,
+                staging_buffer.0,
+                image,
+                width,
+                height,
+            )
             .context("Failed to copy buffer to image")?;
 
         vulkan_context
             .transition_image_layout(
+                self.command_pool,
+                vulkan_context.graphics_queue,
                 image,
                 vk::Format::R8G8B8A8_UNORM,
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
@@ -417,8 +444,8 @@ impl EguiRenderer {
             let (buffer, memory) = vulkan_context
                 .create_buffer(
                     vertex_buffer_size,
-                    vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    vk::BufferUsageFlags::VERTEX_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
                 )
                 .context("Failed to create vertex buffer")?;
                 
@@ -427,18 +454,17 @@ impl EguiRenderer {
 
         // Upload vertex data
         if let Some(buffer) = &self.vertex_buffer {
-            vulkan_context
-                .copy_to_device_local_buffer(
-                    buffer.buffer,
-                    buffer.memory,
-                    unsafe {
-                        std::slice::from_raw_parts(
-                            vertices.as_ptr() as *const u8,
-                            vertex_buffer_size as usize,
-                        )
-                    },
-                )
-                .context("Failed to copy vertex data to buffer")?;
+            Self::write_buffer(
+                vulkan_context,
+                buffer.memory,
+                unsafe {
+                    std::slice::from_raw_parts(
+                        vertices.as_ptr() as *const u8,
+                        vertex_buffer_size as usize,
+                    )
+                },
+            )
+            .context("Failed to copy vertex data to buffer")?;
         }
 
         Ok(())
@@ -472,8 +498,8 @@ impl EguiRenderer {
             let (buffer, memory) = vulkan_context
                 .create_buffer(
                     index_buffer_size,
-                    vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    vk::BufferUsageFlags::INDEX_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
                 )
                 .context("Failed to create index buffer")?;
                 
@@ -482,25 +508,42 @@ impl EguiRenderer {
 
         // Upload index data
         if let Some(buffer) = &self.index_buffer {
-            vulkan_context
-                .copy_to_device_local_buffer(
-                    buffer.buffer,
-                    buffer.memory,
-                    unsafe {
-                        std::slice::from_raw_parts(
-                            indices.as_ptr() as *const u8,
-                            index_buffer_size as usize,
-                        )
-                    },
-                )
-                .context("Failed to copy index data to buffer")?;
+            Self::write_buffer(
+                vulkan_context,
+                buffer.memory,
+                unsafe {
+                    std::slice::from_raw_parts(
+                        indices.as_ptr() as *const u8,
+                        index_buffer_size as usize,
+                    )
+                },
+            )
+            .context("Failed to copy index data to buffer")?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies `data` into host-visible buffer memory
+    fn write_buffer(
+        vulkan_context: &Arc<VulkanContext>,
+        memory: vk::DeviceMemory,
+        data: &[u8],
+    ) -> Result<()> {
+        unsafe {
+            let data_ptr = vulkan_context
+                .device
+                .map_memory(memory, 0, data.len() as vk::DeviceSize, vk::MemoryMapFlags::empty())
+                .context("Failed to map buffer memory")?;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), data_ptr as *mut u8, data.len());
+            vulkan_context.device.unmap_memory(memory);
         }
 
         Ok(())
     }
 
     pub fn render(
-        &self,
+        &mut self,
         vulkan_context: &Arc<VulkanContext>,
         command_buffer: vk::CommandBuffer,
         primitives: &[ClippedPrimitive],
@@ -510,28 +553,40 @@ impl EguiRenderer {
     ) -> Result<()> {
         // Update font texture if needed
         for (texture_id, image_delta) in &textures.set {
-            if texture_id == &egui::TextureId::default() {
-                if let Some(font_texture) = &image_delta.image {
-                    self.upload_font_texture(vulkan_context, font_texture)?;
-                }
+            // Partial updates (`pos` set) patch a region we don't track, so only full uploads apply
+            if texture_id == &egui::TextureId::default() && image_delta.pos.is_none() {
+                self.upload_font_texture(vulkan_context, &image_delta.image)?;
             }
         }
 
+        // Paint callbacks have no geometry of their own
+        let meshes: Vec<_> = primitives
+            .iter()
+            .filter_map(|clipped_primitive| match &clipped_primitive.primitive {
+                Primitive::Mesh(mesh) => Some((clipped_primitive.clip_rect, mesh)),
+                Primitive::Callback(_) => None,
+            })
+            .collect();
+
         // Process vertices and indices
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
         
-        for clipped_primitive in primitives {
+        for (_, mesh) in &meshes {
             let vertex_offset = vertices.len();
-            vertices.extend_from_slice(&clipped_primitive.primitive.vertices);
+            vertices.extend_from_slice(&mesh.vertices);
             
             // Adjust indices for the current vertex offset
             indices.extend(
-                clipped_primitive.primitive.indices
+                mesh.indices
                     .iter()
                     .map(|&idx| idx + vertex_offset as u32)
             );
         }
+
+        if indices.is_empty() {
+            return Ok(());
+        }
         
         self.update_buffers(vulkan_context, &vertices, &indices)?;
 
@@ -583,8 +638,7 @@ impl EguiRenderer {
 
         // Draw primitives
         let mut index_offset = 0;
-        for clipped_primitive in primitives {
-            let clip_rect = clipped_primitive.clip_rect;
+        for (clip_rect, mesh) in &meshes {
             
             // Ensure scissor coordinates are within framebuffer bounds
             let min_x = clip_rect.min.x.max(0.0) as i32;
@@ -594,7 +648,7 @@ impl EguiRenderer {
             
             // Skip drawing if scissor rectangle is invalid
             if min_x >= max_x || min_y >= max_y {
-                index_offset += clipped_primitive.primitive.indices.len() as u32;
+                index_offset += mesh.indices.len() as u32;
                 continue;
             }
             
@@ -634,7 +688,7 @@ impl EguiRenderer {
             unsafe {
                 vulkan_context.device.cmd_draw_indexed(
                     command_buffer,
-                    clipped_primitive.primitive.indices.len() as u32,
+                    mesh.indices.len() as u32,
                     1,
                     index_offset,
                     0,
@@ -642,7 +696,7 @@ impl EguiRenderer {
                 );
             }
 
-            index_offset += clipped_primitive.primitive.indices.len() as u32;
+            index_offset += mesh.indices.len() as u32;
         }
 
         Ok(())
@@ -712,6 +766,7 @@ impl EguiRenderer {
             }
 
             // Clean up Vulkan resources
+            vulkan_context.device.destroy_command_pool(self.command_pool, None);
             vulkan_context.device.destroy_descriptor_pool(self.descriptor_pool, None);
             vulkan_context.device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
             vulkan_context.device.destroy_pipeline(self.pipeline, None);
@@ -1,7 +1,7 @@
 use std::path::{Path, PathBuf};
 use crate::{
-    ui::world::WorldMeta,
-    render::vulkan::{VulkanContext, vk}
+    world::WorldMeta,
+    render::vulkan::VulkanContext
 };
 use egui::{Color32, Ui, Button, Response, Layout, Align, Spinner, ProgressBar, Window, Frame};
 use ash::vk;
@@ -13,6 +13,7 @@ pub fn button(ui: &mut Ui, text: &str) -> Response {
     ui.add_sized(
         [200.0, 40.0],
         Button::new(text).fill(Color32::from_rgb(40, 40, 40))
+    )
 }
 
 /// Creates a small secondary button
@@ -50,7 +51,7 @@ pub fn loading_spinner(ui: &mut Ui, current_task: &str, progress: f32) {
 }
 
 /// Creates a standardized window with consistent styling
-pub fn standard_window(ui: &Ui, title: &str) -> Window {
+pub fn standard_window(title: &str) -> Window<'static> {
     Window::new(title)
         .collapsible(false)
         .resizable(false)
@@ -91,7 +92,8 @@ pub fn load_saved_worlds() -> Vec<WorldMeta> {
 }
 
 fn get_worlds_dir() -> PathBuf {
-    let mut dir = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
+    let mut dir = directories::BaseDirs::new()
+        .map_or_else(|| PathBuf::from("."), |dirs| dirs.data_dir().to_path_buf());
     dir.push("Bloksel");
     dir.push("worlds");
     dir
@@ -128,7 +130,9 @@ pub mod vulkan {
         let alloc_info = vk::MemoryAllocateInfo::builder()
             .allocation_size(mem_requirements.size)
             .memory_type_index(
-                context.find_memory_type(mem_requirements.memory_type_bits, properties)?
+                context
+                    .find_memory_type(mem_requirements.memory_type_bits, properties)
+                    .ok_or_else(|| anyhow::anyhow!("No suitable memory type"))?
             );
 
         let buffer_memory = unsafe {
@@ -173,7 +177,9 @@ pub mod vulkan {
         let alloc_info = vk::MemoryAllocateInfo::builder()
             .allocation_size(mem_requirements.size)
             .memory_type_index(
-                context.find_memory_type(mem_requirements.memory_type_bits, properties)?
+                context
+                    .find_memory_type(mem_requirements.memory_type_bits, properties)
+                    .ok_or_else(|| anyhow::anyhow!("No suitable memory type"))?
             );
 
         let image_memory = unsafe {
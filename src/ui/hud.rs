@@ -1,23 +1,36 @@
 use egui::{Context, TopBottomPanel};
-use crate::world::BlockRegistry;
+use crate::world::{blocks_data::BlockRegistry, GeoPosition};
+use std::sync::Arc;
 
 pub struct HUD {
-    block_registry: BlockRegistry,
+    block_registry: Arc<BlockRegistry>,
+    geo_position: Option<GeoPosition>,
 }
 
 impl HUD {
-    pub fn new(block_registry: BlockRegistry) -> Self {
+    pub fn new(block_registry: Arc<BlockRegistry>) -> Self {
         Self {
             block_registry,
+            geo_position: None,
         }
     }
 
+    /// Sets the player's geographic position, shown while the world is
+    /// placed on real-world coordinates
+    pub fn set_geo_position(&mut self, position: Option<GeoPosition>) {
+        self.geo_position = position;
+    }
+
     pub fn show(&mut self, ctx: &Context) {
         TopBottomPanel::top("hud").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.label("Selected Block: None");
                 ui.separator();
                 ui.label("FPS: 60");
+                if let Some(position) = &self.geo_position {
+                    ui.separator();
+                    ui.label(position.to_string());
+                }
             });
         });
     }
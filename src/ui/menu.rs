@@ -2,7 +2,10 @@ use crate::{
     render::vulkan::VulkanContext,
     ui::{egui_render::EguiRenderer, helpers},
     VoxelEngine, 
-    world::WorldMeta,
+    world::{
+        generator::superflat::{SuperflatError, SuperflatPreset, DEFAULT_SUPERFLAT_PRESET},
+        WorldMeta,
+    },
     config::{core::EngineConfig, worldgen::WorldGenConfig}
 };
 use ash::vk;
@@ -12,7 +15,7 @@ use egui::{
     Color32, ProgressBar, Label, ScrollArea, SelectableLabel, Widget
 };
 use egui_winit::State as EguiWinitState;
-use std::{path::PathBuf, sync::Arc};
+use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -23,6 +26,8 @@ pub enum MenuScreen {
     Settings,
     Credits,
     Loading,
+    /// A world is running; only the HUD is drawn
+    InGame,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -72,15 +77,25 @@ pub struct CreateWorldState {
     pub difficulty: Difficulty,
     pub game_mode: GameMode,
     pub seed: String,
+    /// Superflat preset text; blank uses the default preset
+    pub superflat_preset: String,
+}
+
+impl CreateWorldState {
+    fn superflat(&self) -> Result<SuperflatPreset, SuperflatError> {
+        match self.superflat_preset.trim() {
+            "" => Ok(SuperflatPreset::default()),
+            preset => preset.parse(),
+        }
+    }
 }
 
-#[derive(Debug)]
 pub struct MenuState {
     current_screen: MenuScreen,
     create_world_state: CreateWorldState,
     selected_world: Option<WorldMeta>,
     worlds_list: Vec<WorldMeta>,
-    egui_renderer: Option<Arc<EguiRenderer>>,
+    egui_renderer: Option<EguiRenderer>,
     egui_context: Context,
     egui_winit_state: EguiWinitState,
 }
@@ -90,13 +105,11 @@ impl MenuState {
         let egui_context = Context::default();
         let egui_winit_state = EguiWinitState::new(
             egui_context.clone(),
-
-            egui::ViewportId::ROOT, 
-            window, 
-            None, 
-            None, 
-            None
-            );
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            None,
+        );
         
         let egui_renderer = EguiRenderer::new(&vulkan_context, render_pass)
             .expect("Failed to create egui renderer");
@@ -106,26 +119,14 @@ impl MenuState {
             create_world_state: CreateWorldState::default(),
             selected_world: None,
             worlds_list: Vec::new(),
-            egui_renderer: Some(Arc::new(egui_renderer)),
+            egui_renderer: Some(egui_renderer),
             egui_context,
             egui_winit_state,
         }
     }
 
-    pub fn dummy() -> Self {
-        Self {
-            current_screen: MenuScreen::Main,
-            create_world_state: CreateWorldState::default(),
-            selected_world: None,
-            worlds_list: Vec::new(),
-            egui_renderer: None,
-            egui_context: Context::default(),
-            egui_winit_state: EguiWinitState::new(0, 0, 1.0),
-        }
-    }
-
-    pub fn handle_event(&mut self, event: &winit::event::WindowEvent) -> bool {
-        self.egui_winit_state.on_event(&self.egui_context, event).consumed
+    pub fn handle_event(&mut self, window: &winit::window::Window, event: &winit::event::WindowEvent) -> bool {
+        self.egui_winit_state.on_window_event(window, event).consumed
     }
 
     pub fn update(&mut self, window: &winit::window::Window) {
@@ -140,17 +141,11 @@ impl MenuState {
         viewport_width: u32,
         viewport_height: u32,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        self.egui_winit_state.set_pixels_per_point(1.0);
-
-        if self.egui_renderer.is_none() {
-            self.show(&self.egui_context, &mut VoxelEngine::dummy());
-        }
-
         let full_output = self.egui_context.end_frame();
-        let clipped_primitives = self.egui_context.tessellate(full_output.shapes);
+        let clipped_primitives = self.egui_context.tessellate(full_output.shapes, full_output.pixels_per_point);
         let textures_delta = full_output.textures_delta;
 
-        if let Some(renderer) = &self.egui_renderer {
+        if let Some(renderer) = &mut self.egui_renderer {
             renderer.render(
                 vulkan_context,
                 command_buffer,
@@ -164,6 +159,11 @@ impl MenuState {
         Ok(())
     }
 
+    /// Whether a world is running, so the engine should be updated
+    pub fn in_game(&self) -> bool {
+        self.current_screen == MenuScreen::InGame
+    }
+
     pub fn show(&mut self, ctx: &Context, engine: &mut VoxelEngine) {
         match self.current_screen {
             MenuScreen::Main => self.show_main_menu(ctx),
@@ -172,6 +172,7 @@ impl MenuState {
             MenuScreen::Settings => self.show_settings(ctx),
             MenuScreen::Credits => self.show_credits(ctx),
             MenuScreen::Loading => self.show_loading_screen(ctx),
+            MenuScreen::InGame => engine.hud.show(ctx),
         }
 
         self.handle_transitions(engine);
@@ -223,7 +224,7 @@ impl MenuState {
     }
 
     fn show_load_world(&mut self, ctx: &Context) {
-        helpers::standard_window(ctx, "Load World")
+        helpers::standard_window("Load World")
             .default_size([400.0, 500.0])
             .show(ctx, |ui| {
                 ui.heading("Select a World");
@@ -247,16 +248,14 @@ impl MenuState {
                 ui.add_space(20.0);
                 
                 ui.horizontal(|ui| {
-                    let btn_width = ui.available_width() / 3.0 - 10.0;
-                    
-                    if ui.add_sized([btn_width, 30.0], helpers::small_button(ui, "Play Selected")).clicked() 
+                    if helpers::small_button(ui, "Play Selected").clicked() 
                         && self.selected_world.is_some() {
                         self.current_screen = MenuScreen::Loading;
                     }
                     
                     ui.add_space(10.0);
                     
-                    if ui.add_sized([btn_width, 30.0], helpers::small_button(ui, "Delete")).clicked() {
+                    if helpers::small_button(ui, "Delete").clicked() {
                         if let Some(world) = &self.selected_world {
                             helpers::delete_world(&world.name);
                             self.scan_for_worlds();
@@ -265,7 +264,7 @@ impl MenuState {
                     
                     ui.add_space(10.0);
                     
-                    if ui.add_sized([btn_width, 30.0], helpers::small_button(ui, "Back")).clicked() {
+                    if helpers::small_button(ui, "Back").clicked() {
                         self.current_screen = MenuScreen::Main;
                     }
                 });
@@ -273,7 +272,7 @@ impl MenuState {
     }
 
     fn show_create_world(&mut self, ctx: &Context) {
-        helpers::standard_window(ctx, "Create New World")
+        helpers::standard_window("Create New World")
             .default_size([450.0, 350.0])
             .show(ctx, |ui| {
                 Grid::new("world_settings")
@@ -313,6 +312,16 @@ impl MenuState {
                             });
                         ui.end_row();
 
+                        if self.create_world_state.world_type == WorldType::Superflat {
+                            ui.label("Superflat Preset:");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.create_world_state.superflat_preset)
+                                    .hint_text(DEFAULT_SUPERFLAT_PRESET),
+                            )
+                            .on_hover_text("Layers from the bottom up, e.g. 1*stone,3*dirt,grass;plains");
+                            ui.end_row();
+                        }
+
                         ui.label("Difficulty:");
                         ComboBox::new("difficulty", "")
                             .selected_text(format!("{:?}", self.create_world_state.difficulty))
@@ -371,17 +380,25 @@ impl MenuState {
                     ui.add_space(10.0);
                 }
 
+                let preset_error = match self.create_world_state.world_type {
+                    WorldType::Superflat => self.create_world_state.superflat().err(),
+                    _ => None,
+                };
+                if let Some(e) = &preset_error {
+                    ui.colored_label(Color32::from_rgb(255, 100, 100), format!("⚠ {}", e));
+                    ui.add_space(10.0);
+                }
+
                 ui.horizontal(|ui| {
-                    let button_width = ui.available_width() / 2.0 - 5.0;
-                    
-                    if ui.add_sized([button_width, 30.0], helpers::small_button(ui, "Create"))
-                        .clicked() && !self.create_world_state.name.trim().is_empty() {
+                    if helpers::small_button(ui, "Create")
+                        .clicked() && !self.create_world_state.name.trim().is_empty()
+                        && preset_error.is_none() {
                         self.current_screen = MenuScreen::Loading;
                     }
                     
                     ui.add_space(10.0);
                     
-                    if ui.add_sized([button_width, 30.0], helpers::small_button(ui, "Cancel")).clicked() {
+                    if helpers::small_button(ui, "Cancel").clicked() {
                         self.current_screen = MenuScreen::Main;
                     }
                 });
@@ -389,7 +406,7 @@ impl MenuState {
     }
 
     fn show_settings(&mut self, ctx: &Context) {
-        helpers::standard_window(ctx, "Settings")
+        helpers::standard_window("Settings")
             .default_size([500.0, 400.0])
             .show(ctx, |ui| {
                 ui.heading("Game Settings");
@@ -452,7 +469,7 @@ impl MenuState {
     }
 
     fn show_credits(&mut self, ctx: &Context) {
-        helpers::standard_window(ctx, "Credits")
+        helpers::standard_window("Credits")
             .default_size([500.0, 400.0])
             .show(ctx, |ui| {
                 ui.vertical_centered(|ui| {
@@ -512,11 +529,23 @@ impl MenuState {
     fn handle_transitions(&mut self, engine: &mut VoxelEngine) {
         match self.current_screen {
             MenuScreen::Loading => {
-                if let Some(selected_world) = &self.selected_world {
-                    if let Err(e) = engine.load_world(&PathBuf::from(format!("worlds/{}", selected_world.name))) {
-                        log::error!("Failed to load world: {}", e);
+                if let Some(meta) = &self.selected_world {
+                    // Saved chunks are read back from the world directory as
+                    // they stream in; the rest generates from the saved settings
+                    let superflat = meta.superflat.clone().unwrap_or_default();
+                    if let Err(e) = superflat.resolve(&engine.block_registry) {
+                        log::error!("Invalid superflat preset of {}: {}", meta.name, e);
                         self.current_screen = MenuScreen::LoadWorld;
+                        return;
                     }
+                    let config = engine.create_world_config(
+                        meta.name.clone(),
+                        meta.seed as u64,
+                        meta.world_type,
+                        superflat,
+                    );
+                    engine.start_world(config);
+                    self.current_screen = MenuScreen::InGame;
                 } else {
                     let seed = match self.create_world_state.seed.parse::<u64>() {
                         Ok(s) => s,
@@ -530,15 +559,39 @@ impl MenuState {
                         }
                     };
                     
-                    let config = engine.create_world_config(
-                        self.create_world_state.name.clone(),
-                        seed,
-                    );
-                    
-                    if let Err(e) = engine.load_world(&PathBuf::from(format!("worlds/{}", self.create_world_state.name))) {
+                    // The create screen only checks the syntax; block names are checked here
+                    let superflat = match self.create_world_state.superflat().and_then(|preset| {
+                        preset.resolve(&engine.block_registry).map(|_| preset)
+                    }) {
+                        Ok(preset) => preset,
+                        Err(e) => {
+                            log::error!("Invalid superflat preset: {}", e);
+                            self.current_screen = MenuScreen::CreateWorld;
+                            return;
+                        }
+                    };
+                    let world_type = self.create_world_state.world_type;
+                    let meta = WorldMeta {
+                        name: self.create_world_state.name.clone(),
+                        world_type,
+                        superflat: (world_type == WorldType::Superflat).then(|| superflat.clone()),
+                        seed: seed as i64,
+                        difficulty: self.create_world_state.difficulty,
+                        spawn_point: (0.0, 0.0, 0.0).into(),
+                        last_played: std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .map_or(0, |elapsed| elapsed.as_secs() as i64),
+                    };
+                    if let Err(e) = helpers::save_world(&meta) {
                         log::error!("Failed to create world: {}", e);
                         self.current_screen = MenuScreen::CreateWorld;
+                        return;
                     }
+
+                    let config = engine.create_world_config(meta.name, seed, world_type, superflat);
+                    engine.start_world(config);
+                    engine.player.lock().game_mode = self.create_world_state.game_mode;
+                    self.current_screen = MenuScreen::InGame;
                 }
                 
                 // Reset create world state after loading
@@ -559,6 +612,7 @@ impl MenuState {
                     difficulty: Difficulty::Normal,
                     spawn_point: (0.0, 0.0, 0.0).into(),
                     world_type: WorldType::Normal,
+                    superflat: None,
                     seed: 12345,
                     last_played: 0,
                 },
@@ -567,6 +621,7 @@ impl MenuState {
                     difficulty: Difficulty::Peaceful,
                     spawn_point: (0.0, 0.0, 0.0).into(),
                     world_type: WorldType::Superflat,
+                    superflat: Some(SuperflatPreset::default()),
                     seed: 67890,
                     last_played: 0,
                 }
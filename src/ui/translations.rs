@@ -209,7 +209,7 @@ mod tests {
         {{
             "translations": {{
                 "greeting": "Hello",
-                "welcome": "Welcome, {name}",
+                "welcome": "Welcome, {{name}}",
                 "menu.save": "Save",
                 "menu.save.world": "Save World"
             }}
@@ -221,7 +221,7 @@ mod tests {
         {{
             "translations": {{
                 "greeting": "Hola",
-                "welcome": "Bienvenido, {name}",
+                "welcome": "Bienvenido, {{name}}",
                 "menu.save": "Guardar",
                 "menu.save.world": "Guardar Mundo"
             }}
@@ -5,10 +5,43 @@ use crate::world::block_orientation::BlockOrientation;
 use crate::world::block_tech::BlockPhysics;
 use crate::world::block_visual::ConnectedDirections;
 use crate::world::blocks_data::BlockRegistry;
+use glam::{IVec3, Vec3};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Sub-blocks per block edge. Two cells per axis gives the 1/8 cube detail:
+/// every block splits into 2×2×2 sub-blocks of one eighth of its volume.
+pub const DEFAULT_SUB_RESOLUTION: u8 = 2;
+
+/// Finest sub-block grid a world may use
+pub const MAX_SUB_RESOLUTION: u8 = 16;
+
+/// Edge length of a single sub-block, in blocks
+pub fn sub_block_size(resolution: u8) -> f32 {
+    1.0 / resolution.max(1) as f32
+}
+
+/// Splits a fractional world position into the containing block position and
+/// the sub-block cell inside that block
+pub fn split_sub_block_pos(world_pos: Vec3, resolution: u8) -> (IVec3, (u8, u8, u8)) {
+    let resolution = resolution.max(1);
+    let block_pos = world_pos.floor();
+    let local = (world_pos - block_pos) * resolution as f32;
+    let cell = |v: f32| (v.floor() as i32).clamp(0, resolution as i32 - 1) as u8;
+
+    (
+        block_pos.as_ivec3(),
+        (cell(local.x), cell(local.y), cell(local.z)),
+    )
+}
+
+/// World-space minimum corner of a sub-block cell
+pub fn sub_block_origin(block_pos: IVec3, sub_pos: (u8, u8, u8), resolution: u8) -> Vec3 {
+    let size = sub_block_size(resolution);
+    block_pos.as_vec3() + Vec3::new(sub_pos.0 as f32, sub_pos.1 as f32, sub_pos.2 as f32) * size
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
     pub id: BlockId,
@@ -82,6 +115,20 @@ impl Block {
         self.sub_blocks.insert(pos, sub_block);
     }
 
+    /// Places a sub-block, rejecting cells outside the given resolution
+    pub fn try_place_sub_block(
+        &mut self,
+        pos: (u8, u8, u8),
+        sub_block: SubBlock,
+        resolution: u8,
+    ) -> bool {
+        if pos.0 >= resolution || pos.1 >= resolution || pos.2 >= resolution {
+            return false;
+        }
+        self.place_sub_block(pos, sub_block);
+        true
+    }
+
     pub fn remove_sub_block(&mut self, pos: &(u8, u8, u8)) -> Option<SubBlock> {
         self.sub_blocks.remove(pos)
     }
@@ -103,7 +150,13 @@ impl Block {
     }
 
     pub fn is_solid(&self) -> bool {
-        self.get_physics(&BlockRegistry::default()).solid
+        self.get_physics(BlockRegistry::builtin()).solid
+    }
+
+    /// Whether the sub-block cell at `pos` is occupied by a solid sub-block
+    pub fn is_sub_block_solid(&self, pos: &(u8, u8, u8), registry: &BlockRegistry) -> bool {
+        self.get_sub_block(pos)
+            .is_some_and(|sub| sub.is_solid(registry))
     }
 }
 
@@ -147,4 +200,43 @@ impl SubBlock {
     pub fn set_orientation(&mut self, orientation: BlockOrientation) {
         self.orientation = orientation;
     }
+
+    pub fn is_solid(&self, registry: &BlockRegistry) -> bool {
+        registry.get_block_physics(BlockId(self.id)).solid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_sub_block_pos() {
+        let (block, sub) = split_sub_block_pos(Vec3::new(1.25, 2.75, 3.5), 2);
+        assert_eq!(block, IVec3::new(1, 2, 3));
+        assert_eq!(sub, (0, 1, 1));
+
+        let (block, sub) = split_sub_block_pos(Vec3::new(-0.25, -1.75, 0.0), 2);
+        assert_eq!(block, IVec3::new(-1, -2, 0));
+        assert_eq!(sub, (1, 0, 0));
+    }
+
+    #[test]
+    fn test_sub_block_origin_round_trip() {
+        let resolution = DEFAULT_SUB_RESOLUTION;
+        let origin = sub_block_origin(IVec3::new(4, -3, 7), (1, 0, 1), resolution);
+        assert_eq!(origin, Vec3::new(4.5, -3.0, 7.5));
+
+        let (block, sub) = split_sub_block_pos(origin, resolution);
+        assert_eq!(block, IVec3::new(4, -3, 7));
+        assert_eq!(sub, (1, 0, 1));
+    }
+
+    #[test]
+    fn test_try_place_sub_block_bounds() {
+        let mut block = Block::new(BlockId::new(1, 0, 0));
+        assert!(block.try_place_sub_block((1, 1, 1), SubBlock::new(1), 2));
+        assert!(!block.try_place_sub_block((2, 0, 0), SubBlock::new(1), 2));
+        assert_eq!(block.sub_blocks.len(), 1);
+    }
 }
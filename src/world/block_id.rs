@@ -1,6 +1,7 @@
 use crate::world::block::Block;
 use crate::world::block_error::BlockError;
 use crate::world::block_facing::BlockFacing;
+use crate::world::block_flags::BlockFlags as BlockProperties;
 use crate::world::block_material::{BlockMaterial, MaterialModifiers, TintSettings};
 use crate::world::block_orientation::BlockOrientation;
 use crate::world::block_tech::{BlockFlags, BlockPhysics};
@@ -34,9 +35,18 @@ pub enum BlockCategory {
 pub struct BlockId(pub u16);
 
 impl BlockId {
+    /// Packs the base ID into the high byte and the variation and colour
+    /// into the two low nibbles, matching `base_id`/`variation`/`color_id`.
+    /// Base IDs above 255 and nibbles above 15 don't fit.
     pub fn new(base_id: u16, variation: u8, color_id: u8) -> Self {
-        let combined = ((base_id as u32) << 16) | ((variation as u32) << 8) | (color_id as u32);
-        Self((combined & 0xFFFF) as u16)
+        debug_assert!(
+            base_id <= 0xFF && variation <= 0xF && color_id <= 0xF,
+            "BlockId fields out of range: {}:{}:{}",
+            base_id,
+            variation,
+            color_id
+        );
+        Self(((base_id & 0xFF) << 8) | (((variation & 0xF) as u16) << 4) | (color_id & 0xF) as u16)
     }
 
     pub fn base_id(&self) -> u16 {
@@ -62,11 +72,11 @@ impl BlockId {
     pub const AIR: BlockId = BlockId(0);
 
     pub fn with_variation(base_id: u16, variation: u8) -> Self {
-        Self(base_id << 8 | variation as u16)
+        Self::new(base_id, variation, 0)
     }
 
     pub fn with_color(base_id: u16, color_id: u8) -> Self {
-        Self(base_id << 4 | color_id as u16)
+        Self::new(base_id, 0, color_id)
     }
 
     pub fn from_str(s: &str) -> Result<Self, BlockError> {
@@ -185,6 +195,8 @@ pub struct BlockDefinition {
     #[serde(default)]
     pub flags: BlockFlags,
     #[serde(default)]
+    pub properties: BlockProperties,
+    #[serde(default)]
     pub physics: BlockPhysics,
     #[serde(default)]
     pub variations: Vec<BlockVariant>,
@@ -194,6 +206,70 @@ pub struct BlockDefinition {
     pub tint_settings: TintSettings,
 }
 
+impl BlockDefinition {
+    /// Effective material for a variation/colour of this block: the base material,
+    /// then the variant's modifiers, then the colour variant's modifiers and tint
+    pub fn resolve_material(&self, id: BlockId) -> BlockMaterial {
+        let mut material = self.material.clone();
+        let mut tint_settings = self.tint_settings;
+
+        if let Some(variant) = self
+            .variations
+            .iter()
+            .filter(|_| !self.reserves_variation())
+            .find(|variant| variant.id == id.variation() as u16)
+        {
+            variant.material_modifiers.apply(&mut material);
+            tint_settings = variant.material_modifiers.tint_settings(&tint_settings);
+        }
+
+        if let Some(color) = self
+            .color_variations
+            .iter()
+            .find(|color| color.id == id.color_id() as u16)
+        {
+            color.material_modifiers.apply(&mut material);
+            tint_settings = color.material_modifiers.tint_settings(&tint_settings);
+            if material.tintable {
+                material.apply_tint(color.color, &tint_settings);
+            }
+        }
+
+        material
+    }
+
+    /// Liquids keep their flow level in the variation nibble, so it never
+    /// selects a `BlockVariant` for them
+    pub fn reserves_variation(&self) -> bool {
+        self.flags.contains(BlockFlags::LIQUID)
+    }
+
+    /// Every ID this definition covers: the base plus each variation and
+    /// colour variant combination. Reserved variations cover all 16 values.
+    pub fn variant_ids(&self) -> Vec<BlockId> {
+        let base = self.id.base_id();
+        let variations: Vec<u8> = if self.reserves_variation() {
+            (0..=0xF).collect()
+        } else {
+            std::iter::once(0)
+                .chain(self.variations.iter().map(|v| v.id as u8))
+                .collect()
+        };
+        let colors: Vec<u8> = std::iter::once(0)
+            .chain(self.color_variations.iter().map(|c| c.id as u8))
+            .collect();
+
+        variations
+            .into_iter()
+            .flat_map(|variation| {
+                colors
+                    .iter()
+                    .map(move |&color| BlockId::new(base, variation, color))
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BlockVariant {
     pub id: u16,
@@ -253,3 +329,71 @@ impl BlockRegistry {
         self.physics.get(&id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::blocks_data::BLOCKS;
+
+    #[test]
+    fn test_block_id_packing() {
+        let id = BlockId::new(42, 3, 7);
+        assert_eq!(id.base_id(), 42);
+        assert_eq!(id.variation(), 3);
+        assert_eq!(id.color_id(), 7);
+        assert_ne!(BlockId::new(1, 0, 0), BlockId::new(2, 0, 0));
+    }
+
+    #[test]
+    fn test_block_id_round_trips() {
+        for base_id in [0, 1, 42, 0xFF] {
+            for variation in 0..16 {
+                for color_id in 0..16 {
+                    let id = BlockId::new(base_id, variation, color_id);
+                    assert_eq!(
+                        (id.base_id(), id.variation(), id.color_id()),
+                        (base_id, variation, color_id)
+                    );
+                    assert_eq!(BlockId(id.get_id()), id);
+                }
+            }
+        }
+        assert_eq!(BlockId::with_variation(7, 3), BlockId::new(7, 3, 0));
+        assert_eq!(BlockId::with_color(7, 3), BlockId::new(7, 0, 3));
+    }
+
+    #[test]
+    fn test_resolve_material_variants() {
+        let mut stone = BLOCKS[0].clone();
+        stone.variations[0].material_modifiers.roughness_offset = Some(0.2);
+
+        let base = stone.resolve_material(stone.id);
+        assert_eq!(base, stone.material);
+
+        let cracked = stone.resolve_material(BlockId::new(1, 1, 0));
+        assert!((cracked.roughness - (stone.material.roughness + 0.2)).abs() < 1e-6);
+
+        let mossy = stone.resolve_material(BlockId::new(1, 0, 1));
+        assert_ne!(mossy.albedo, stone.material.albedo);
+
+        assert_eq!(stone.variant_ids().len(), 4);
+    }
+
+    #[test]
+    fn test_liquid_level_ignores_variants() {
+        let mut water = BLOCKS
+            .iter()
+            .find(|def| def.name == "water")
+            .unwrap()
+            .clone();
+        let mut variant = BLOCKS[0].variations[0].clone();
+        variant.material_modifiers.roughness_offset = Some(0.5);
+        water.variations.push(variant);
+
+        let level = BlockId::new(water.id.base_id(), 1, 0);
+        assert_eq!(water.resolve_material(level), water.material);
+        assert!(water
+            .variant_ids()
+            .contains(&BlockId::new(water.id.base_id(), 7, 0)));
+    }
+}
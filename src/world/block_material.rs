@@ -104,3 +104,32 @@ pub struct MaterialModifiers {
     #[serde(default)]
     pub tint_strength: Option<f32>,
 }
+
+impl MaterialModifiers {
+    /// Applies the modifiers on top of a material. `tint_strength` is not applied
+    /// here; it overrides the tint settings used by `apply_tint`.
+    pub fn apply(&self, material: &mut BlockMaterial) {
+        if let Some([r, g, b]) = self.albedo_factor {
+            material.albedo[0] = (material.albedo[0] * r).clamp(0.0, 1.0);
+            material.albedo[1] = (material.albedo[1] * g).clamp(0.0, 1.0);
+            material.albedo[2] = (material.albedo[2] * b).clamp(0.0, 1.0);
+        }
+        if let Some(offset) = self.roughness_offset {
+            material.roughness = (material.roughness + offset).clamp(0.0, 1.0);
+        }
+        if let Some(offset) = self.metallic_offset {
+            material.metallic = (material.metallic + offset).clamp(0.0, 1.0);
+        }
+        if let Some([r, g, b]) = self.emissive_boost {
+            // Emission is scalar, so boost by the brightest channel
+            material.emissive += r.max(g).max(b);
+        }
+    }
+
+    pub fn tint_settings(&self, base: &TintSettings) -> TintSettings {
+        TintSettings {
+            strength: self.tint_strength.unwrap_or(base.strength),
+            ..*base
+        }
+    }
+}
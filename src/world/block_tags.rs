@@ -0,0 +1,238 @@
+use crate::world::block_id::BlockId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use thiserror::Error;
+
+/// Built-in tag definitions shipped with the engine
+pub const DEFAULT_TAGS: &str = include_str!("../assets/tags.json");
+
+/// Prefix marking a tag entry as a reference to another tag
+pub const TAG_REFERENCE_PREFIX: char = '#';
+
+#[derive(Debug, Error)]
+pub enum TagError {
+    #[error("Unknown block '{block}' in tag '{tag}'")]
+    UnknownBlock { tag: String, block: String },
+    #[error("Unknown tag '{reference}' referenced from tag '{tag}'")]
+    UnknownTag { tag: String, reference: String },
+    #[error("Tag reference cycle through '{0}'")]
+    Cycle(String),
+    #[error("Invalid tag file format: {0}")]
+    FormatError(#[from] serde_json::Error),
+}
+
+/// Raw tag definitions as read from a data file. Each tag lists block names
+/// and `#other_tag` references.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TagDefinitions {
+    pub tags: BTreeMap<String, Vec<String>>,
+}
+
+impl TagDefinitions {
+    pub fn from_json(json: &str) -> Result<Self, TagError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Merges another set of definitions, appending entries to existing tags
+    pub fn extend(&mut self, other: TagDefinitions) {
+        for (tag, entries) in other.tags {
+            self.tags.entry(tag).or_default().extend(entries);
+        }
+    }
+}
+
+/// Bitset over the full `BlockId` space, one bit per ID
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockIdSet {
+    words: Vec<u64>,
+}
+
+impl BlockIdSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, id: BlockId) {
+        let (word, bit) = Self::slot(id);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << bit;
+    }
+
+    pub fn remove(&mut self, id: BlockId) {
+        let (word, bit) = Self::slot(id);
+        if let Some(bits) = self.words.get_mut(word) {
+            *bits &= !(1 << bit);
+        }
+    }
+
+    pub fn contains(&self, id: BlockId) -> bool {
+        let (word, bit) = Self::slot(id);
+        self.words
+            .get(word)
+            .is_some_and(|bits| bits & (1 << bit) != 0)
+    }
+
+    pub fn union_with(&mut self, other: &BlockIdSet) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (word, bits) in self.words.iter_mut().zip(&other.words) {
+            *word |= bits;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|bits| bits.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|bits| *bits == 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = BlockId> + '_ {
+        self.words.iter().enumerate().flat_map(|(word, bits)| {
+            (0..64)
+                .filter(move |bit| bits & (1 << bit) != 0)
+                .map(move |bit| BlockId((word * 64 + bit) as u16))
+        })
+    }
+
+    fn slot(id: BlockId) -> (usize, u32) {
+        (id.0 as usize / 64, id.0 as u32 % 64)
+    }
+}
+
+/// Resolves tag definitions into one `BlockIdSet` per tag, following
+/// `#tag` references and rejecting unknown names and reference cycles
+pub fn resolve_tags(
+    definitions: &TagDefinitions,
+    lookup: impl Fn(&str) -> Option<BlockId>,
+) -> Result<HashMap<String, BlockIdSet>, TagError> {
+    let mut resolved = HashMap::with_capacity(definitions.tags.len());
+    let mut visiting = HashSet::new();
+
+    for tag in definitions.tags.keys() {
+        resolve_tag(tag, definitions, &lookup, &mut resolved, &mut visiting)?;
+    }
+
+    Ok(resolved)
+}
+
+fn resolve_tag(
+    tag: &str,
+    definitions: &TagDefinitions,
+    lookup: &impl Fn(&str) -> Option<BlockId>,
+    resolved: &mut HashMap<String, BlockIdSet>,
+    visiting: &mut HashSet<String>,
+) -> Result<(), TagError> {
+    if resolved.contains_key(tag) {
+        return Ok(());
+    }
+    if !visiting.insert(tag.to_string()) {
+        return Err(TagError::Cycle(tag.to_string()));
+    }
+
+    let mut set = BlockIdSet::new();
+    for entry in &definitions.tags[tag] {
+        if let Some(reference) = entry.strip_prefix(TAG_REFERENCE_PREFIX) {
+            if !definitions.tags.contains_key(reference) {
+                return Err(TagError::UnknownTag {
+                    tag: tag.to_string(),
+                    reference: reference.to_string(),
+                });
+            }
+            resolve_tag(reference, definitions, lookup, resolved, visiting)?;
+            set.union_with(&resolved[reference]);
+        } else {
+            let id = lookup(entry).ok_or_else(|| TagError::UnknownBlock {
+                tag: tag.to_string(),
+                block: entry.clone(),
+            })?;
+            set.insert(id);
+        }
+    }
+
+    visiting.remove(tag);
+    resolved.insert(tag.to_string(), set);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<BlockId> {
+        match name {
+            "stone" => Some(BlockId::new(1, 0, 0)),
+            "grass" => Some(BlockId::new(2, 0, 0)),
+            "sand" => Some(BlockId::new(5, 0, 0)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_block_id_set() {
+        let mut set = BlockIdSet::new();
+        set.insert(BlockId::new(5, 1, 2));
+        set.insert(BlockId(u16::MAX));
+
+        assert!(set.contains(BlockId::new(5, 1, 2)));
+        assert!(set.contains(BlockId(u16::MAX)));
+        assert!(!set.contains(BlockId::new(5, 0, 0)));
+        assert_eq!(set.len(), 2);
+
+        set.remove(BlockId(u16::MAX));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![BlockId::new(5, 1, 2)]);
+    }
+
+    #[test]
+    fn test_resolve_nested_tags() {
+        let definitions = TagDefinitions::from_json(
+            r##"{
+                "mineable/pickaxe": ["stone"],
+                "mineable/shovel": ["grass", "sand"],
+                "mineable": ["#mineable/pickaxe", "#mineable/shovel"]
+            }"##,
+        )
+        .unwrap();
+
+        let tags = resolve_tags(&definitions, lookup).unwrap();
+        let mineable = &tags["mineable"];
+        assert_eq!(mineable.len(), 3);
+        assert!(mineable.contains(BlockId::new(1, 0, 0)));
+        assert!(!tags["mineable/pickaxe"].contains(BlockId::new(2, 0, 0)));
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let cycle = TagDefinitions::from_json(r##"{"a": ["#b"], "b": ["#a"]}"##).unwrap();
+        assert!(matches!(
+            resolve_tags(&cycle, lookup),
+            Err(TagError::Cycle(_))
+        ));
+
+        let unknown = TagDefinitions::from_json(r##"{"a": ["bedrock"]}"##).unwrap();
+        assert!(matches!(
+            resolve_tags(&unknown, lookup),
+            Err(TagError::UnknownBlock { .. })
+        ));
+
+        let missing = TagDefinitions::from_json(r##"{"a": ["#b"]}"##).unwrap();
+        assert!(matches!(
+            resolve_tags(&missing, lookup),
+            Err(TagError::UnknownTag { .. })
+        ));
+    }
+
+    #[test]
+    fn test_default_tags_parse() {
+        let definitions = TagDefinitions::from_json(DEFAULT_TAGS).unwrap();
+        assert!(definitions.tags.contains_key("mineable/pickaxe"));
+    }
+}
@@ -5,8 +5,10 @@ use super::block_flags::BlockFlags;
 use super::block_id::{BlockCategory, BlockDefinition, BlockId, BlockVariant, ColorVariant};
 use super::block_material::{BlockMaterial, MaterialModifiers, TintSettings};
 use super::block_orientation::BlockOrientation;
+use super::block_tags::{self, BlockIdSet, TagDefinitions, TagError};
 use crate::world::block_tech::{BlockFlags as TechBlockFlags, BlockPhysics};
 use lazy_static::lazy_static;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
@@ -21,6 +23,9 @@ thread_local! {
 pub struct BlockRegistry {
     blocks: HashMap<String, BlockDefinition>,
     id_to_name: HashMap<BlockId, String>,
+    materials: HashMap<BlockId, BlockMaterial>,
+    tag_definitions: TagDefinitions,
+    tags: HashMap<String, BlockIdSet>,
 }
 
 impl BlockRegistry {
@@ -28,10 +33,17 @@ impl BlockRegistry {
         Self {
             blocks: HashMap::new(),
             id_to_name: HashMap::new(),
+            materials: HashMap::new(),
+            tag_definitions: TagDefinitions::default(),
+            tags: HashMap::new(),
         }
     }
 
     pub fn register(&mut self, definition: BlockDefinition) {
+        // Resolve the material of every variation/colour combination up front
+        for id in definition.variant_ids() {
+            self.materials.insert(id, definition.resolve_material(id));
+        }
         self.blocks
             .insert(definition.name.clone(), definition.clone());
         self.id_to_name.insert(definition.id, definition.name);
@@ -41,29 +53,92 @@ impl BlockRegistry {
         self.blocks.get(name)
     }
 
+    /// Looks up a definition, falling back to the base block for variant IDs
     pub fn get_by_id(&self, id: BlockId) -> Option<&BlockDefinition> {
         self.id_to_name
             .get(&id)
+            .or_else(|| self.id_to_name.get(&BlockId::new(id.base_id(), 0, 0)))
             .and_then(|name| self.blocks.get(name))
     }
 
+    /// Effective material for an ID, including its variation, colour variant and tint
     pub fn get_block_material(&self, id: BlockId) -> Option<&BlockMaterial> {
-        self.get_by_id(id).map(|def| &def.material)
+        self.materials
+            .get(&id)
+            .or_else(|| self.get_by_id(id).map(|def| &def.material))
+    }
+
+    pub fn resolved_materials(&self) -> impl Iterator<Item = (BlockId, &BlockMaterial)> {
+        self.materials.iter().map(|(id, material)| (*id, material))
     }
 
     pub fn get_block_flags(&self, id: BlockId) -> Option<TechBlockFlags> {
         self.get_by_id(id).map(|def| def.flags)
     }
 
+    /// Break resistance from the definition's property flags; 255 marks an unbreakable block
+    pub fn get_break_resistance(&self, id: BlockId) -> u8 {
+        self.get_by_id(id)
+            .map(|def| def.properties.break_resistance())
+            .unwrap_or(0)
+    }
+
     pub fn get_block_physics(&self, id: BlockId) -> BlockPhysics {
         self.get_by_id(id)
             .map(|def| BlockPhysics::from(def.flags))
             .unwrap_or_default()
     }
+
+    /// Adds tag definitions and re-resolves every tag against the registered blocks.
+    /// On error the previously resolved tags are kept.
+    pub fn load_tags(&mut self, definitions: TagDefinitions) -> Result<(), TagError> {
+        let mut merged = self.tag_definitions.clone();
+        merged.extend(definitions);
+
+        self.tags =
+            block_tags::resolve_tags(&merged, |name| self.blocks.get(name).map(|def| def.id))?;
+        self.tag_definitions = merged;
+        Ok(())
+    }
+
+    pub fn load_tags_json(&mut self, json: &str) -> Result<(), TagError> {
+        self.load_tags(TagDefinitions::from_json(json)?)
+    }
+
+    /// Whether the block carries the tag. Variations and colour variants
+    /// inherit the tags of their base block.
+    pub fn has_tag(&self, id: BlockId, tag: &str) -> bool {
+        self.tags
+            .get(tag)
+            .is_some_and(|set| set.contains(id) || set.contains(BlockId::new(id.base_id(), 0, 0)))
+    }
+
+    /// Resolved set for a tag, for hot loops that test many blocks against it
+    pub fn tag(&self, tag: &str) -> Option<&BlockIdSet> {
+        self.tags.get(tag)
+    }
+
+    pub fn tag_names(&self) -> impl Iterator<Item = &str> {
+        self.tags.keys().map(String::as_str)
+    }
 }
 
+/// Built-in blocks with the default tags resolved, built on first use.
+static DEFAULT_REGISTRY: Lazy<BlockRegistry> = Lazy::new(BlockRegistry::with_builtin_blocks);
+
 impl Default for BlockRegistry {
     fn default() -> Self {
+        DEFAULT_REGISTRY.clone()
+    }
+}
+
+impl BlockRegistry {
+    /// Shared built-in registry for lookups with no registry at hand.
+    pub fn builtin() -> &'static BlockRegistry {
+        &DEFAULT_REGISTRY
+    }
+
+    fn with_builtin_blocks() -> Self {
         let mut registry = Self::new();
 
         // Air
@@ -77,6 +152,7 @@ impl Default for BlockRegistry {
             texture_faces: HashMap::new(),
             material: BlockMaterial::default(),
             flags: TechBlockFlags::NONE,
+            properties: BlockFlags::empty().with_break_resistance(0),
             variations: Vec::new(),
             color_variations: Vec::new(),
             tint_settings: Default::default(),
@@ -94,6 +170,7 @@ impl Default for BlockRegistry {
             texture_faces: HashMap::new(),
             material: BlockMaterial::new([0.5, 0.5, 0.5, 1.0], 0.8, 0.0, 0.0),
             flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(15),
             variations: Vec::new(),
             color_variations: Vec::new(),
             tint_settings: Default::default(),
@@ -111,6 +188,7 @@ impl Default for BlockRegistry {
             texture_faces: HashMap::new(),
             material: BlockMaterial::new([0.3, 0.8, 0.3, 1.0], 0.6, 0.0, 0.0),
             flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(6),
             variations: Vec::new(),
             color_variations: Vec::new(),
             tint_settings: Default::default(),
@@ -128,6 +206,7 @@ impl Default for BlockRegistry {
             texture_faces: HashMap::new(),
             material: BlockMaterial::new([0.2, 0.3, 0.9, 0.8], 0.1, 0.0, 0.0),
             flags: TechBlockFlags::LIQUID,
+            properties: BlockFlags::empty().with_break_resistance(255),
             variations: Vec::new(),
             color_variations: Vec::new(),
             tint_settings: Default::default(),
@@ -145,6 +224,7 @@ impl Default for BlockRegistry {
             texture_faces: HashMap::new(),
             material: BlockMaterial::new([1.0, 0.5, 0.0, 1.0], 0.3, 0.0, 1.0),
             flags: TechBlockFlags::LIQUID,
+            properties: BlockFlags::empty().with_break_resistance(255),
             variations: Vec::new(),
             color_variations: Vec::new(),
             tint_settings: Default::default(),
@@ -162,6 +242,7 @@ impl Default for BlockRegistry {
             texture_faces: HashMap::new(),
             material: BlockMaterial::new([0.9, 0.9, 0.7, 1.0], 0.9, 0.0, 0.0),
             flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(5),
             variations: Vec::new(),
             color_variations: Vec::new(),
             tint_settings: Default::default(),
@@ -179,12 +260,251 @@ impl Default for BlockRegistry {
             texture_faces: HashMap::new(),
             material: BlockMaterial::new([0.9, 0.9, 0.9, 0.5], 0.1, 0.0, 0.0),
             flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(3),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::solid(),
+        });
+
+        // Dirt
+        registry.register(BlockDefinition {
+            id: BlockId::new(7, 0, 0),
+            name: "dirt".to_string(),
+            category: BlockCategory::Solid,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.45, 0.3, 0.2, 1.0], 0.9, 0.0, 0.0),
+            flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(5),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::solid(),
+        });
+
+        // Snow
+        registry.register(BlockDefinition {
+            id: BlockId::new(8, 0, 0),
+            name: "snow".to_string(),
+            category: BlockCategory::Solid,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.95, 0.95, 1.0, 1.0], 0.7, 0.0, 0.0),
+            flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(2),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::solid(),
+        });
+
+        // Gravel
+        registry.register(BlockDefinition {
+            id: BlockId::new(9, 0, 0),
+            name: "gravel".to_string(),
+            category: BlockCategory::Solid,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.55, 0.52, 0.5, 1.0], 0.95, 0.0, 0.0),
+            flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(6),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::solid(),
+        });
+
+        // Coal ore
+        registry.register(BlockDefinition {
+            id: BlockId::new(11, 0, 0),
+            name: "coal_ore".to_string(),
+            category: BlockCategory::Ore,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.3, 0.3, 0.3, 1.0], 0.8, 0.0, 0.0),
+            flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(15),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::solid(),
+        });
+
+        // Iron ore
+        registry.register(BlockDefinition {
+            id: BlockId::new(12, 0, 0),
+            name: "iron_ore".to_string(),
+            category: BlockCategory::Ore,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.6, 0.5, 0.45, 1.0], 0.8, 0.3, 0.0),
+            flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(15),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::solid(),
+        });
+
+        // Gold ore
+        registry.register(BlockDefinition {
+            id: BlockId::new(13, 0, 0),
+            name: "gold_ore".to_string(),
+            category: BlockCategory::Ore,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.8, 0.7, 0.3, 1.0], 0.8, 0.5, 0.0),
+            flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(15),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::solid(),
+        });
+
+        // Diamond ore
+        registry.register(BlockDefinition {
+            id: BlockId::new(14, 0, 0),
+            name: "diamond_ore".to_string(),
+            category: BlockCategory::Ore,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.4, 0.8, 0.8, 1.0], 0.8, 0.1, 0.0),
+            flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(15),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::solid(),
+        });
+
+        // Log
+        registry.register(BlockDefinition {
+            id: BlockId::new(15, 0, 0),
+            name: "log".to_string(),
+            category: BlockCategory::Solid,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.4, 0.28, 0.15, 1.0], 0.9, 0.0, 0.0),
+            flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(10),
             variations: Vec::new(),
             color_variations: Vec::new(),
             tint_settings: Default::default(),
             physics: BlockPhysics::solid(),
         });
 
+        // Leaves
+        registry.register(BlockDefinition {
+            id: BlockId::new(16, 0, 0),
+            name: "leaves".to_string(),
+            category: BlockCategory::Flora,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.2, 0.55, 0.2, 0.9], 0.8, 0.0, 0.0),
+            flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(1),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::solid(),
+        });
+
+        // Flower
+        registry.register(BlockDefinition {
+            id: BlockId::new(17, 0, 0),
+            name: "flower".to_string(),
+            category: BlockCategory::Flora,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.9, 0.3, 0.4, 1.0], 0.8, 0.0, 0.0),
+            flags: TechBlockFlags::NONE,
+            properties: BlockFlags::empty().with_break_resistance(0),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::from(TechBlockFlags::NONE),
+        });
+
+        // Tall grass
+        registry.register(BlockDefinition {
+            id: BlockId::new(18, 0, 0),
+            name: "tall_grass".to_string(),
+            category: BlockCategory::Flora,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.35, 0.75, 0.3, 1.0], 0.8, 0.0, 0.0),
+            flags: TechBlockFlags::NONE,
+            properties: BlockFlags::empty().with_break_resistance(0),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::from(TechBlockFlags::NONE),
+        });
+
+        // Asphalt
+        registry.register(BlockDefinition {
+            id: BlockId::new(19, 0, 0),
+            name: "asphalt".to_string(),
+            category: BlockCategory::Solid,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.2, 0.2, 0.22, 1.0], 0.9, 0.0, 0.0),
+            flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(12),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::solid(),
+        });
+
+        // Bricks
+        registry.register(BlockDefinition {
+            id: BlockId::new(20, 0, 0),
+            name: "bricks".to_string(),
+            category: BlockCategory::Solid,
+            default_facing: BlockFacing::default(),
+            default_orientation: BlockOrientation::default(),
+            connects_to: HashSet::new(),
+            texture_faces: HashMap::new(),
+            material: BlockMaterial::new([0.6, 0.3, 0.25, 1.0], 0.85, 0.0, 0.0),
+            flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(18),
+            variations: Vec::new(),
+            color_variations: Vec::new(),
+            tint_settings: Default::default(),
+            physics: BlockPhysics::solid(),
+        });
+
+        if let Err(e) = registry.load_tags_json(block_tags::DEFAULT_TAGS) {
+            log::error!("Failed to load default block tags: {}", e);
+        }
+
         registry
     }
 }
@@ -209,6 +529,7 @@ lazy_static! {
             ]),
             material: BlockMaterial::new([0.8, 0.8, 0.8, 1.0], 0.7, 0.0, 0.0),
             flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(15),
             variations: vec![BlockVariant {
                 id: 1,
                 name: "cracked".into(),
@@ -246,6 +567,7 @@ lazy_static! {
             ]),
             material: BlockMaterial::new([0.4, 0.8, 0.3, 1.0], 0.9, 0.0, 0.0),
             flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(6),
             variations: vec![],
             color_variations: vec![],
             tint_settings: TintSettings {
@@ -273,6 +595,7 @@ lazy_static! {
             ]),
             material: BlockMaterial::new([0.2, 0.4, 0.8, 0.8], 0.1, 0.0, 0.0),
             flags: TechBlockFlags::LIQUID,
+            properties: BlockFlags::empty().with_break_resistance(255),
             variations: vec![],
             color_variations: vec![],
             tint_settings: TintSettings {
@@ -300,6 +623,7 @@ lazy_static! {
             ]),
             material: BlockMaterial::new([0.8, 0.2, 0.1, 0.8], 0.1, 0.0, 0.5),
             flags: TechBlockFlags::LIQUID,
+            properties: BlockFlags::empty().with_break_resistance(255),
             variations: vec![],
             color_variations: vec![],
             tint_settings: TintSettings {
@@ -327,6 +651,7 @@ lazy_static! {
             ]),
             material: BlockMaterial::new([0.9, 0.9, 0.7, 1.0], 0.9, 0.0, 0.0),
             flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(5),
             variations: vec![],
             color_variations: vec![],
             tint_settings: TintSettings {
@@ -354,6 +679,7 @@ lazy_static! {
             ]),
             material: BlockMaterial::new([0.9, 0.9, 0.9, 0.3], 0.1, 0.0, 0.0),
             flags: TechBlockFlags::SOLID,
+            properties: BlockFlags::empty().with_break_resistance(3),
             variations: vec![],
             color_variations: vec![],
             tint_settings: TintSettings {
@@ -411,3 +737,53 @@ pub fn create_glass_block() -> (TechBlockFlags, BlockMaterial) {
 pub fn get_block_registry() -> BlockRegistry {
     BLOCK_REGISTRY.with(|registry| registry.borrow().clone())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_registry_tags() {
+        let registry = BlockRegistry::default();
+        let id = |name| registry.get_by_name(name).unwrap().id;
+        assert!(registry.has_tag(id("stone"), "mineable/pickaxe"));
+        // Through tag references, one and two levels deep
+        assert!(registry.has_tag(id("iron_ore"), "mineable/pickaxe"));
+        assert!(registry.has_tag(id("iron_ore"), "mineable"));
+        assert!(!registry.has_tag(id("stone"), "liquids"));
+        assert!(!registry.has_tag(id("stone"), "no_such_tag"));
+
+        // Variants fall back to the tags of their base block
+        let mossy = BlockId::new(id("stone").base_id(), 1, 1);
+        assert!(registry.tag("mineable/pickaxe").is_some_and(|set| !set.contains(mossy)));
+        assert!(registry.has_tag(mossy, "mineable/pickaxe"));
+        assert!(!registry.has_tag(mossy, "liquids"));
+        assert!(!registry.has_tag(BlockId::new(id("water").base_id(), 2, 0), "falling"));
+    }
+
+    #[test]
+    fn test_register_caches_a_material_per_variant() {
+        let mut stone = BLOCKS[0].clone();
+        stone.variations[0].material_modifiers.roughness_offset = Some(0.2);
+        let mut registry = BlockRegistry::new();
+        registry.register(stone.clone());
+
+        let base = stone.id.base_id();
+        let plain = BlockId::new(base, 0, 0);
+        let cracked = BlockId::new(base, 1, 0);
+        let mossy = BlockId::new(base, 0, 1);
+        let cracked_mossy = BlockId::new(base, 1, 1);
+        let materials: HashMap<_, _> = registry.resolved_materials().collect();
+        assert_eq!(materials.len(), 4);
+        for id in [plain, cracked, mossy, cracked_mossy] {
+            assert_eq!(materials[&id].albedo, stone.resolve_material(id).albedo);
+            assert_eq!(materials[&id].roughness, stone.resolve_material(id).roughness);
+        }
+
+        // The variation and the colour variant each change the material
+        assert_ne!(materials[&cracked].roughness, materials[&plain].roughness);
+        assert_ne!(materials[&mossy].albedo, materials[&plain].albedo);
+        assert_eq!(materials[&cracked_mossy].albedo, materials[&mossy].albedo);
+        assert_eq!(materials[&cracked_mossy].roughness, materials[&cracked].roughness);
+    }
+}
@@ -1,15 +1,19 @@
 use crate::config::WorldGenConfig;
 use crate::render::core::Camera;
 use crate::render::pipeline::{ChunkRenderer, RenderError};
-use crate::world::block::{Block, SubBlock};
+use crate::world::block::{self, Block, SubBlock, DEFAULT_SUB_RESOLUTION};
 use crate::world::block_id::BlockId;
 use crate::world::block_visual::ConnectedDirections;
+use crate::world::blocks_data;
 use crate::world::chunk_coord::ChunkCoord;
+use crate::world::generator::lighting;
+use crate::world::storage::chunk_file;
 use crate::world::storage::core::{CompressedBlock, CompressedSubBlock};
 use crate::world::BlockRegistry;
 use ash::vk;
 use bincode;
-use glam::{IVec3, Mat4, Vec2, Vec3, Vec4};
+use crate::world::origin::FloatingOrigin;
+use glam::{DVec3, IVec3, Mat4, Vec2, Vec3, Vec4};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{self, File};
@@ -23,7 +27,11 @@ pub const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompressedChunk {
     pub coord: ChunkCoord,
+    pub sub_resolution: u8,
+    pub status: ChunkStatus,
     pub regions: Vec<CompressedRegion>,
+    /// Runs of [`Chunk::light`] values, empty for an unlit chunk
+    pub light: Vec<(u8, u16)>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +60,7 @@ pub struct ChunkMesh {
     pub uvs: Vec<f32>,          // Texture coordinates (u, v)
     pub block_ids: Vec<u32>,    // Block type identifiers
     pub variant_data: Vec<u32>, // Variant and connection data
+    pub colors: Vec<f32>,       // Material colours (r, g, b, a)
     pub indices: Vec<u32>,      // Vertex indices
     pub vertex_count: usize,    // Total vertex count
     pub index_count: usize,     // Total index count
@@ -65,6 +74,7 @@ impl ChunkMesh {
             uvs: Vec::new(),
             block_ids: Vec::new(),
             variant_data: Vec::new(),
+            colors: Vec::new(),
             indices: Vec::new(),
             vertex_count: 0,
             index_count: 0,
@@ -77,6 +87,7 @@ impl ChunkMesh {
         self.uvs.clear();
         self.block_ids.clear();
         self.variant_data.clear();
+        self.colors.clear();
         self.indices.clear();
         self.vertex_count = 0;
         self.index_count = 0;
@@ -89,6 +100,7 @@ impl ChunkMesh {
         uvs: &[Vec2],
         block_id: u32,
         variant_data: u32,
+        color: [f32; 4],
     ) {
         let base_index = self.vertex_count as u32;
 
@@ -99,6 +111,7 @@ impl ChunkMesh {
             self.uvs.extend(&[uv.x, uv.y]);
             self.block_ids.push(block_id);
             self.variant_data.push(variant_data);
+            self.colors.extend(&color);
             self.vertex_count += 1;
         }
 
@@ -202,31 +215,55 @@ impl Frustum {
     }
 }
 
+/// The last generation stage a chunk has been through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ChunkStatus {
+    /// Nothing generated yet
+    Empty,
+    BaseDensity,
+    Surface,
+    Carvers,
+    Features,
+    Structures,
+    Lighting,
+    /// Every stage has run, or the chunk predates stage tracking
+    Full,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chunk {
     pub position: ChunkCoord,
     pub blocks: Vec<Option<Block>>,
     pub mesh: Option<ChunkMesh>,
     pub needs_remesh: bool,
+    #[serde(default = "default_sub_resolution")]
+    pub sub_resolution: u8, // Sub-blocks per block edge
+    #[serde(default = "default_status")]
+    pub status: ChunkStatus,
+    /// Sky light in the high and block light in the low nibble of each
+    /// block, set by the lighting stage. Empty until it runs.
     #[serde(skip)]
-    pub bounds: (Vec3, Vec3), // (min, max) world-space AABB
+    pub light: Vec<u8>,
+}
+
+fn default_sub_resolution() -> u8 {
+    DEFAULT_SUB_RESOLUTION
+}
+
+fn default_status() -> ChunkStatus {
+    ChunkStatus::Full
 }
 
 impl Chunk {
     pub fn new(position: ChunkCoord) -> Self {
-        let min = Vec3::new(
-            position.x() as f32 * CHUNK_SIZE as f32,
-            position.y() as f32 * CHUNK_SIZE as f32,
-            position.z() as f32 * CHUNK_SIZE as f32,
-        );
-        let max = min + Vec3::new(CHUNK_SIZE as f32, CHUNK_SIZE as f32, CHUNK_SIZE as f32);
-
         Self {
             position,
             blocks: vec![None; CHUNK_VOLUME],
             mesh: None,
             needs_remesh: true,
-            bounds: (min, max),
+            sub_resolution: DEFAULT_SUB_RESOLUTION,
+            status: ChunkStatus::Empty,
+            light: Vec::new(),
         }
     }
 
@@ -240,20 +277,11 @@ impl Chunk {
             blocks: serialized.blocks,
             mesh: None,
             needs_remesh: true,
-            bounds: (Vec3::ZERO, Vec3::ZERO),
+            sub_resolution: DEFAULT_SUB_RESOLUTION,
+            status: serialized.status,
+            light: Vec::new(),
         };
 
-        // Recalculate bounds
-        let min = Vec3::new(
-            chunk.position.x() as f32 * CHUNK_SIZE as f32,
-            chunk.position.y() as f32 * CHUNK_SIZE as f32,
-            chunk.position.z() as f32 * CHUNK_SIZE as f32,
-        );
-        chunk.bounds = (
-            min,
-            min + Vec3::new(CHUNK_SIZE as f32, CHUNK_SIZE as f32, CHUNK_SIZE as f32),
-        );
-
         Ok(chunk)
     }
 
@@ -273,22 +301,36 @@ impl Chunk {
         self.needs_remesh = true;
     }
 
+    /// Sky light at a block, full daylight if the chunk has not been lit
+    pub fn sky_light(&self, x: u32, y: u32, z: u32) -> u8 {
+        self.light
+            .get(self.get_index(x, y, z))
+            .map_or(lighting::MAX_LIGHT, |light| light >> 4)
+    }
+
+    /// Light from emitting blocks, none if the chunk has not been lit
+    pub fn block_light(&self, x: u32, y: u32, z: u32) -> u8 {
+        self.light
+            .get(self.get_index(x, y, z))
+            .map_or(0, |light| light & 0xF)
+    }
+
     fn get_index(&self, x: u32, y: u32, z: u32) -> usize {
         (x + y * CHUNK_SIZE + z * CHUNK_SIZE * CHUNK_SIZE) as usize
     }
 
-    pub fn get_block_at(&self, world_x: i32, world_y: i32, world_z: i32) -> Option<&Block> {
-        let local_x = (world_x.rem_euclid(CHUNK_SIZE as i32)) as u32;
-        let local_y = (world_y.rem_euclid(CHUNK_SIZE as i32)) as u32;
-        let local_z = (world_z.rem_euclid(CHUNK_SIZE as i32)) as u32;
+    pub fn get_block_at(&self, world_x: i64, world_y: i64, world_z: i64) -> Option<&Block> {
+        let local_x = (world_x.rem_euclid(CHUNK_SIZE as i64)) as u32;
+        let local_y = (world_y.rem_euclid(CHUNK_SIZE as i64)) as u32;
+        let local_z = (world_z.rem_euclid(CHUNK_SIZE as i64)) as u32;
         self.get_block(local_x, local_y, local_z)
     }
 
     pub fn get_subblock_at(
         &self,
-        world_x: i32,
-        world_y: i32,
-        world_z: i32,
+        world_x: i64,
+        world_y: i64,
+        world_z: i64,
         sub_x: u8,
         sub_y: u8,
         sub_z: u8,
@@ -297,6 +339,20 @@ impl Chunk {
             .and_then(|block| block.get_sub_block(&(sub_x, sub_y, sub_z)))
     }
 
+    /// Looks up the sub-block covering a fractional position relative to the
+    /// chunk's minimum corner
+    pub fn get_subblock_at_pos(&self, local_pos: Vec3) -> Option<&SubBlock> {
+        let (block_pos, sub_pos) = block::split_sub_block_pos(local_pos, self.sub_resolution);
+        self.get_subblock_at(
+            block_pos.x as i64,
+            block_pos.y as i64,
+            block_pos.z as i64,
+            sub_pos.0,
+            sub_pos.1,
+            sub_pos.2,
+        )
+    }
+
     pub fn save_world(&self, world_dir: &Path) -> std::io::Result<()> {
         let chunk_file = world_dir.join(format!(
             "chunk_{}_{}_{}.bin",
@@ -359,15 +415,16 @@ impl Chunk {
     }
 
     pub fn save_to_writer(&self, writer: impl io::Write) -> io::Result<()> {
-        let compressed = self.compress();
-        bincode::serialize_into(writer, &compressed)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        chunk_file::write_chunk(writer, &self.compress())
     }
 
+    /// Reads a chunk file, converting files from before the format version
     pub fn load_from_reader(reader: impl io::Read) -> io::Result<Self> {
-        let compressed: CompressedChunk = bincode::deserialize_from(reader)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let compressed = chunk_file::read_chunk(reader)?;
         let mut chunk = Chunk::new(compressed.coord);
+        chunk.sub_resolution = compressed.sub_resolution;
+        chunk.status = compressed.status;
+        chunk.light = chunk_file::light_from_runs(&compressed.light);
         chunk.decompress(compressed)?;
         Ok(chunk)
     }
@@ -386,7 +443,7 @@ impl Chunk {
                     let region_origin = (rx * REGION_SIZE, ry * REGION_SIZE, rz * REGION_SIZE);
                     let region_blocks = self.collect_region_blocks(region_origin, REGION_SIZE);
 
-                    regions.push(match Self::analyze_region(region_blocks) {
+                    regions.push(match Self::analyze_region(region_blocks, REGION_SIZE) {
                         RegionAnalysis::Empty => CompressedRegion::Empty,
                         RegionAnalysis::Uniform(block) => CompressedRegion::Uniform {
                             block_id: block.id,
@@ -400,7 +457,10 @@ impl Chunk {
 
         CompressedChunk {
             coord: self.position,
+            sub_resolution: self.sub_resolution,
+            status: self.status,
             regions,
+            light: chunk_file::light_runs(&self.light),
         }
     }
 
@@ -440,16 +500,19 @@ impl Chunk {
         blocks
     }
 
-    fn analyze_region(blocks: Vec<CompressedBlock>) -> RegionAnalysis {
+    fn analyze_region(blocks: Vec<CompressedBlock>, size: u32) -> RegionAnalysis {
         if blocks.is_empty() {
             return RegionAnalysis::Empty;
         }
 
+        // Only a full region is uniform; gaps must survive the round trip
         let first_block = &blocks[0];
-        if blocks.iter().all(|b| {
-            b.id == first_block.id
-                && Self::compare_sub_blocks(&b.sub_blocks, &first_block.sub_blocks)
-        }) {
+        if blocks.len() == (size * size * size) as usize
+            && blocks.iter().all(|b| {
+                b.id == first_block.id
+                    && Self::compare_sub_blocks(&b.sub_blocks, &first_block.sub_blocks)
+            })
+        {
             RegionAnalysis::Uniform(first_block.clone())
         } else {
             RegionAnalysis::Varied(blocks)
@@ -533,7 +596,7 @@ impl Chunk {
         }
     }
 
-    pub fn generate_mesh(&mut self, _renderer: &ChunkRenderer) -> Result<(), RenderError> {
+    pub fn generate_mesh(&mut self, renderer: &ChunkRenderer) -> Result<(), RenderError> {
         if !self.needs_remesh {
             return Ok(());
         }
@@ -551,9 +614,10 @@ impl Chunk {
             }
         }
 
+        let registry = renderer.block_registry();
         for (x, y, z, block) in blocks {
             if let Some(block) = block {
-                self.generate_block_mesh(&mut mesh, &block, x, y, z);
+                self.generate_block_mesh(&mut mesh, &block, x, y, z, registry);
             }
         }
 
@@ -562,58 +626,75 @@ impl Chunk {
         Ok(())
     }
 
-    fn generate_block_mesh(&self, mesh: &mut ChunkMesh, block: &Block, x: u32, y: u32, z: u32) {
-        // Convert block coordinates to world space
-        let world_pos = Vec3::new(
-            x as f32 + self.position.x() as f32 * CHUNK_SIZE as f32,
-            y as f32 + self.position.y() as f32 * CHUNK_SIZE as f32,
-            z as f32 + self.position.z() as f32 * CHUNK_SIZE as f32,
-        );
+    fn generate_block_mesh(
+        &self,
+        mesh: &mut ChunkMesh,
+        block: &Block,
+        x: u32,
+        y: u32,
+        z: u32,
+        registry: &blocks_data::BlockRegistry,
+    ) {
+        // Vertices stay chunk-local; `transform` places the chunk relative to
+        // the floating origin so they never lose precision far from spawn
+        let local_pos = Vec3::new(x as f32, y as f32, z as f32);
 
-        // Generate mesh for each sub-block
+        // Generate mesh for each sub-block, offset and scaled by the sub-block size
+        let size = block::sub_block_size(self.sub_resolution);
         for ((sub_x, sub_y, sub_z), sub_block) in &block.sub_blocks {
-            let sub_pos = world_pos + Vec3::new(*sub_x as f32, *sub_y as f32, *sub_z as f32);
-            self.generate_subblock_mesh(mesh, sub_block, sub_pos);
+            let sub_pos = local_pos + Vec3::new(*sub_x as f32, *sub_y as f32, *sub_z as f32) * size;
+            self.generate_subblock_mesh(mesh, sub_block, sub_pos, size, registry);
         }
     }
 
-    fn generate_subblock_mesh(&self, mesh: &mut ChunkMesh, sub_block: &SubBlock, position: Vec3) {
+    fn generate_subblock_mesh(
+        &self,
+        mesh: &mut ChunkMesh,
+        sub_block: &SubBlock,
+        position: Vec3,
+        size: f32,
+        registry: &blocks_data::BlockRegistry,
+    ) {
         // Generate faces based on block type and connections
         let block_id = sub_block.id;
         let variant_data = self.calculate_variant_data(sub_block);
+        // Colour of the variation and colour variant, resolved at registration
+        let color = registry
+            .get_block_material(BlockId(block_id))
+            .map_or([1.0; 4], |material| material.albedo);
 
         // Define cube vertices
         let vertices = [
             // Front face
-            position + Vec3::new(0.0, 0.0, 1.0),
-            position + Vec3::new(1.0, 0.0, 1.0),
-            position + Vec3::new(1.0, 1.0, 1.0),
-            position + Vec3::new(0.0, 1.0, 1.0),
+            position + Vec3::new(0.0, 0.0, 1.0) * size,
+            position + Vec3::new(1.0, 0.0, 1.0) * size,
+            position + Vec3::new(1.0, 1.0, 1.0) * size,
+            position + Vec3::new(0.0, 1.0, 1.0) * size,
             // Back face
-            position + Vec3::new(1.0, 0.0, 0.0),
-            position + Vec3::new(0.0, 0.0, 0.0),
-            position + Vec3::new(0.0, 1.0, 0.0),
-            position + Vec3::new(1.0, 1.0, 0.0),
+            position + Vec3::new(1.0, 0.0, 0.0) * size,
+            position + Vec3::new(0.0, 0.0, 0.0) * size,
+            position + Vec3::new(0.0, 1.0, 0.0) * size,
+            position + Vec3::new(1.0, 1.0, 0.0) * size,
             // Top face
-            position + Vec3::new(0.0, 1.0, 1.0),
-            position + Vec3::new(1.0, 1.0, 1.0),
-            position + Vec3::new(1.0, 1.0, 0.0),
-            position + Vec3::new(0.0, 1.0, 0.0),
+            position + Vec3::new(0.0, 1.0, 1.0) * size,
+            position + Vec3::new(1.0, 1.0, 1.0) * size,
+            position + Vec3::new(1.0, 1.0, 0.0) * size,
+            position + Vec3::new(0.0, 1.0, 0.0) * size,
             // Bottom face
-            position + Vec3::new(0.0, 0.0, 0.0),
-            position + Vec3::new(1.0, 0.0, 0.0),
-            position + Vec3::new(1.0, 0.0, 1.0),
-            position + Vec3::new(0.0, 0.0, 1.0),
+            position + Vec3::new(0.0, 0.0, 0.0) * size,
+            position + Vec3::new(1.0, 0.0, 0.0) * size,
+            position + Vec3::new(1.0, 0.0, 1.0) * size,
+            position + Vec3::new(0.0, 0.0, 1.0) * size,
             // Right face
-            position + Vec3::new(1.0, 0.0, 1.0),
-            position + Vec3::new(1.0, 0.0, 0.0),
-            position + Vec3::new(1.0, 1.0, 0.0),
-            position + Vec3::new(1.0, 1.0, 1.0),
+            position + Vec3::new(1.0, 0.0, 1.0) * size,
+            position + Vec3::new(1.0, 0.0, 0.0) * size,
+            position + Vec3::new(1.0, 1.0, 0.0) * size,
+            position + Vec3::new(1.0, 1.0, 1.0) * size,
             // Left face
-            position + Vec3::new(0.0, 0.0, 0.0),
-            position + Vec3::new(0.0, 0.0, 1.0),
-            position + Vec3::new(0.0, 1.0, 1.0),
-            position + Vec3::new(0.0, 1.0, 0.0),
+            position + Vec3::new(0.0, 0.0, 0.0) * size,
+            position + Vec3::new(0.0, 0.0, 1.0) * size,
+            position + Vec3::new(0.0, 1.0, 1.0) * size,
+            position + Vec3::new(0.0, 1.0, 0.0) * size,
         ];
 
         // Define face normals
@@ -652,6 +733,7 @@ impl Chunk {
                     &uv_coords,
                     block_id as u32,
                     variant_data,
+                    color,
                 );
             }
         }
@@ -681,14 +763,7 @@ impl Chunk {
 
     fn calculate_variant_data(&self, sub_block: &SubBlock) -> u32 {
         // Pack variant and connection data into a single u32
-        // Extract the variant from the block ID (assuming BlockId is a tuple or has a method to get variant)
-        let variant = match sub_block.id {
-            // If BlockId is a tuple type (u16, u16)
-            (_, variant) => variant as u32,
-            // If it's not a tuple, you'll need to adjust this based on your actual BlockId type
-            #[allow(unreachable_patterns)]
-            _ => 0,
-        };
+        let variant = BlockId(sub_block.id).variation() as u32;
 
         let connections = sub_block.connections.bits() as u32;
 
@@ -696,21 +771,24 @@ impl Chunk {
         (variant << 16) | connections
     }
 
-    pub fn transform(&self) -> Mat4 {
-        let pos = Vec3::new(
-            self.position.x() as f32 * CHUNK_SIZE as f32,
-            self.position.y() as f32 * CHUNK_SIZE as f32,
-            self.position.z() as f32 * CHUNK_SIZE as f32,
-        );
-        Mat4::from_translation(pos)
+    /// Model matrix relative to the floating origin
+    pub fn transform(&self, origin: &FloatingOrigin) -> Mat4 {
+        Mat4::from_translation(origin.chunk_to_local(self.position))
+    }
+
+    /// (min, max) AABB relative to the floating origin
+    pub fn bounds(&self, origin: &FloatingOrigin) -> (Vec3, Vec3) {
+        let min = origin.chunk_to_local(self.position);
+        (min, min + Vec3::splat(CHUNK_SIZE as f32))
     }
 
-    pub fn is_visible(&self, frustum: &Frustum) -> bool {
-        frustum.intersects_aabb(self.bounds.0, self.bounds.1)
+    pub fn is_visible(&self, frustum: &Frustum, origin: &FloatingOrigin) -> bool {
+        let (min, max) = self.bounds(origin);
+        frustum.intersects_aabb(min, max)
     }
 
-    pub fn get_aabb_corners(&self) -> [Vec3; 8] {
-        let (min, max) = self.bounds;
+    pub fn get_aabb_corners(&self, origin: &FloatingOrigin) -> [Vec3; 8] {
+        let (min, max) = self.bounds(origin);
         [
             Vec3::new(min.x, min.y, min.z),
             Vec3::new(max.x, min.y, min.z),
@@ -723,10 +801,27 @@ impl Chunk {
         ]
     }
 
-    pub fn is_solid_at(&self, world_x: i32, world_y: i32, world_z: i32) -> bool {
+    pub fn is_solid_at(&self, world_x: i64, world_y: i64, world_z: i64) -> bool {
         self.get_block_at(world_x, world_y, world_z)
             .map_or(false, |block| block.is_solid())
     }
+
+    /// Solidity at a fractional position relative to the chunk's minimum
+    /// corner. Sub-blocks only collide in air cells, where a solid sub-block
+    /// occupies the cell under `local_pos`; other blocks collide whole.
+    pub fn is_solid_at_point(
+        &self,
+        local_pos: Vec3,
+        registry: &blocks_data::BlockRegistry,
+    ) -> bool {
+        let (block_pos, sub_pos) = block::split_sub_block_pos(local_pos, self.sub_resolution);
+        let block_pos = block_pos.as_i64vec3();
+        match self.get_block_at(block_pos.x, block_pos.y, block_pos.z) {
+            Some(block) if block.id == BlockId::AIR => block.is_sub_block_solid(&sub_pos, registry),
+            Some(block) => block.get_physics(registry).solid,
+            None => false,
+        }
+    }
 }
 
 enum RegionAnalysis {
@@ -738,6 +833,8 @@ enum RegionAnalysis {
 pub struct SerializedChunk {
     pub coord: ChunkCoord,
     pub blocks: Vec<Option<Block>>,
+    #[serde(default = "default_status")]
+    pub status: ChunkStatus,
 }
 
 impl SerializedChunk {
@@ -745,6 +842,7 @@ impl SerializedChunk {
         Self {
             coord,
             blocks: chunk.blocks.clone(),
+            status: chunk.status,
         }
     }
 }
@@ -757,6 +855,7 @@ pub struct ChunkManager {
     block_registry: Arc<BlockRegistry>,
     visible_chunks: Vec<Arc<Chunk>>,
     last_view_proj: Option<Mat4>,
+    last_origin: FloatingOrigin,
 }
 
 impl ChunkManager {
@@ -773,6 +872,7 @@ impl ChunkManager {
             block_registry,
             visible_chunks: Vec::new(),
             last_view_proj: None,
+            last_origin: FloatingOrigin::default(),
         }
     }
 
@@ -847,20 +947,21 @@ impl ChunkManager {
         Ok(())
     }
 
-    pub fn update_visibility(&mut self, view_proj: &Mat4) {
-        // Only recalculate if view-projection matrix changed
+    pub fn update_visibility(&mut self, view_proj: &Mat4, origin: &FloatingOrigin) {
+        // Only recalculate if the view-projection matrix or the origin changed
         if let Some(last) = &self.last_view_proj {
-            if last.abs_diff_eq(*view_proj, 0.001) {
+            if last.abs_diff_eq(*view_proj, 0.001) && self.last_origin == *origin {
                 return;
             }
         }
         self.last_view_proj = Some(*view_proj);
+        self.last_origin = *origin;
 
         let frustum = Frustum::from_view_projection(view_proj);
         self.visible_chunks.clear();
 
         for chunk in self.chunks.values() {
-            if chunk.is_visible(&frustum) {
+            if chunk.is_visible(&frustum, origin) {
                 self.visible_chunks.push(chunk.clone());
             }
         }
@@ -909,36 +1010,146 @@ impl ChunkManager {
         Ok(())
     }
 
-    pub fn get_block_at(&self, world_pos: Vec3) -> Option<(&Block, IVec3)> {
+    pub fn get_block_at(&self, world_pos: DVec3) -> Option<(&Block, IVec3)> {
         // Use CHUNK_SIZE constant for the chunk size
         let chunk_coord = ChunkCoord::from_world_pos(world_pos, CHUNK_SIZE as i32);
         let chunk = self.chunks.get(&chunk_coord)?;
 
         // Calculate local position within the chunk
-        let chunk_world_pos = chunk_coord.to_world_pos(CHUNK_SIZE as i32);
-        let local_pos = IVec3::new(
-            (world_pos.x - chunk_world_pos.x) as i32,
-            (world_pos.y - chunk_world_pos.y) as i32,
-            (world_pos.z - chunk_world_pos.z) as i32,
-        );
+        let local_pos = (world_pos - chunk_coord.to_world_pos(CHUNK_SIZE as i32))
+            .floor()
+            .as_ivec3();
 
         let block = chunk.get_block(local_pos.x as u32, local_pos.y as u32, local_pos.z as u32)?;
 
         Some((block, local_pos))
     }
 
-    pub fn get_subblock_at(&self, world_pos: Vec3) -> Option<(&SubBlock, IVec3)> {
+    pub fn get_subblock_at(&self, world_pos: DVec3) -> Option<(&SubBlock, IVec3)> {
         let (block, local_pos) = self.get_block_at(world_pos)?;
 
-        // Calculate sub-block position within the block
-        // Use the fractional part of the world position to determine the sub-block
-        let sub_pos = (
-            (world_pos.x.fract() * 16.0) as u8,
-            (world_pos.y.fract() * 16.0) as u8,
-            (world_pos.z.fract() * 16.0) as u8,
-        );
+        // Calculate sub-block position within the block at the chunk's resolution
+        let chunk_coord = ChunkCoord::from_world_pos(world_pos, CHUNK_SIZE as i32);
+        let resolution = self.chunks.get(&chunk_coord)?.sub_resolution;
+        let within_chunk = (world_pos - chunk_coord.to_world_pos(CHUNK_SIZE as i32)).as_vec3();
+        let (_, sub_pos) = block::split_sub_block_pos(within_chunk, resolution);
 
         let sub_block = block.sub_blocks.get(&sub_pos)?;
         Some((sub_block, local_pos))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_with_sub_block(sub_pos: (u8, u8, u8)) -> Chunk {
+        let mut chunk = Chunk::new(ChunkCoord::new(0, 0, 0));
+        let mut block = Block::new(BlockId::AIR);
+        // Stone, which is solid
+        block.place_sub_block(sub_pos, SubBlock::new(BlockId::new(1, 0, 0).0));
+        chunk.set_block(2, 3, 4, Some(block));
+        chunk
+    }
+
+    #[test]
+    fn test_subblock_mesh_uses_sub_block_size() {
+        let chunk = chunk_with_sub_block((1, 0, 1));
+        let mut mesh = ChunkMesh::new();
+        let block = chunk.get_block(2, 3, 4).unwrap().clone();
+        let registry = blocks_data::BlockRegistry::default();
+        chunk.generate_block_mesh(&mut mesh, &block, 2, 3, 4, &registry);
+
+        let size = block::sub_block_size(chunk.sub_resolution);
+        let xs = mesh.vertices.iter().step_by(3);
+        let min_x = xs.clone().cloned().fold(f32::INFINITY, f32::min);
+        let max_x = xs.cloned().fold(f32::NEG_INFINITY, f32::max);
+        assert_eq!(min_x, 2.0 + size);
+        assert_eq!(max_x, 2.0 + 2.0 * size);
+        assert!(max_x <= 3.0);
+    }
+
+    #[test]
+    fn test_mesh_takes_the_resolved_material_colour() {
+        let mut registry = blocks_data::BlockRegistry::new();
+        registry.register(blocks_data::BLOCKS[0].clone());
+        let mossy = BlockId::new(1, 0, 1);
+        let mut block = Block::new(BlockId::AIR);
+        block.place_sub_block((0, 0, 0), SubBlock::new(mossy.0));
+        let chunk = Chunk::new(ChunkCoord::new(0, 0, 0));
+        let mut mesh = ChunkMesh::new();
+        chunk.generate_block_mesh(&mut mesh, &block, 0, 0, 0, &registry);
+
+        let color = registry.get_block_material(mossy).unwrap().albedo;
+        assert_ne!(color, registry.get_block_material(BlockId::new(1, 0, 0)).unwrap().albedo);
+        assert_eq!(mesh.colors.len(), mesh.vertex_count * 4);
+        assert!(mesh.colors.chunks(4).all(|vertex| vertex == color));
+    }
+
+    #[test]
+    fn test_get_subblock_at_pos() {
+        let chunk = chunk_with_sub_block((1, 0, 1));
+        assert!(chunk
+            .get_subblock_at_pos(Vec3::new(2.75, 3.25, 4.5))
+            .is_some());
+        assert!(chunk
+            .get_subblock_at_pos(Vec3::new(2.25, 3.25, 4.5))
+            .is_none());
+    }
+
+    #[test]
+    fn test_is_solid_at_point_honours_sub_blocks() {
+        let chunk = chunk_with_sub_block((0, 0, 0));
+        let registry = blocks_data::BlockRegistry::default();
+        assert!(chunk.is_solid_at_point(Vec3::new(2.1, 3.1, 4.1), &registry));
+        assert!(!chunk.is_solid_at_point(Vec3::new(2.9, 3.9, 4.9), &registry));
+    }
+
+    #[test]
+    fn test_save_and_load_keep_ids_status_and_light() {
+        let mut chunk = chunk_with_sub_block((1, 0, 1));
+        let grass = BlockId::new(2, 1, 3);
+        chunk.set_block(5, 6, 7, Some(Block::new(grass)));
+        chunk.status = ChunkStatus::Full;
+        lighting::light_chunk(&mut chunk, &blocks_data::BlockRegistry::default(), |_, _| 0);
+
+        let mut bytes = Vec::new();
+        chunk.save_to_writer(&mut bytes).unwrap();
+        let loaded = Chunk::load_from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(loaded.status, ChunkStatus::Full);
+        assert_eq!(loaded.light, chunk.light);
+        assert_eq!(loaded.sky_light(5, 5, 7), chunk.sky_light(5, 5, 7));
+        assert!(loaded.sky_light(5, 5, 7) < lighting::MAX_LIGHT);
+        assert_eq!(loaded.get_block(5, 6, 7).map(|block| block.id), Some(grass));
+        assert!(loaded
+            .get_subblock_at_pos(Vec3::new(2.75, 3.25, 4.5))
+            .is_some());
+    }
+
+    #[test]
+    fn test_legacy_chunks_keep_their_blocks() {
+        use crate::world::storage::chunk_file::LegacyCompressedChunk;
+
+        let coord = ChunkCoord::new(3, -1, 4);
+        let id = BlockId::with_variation(2, 1);
+        let mut regions = vec![CompressedRegion::Empty; 64];
+        regions[0] = CompressedRegion::Sparse(vec![CompressedBlock {
+            position: (1, 2, 3),
+            id,
+            sub_blocks: Vec::new(),
+        }]);
+        regions[63] = CompressedRegion::Uniform {
+            block_id: id,
+            sub_blocks: Vec::new(),
+        };
+        let bytes = bincode::serialize(&LegacyCompressedChunk { coord, regions }).unwrap();
+
+        let loaded = Chunk::load_from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(loaded.position, coord);
+        // Saved without light, so it is lit again
+        assert_eq!(loaded.status, ChunkStatus::Structures);
+        assert_eq!(loaded.get_block(1, 2, 3).map(|block| block.id), Some(id));
+        assert_eq!(loaded.get_block(31, 31, 31).map(|block| block.id), Some(id));
+        assert!(loaded.get_block(0, 0, 0).is_none());
+    }
+}
@@ -1,10 +1,11 @@
-use glam::{IVec3, Vec3};
+use crate::world::geo::{GeoPosition, GeoProjection};
+use glam::{DVec3, I64Vec3, IVec3};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::Ordering;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct ChunkCoord(pub IVec3);
+pub struct ChunkCoord(pub I64Vec3);
 
 impl Serialize for ChunkCoord {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -20,8 +21,8 @@ impl<'de> Deserialize<'de> for ChunkCoord {
     where
         D: Deserializer<'de>,
     {
-        let (x, y, z) = <(i32, i32, i32)>::deserialize(deserializer)?;
-        Ok(ChunkCoord(IVec3::new(x, y, z)))
+        let (x, y, z) = <(i64, i64, i64)>::deserialize(deserializer)?;
+        Ok(ChunkCoord::new(x, y, z))
     }
 }
 
@@ -44,44 +45,51 @@ impl Ord for ChunkCoord {
 }
 
 impl ChunkCoord {
-    pub fn new(x: i32, y: i32, z: i32) -> Self {
-        Self(IVec3::new(x, y, z))
+    pub fn new(x: i64, y: i64, z: i64) -> Self {
+        Self(I64Vec3::new(x, y, z))
     }
 
-    pub fn from_world_pos(pos: Vec3, chunk_size: i32) -> Self {
-        let x = (pos.x / chunk_size as f32).floor() as i32;
-        let y = (pos.y / chunk_size as f32).floor() as i32;
-        let z = (pos.z / chunk_size as f32).floor() as i32;
+    pub fn from_world_pos(pos: DVec3, chunk_size: i32) -> Self {
+        let x = (pos.x / chunk_size as f64).floor() as i64;
+        let y = (pos.y / chunk_size as f64).floor() as i64;
+        let z = (pos.z / chunk_size as f64).floor() as i64;
         Self::new(x, y, z)
     }
 
-    pub fn from_world(position: Vec3) -> Self {
-        Self::from_world_pos(position, 16) // Using standard chunk size of 16
+    /// Chunk containing a block position
+    pub fn from_block_pos(pos: I64Vec3, chunk_size: i32) -> Self {
+        Self(pos.div_euclid(I64Vec3::splat(chunk_size as i64)))
     }
 
-    pub fn x(&self) -> i32 {
+    pub fn x(&self) -> i64 {
         self.0.x
     }
 
-    pub fn y(&self) -> i32 {
+    pub fn y(&self) -> i64 {
         self.0.y
     }
 
-    pub fn z(&self) -> i32 {
+    pub fn z(&self) -> i64 {
         self.0.z
     }
 
-    pub fn to_world_pos(&self, chunk_size: i32) -> Vec3 {
-        Vec3::new(
-            self.0.x as f32 * chunk_size as f32,
-            self.0.y as f32 * chunk_size as f32,
-            self.0.z as f32 * chunk_size as f32,
-        )
+    /// Block position of the chunk's minimum corner
+    pub fn to_block_pos(&self, chunk_size: i32) -> I64Vec3 {
+        self.0 * chunk_size as i64
     }
 
-    pub fn to_world_center(&self, chunk_size: i32) -> Vec3 {
+    pub fn to_world_pos(&self, chunk_size: i32) -> DVec3 {
+        self.to_block_pos(chunk_size).as_dvec3()
+    }
+
+    pub fn to_world_center(&self, chunk_size: i32) -> DVec3 {
         let pos = self.to_world_pos(chunk_size);
-        pos + Vec3::splat(chunk_size as f32 / 2.0)
+        pos + DVec3::splat(chunk_size as f64 / 2.0)
+    }
+
+    /// Geographic position of the chunk centre
+    pub fn to_geo(&self, chunk_size: i32, projection: &GeoProjection) -> GeoPosition {
+        projection.to_geo(self.to_world_center(chunk_size))
     }
 
     pub fn to_path(&self) -> PathBuf {
@@ -106,8 +114,8 @@ impl ChunkCoord {
 
         let coords = file_name[6..file_name.len() - 4]
             .split('_')
-            .map(|s| s.parse::<i32>())
-            .collect::<Result<Vec<i32>, _>>()
+            .map(|s| s.parse::<i64>())
+            .collect::<Result<Vec<i64>, _>>()
             .map_err(|_| {
                 std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid coordinates")
             })?;
@@ -130,14 +138,14 @@ impl ChunkCoord {
                     if x == 0 && y == 0 && z == 0 {
                         continue;
                     }
-                    neighbors.push(Self(self.0 + IVec3::new(x, y, z)));
+                    neighbors.push(Self(self.0 + I64Vec3::new(x, y, z)));
                 }
             }
         }
         neighbors
     }
 
-    pub fn manhattan_distance(&self, other: &Self) -> i32 {
+    pub fn manhattan_distance(&self, other: &Self) -> i64 {
         (self.0.x - other.0.x).abs() + (self.0.y - other.0.y).abs() + (self.0.z - other.0.z).abs()
     }
 
@@ -151,11 +159,17 @@ impl ChunkCoord {
 
 impl From<IVec3> for ChunkCoord {
     fn from(vec: IVec3) -> Self {
+        Self(vec.as_i64vec3())
+    }
+}
+
+impl From<I64Vec3> for ChunkCoord {
+    fn from(vec: I64Vec3) -> Self {
         Self(vec)
     }
 }
 
-impl From<ChunkCoord> for IVec3 {
+impl From<ChunkCoord> for I64Vec3 {
     fn from(coord: ChunkCoord) -> Self {
         coord.0
     }
@@ -1,89 +1,504 @@
-use crate::config::{core::EngineConfig, worldgen::WorldGenConfig};
-use crate::render::pipeline::ChunkRenderer;
-use crate::world::BlockFacing;
-use crate::world::BlockOrientation;
-use crate::world::block::Block;
+use crate::world::block::{self, Block, SubBlock};
 use crate::world::block_id::BlockId;
-use crate::world::block_material::BlockMaterial;
-use crate::world::block_material::MaterialModifiers;
 use crate::world::blocks_data::BlockRegistry;
-use crate::world::chunk::{Chunk, ChunkCoord};
+use crate::world::chunk::{Chunk, ChunkStatus, CHUNK_SIZE};
+use crate::world::chunk_coord::ChunkCoord;
+use crate::world::generator::core::{GenerationPipeline, WorldGenerator};
+use crate::world::generator::features::{FeatureWrite, PendingFeatureWrites};
 use crate::world::generator::terrain::TerrainGenerator;
-use crate::world::pool::ChunkPool;
-use crate::world::pool::PoolStats;
-use crate::world::spatial::QuadTree;
-use crate::world::spatial::SpatialPartition;
+use crate::world::geo::GeoProjection;
 use crate::world::storage::core::ChunkStorage;
 use crate::world::storage::file::FileChunkStorage;
-use glam::Vec3;
-use std::path::Path;
+use crate::world::ticks::{BlockBehaviors, TickScheduler, MAX_NEIGHBOR_UPDATES_PER_TICK};
+use glam::{DVec3, I64Vec3};
+use std::collections::VecDeque;
 use std::sync::Arc;
 
 pub struct World {
     storage: Box<dyn ChunkStorage>,
-    pool: ChunkPool,
-    generator: TerrainGenerator,
-    config: EngineConfig,
+    pipeline: GenerationPipeline,
+    terrain: Arc<TerrainGenerator>,
+    sub_resolution: u8,
+    block_registry: Arc<BlockRegistry>,
+    ticks: TickScheduler,
+    behaviors: BlockBehaviors,
+    feature_writes: PendingFeatureWrites,
+    /// Chunks that may be able to run their next stage
+    stage_queue: VecDeque<ChunkCoord>,
 }
 
 impl World {
-    pub fn new(engine_config: EngineConfig, world_config: WorldGenConfig) -> Self {
+    /// World generated by `terrain`, which also supplies its block registry
+    pub fn new(terrain: Arc<TerrainGenerator>) -> Self {
+        let sub_resolution = terrain.sub_resolution();
+        let block_registry = terrain.block_registry().clone();
         Self {
             storage: Box::new(FileChunkStorage::new("world")),
-            pool: ChunkPool::new(1000), // Maximum 1000 chunks in pool
-            generator: TerrainGenerator::new(world_config, Arc::new(Default::default())),
-            config: engine_config,
+            pipeline: GenerationPipeline::terrain(terrain.clone()),
+            terrain,
+            sub_resolution,
+            behaviors: BlockBehaviors::with_defaults(&block_registry),
+            block_registry,
+            ticks: TickScheduler::new(),
+            feature_writes: PendingFeatureWrites::new(),
+            stage_queue: VecDeque::new(),
         }
     }
 
-    pub fn get_block(&self, x: i32, y: i32, z: i32) -> Option<&Block> {
-        let chunk_coord = ChunkCoord::from_world_pos(Vec3::new(x as f32, y as f32, z as f32), 32);
-        let (local_x, local_y, local_z) = self.get_local_coords(x, y, z);
+    pub fn get_block(&self, x: i64, y: i64, z: i64) -> Option<Block> {
+        self.block_at(I64Vec3::new(x, y, z))
+    }
 
-        if let Some(chunk) = self.storage.get_chunk(&chunk_coord) {
-            chunk.get_block(local_x as u32, local_y as u32, local_z as u32)
-        } else {
-            None
+    pub fn set_block(&mut self, x: i64, y: i64, z: i64, block: Option<Block>) {
+        self.set_block_at(I64Vec3::new(x, y, z), block);
+    }
+
+    /// Replaces the block at `pos` and notifies it and its six neighbours.
+    /// Returns false if the containing chunk is not loaded.
+    pub fn set_block_at(&mut self, pos: I64Vec3, block: Option<Block>) -> bool {
+        let chunk_coord = ChunkCoord::from_block_pos(pos, CHUNK_SIZE as i32);
+        let (local_x, local_y, local_z) = self.get_local_coords(pos.x, pos.y, pos.z);
+
+        let Some(chunk) = self.storage.get_chunk_mut(chunk_coord) else {
+            return false;
+        };
+        Arc::make_mut(chunk).set_block(local_x as u32, local_y as u32, local_z as u32, block);
+        self.ticks.block_changed(pos);
+        true
+    }
+
+    /// Removes and returns the block at `pos`, notifying its neighbours
+    pub fn take_block(&mut self, pos: I64Vec3) -> Option<Block> {
+        let block = self.block_at(pos);
+        if block.is_some() {
+            self.set_block_at(pos, None);
+        }
+        block
+    }
+
+    /// Whether the chunk containing the block position is loaded
+    pub fn is_loaded(&self, pos: I64Vec3) -> bool {
+        self.has_chunk(&ChunkCoord::from_block_pos(pos, CHUNK_SIZE as i32))
+    }
+
+    /// Copy of the block at `pos`, if its chunk is loaded and the cell is filled
+    pub fn block_at(&self, pos: I64Vec3) -> Option<Block> {
+        let chunk_coord = ChunkCoord::from_block_pos(pos, CHUNK_SIZE as i32);
+        let (local_x, local_y, local_z) = self.get_local_coords(pos.x, pos.y, pos.z);
+        self.storage
+            .get_chunk(chunk_coord)?
+            .get_block(local_x as u32, local_y as u32, local_z as u32)
+            .cloned()
+    }
+
+    /// ID of the block at `pos`, treating empty cells and unloaded chunks as air
+    pub fn block_id_at(&self, pos: I64Vec3) -> BlockId {
+        let chunk_coord = ChunkCoord::from_block_pos(pos, CHUNK_SIZE as i32);
+        let (local_x, local_y, local_z) = self.get_local_coords(pos.x, pos.y, pos.z);
+        self.storage
+            .get_chunk(chunk_coord)
+            .and_then(|chunk| {
+                chunk
+                    .get_block(local_x as u32, local_y as u32, local_z as u32)
+                    .map(|block| block.id)
+            })
+            .unwrap_or(BlockId::AIR)
+    }
+
+    pub fn block_registry(&self) -> &Arc<BlockRegistry> {
+        &self.block_registry
+    }
+
+    pub fn behaviors_mut(&mut self) -> &mut BlockBehaviors {
+        &mut self.behaviors
+    }
+
+    /// Schedules a tick for `block` at `pos` after `delay` game ticks
+    pub fn schedule_tick(&mut self, pos: I64Vec3, block: BlockId, delay: u32) -> bool {
+        self.ticks.schedule(pos, block, delay)
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.ticks.current_tick()
+    }
+
+    /// Runs one game tick: pending neighbour updates first, then every
+    /// scheduled tick that is due, in the order they were scheduled
+    pub fn tick(&mut self) {
+        for _ in 0..MAX_NEIGHBOR_UPDATES_PER_TICK {
+            let Some(update) = self.ticks.pop_neighbor_update() else {
+                break;
+            };
+            let id = self.block_id_at(update.position);
+            if let Some(behavior) = self.behaviors.get(id) {
+                behavior.neighbor_changed(self, update.position, update.source);
+            }
         }
+
+        for scheduled in self.ticks.advance() {
+            // The block may have changed since the tick was scheduled
+            if self.block_id_at(scheduled.position) != scheduled.block {
+                continue;
+            }
+            if let Some(behavior) = self.behaviors.get(scheduled.block) {
+                behavior.scheduled_tick(self, scheduled.position);
+            }
+        }
+    }
+
+    /// Projection placing the world on real-world coordinates, if it has one
+    pub fn geo_projection(&self) -> Option<&GeoProjection> {
+        self.terrain.geo_projection()
+    }
+
+    /// Sub-blocks per block edge used by this world
+    pub fn sub_resolution(&self) -> u8 {
+        self.sub_resolution
+    }
+
+    /// Places a sub-block in the cell covering a fractional world position
+    /// and notifies the block and its six neighbours. Returns false if the
+    /// containing chunk is not loaded or holds sub-blocks of another
+    /// resolution.
+    pub fn place_sub_block(&mut self, world_pos: DVec3, sub_block: SubBlock) -> bool {
+        let resolution = self.sub_resolution;
+        let (block_pos, sub_pos) = self.split_sub_block_pos(world_pos);
+        let chunk_coord = ChunkCoord::from_block_pos(block_pos, CHUNK_SIZE as i32);
+        let (local_x, local_y, local_z) =
+            self.get_local_coords(block_pos.x, block_pos.y, block_pos.z);
+
+        let Some(chunk) = self.storage.get_chunk_mut(chunk_coord) else {
+            return false;
+        };
+        if chunk.sub_resolution != resolution {
+            log::warn!(
+                "Chunk {:?} has sub-block resolution {}, not the world's {}",
+                chunk_coord,
+                chunk.sub_resolution,
+                resolution
+            );
+            return false;
+        }
+        let chunk = Arc::make_mut(chunk);
+
+        let mut target = chunk
+            .get_block(local_x as u32, local_y as u32, local_z as u32)
+            .cloned()
+            .unwrap_or_else(|| Block::new(BlockId::AIR));
+        if !target.try_place_sub_block(sub_pos, sub_block, resolution) {
+            return false;
+        }
+        chunk.set_block(local_x as u32, local_y as u32, local_z as u32, Some(target));
+        self.ticks.block_changed(block_pos);
+        true
     }
 
-    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block: Option<Block>) {
-        let chunk_coord = ChunkCoord::from_world_pos(Vec3::new(x as f32, y as f32, z as f32), 32);
-        let (local_x, local_y, local_z) = self.get_local_coords(x, y, z);
+    /// Removes the sub-block covering a fractional world position and
+    /// notifies the block and its six neighbours. An air block left without
+    /// sub-blocks is cleared.
+    pub fn remove_sub_block(&mut self, world_pos: DVec3) -> Option<SubBlock> {
+        let (block_pos, sub_pos) = self.split_sub_block_pos(world_pos);
+        let chunk_coord = ChunkCoord::from_block_pos(block_pos, CHUNK_SIZE as i32);
+        let (local_x, local_y, local_z) =
+            self.get_local_coords(block_pos.x, block_pos.y, block_pos.z);
+        let (x, y, z) = (local_x as u32, local_y as u32, local_z as u32);
 
-        if let Some(chunk) = self.storage.get_chunk_mut(&chunk_coord) {
-            chunk.set_block(local_x as u32, local_y as u32, local_z as u32, block);
+        let chunk = Arc::make_mut(self.storage.get_chunk_mut(chunk_coord)?);
+        let block = chunk.get_block_mut(x, y, z)?;
+        let removed = block.remove_sub_block(&sub_pos)?;
+        if block.id == BlockId::AIR && !block.has_sub_blocks() {
+            chunk.set_block(x, y, z, None);
+        } else {
+            chunk.needs_remesh = true;
         }
+        self.ticks.block_changed(block_pos);
+        Some(removed)
+    }
+
+    /// Returns the sub-block covering a fractional world position
+    pub fn get_sub_block(&self, world_pos: DVec3) -> Option<SubBlock> {
+        let (block_pos, (sub_x, sub_y, sub_z)) = self.split_sub_block_pos(world_pos);
+        let chunk_coord = ChunkCoord::from_block_pos(block_pos, CHUNK_SIZE as i32);
+        self.storage
+            .get_chunk(chunk_coord)?
+            .get_subblock_at(block_pos.x, block_pos.y, block_pos.z, sub_x, sub_y, sub_z)
+            .cloned()
     }
 
-    fn get_local_coords(&self, x: i32, y: i32, z: i32) -> (i32, i32, i32) {
-        let chunk_size = 32;
-        let local_x = ((x % chunk_size) + chunk_size) % chunk_size;
-        let local_y = ((y % chunk_size) + chunk_size) % chunk_size;
-        let local_z = ((z % chunk_size) + chunk_size) % chunk_size;
+    /// Splits a world position into its block and the sub-block cell within
+    /// it. The fraction is taken in `f64` so cells stay exact far from spawn.
+    fn split_sub_block_pos(&self, world_pos: DVec3) -> (I64Vec3, (u8, u8, u8)) {
+        let block_pos = world_pos.floor();
+        let fraction = (world_pos - block_pos).as_vec3();
+        let (_, sub_pos) = block::split_sub_block_pos(fraction, self.sub_resolution);
+        (block_pos.as_i64vec3(), sub_pos)
+    }
+
+    fn get_local_coords(&self, x: i64, y: i64, z: i64) -> (i64, i64, i64) {
+        let chunk_size = CHUNK_SIZE as i64;
+        let local_x = x.rem_euclid(chunk_size);
+        let local_y = y.rem_euclid(chunk_size);
+        let local_z = z.rem_euclid(chunk_size);
         (local_x, local_y, local_z)
     }
 
     pub fn get_chunk(&self, coord: &ChunkCoord) -> Option<Arc<Chunk>> {
-        self.storage.get_chunk(coord)
+        self.storage.get_chunk(*coord)
     }
 
     pub fn get_chunk_mut(&mut self, coord: &ChunkCoord) -> Option<&mut Arc<Chunk>> {
-        self.storage.get_chunk_mut(coord)
+        self.storage.get_chunk_mut(*coord)
     }
 
     pub fn has_chunk(&self, coord: &ChunkCoord) -> bool {
-        self.storage.has_chunk(coord)
+        self.storage.get_chunk(*coord).is_some()
     }
 
     pub fn set_chunk(&mut self, coord: ChunkCoord, chunk: Chunk) {
         self.storage.set_chunk(coord, Arc::new(chunk));
     }
 
+    /// Takes a chunk out of the world, e.g. to save it once the player is far away
+    pub fn remove_chunk(&mut self, coord: ChunkCoord) -> Option<Arc<Chunk>> {
+        let chunk = self.storage.get_chunk(coord)?;
+        self.storage.remove_chunk(coord);
+        Some(chunk)
+    }
+
+    pub fn chunk_coords(&self) -> Vec<ChunkCoord> {
+        self.storage.chunk_coords()
+    }
+
+    /// The generation stages chunks go through, for registering more
+    pub fn pipeline_mut(&mut self) -> &mut GenerationPipeline {
+        &mut self.pipeline
+    }
+
+    /// Position of the nearest structure with the given name, searching up
+    /// to `max_cells` cells of its spawn grid around `near`
+    pub fn locate_structure(&self, name: &str, near: I64Vec3, max_cells: i64) -> Option<I64Vec3> {
+        self.terrain
+            .locate_structure(name, near, max_cells)
+            .map(|start| start.position)
+    }
+
+    /// The generation stages chunks go through
+    pub fn pipeline(&self) -> &GenerationPipeline {
+        &self.pipeline
+    }
+
+    /// Starts a chunk, then runs every stage that has become possible on it
+    /// and the chunks around it
     pub fn generate_chunk(&mut self, coord: ChunkCoord) {
-        if !self.storage.has_chunk(&coord) {
-            let chunk = self.generator.generate_chunk(coord);
-            self.storage.set_chunk(coord, chunk);
+        if !self.has_chunk(&coord) {
+            self.integrate_chunk(coord, Chunk::new(coord));
+        }
+    }
+
+    /// Adds a chunk that may have been through its first stages elsewhere,
+    /// then runs every stage that has become possible on it and the chunks
+    /// around it. Replaces any chunk already at `coord`. Returns how many
+    /// stages ran.
+    pub fn integrate_chunk(&mut self, coord: ChunkCoord, chunk: Chunk) -> usize {
+        self.insert_chunk(coord, chunk);
+        self.run_stages(usize::MAX)
+    }
+
+    /// Adds a chunk like [`World::integrate_chunk`], but leaves the stages it
+    /// makes possible to [`World::run_stages`]
+    pub fn insert_chunk(&mut self, coord: ChunkCoord, chunk: Chunk) {
+        self.storage.set_chunk(coord, Arc::new(chunk));
+        // Writes from neighbours may have been waiting for this chunk
+        self.apply_ready_writes();
+        // Chunks waiting on this one may run even if it can't go on yet
+        self.stage_queue.extend(self.chunks_around(coord));
+    }
+
+    /// Runs at most `max` pending stages on the chunks added so far and the
+    /// chunks around them. Returns how many ran.
+    pub fn run_stages(&mut self, max: usize) -> usize {
+        let mut ran = 0;
+        while ran < max {
+            let Some(coord) = self.stage_queue.pop_front() else {
+                break;
+            };
+            if !self.advance_chunk(coord) {
+                continue;
+            }
+            ran += 1;
+            // The chunk may go on, and chunks waiting on it may now run
+            let around: Vec<_> = self
+                .chunks_around(coord)
+                .filter(|other| self.has_chunk(other))
+                .collect();
+            self.stage_queue.extend(around);
+        }
+        ran
+    }
+
+    /// `coord` and the chunks in the same layer within the widest stage radius
+    fn chunks_around(&self, coord: ChunkCoord) -> impl Iterator<Item = ChunkCoord> {
+        let radius = self.pipeline.max_neighbor_radius() as i64;
+        (-radius..=radius).flat_map(move |dx| {
+            (-radius..=radius)
+                .map(move |dz| ChunkCoord::new(coord.x() + dx, coord.y(), coord.z() + dz))
+        })
+    }
+
+    fn chunk_status(&self, coord: ChunkCoord) -> Option<ChunkStatus> {
+        self.storage.get_chunk(coord).map(|chunk| chunk.status)
+    }
+
+    /// Runs the next stage on a chunk if its neighbours are far enough along
+    fn advance_chunk(&mut self, coord: ChunkCoord) -> bool {
+        let Some(status) = self.chunk_status(coord) else {
+            return false;
+        };
+        if !self
+            .pipeline
+            .neighbors_ready(coord, status, |other| self.chunk_status(other))
+        {
+            return false;
         }
+        let Some(chunk) = self.storage.get_chunk_mut(coord) else {
+            return false;
+        };
+        let Some(spill) = self.pipeline.advance(Arc::make_mut(chunk), coord) else {
+            return false;
+        };
+        for (pos, block) in spill {
+            self.feature_writes.push(coord, pos, block);
+        }
+        self.apply_ready_writes();
+        true
+    }
+
+    /// Queues feature writes taken out of the world earlier, such as ones
+    /// saved while their chunk was far away
+    pub fn queue_feature_writes(&mut self, coord: ChunkCoord, writes: Vec<FeatureWrite>) {
+        self.feature_writes.extend(coord, writes);
+        self.apply_ready_writes();
+    }
+
+    /// Takes out the pending feature writes of every chunk `evict` returns
+    /// true for, so they can be kept elsewhere until the chunk is back
+    pub fn take_feature_writes(
+        &mut self,
+        evict: impl Fn(ChunkCoord) -> bool,
+    ) -> Vec<(ChunkCoord, Vec<FeatureWrite>)> {
+        self.feature_writes.take_where(evict)
+    }
+
+    /// Writes pending feature blocks into the chunks no other chunk can
+    /// write into any more
+    fn apply_ready_writes(&mut self) {
+        // Writes land once all their sources are in; the rest wait
+        let registry = self.block_registry.clone();
+        let ready: Vec<ChunkCoord> = self
+            .feature_writes
+            .chunks()
+            .filter(|target| {
+                self.pipeline
+                    .spills_complete(*target, |other| self.chunk_status(other))
+            })
+            .collect();
+        for target in ready {
+            if let Some(chunk) = self.storage.get_chunk_mut(target) {
+                self.feature_writes
+                    .apply(target, Arc::make_mut(chunk), |id| {
+                        registry.has_tag(id, "replaceable/feature")
+                    });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world_with_chunk(coord: ChunkCoord) -> World {
+        let terrain = TerrainGenerator::new(Default::default(), Arc::new(BlockRegistry::default()));
+        let mut world = World::new(Arc::new(terrain));
+        world.set_chunk(coord, Chunk::new(coord));
+        world
+    }
+
+    #[test]
+    fn test_sub_blocks_are_placed_read_and_removed() {
+        let mut world = world_with_chunk(ChunkCoord::new(0, 0, 0));
+        assert_eq!(world.sub_resolution(), block::DEFAULT_SUB_RESOLUTION);
+        let pos = DVec3::new(3.75, 4.25, 5.75);
+
+        assert!(world.get_sub_block(pos).is_none());
+        assert!(world.place_sub_block(pos, SubBlock::new(7)));
+        assert_eq!(world.get_sub_block(pos).map(|sub| sub.id), Some(7));
+        // Another point of the same cell finds it, the next cell does not
+        assert_eq!(
+            world.get_sub_block(DVec3::new(3.6, 4.4, 5.9)).map(|sub| sub.id),
+            Some(7)
+        );
+        assert!(world.get_sub_block(DVec3::new(3.25, 4.25, 5.75)).is_none());
+
+        assert_eq!(world.remove_sub_block(pos).map(|sub| sub.id), Some(7));
+        assert!(world.get_sub_block(pos).is_none());
+        assert!(world.remove_sub_block(pos).is_none());
+        // The emptied air block is cleared rather than left behind
+        assert!(world.block_at(I64Vec3::new(3, 4, 5)).is_none());
+    }
+
+    #[test]
+    fn test_sub_block_edits_notify_neighbours() {
+        let mut world = world_with_chunk(ChunkCoord::new(0, 0, 0));
+        let pos = DVec3::new(3.75, 4.25, 5.75);
+
+        assert!(world.place_sub_block(pos, SubBlock::new(7)));
+        let update = world.ticks.pop_neighbor_update().unwrap();
+        assert_eq!(update.source, I64Vec3::new(3, 4, 5));
+        while world.ticks.pop_neighbor_update().is_some() {}
+
+        world.remove_sub_block(pos);
+        assert!(world.ticks.pop_neighbor_update().is_some());
+    }
+
+    #[test]
+    fn test_sub_blocks_of_another_resolution_are_kept() {
+        let coord = ChunkCoord::new(0, 0, 0);
+        let mut world = world_with_chunk(coord);
+        let mut chunk = Chunk::new(coord);
+        chunk.sub_resolution = 4;
+        let mut block = Block::new(BlockId::AIR);
+        block.place_sub_block((3, 3, 3), SubBlock::new(5));
+        chunk.set_block(0, 0, 0, Some(block));
+        world.set_chunk(coord, chunk);
+
+        assert!(!world.place_sub_block(DVec3::new(0.25, 0.25, 0.25), SubBlock::new(7)));
+        let chunk = world.get_chunk(&coord).unwrap();
+        assert_eq!(chunk.sub_resolution, 4);
+        assert_eq!(
+            chunk.get_subblock_at(0, 0, 0, 3, 3, 3).map(|sub| sub.id),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_sub_blocks_far_from_the_origin() {
+        let coord = ChunkCoord::new(-100_000, 0, 100_000);
+        let mut world = world_with_chunk(coord);
+        let pos = DVec3::new(-3_200_000.0 + 1.75, 2.25, 3_200_000.0 + 30.25);
+
+        assert!(world.place_sub_block(pos, SubBlock::new(3)));
+        assert_eq!(world.get_sub_block(pos).map(|sub| sub.id), Some(3));
+        assert_eq!(world.remove_sub_block(pos).map(|sub| sub.id), Some(3));
+    }
+
+    #[test]
+    fn test_sub_blocks_need_a_loaded_chunk() {
+        let mut world = world_with_chunk(ChunkCoord::new(0, 0, 0));
+        let unloaded = DVec3::new(40.5, 0.5, 0.5);
+
+        assert!(!world.place_sub_block(unloaded, SubBlock::new(1)));
+        assert!(world.get_sub_block(unloaded).is_none());
+        assert!(world.remove_sub_block(unloaded).is_none());
     }
 }
@@ -0,0 +1,355 @@
+use crate::world::block::Block;
+use crate::world::block_id::BlockId;
+use crate::world::chunk::{Chunk, CHUNK_SIZE, CHUNK_VOLUME};
+use crate::world::chunk_coord::ChunkCoord;
+use glam::{I64Vec3, IVec3, Vec3};
+use noise::{NoiseFn, Perlin};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use std::f32::consts::PI;
+
+/// Chunks (horizontally) a carver may travel from the chunk it starts in.
+/// Every chunk checks all origins in this range, so tunnels cross borders seamlessly.
+pub const CARVER_RANGE: i64 = 4;
+
+/// Lowest Y any cave reaches
+pub const CAVE_MIN_Y: i32 = 0;
+
+/// Solid blocks kept between caves and any water above them
+pub const OCEAN_FLOOR_SEAL: i32 = 4;
+
+/// Cheese caves stay this far below the surface; openings come from tunnels
+const CHEESE_SURFACE_MARGIN: i32 = 6;
+const CHEESE_FREQUENCY: f64 = 1.0 / 48.0;
+/// Vertical frequency multiplier, giving caverns wider than they are tall
+const CHEESE_VERTICAL_SQUASH: f64 = 2.0;
+
+const MAX_TUNNELS_PER_CHUNK: u32 = 3;
+const TUNNEL_LENGTH: std::ops::Range<u32> = 48..112;
+const TUNNEL_RADIUS: std::ops::Range<f32> = 1.5..3.5;
+/// Ravines are this much rarer than tunnels
+const RAVINE_RARITY: f64 = 0.1;
+const RAVINE_LENGTH: std::ops::Range<u32> = 64..112;
+const RAVINE_RADIUS: std::ops::Range<f32> = 1.5..2.5;
+const RAVINE_VERTICAL_SCALE: f32 = 4.0;
+
+/// A carved path: spheres (or vertically stretched ellipsoids) along a walk.
+/// Points are relative to `origin`, the block corner of the chunk column the
+/// walk starts in, so they stay precise anywhere in the world.
+#[derive(Debug, Clone)]
+pub struct Tunnel {
+    pub origin: I64Vec3,
+    pub points: Vec<(Vec3, f32)>,
+    pub vertical_scale: f32,
+}
+
+impl Tunnel {
+    /// Calls `mark` with the local coordinates of every cell of the chunk
+    /// at `chunk_base` whose centre lies inside the tunnel
+    fn mark(&self, chunk_base: I64Vec3, mark: &mut impl FnMut(i32, i32, i32)) {
+        let offset = (self.origin - chunk_base).as_vec3();
+        for (center, radius) in &self.points {
+            let reach = Vec3::new(*radius, radius * self.vertical_scale, *radius);
+            let local = *center + offset;
+            let low = (local - reach).floor().as_ivec3().max(IVec3::ZERO);
+            let high = (local + reach)
+                .ceil()
+                .as_ivec3()
+                .min(IVec3::splat(CHUNK_SIZE as i32 - 1));
+
+            for x in low.x..=high.x {
+                for y in low.y..=high.y {
+                    for z in low.z..=high.z {
+                        let mut offset = IVec3::new(x, y, z).as_vec3() + Vec3::splat(0.5) - local;
+                        offset.y /= self.vertical_scale;
+                        if offset.length_squared() <= radius * radius {
+                            mark(x, y, z);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Carves cheese caves from 3D density noise and tunnels/ravines from
+/// seeded random walks
+pub struct CaveCarver {
+    seed: u64,
+    cheese_noise: Perlin,
+    detail_noise: Perlin,
+    threshold: f64,
+    density: f64,
+    water_level: i32,
+}
+
+impl CaveCarver {
+    /// `threshold` is the density-noise value above which cheese caves open
+    /// (higher means fewer caverns); `density` in 0..1 scales how many
+    /// tunnels and ravines start per chunk
+    pub fn new(seed: u64, threshold: f64, density: f64, water_level: i32) -> Self {
+        Self {
+            seed,
+            cheese_noise: Perlin::new((seed as u32) ^ 0x5EED_CA7E),
+            detail_noise: Perlin::new((seed as u32) ^ 0x0DE7_A11E),
+            threshold,
+            density: density.clamp(0.0, 1.0),
+            water_level,
+        }
+    }
+
+    /// Whether the density noise opens a cheese cave at this position
+    pub fn is_cheese_cave(&self, x: i64, y: i64, z: i64) -> bool {
+        let point = [
+            x as f64 * CHEESE_FREQUENCY,
+            y as f64 * CHEESE_FREQUENCY * CHEESE_VERTICAL_SQUASH,
+            z as f64 * CHEESE_FREQUENCY,
+        ];
+        let detail = [point[0] * 4.0, point[1] * 4.0, point[2] * 4.0];
+        let density = self.cheese_noise.get(point) + self.detail_noise.get(detail) * 0.25;
+        density > self.threshold
+    }
+
+    /// Tunnels and ravines starting in the chunk column `(chunk_x, chunk_z)`.
+    /// Depends only on the seed and the column, never on which chunk asks.
+    pub fn tunnels_from(
+        &self,
+        chunk_x: i64,
+        chunk_z: i64,
+        surface: &impl Fn(i64, i64) -> i32,
+    ) -> Vec<Tunnel> {
+        let mut rng = self.column_rng(chunk_x, chunk_z);
+        let mut tunnels = Vec::new();
+
+        let count = (0..MAX_TUNNELS_PER_CHUNK)
+            .filter(|_| rng.gen_bool(self.density))
+            .count();
+        for _ in 0..count {
+            if let Some(tunnel) = self.walk(&mut rng, chunk_x, chunk_z, surface, false) {
+                tunnels.push(tunnel);
+            }
+        }
+
+        if rng.gen_bool(self.density * RAVINE_RARITY) {
+            if let Some(ravine) = self.walk(&mut rng, chunk_x, chunk_z, surface, true) {
+                tunnels.push(ravine);
+            }
+        }
+
+        tunnels
+    }
+
+    /// Replaces carvable blocks in `chunk` with air. `surface` returns the
+    /// terrain height of a column and `carvable` decides which blocks may be removed.
+    pub fn carve_chunk(
+        &self,
+        chunk: &mut Chunk,
+        coord: ChunkCoord,
+        surface: impl Fn(i64, i64) -> i32,
+        carvable: impl Fn(BlockId) -> bool,
+    ) {
+        let water_level = self.water_level;
+        self.carve_chunk_under_water(chunk, coord, surface, |_, _| water_level, carvable);
+    }
+
+    /// Like [`Self::carve_chunk`], for terrain whose water surface varies by
+    /// column, as over lakes and rivers. `water` returns that surface.
+    pub fn carve_chunk_under_water(
+        &self,
+        chunk: &mut Chunk,
+        coord: ChunkCoord,
+        surface: impl Fn(i64, i64) -> i32,
+        water: impl Fn(i64, i64) -> i32,
+        carvable: impl Fn(BlockId) -> bool,
+    ) {
+        let size = CHUNK_SIZE as i32;
+        let base = coord.to_block_pos(size);
+        if base.y + size as i64 <= CAVE_MIN_Y as i64 {
+            return;
+        }
+
+        // Surface heights and water levels with a one-column border for the
+        // water seal
+        let span = size + 2;
+        let columns: Vec<(i32, i32)> = (0..span * span)
+            .map(|i| {
+                let (x, z) = (
+                    base.x + (i % span - 1) as i64,
+                    base.z + (i / span - 1) as i64,
+                );
+                (surface(x, z), water(x, z))
+            })
+            .collect();
+        let column_at = |x: i32, z: i32| columns[((z + 1) * span + x + 1) as usize];
+
+        let mut tunnel_mask = vec![false; CHUNK_VOLUME];
+        let mask_index = |x: i32, y: i32, z: i32| (x + y * size + z * size * size) as usize;
+        for dx in -CARVER_RANGE..=CARVER_RANGE {
+            for dz in -CARVER_RANGE..=CARVER_RANGE {
+                for tunnel in self.tunnels_from(coord.x() + dx, coord.z() + dz, &surface) {
+                    tunnel.mark(base, &mut |x, y, z| tunnel_mask[mask_index(x, y, z)] = true);
+                }
+            }
+        }
+
+        for x in 0..size {
+            for z in 0..size {
+                let surface_y = column_at(x, z).0 as i64;
+                for y in 0..size {
+                    let world = base + IVec3::new(x, y, z).as_i64vec3();
+                    if world.y < CAVE_MIN_Y as i64 || world.y > surface_y {
+                        continue;
+                    }
+                    if !self.below_water_seal(world.y, x, z, &column_at) {
+                        continue;
+                    }
+
+                    let cheese = world.y < surface_y - CHEESE_SURFACE_MARGIN as i64
+                        && self.is_cheese_cave(world.x, world.y, world.z);
+                    if !cheese && !tunnel_mask[mask_index(x, y, z)] {
+                        continue;
+                    }
+
+                    let (lx, ly, lz) = (x as u32, y as u32, z as u32);
+                    if chunk
+                        .get_block(lx, ly, lz)
+                        .is_some_and(|block| carvable(block.id))
+                    {
+                        chunk.set_block(lx, ly, lz, Some(Block::new(BlockId::AIR)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Keeps caves away from water: any column here or beside it whose
+    /// surface lies under its water level must keep a solid floor above `y`
+    fn below_water_seal(
+        &self,
+        y: i64,
+        x: i32,
+        z: i32,
+        column_at: &impl Fn(i32, i32) -> (i32, i32),
+    ) -> bool {
+        [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)]
+            .iter()
+            .map(|(dx, dz)| column_at(x + dx, z + dz))
+            .all(|(height, water)| height >= water || y < (height - OCEAN_FLOOR_SEAL) as i64)
+    }
+
+    fn walk(
+        &self,
+        rng: &mut ChaCha12Rng,
+        chunk_x: i64,
+        chunk_z: i64,
+        surface: &impl Fn(i64, i64) -> i32,
+        ravine: bool,
+    ) -> Option<Tunnel> {
+        let origin = I64Vec3::new(chunk_x, 0, chunk_z) * CHUNK_SIZE as i64;
+        let start_x = rng.gen_range(0..CHUNK_SIZE as i32);
+        let start_z = rng.gen_range(0..CHUNK_SIZE as i32);
+        let ceiling = surface(origin.x + start_x as i64, origin.z + start_z as i64)
+            - if ravine { 4 } else { 8 };
+        if ceiling <= CAVE_MIN_Y + 4 {
+            return None;
+        }
+        let start_y = rng.gen_range(CAVE_MIN_Y + 4..ceiling);
+
+        let (length, radius_range, vertical_scale, max_pitch) = if ravine {
+            (RAVINE_LENGTH, RAVINE_RADIUS, RAVINE_VERTICAL_SCALE, 0.1_f32)
+        } else {
+            (TUNNEL_LENGTH, TUNNEL_RADIUS, 1.0, 0.6)
+        };
+        let steps = rng.gen_range(length);
+        let base_radius = rng.gen_range(radius_range);
+
+        let mut position = Vec3::new(start_x as f32, start_y as f32, start_z as f32);
+        let mut yaw = rng.gen_range(0.0..2.0 * PI);
+        let mut pitch = rng.gen_range(-max_pitch..max_pitch) * 0.5;
+        let mut points = Vec::with_capacity(steps as usize);
+
+        for step in 0..steps {
+            // Tunnels swell in the middle and taper at both ends
+            let t = step as f32 / steps as f32;
+            let radius = base_radius * (0.5 + 0.5 * (t * PI).sin());
+            points.push((position, radius));
+
+            position += Vec3::new(
+                yaw.cos() * pitch.cos(),
+                pitch.sin(),
+                yaw.sin() * pitch.cos(),
+            );
+            yaw += rng.gen_range(-0.2..0.2);
+            pitch = (pitch + rng.gen_range(-0.1..0.1)).clamp(-max_pitch, max_pitch);
+        }
+
+        Some(Tunnel {
+            origin,
+            points,
+            vertical_scale,
+        })
+    }
+
+    fn column_rng(&self, chunk_x: i64, chunk_z: i64) -> ChaCha12Rng {
+        let hash = (chunk_x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (chunk_z as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+        ChaCha12Rng::seed_from_u64(self.seed ^ hash ^ 0xCA7E_CA7E)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::generator::stone_chunk;
+
+    fn flat_surface(_x: i64, _z: i64) -> i32 {
+        100
+    }
+
+    #[test]
+    fn test_tunnels_are_deterministic() {
+        let carver = CaveCarver::new(7, 0.6, 1.0, 62);
+        let a = carver.tunnels_from(3, -2, &flat_surface);
+        let b = CaveCarver::new(7, 0.6, 1.0, 62).tunnels_from(3, -2, &flat_surface);
+        assert!(!a.is_empty());
+        assert_eq!(a.len(), b.len());
+        assert_eq!(a[0].points, b[0].points);
+    }
+
+    #[test]
+    fn test_tunnels_carve_neighbouring_chunks() {
+        let carver = CaveCarver::new(11, 2.0, 1.0, 0);
+        let tunnel = &carver.tunnels_from(0, 0, &flat_surface)[0];
+
+        // Pick a wide tunnel point that lies outside its starting chunk column
+        let (point, _) = tunnel
+            .points
+            .iter()
+            .filter(|(p, radius)| *radius >= 1.0 && p.y >= 0.0 && p.y < 90.0)
+            .find(|(p, _)| !(0.0..32.0).contains(&p.x) || !(0.0..32.0).contains(&p.z))
+            .expect("tunnel never leaves its chunk");
+        let cell = tunnel.origin + point.floor().as_i64vec3();
+        let coord = ChunkCoord::from_block_pos(cell, 32);
+
+        let mut chunk = stone_chunk(coord, BlockId::new(1, 0, 0));
+        carver.carve_chunk(&mut chunk, coord, flat_surface, |id| id != BlockId::AIR);
+        let local = cell - coord.to_block_pos(32);
+        let block = chunk.get_block(local.x as u32, local.y as u32, local.z as u32);
+        assert_eq!(block.map(|b| b.id), Some(BlockId::AIR));
+    }
+
+    #[test]
+    fn test_caves_keep_ocean_floor_sealed() {
+        let carver = CaveCarver::new(3, -10.0, 1.0, 62);
+        let coord = ChunkCoord::new(0, 1, 0);
+        let mut chunk = stone_chunk(coord, BlockId::new(1, 0, 0));
+        carver.carve_chunk(&mut chunk, coord, |_, _| 50, |id| id != BlockId::AIR);
+
+        // Every cell is a cheese cave at this threshold, but the seal below the seabed survives
+        for y in (50 - OCEAN_FLOOR_SEAL - 32)..=(50 - 32) {
+            let block = chunk.get_block(0, y as u32, 0).unwrap();
+            assert_ne!(block.id, BlockId::AIR, "breach at y={}", y + 32);
+        }
+        assert_eq!(chunk.get_block(0, 0, 0).unwrap().id, BlockId::AIR);
+    }
+}
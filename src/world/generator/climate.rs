@@ -0,0 +1,355 @@
+use crate::world::generator::terrain::BiomeType;
+use crate::world::geo::GeoProjection;
+use serde::{Deserialize, Serialize};
+
+/// Latitude up to which the trade winds blow from the east
+const TRADE_WIND_LATITUDE: f64 = 30.0;
+/// Latitude beyond which the polar easterlies replace the westerlies
+const POLAR_EASTERLY_LATITUDE: f64 = 60.0;
+
+/// Half-open interval of a climate variable; unset bounds are unbounded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClimateRange {
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+}
+
+impl ClimateRange {
+    pub fn below(max: f64) -> Self {
+        Self {
+            min: None,
+            max: Some(max),
+        }
+    }
+
+    pub fn above(min: f64) -> Self {
+        Self {
+            min: Some(min),
+            max: None,
+        }
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value < max)
+    }
+}
+
+/// One cell of the Whittaker table: a biome and the climate it grows in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClimateBiome {
+    pub biome: BiomeType,
+    /// Mean annual temperature, °C
+    #[serde(default)]
+    pub temperature: ClimateRange,
+    /// Annual precipitation, mm
+    #[serde(default)]
+    pub precipitation: ClimateRange,
+    /// Metres above sea level
+    #[serde(default)]
+    pub altitude: ClimateRange,
+}
+
+impl ClimateBiome {
+    fn new(biome: BiomeType) -> Self {
+        Self {
+            biome,
+            temperature: ClimateRange::default(),
+            precipitation: ClimateRange::default(),
+            altitude: ClimateRange::default(),
+        }
+    }
+
+    fn matches(&self, climate: &Climate) -> bool {
+        self.temperature.contains(climate.temperature)
+            && self.precipitation.contains(climate.precipitation)
+            && self.altitude.contains(climate.altitude)
+    }
+}
+
+/// Simplified Whittaker biome table. The first matching entry wins.
+pub fn default_climate_biomes() -> Vec<ClimateBiome> {
+    vec![
+        ClimateBiome {
+            altitude: ClimateRange::below(0.0),
+            ..ClimateBiome::new(BiomeType::Ocean)
+        },
+        ClimateBiome {
+            temperature: ClimateRange::below(-2.0),
+            ..ClimateBiome::new(BiomeType::Tundra)
+        },
+        ClimateBiome {
+            altitude: ClimateRange::above(2000.0),
+            ..ClimateBiome::new(BiomeType::Mountains)
+        },
+        ClimateBiome {
+            temperature: ClimateRange::above(5.0),
+            precipitation: ClimateRange::below(400.0),
+            ..ClimateBiome::new(BiomeType::Desert)
+        },
+        ClimateBiome {
+            temperature: ClimateRange::above(18.0),
+            precipitation: ClimateRange::above(2500.0),
+            altitude: ClimateRange::below(50.0),
+            ..ClimateBiome::new(BiomeType::Swamp)
+        },
+        // Boreal forest needs far less rain than temperate or tropical forest
+        ClimateBiome {
+            temperature: ClimateRange::below(5.0),
+            precipitation: ClimateRange::above(400.0),
+            ..ClimateBiome::new(BiomeType::Forest)
+        },
+        ClimateBiome {
+            precipitation: ClimateRange::above(900.0),
+            ..ClimateBiome::new(BiomeType::Forest)
+        },
+        ClimateBiome::new(BiomeType::Plains),
+    ]
+}
+
+/// Parameters of the climate model biomes are picked from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClimateConfig {
+    /// Mean annual temperature at sea level on the equator, °C
+    pub equator_temperature: f64,
+    /// Mean annual temperature at sea level at the poles, °C
+    pub pole_temperature: f64,
+    /// Temperature drop per 1000 m of altitude, °C
+    pub lapse_rate: f64,
+    /// Amplitude of the temperature noise, °C
+    pub temperature_variation: f64,
+    /// Amplitude of the precipitation noise, as a natural log factor
+    pub precipitation_variation: f64,
+    /// Blocks upwind searched for mountains casting a rain shadow
+    pub rain_shadow_distance: f64,
+    pub rain_shadow_samples: u32,
+    /// Metres by which an upwind barrier must rise above a column to cut
+    /// its precipitation to 1/e
+    pub rain_shadow_height: f64,
+    /// Blocks per degree of latitude along Z, for worlds much smaller than
+    /// a planet. Unset, latitude comes from the world's projection.
+    pub blocks_per_degree: Option<f64>,
+    pub biomes: Vec<ClimateBiome>,
+}
+
+impl Default for ClimateConfig {
+    fn default() -> Self {
+        Self {
+            equator_temperature: 27.0,
+            pole_temperature: -25.0,
+            lapse_rate: 6.5,
+            temperature_variation: 3.0,
+            precipitation_variation: 0.4,
+            rain_shadow_distance: 256.0,
+            rain_shadow_samples: 8,
+            rain_shadow_height: 800.0,
+            blocks_per_degree: None,
+            biomes: default_climate_biomes(),
+        }
+    }
+}
+
+/// Climate of a block column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Climate {
+    /// Mean annual temperature, °C
+    pub temperature: f64,
+    /// Annual precipitation, mm
+    pub precipitation: f64,
+    /// Metres above sea level
+    pub altitude: f64,
+}
+
+/// Temperature from latitude and altitude, precipitation from latitude
+/// bands and the rain shadow of upwind terrain
+#[derive(Debug, Clone)]
+pub struct ClimateModel {
+    config: ClimateConfig,
+    projection: GeoProjection,
+}
+
+impl ClimateModel {
+    pub fn new(config: ClimateConfig, projection: GeoProjection) -> Self {
+        Self { config, projection }
+    }
+
+    /// Latitude of a block column in degrees. With `blocks_per_degree` the
+    /// world folds back at the poles, so walking north never ends.
+    pub fn latitude(&self, x: i64, z: i64) -> f64 {
+        match self.config.blocks_per_degree {
+            Some(blocks_per_degree) => {
+                let lat = self.projection.origin_lat - z as f64 / blocks_per_degree;
+                let folded = (lat + 90.0).rem_euclid(360.0);
+                if folded > 180.0 {
+                    270.0 - folded
+                } else {
+                    folded - 90.0
+                }
+            }
+            None => self.projection.column_to_geo(x, z).lat,
+        }
+    }
+
+    /// Mean annual temperature without noise, °C. Falls off slowly near the
+    /// equator and faster towards the poles, and with altitude above sea level.
+    pub fn temperature(&self, latitude: f64, altitude: f64) -> f64 {
+        let config = &self.config;
+        let polar = (latitude.abs() / 90.0).min(1.0).powf(1.5);
+        config.equator_temperature
+            - (config.equator_temperature - config.pole_temperature) * polar
+            - config.lapse_rate * altitude.max(0.0) / 1000.0
+    }
+
+    /// Annual precipitation of a latitude band before terrain and noise, mm:
+    /// wet at the equator and in the mid-latitudes, dry in the subtropical
+    /// desert belts and at the poles
+    pub fn zonal_precipitation(latitude: f64) -> f64 {
+        let lat = latitude.abs();
+        250.0
+            + 1750.0 * (-(lat / 12.0).powi(2)).exp()
+            + 900.0 * (-((lat - 50.0) / 12.0).powi(2)).exp()
+    }
+
+    /// Direction along X the prevailing wind comes from: +1 for the trade
+    /// winds and polar easterlies, -1 for the westerlies
+    pub fn upwind(latitude: f64) -> i64 {
+        let lat = latitude.abs();
+        if (TRADE_WIND_LATITUDE..POLAR_EASTERLY_LATITUDE).contains(&lat) {
+            -1
+        } else {
+            1
+        }
+    }
+
+    /// Climate of a column. `height` gives the terrain height of any
+    /// column and must not depend on biomes; the noise samples lie in -1..1.
+    pub fn sample(
+        &self,
+        x: i64,
+        z: i64,
+        height: &impl Fn(i64, i64) -> f64,
+        temperature_noise: f64,
+        moisture_noise: f64,
+    ) -> Climate {
+        let config = &self.config;
+        let latitude = self.latitude(x, z);
+        let altitude = self.altitude(height(x, z));
+
+        // Air crossing higher ground upwind has rained out before it arrives
+        let upwind = Self::upwind(latitude);
+        let samples = config.rain_shadow_samples.max(1);
+        let barrier = (1..=samples)
+            .map(|i| {
+                let distance = (config.rain_shadow_distance * i as f64 / samples as f64) as i64;
+                self.altitude(height(x + upwind * distance, z))
+            })
+            .fold(altitude, f64::max)
+            - altitude;
+        let shadow = (-barrier / config.rain_shadow_height).exp();
+
+        Climate {
+            temperature: self.temperature(latitude, altitude)
+                + temperature_noise * config.temperature_variation,
+            precipitation: Self::zonal_precipitation(latitude)
+                * (moisture_noise * config.precipitation_variation).exp()
+                * shadow,
+            altitude,
+        }
+    }
+
+    /// Biome of a climate from the Whittaker table
+    pub fn biome(&self, climate: &Climate) -> BiomeType {
+        self.config
+            .biomes
+            .iter()
+            .find(|entry| entry.matches(climate))
+            .map_or(BiomeType::Plains, |entry| entry.biome)
+    }
+
+    /// Metres above sea level of a block height
+    fn altitude(&self, height: f64) -> f64 {
+        (height - self.projection.sea_level as f64) / self.projection.vertical_scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(blocks_per_degree: Option<f64>) -> ClimateModel {
+        ClimateModel::new(
+            ClimateConfig {
+                blocks_per_degree,
+                ..Default::default()
+            },
+            GeoProjection::default(),
+        )
+    }
+
+    #[test]
+    fn test_temperature_falls_with_latitude_and_altitude() {
+        let model = model(None);
+        let by_latitude: Vec<f64> = (0..=9)
+            .map(|i| model.temperature(i as f64 * 10.0, 0.0))
+            .collect();
+        assert!(by_latitude.windows(2).all(|w| w[0] > w[1]));
+        assert_eq!(by_latitude[0], 27.0);
+        assert_eq!(by_latitude[9], -25.0);
+        assert_eq!(model.temperature(-40.0, 0.0), model.temperature(40.0, 0.0));
+
+        let by_altitude = model.temperature(0.0, 0.0) - model.temperature(0.0, 2000.0);
+        assert!((by_altitude - 13.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_latitude_folds_at_the_poles() {
+        let model = model(Some(100.0));
+        assert_eq!(model.latitude(0, 0), 0.0);
+        assert_eq!(model.latitude(0, -4500), 45.0);
+        assert_eq!(model.latitude(0, 4500), -45.0);
+        assert_eq!(model.latitude(0, -9000), 90.0);
+        assert_eq!(model.latitude(0, -13500), 45.0);
+        assert_eq!(model.latitude(0, -18000), 0.0);
+        assert_eq!(model.latitude(0, -27000), -90.0);
+    }
+
+    #[test]
+    fn test_rain_shadow() {
+        // A 1500 m ridge at x = 0 in the westerlies, which blow from -X
+        let model = model(Some(100.0));
+        let z = -4500;
+        assert_eq!(ClimateModel::upwind(model.latitude(0, z)), -1);
+        let ridge = |x: i64, _: i64| 62.0 + 1500.0 * (-(x as f64 / 40.0).powi(2)).exp();
+
+        let windward = model.sample(-300, z, &ridge, 0.0, 0.0);
+        let lee = model.sample(100, z, &ridge, 0.0, 0.0);
+        let open = model.sample(100, z, &|_, _| 62.0, 0.0, 0.0);
+        assert_eq!(windward.precipitation, open.precipitation);
+        assert!(lee.precipitation < open.precipitation * 0.25);
+    }
+
+    #[test]
+    fn test_whittaker_biomes() {
+        let model = model(Some(100.0));
+        let flat = |_: i64, _: i64| 62.0;
+        let biome = |z: i64, height: f64| {
+            let climate = model.sample(0, z, &|_, _| height, 0.0, 0.0);
+            model.biome(&climate)
+        };
+
+        assert_eq!(biome(0, 70.0), BiomeType::Forest);
+        assert_eq!(biome(-2500, 70.0), BiomeType::Desert);
+        assert_eq!(biome(-5000, 70.0), BiomeType::Forest);
+        assert_eq!(biome(-8500, 70.0), BiomeType::Tundra);
+        assert_eq!(biome(0, 40.0), BiomeType::Ocean);
+        // Mountain tops are tundra even on the equator
+        assert_eq!(biome(0, 62.0 + 2500.0), BiomeType::Mountains);
+        assert_eq!(biome(0, 62.0 + 5000.0), BiomeType::Tundra);
+        // A very wet lowland in the tropics
+        let climate = model.sample(0, 0, &flat, 0.0, 1.0);
+        assert_eq!(model.biome(&climate), BiomeType::Swamp);
+        assert_eq!(biome(-3500, 70.0), BiomeType::Plains);
+    }
+}
@@ -1,15 +1,231 @@
 use crate::world::block::Block;
 use crate::world::block_id::BlockId;
-use crate::world::chunk::Chunk;
+use crate::world::chunk::{Chunk, ChunkStatus};
 use crate::world::chunk_coord::ChunkCoord;
 use crate::world::generator::terrain::TerrainGenerator;
-use glam::Vec3;
-use rand::Rng;
+use glam::I64Vec3;
 use std::sync::Arc;
 
+/// Blocks a stage placed outside its own chunk, at world positions
+pub type StageSpill = Vec<(I64Vec3, Block)>;
+
+/// One step of chunk generation. A stage may only write outside its chunk
+/// within its neighbour radius.
+pub trait GenerationStage: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Status a chunk has once this stage has run on it
+    fn status(&self) -> ChunkStatus;
+
+    /// Horizontal radius, in chunks, of the neighbours that must have
+    /// reached the status before this stage before it may run
+    fn neighbor_radius(&self) -> i32 {
+        0
+    }
+
+    fn generate(&self, chunk: &mut Chunk, coord: ChunkCoord) -> StageSpill;
+}
+
+/// Generates chunks by running ordered stages over them. Chunks record the
+/// last stage they went through in [`Chunk::status`], and the caller runs
+/// each further stage once [`WorldGenerator::neighbors_ready`] allows it.
 pub trait WorldGenerator: Send + Sync {
-    fn generate_chunk(&self, coord: ChunkCoord) -> Chunk;
-    fn get_block(&self, x: i32, y: i32, z: i32) -> Block;
+    /// Stages in the order they run, one per status
+    fn stages(&self) -> &[Arc<dyn GenerationStage>];
+
+    /// The first stage a chunk at `status` has not been through
+    fn next_stage(&self, status: ChunkStatus) -> Option<&Arc<dyn GenerationStage>> {
+        self.stages().iter().find(|stage| stage.status() > status)
+    }
+
+    /// Whether the next stage of a chunk may run, given the status of each
+    /// chunk around it. `neighbor` returns `None` for chunks not generated yet.
+    fn neighbors_ready(
+        &self,
+        coord: ChunkCoord,
+        status: ChunkStatus,
+        neighbor: impl Fn(ChunkCoord) -> Option<ChunkStatus>,
+    ) -> bool
+    where
+        Self: Sized,
+    {
+        let Some(stage) = self.next_stage(status) else {
+            return false;
+        };
+        let radius = stage.neighbor_radius() as i64;
+        (-radius..=radius).all(|dx| {
+            (-radius..=radius).all(|dz| {
+                let other = ChunkCoord::new(coord.x() + dx, coord.y(), coord.z() + dz);
+                (dx == 0 && dz == 0) || neighbor(other).is_some_and(|other| other >= status)
+            })
+        })
+    }
+
+    /// Runs the next stage on a chunk, without checking its neighbours.
+    /// Returns `None` if every stage has already run.
+    fn advance(&self, chunk: &mut Chunk, coord: ChunkCoord) -> Option<StageSpill> {
+        let stage = self.next_stage(chunk.status)?.clone();
+        let spill = stage.generate(chunk, coord);
+        chunk.status = match self.next_stage(stage.status()) {
+            Some(_) => stage.status(),
+            None => ChunkStatus::Full,
+        };
+        Some(spill)
+    }
+
+    /// Status chunks need before neighbours may write into them: the last
+    /// status before a stage that reads its neighbours
+    fn spill_target_status(&self) -> ChunkStatus {
+        self.stages()
+            .iter()
+            .take_while(|stage| stage.neighbor_radius() == 0)
+            .last()
+            .map_or(ChunkStatus::Empty, |stage| stage.status())
+    }
+
+    /// Whether every chunk that may write into `coord` has done so: all
+    /// chunks within the widest stage radius, above and below included, are
+    /// past the last stage that reads its neighbours. `neighbor` returns
+    /// `None` for chunks not generated yet.
+    fn spills_complete(
+        &self,
+        coord: ChunkCoord,
+        neighbor: impl Fn(ChunkCoord) -> Option<ChunkStatus>,
+    ) -> bool
+    where
+        Self: Sized,
+    {
+        let Some(last) = self
+            .stages()
+            .iter()
+            .filter(|stage| stage.neighbor_radius() > 0)
+            .map(|stage| stage.status())
+            .max()
+        else {
+            return neighbor(coord).is_some();
+        };
+        let radius = self.max_neighbor_radius() as i64;
+        (-radius..=radius).all(|dx| {
+            (-radius..=radius).all(|dy| {
+                (-radius..=radius).all(|dz| {
+                    let other = ChunkCoord::new(coord.x() + dx, coord.y() + dy, coord.z() + dz);
+                    neighbor(other).is_some_and(|status| status >= last)
+                })
+            })
+        })
+    }
+
+    /// Widest neighbour radius of any stage
+    fn max_neighbor_radius(&self) -> i32 {
+        self.stages()
+            .iter()
+            .map(|stage| stage.neighbor_radius())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Ordered generation stages that can be assembled and extended
+#[derive(Clone, Default)]
+pub struct GenerationPipeline {
+    stages: Vec<Arc<dyn GenerationStage>>,
+}
+
+impl GenerationPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Base density, surface, carvers, features, structures and lighting of
+    /// a terrain generator
+    pub fn terrain(terrain: Arc<TerrainGenerator>) -> Self {
+        [
+            ChunkStatus::BaseDensity,
+            ChunkStatus::Surface,
+            ChunkStatus::Carvers,
+            ChunkStatus::Features,
+            ChunkStatus::Structures,
+            ChunkStatus::Lighting,
+        ]
+        .into_iter()
+        .fold(Self::new(), |pipeline, status| {
+            pipeline.with(TerrainStage::new(terrain.clone(), status))
+        })
+    }
+
+    /// Adds a stage in status order. A stage for a status that already has
+    /// one replaces it.
+    pub fn register(&mut self, stage: impl GenerationStage + 'static) -> &mut Self {
+        let status = stage.status();
+        self.stages.retain(|existing| existing.status() != status);
+        let index = self
+            .stages
+            .partition_point(|existing| existing.status() < status);
+        self.stages.insert(index, Arc::new(stage));
+        self
+    }
+
+    pub fn with(mut self, stage: impl GenerationStage + 'static) -> Self {
+        self.register(stage);
+        self
+    }
+}
+
+impl WorldGenerator for GenerationPipeline {
+    fn stages(&self) -> &[Arc<dyn GenerationStage>] {
+        &self.stages
+    }
+}
+
+/// A stage of [`TerrainGenerator`]
+pub struct TerrainStage {
+    terrain: Arc<TerrainGenerator>,
+    status: ChunkStatus,
+}
+
+impl TerrainStage {
+    pub fn new(terrain: Arc<TerrainGenerator>, status: ChunkStatus) -> Self {
+        Self { terrain, status }
+    }
+}
+
+impl GenerationStage for TerrainStage {
+    fn name(&self) -> &str {
+        match self.status {
+            ChunkStatus::BaseDensity => "terrain:base_density",
+            ChunkStatus::Surface => "terrain:surface",
+            ChunkStatus::Carvers => "terrain:carvers",
+            ChunkStatus::Features => "terrain:features",
+            ChunkStatus::Structures => "terrain:structures",
+            ChunkStatus::Lighting => "terrain:lighting",
+            _ => "terrain:none",
+        }
+    }
+
+    fn status(&self) -> ChunkStatus {
+        self.status
+    }
+
+    /// Trees and boulders reach into the chunks around them
+    fn neighbor_radius(&self) -> i32 {
+        match self.status {
+            ChunkStatus::Features => 1,
+            _ => 0,
+        }
+    }
+
+    fn generate(&self, chunk: &mut Chunk, coord: ChunkCoord) -> StageSpill {
+        match self.status {
+            ChunkStatus::BaseDensity => self.terrain.generate_base(chunk, coord),
+            ChunkStatus::Surface => self.terrain.generate_surface(chunk, coord),
+            ChunkStatus::Carvers => self.terrain.carve(chunk, coord),
+            ChunkStatus::Features => return self.terrain.place_features(chunk, coord),
+            ChunkStatus::Structures => self.terrain.place_structures(chunk, coord),
+            ChunkStatus::Lighting => self.terrain.light(chunk),
+            _ => {}
+        }
+        Vec::new()
+    }
 }
 
 pub struct ChunkGenerator {
@@ -35,3 +251,174 @@ impl ChunkGenerator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Writes its status into block (0, 0, 0) and, with a radius, one block
+    /// into the chunk to the east
+    struct MarkStage {
+        status: ChunkStatus,
+        radius: i32,
+    }
+
+    impl GenerationStage for MarkStage {
+        fn name(&self) -> &str {
+            "mark"
+        }
+
+        fn status(&self) -> ChunkStatus {
+            self.status
+        }
+
+        fn neighbor_radius(&self) -> i32 {
+            self.radius
+        }
+
+        fn generate(&self, chunk: &mut Chunk, coord: ChunkCoord) -> StageSpill {
+            let mark = Block::new(BlockId::new(self.status as u16, 0, 0));
+            chunk.set_block(0, 0, 0, Some(mark.clone()));
+            match self.radius {
+                0 => Vec::new(),
+                _ => vec![(coord.to_block_pos(32) + I64Vec3::new(32, 0, 0), mark)],
+            }
+        }
+    }
+
+    fn mark(status: ChunkStatus, radius: i32) -> MarkStage {
+        MarkStage { status, radius }
+    }
+
+    #[test]
+    fn test_stages_run_in_status_order() {
+        let pipeline = GenerationPipeline::new()
+            .with(mark(ChunkStatus::Features, 1))
+            .with(mark(ChunkStatus::BaseDensity, 0))
+            .with(mark(ChunkStatus::Carvers, 0));
+        let statuses: Vec<_> = pipeline.stages().iter().map(|s| s.status()).collect();
+        assert_eq!(
+            statuses,
+            [
+                ChunkStatus::BaseDensity,
+                ChunkStatus::Carvers,
+                ChunkStatus::Features
+            ]
+        );
+        assert_eq!(pipeline.spill_target_status(), ChunkStatus::Carvers);
+        assert_eq!(pipeline.max_neighbor_radius(), 1);
+
+        let coord = ChunkCoord::new(0, 0, 0);
+        let mut chunk = Chunk::new(coord);
+        assert!(pipeline.advance(&mut chunk, coord).unwrap().is_empty());
+        assert_eq!(chunk.status, ChunkStatus::BaseDensity);
+        pipeline.advance(&mut chunk, coord);
+        assert_eq!(chunk.status, ChunkStatus::Carvers);
+        let spill = pipeline.advance(&mut chunk, coord).unwrap();
+        assert_eq!(spill[0].0, I64Vec3::new(32, 0, 0));
+        assert_eq!(chunk.status, ChunkStatus::Full);
+        assert_eq!(
+            chunk.get_block(0, 0, 0).map(|block| block.id),
+            Some(BlockId::new(ChunkStatus::Features as u16, 0, 0))
+        );
+        assert!(pipeline.advance(&mut chunk, coord).is_none());
+    }
+
+    #[test]
+    fn test_register_replaces_a_stage() {
+        let mut pipeline = GenerationPipeline::new()
+            .with(mark(ChunkStatus::Surface, 0))
+            .with(mark(ChunkStatus::Features, 0));
+        pipeline.register(mark(ChunkStatus::Surface, 2));
+        assert_eq!(pipeline.stages().len(), 2);
+        assert_eq!(pipeline.stages()[0].neighbor_radius(), 2);
+    }
+
+    #[test]
+    fn test_neighbors_gate_stages() {
+        let pipeline = GenerationPipeline::new()
+            .with(mark(ChunkStatus::Carvers, 0))
+            .with(mark(ChunkStatus::Features, 1));
+        let coord = ChunkCoord::new(4, 1, -2);
+        let mut statuses = HashMap::new();
+        let ready = |statuses: &HashMap<ChunkCoord, ChunkStatus>, status| {
+            pipeline.neighbors_ready(coord, status, |other| statuses.get(&other).copied())
+        };
+
+        // The first stage needs no neighbours
+        assert!(ready(&statuses, ChunkStatus::Empty));
+        // Features wait for all eight neighbours to be carved
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                let other = ChunkCoord::new(coord.x() + dx, coord.y(), coord.z() + dz);
+                if other != coord {
+                    statuses.insert(other, ChunkStatus::Carvers);
+                }
+            }
+        }
+        assert!(ready(&statuses, ChunkStatus::Carvers));
+        statuses.insert(ChunkCoord::new(5, 1, -1), ChunkStatus::Empty);
+        assert!(!ready(&statuses, ChunkStatus::Carvers));
+        statuses.insert(ChunkCoord::new(5, 1, -1), ChunkStatus::Full);
+        assert!(ready(&statuses, ChunkStatus::Carvers));
+        // Nothing is left for a finished chunk
+        assert!(!ready(&statuses, ChunkStatus::Full));
+    }
+
+    #[test]
+    fn test_spills_complete_once_every_neighbour_spilled() {
+        let pipeline = GenerationPipeline::new()
+            .with(mark(ChunkStatus::Carvers, 0))
+            .with(mark(ChunkStatus::Features, 1))
+            .with(mark(ChunkStatus::Lighting, 0));
+        let coord = ChunkCoord::new(4, 1, -2);
+        let mut statuses = HashMap::new();
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let other = ChunkCoord::new(coord.x() + dx, coord.y() + dy, coord.z() + dz);
+                    statuses.insert(other, ChunkStatus::Features);
+                }
+            }
+        }
+        let complete = |statuses: &HashMap<ChunkCoord, ChunkStatus>| {
+            pipeline.spills_complete(coord, |other| statuses.get(&other).copied())
+        };
+
+        assert!(complete(&statuses));
+        // The chunk below may still grow a tree into this one
+        statuses.insert(ChunkCoord::new(4, 0, -2), ChunkStatus::Carvers);
+        assert!(!complete(&statuses));
+        statuses.insert(ChunkCoord::new(4, 0, -2), ChunkStatus::Lighting);
+        assert!(complete(&statuses));
+        statuses.remove(&ChunkCoord::new(5, 2, -1));
+        assert!(!complete(&statuses));
+    }
+
+    #[test]
+    fn test_terrain_pipeline_matches_generate_chunk() {
+        let terrain = Arc::new(TerrainGenerator::new(
+            Default::default(),
+            Arc::new(crate::world::blocks_data::BlockRegistry::default()),
+        ));
+        let pipeline = GenerationPipeline::terrain(terrain.clone());
+        assert_eq!(pipeline.spill_target_status(), ChunkStatus::Carvers);
+        assert_eq!(pipeline.stages().last().unwrap().name(), "terrain:lighting");
+
+        let coord = ChunkCoord::new(1, 3, 2);
+        let mut staged = Chunk::new(coord);
+        while staged.status < ChunkStatus::Carvers {
+            pipeline.advance(&mut staged, coord);
+        }
+        let direct = terrain.generate_chunk(coord);
+        let ids = |chunk: &Chunk| -> Vec<_> {
+            chunk
+                .blocks
+                .iter()
+                .map(|block| block.as_ref().map(|block| block.id))
+                .collect()
+        };
+        assert_eq!(ids(&staged), ids(&direct));
+    }
+}
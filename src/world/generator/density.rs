@@ -0,0 +1,618 @@
+use noise::{Billow, Fbm, MultiFractal, NoiseFn, Perlin, RidgedMulti};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+/// Example density graph shaping continents with a spline and overhangs
+/// with 3D noise, for starting a new terrain from
+pub const EXAMPLE_DENSITY: &str = include_str!("../../assets/density.json");
+
+/// Step the surface search takes down a column before narrowing in
+const SURFACE_SEARCH_STEP: i32 = 4;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DensityError {
+    #[error("Unknown density function '{0}'")]
+    UnknownFunction(String),
+    #[error("Density function '{0}' refers to itself")]
+    Cycle(String),
+    #[error("Spline in density function '{0}' needs points in increasing input order")]
+    InvalidSpline(String),
+    #[error("Clamp in density function '{0}' needs a minimum no greater than its maximum")]
+    InvalidClamp(String),
+    #[error("Density function '{0}' combines no inputs")]
+    NoInputs(String),
+    #[error("Invalid density graph format: {0}")]
+    FormatError(String),
+}
+
+/// Fractal noise a noise node samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseKind {
+    /// Octaves of Perlin noise summed (fBm)
+    Perlin,
+    /// Sharp ridges where the noise crosses zero
+    Ridged,
+    /// Rounded lumps, the absolute value of each octave
+    Billow,
+}
+
+fn default_octaves() -> u32 {
+    4
+}
+
+fn default_scale() -> f64 {
+    0.01
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+fn default_persistence() -> f64 {
+    0.5
+}
+
+fn default_lacunarity() -> f64 {
+    2.0
+}
+
+/// A node of a density function as read from a config file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DensityNode {
+    Constant {
+        value: f64,
+    },
+    /// `offset + amplitude * noise(position * scale)`. Without a vertical
+    /// scale the noise is 2D and computed once per column.
+    Noise {
+        kind: NoiseKind,
+        /// Added to the world seed, so nodes can sample different noise
+        #[serde(default)]
+        seed: u32,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default)]
+        vertical_scale: Option<f64>,
+        #[serde(default = "one")]
+        amplitude: f64,
+        #[serde(default)]
+        offset: f64,
+        #[serde(default = "default_persistence")]
+        persistence: f64,
+        #[serde(default = "default_lacunarity")]
+        lacunarity: f64,
+    },
+    /// Goes linearly from `from_value` at `from_y` to `to_value` at `to_y`,
+    /// holding the end values beyond them
+    YGradient {
+        from_y: i32,
+        to_y: i32,
+        from_value: f64,
+        to_value: f64,
+    },
+    Add {
+        inputs: Vec<DensityNode>,
+    },
+    Mul {
+        inputs: Vec<DensityNode>,
+    },
+    Clamp {
+        input: Box<DensityNode>,
+        min: f64,
+        max: f64,
+    },
+    /// Maps the input through `[input, output]` points, interpolating
+    /// linearly between them and holding the end outputs beyond them
+    Spline {
+        input: Box<DensityNode>,
+        points: Vec<[f64; 2]>,
+    },
+    /// Another named function; shared functions are evaluated once per
+    /// position however often they are referenced
+    Ref {
+        name: String,
+    },
+}
+
+fn default_max_y() -> i32 {
+    256
+}
+
+/// Terrain shape as a graph of named density functions. Positions where
+/// the final function is positive are solid ground.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DensityConfig {
+    pub functions: BTreeMap<String, DensityNode>,
+    pub final_density: String,
+    /// Lowest height the ground surface is searched down to
+    #[serde(default)]
+    pub min_y: i32,
+    /// Height the ground surface is searched down from
+    #[serde(default = "default_max_y")]
+    pub max_y: i32,
+}
+
+impl DensityConfig {
+    pub fn from_json(json: &str) -> Result<Self, DensityError> {
+        serde_json::from_str(json).map_err(|e| DensityError::FormatError(e.to_string()))
+    }
+}
+
+enum NoiseSource {
+    Perlin(Fbm<Perlin>),
+    Ridged(RidgedMulti<Perlin>),
+    Billow(Billow<Perlin>),
+}
+
+impl NoiseSource {
+    fn new(kind: NoiseKind, seed: u32, octaves: usize, persistence: f64, lacunarity: f64) -> Self {
+        match kind {
+            NoiseKind::Perlin => NoiseSource::Perlin(
+                Fbm::new(seed)
+                    .set_octaves(octaves)
+                    .set_frequency(1.0)
+                    .set_persistence(persistence)
+                    .set_lacunarity(lacunarity),
+            ),
+            NoiseKind::Ridged => NoiseSource::Ridged(
+                RidgedMulti::new(seed)
+                    .set_octaves(octaves)
+                    .set_frequency(1.0)
+                    .set_persistence(persistence)
+                    .set_lacunarity(lacunarity),
+            ),
+            NoiseKind::Billow => NoiseSource::Billow(
+                Billow::new(seed)
+                    .set_octaves(octaves)
+                    .set_frequency(1.0)
+                    .set_persistence(persistence)
+                    .set_lacunarity(lacunarity),
+            ),
+        }
+    }
+
+    fn get2(&self, point: [f64; 2]) -> f64 {
+        match self {
+            NoiseSource::Perlin(noise) => noise.get(point),
+            NoiseSource::Ridged(noise) => noise.get(point),
+            NoiseSource::Billow(noise) => noise.get(point),
+        }
+    }
+
+    fn get3(&self, point: [f64; 3]) -> f64 {
+        match self {
+            NoiseSource::Perlin(noise) => noise.get(point),
+            NoiseSource::Ridged(noise) => noise.get(point),
+            NoiseSource::Billow(noise) => noise.get(point),
+        }
+    }
+}
+
+enum Op {
+    Constant(f64),
+    Noise {
+        source: NoiseSource,
+        scale: f64,
+        vertical_scale: Option<f64>,
+        amplitude: f64,
+        offset: f64,
+    },
+    YGradient {
+        from_y: i32,
+        to_y: i32,
+        from_value: f64,
+        to_value: f64,
+    },
+    Add(Vec<usize>),
+    Mul(Vec<usize>),
+    Clamp {
+        input: usize,
+        min: f64,
+        max: f64,
+    },
+    Spline {
+        input: usize,
+        points: Vec<[f64; 2]>,
+    },
+}
+
+impl Op {
+    fn inputs(&self) -> &[usize] {
+        match self {
+            Op::Add(inputs) | Op::Mul(inputs) => inputs,
+            Op::Clamp { input, .. } | Op::Spline { input, .. } => std::slice::from_ref(input),
+            _ => &[],
+        }
+    }
+
+    fn eval(&self, values: &[f64], x: i64, y: i64, z: i64) -> f64 {
+        match self {
+            Op::Constant(value) => *value,
+            Op::Noise {
+                source,
+                scale,
+                vertical_scale,
+                amplitude,
+                offset,
+            } => {
+                let (x, z) = (x as f64 * scale, z as f64 * scale);
+                let noise = match vertical_scale {
+                    Some(vertical) => source.get3([x, y as f64 * vertical, z]),
+                    None => source.get2([x, z]),
+                };
+                offset + amplitude * noise
+            }
+            Op::YGradient {
+                from_y,
+                to_y,
+                from_value,
+                to_value,
+            } => {
+                if from_y == to_y {
+                    return if y < *from_y as i64 {
+                        *from_value
+                    } else {
+                        *to_value
+                    };
+                }
+                let t = ((y - *from_y as i64) as f64 / (to_y - from_y) as f64).clamp(0.0, 1.0);
+                from_value + (to_value - from_value) * t
+            }
+            Op::Add(inputs) => inputs.iter().map(|&i| values[i]).sum(),
+            Op::Mul(inputs) => inputs.iter().map(|&i| values[i]).product(),
+            Op::Clamp { input, min, max } => values[*input].clamp(*min, *max),
+            Op::Spline { input, points } => spline(points, values[*input]),
+        }
+    }
+}
+
+fn spline(points: &[[f64; 2]], input: f64) -> f64 {
+    let upper = points.partition_point(|point| point[0] < input);
+    match (points.get(upper.wrapping_sub(1)), points.get(upper)) {
+        (Some(a), Some(b)) => a[1] + (b[1] - a[1]) * (input - a[0]) / (b[0] - a[0]),
+        (Some(end), None) | (None, Some(end)) => end[1],
+        (None, None) => 0.0,
+    }
+}
+
+/// A density config compiled for evaluation. Nodes are stored inputs
+/// first, so one pass in order evaluates the whole graph with every shared
+/// function computed once.
+pub struct DensityGraph {
+    ops: Vec<Op>,
+    /// Nodes that do not vary with height, evaluated once per column
+    column_only: Vec<bool>,
+    root: usize,
+    min_y: i32,
+    max_y: i32,
+}
+
+impl DensityGraph {
+    pub fn new(config: &DensityConfig, seed: u64) -> Result<Self, DensityError> {
+        let mut compiler = Compiler {
+            config,
+            seed: seed as u32,
+            ops: Vec::new(),
+            named: HashMap::new(),
+            visiting: Vec::new(),
+        };
+        let root = compiler.function(&config.final_density)?;
+        let ops = compiler.ops;
+
+        let mut column_only = Vec::with_capacity(ops.len());
+        for op in &ops {
+            let own = match op {
+                Op::Noise { vertical_scale, .. } => vertical_scale.is_none(),
+                Op::YGradient { .. } => false,
+                _ => true,
+            };
+            let inputs = op.inputs().iter().all(|&i| column_only[i]);
+            column_only.push(own && inputs);
+        }
+
+        Ok(Self {
+            ops,
+            column_only,
+            root,
+            min_y: config.min_y,
+            max_y: config.max_y,
+        })
+    }
+
+    /// Evaluates the parts of the graph that are the same all the way up a
+    /// column, for sampling the column at many heights
+    pub fn column(&self, x: i64, z: i64) -> ColumnDensity<'_> {
+        let mut values = vec![0.0; self.ops.len()];
+        for (i, op) in self.ops.iter().enumerate() {
+            if self.column_only[i] {
+                values[i] = op.eval(&values, x, 0, z);
+            }
+        }
+        ColumnDensity {
+            graph: self,
+            x,
+            z,
+            values,
+        }
+    }
+
+    pub fn density_at(&self, x: i64, y: i64, z: i64) -> f64 {
+        self.column(x, z).at(y)
+    }
+
+    /// Height of the highest solid block of a column within the search
+    /// range, or the bottom of the range if the column is empty
+    pub fn surface_height(&self, x: i64, z: i64) -> i32 {
+        let mut column = self.column(x, z);
+        let mut y = self.max_y;
+        while y >= self.min_y {
+            if column.at(y as i64) > 0.0 {
+                // Solid here; the surface is at most a step above
+                let top = (y + SURFACE_SEARCH_STEP - 1).min(self.max_y);
+                return (y..=top)
+                    .rev()
+                    .find(|&y| column.at(y as i64) > 0.0)
+                    .unwrap_or(y);
+            }
+            y -= SURFACE_SEARCH_STEP;
+        }
+        self.min_y
+    }
+}
+
+/// Density graph values of one column
+pub struct ColumnDensity<'a> {
+    graph: &'a DensityGraph,
+    x: i64,
+    z: i64,
+    values: Vec<f64>,
+}
+
+impl ColumnDensity<'_> {
+    pub fn at(&mut self, y: i64) -> f64 {
+        for (i, op) in self.graph.ops.iter().enumerate() {
+            if !self.graph.column_only[i] {
+                self.values[i] = op.eval(&self.values, self.x, y, self.z);
+            }
+        }
+        self.values[self.graph.root]
+    }
+}
+
+struct Compiler<'a> {
+    config: &'a DensityConfig,
+    seed: u32,
+    ops: Vec<Op>,
+    named: HashMap<&'a str, usize>,
+    /// Named functions being compiled, to catch references back to them
+    visiting: Vec<&'a str>,
+}
+
+impl<'a> Compiler<'a> {
+    fn function(&mut self, name: &str) -> Result<usize, DensityError> {
+        let config: &'a DensityConfig = self.config;
+        let (name, node) = config
+            .functions
+            .get_key_value(name)
+            .ok_or_else(|| DensityError::UnknownFunction(name.to_string()))?;
+        if let Some(&index) = self.named.get(name.as_str()) {
+            return Ok(index);
+        }
+        if self.visiting.contains(&name.as_str()) {
+            return Err(DensityError::Cycle(name.clone()));
+        }
+
+        self.visiting.push(name);
+        let index = self.node(node, name)?;
+        self.visiting.pop();
+        self.named.insert(name, index);
+        Ok(index)
+    }
+
+    fn node(&mut self, node: &'a DensityNode, function: &str) -> Result<usize, DensityError> {
+        let op = match node {
+            DensityNode::Ref { name } => return self.function(name),
+            DensityNode::Constant { value } => Op::Constant(*value),
+            DensityNode::Noise {
+                kind,
+                seed,
+                octaves,
+                scale,
+                vertical_scale,
+                amplitude,
+                offset,
+                persistence,
+                lacunarity,
+            } => Op::Noise {
+                source: NoiseSource::new(
+                    *kind,
+                    self.seed.wrapping_add(seed.wrapping_mul(0x85EB_CA6B)),
+                    *octaves as usize,
+                    *persistence,
+                    *lacunarity,
+                ),
+                scale: *scale,
+                vertical_scale: *vertical_scale,
+                amplitude: *amplitude,
+                offset: *offset,
+            },
+            DensityNode::YGradient {
+                from_y,
+                to_y,
+                from_value,
+                to_value,
+            } => Op::YGradient {
+                from_y: *from_y,
+                to_y: *to_y,
+                from_value: *from_value,
+                to_value: *to_value,
+            },
+            DensityNode::Add { inputs } | DensityNode::Mul { inputs } => {
+                if inputs.is_empty() {
+                    return Err(DensityError::NoInputs(function.to_string()));
+                }
+                let inputs = inputs
+                    .iter()
+                    .map(|input| self.node(input, function))
+                    .collect::<Result<_, _>>()?;
+                match node {
+                    DensityNode::Add { .. } => Op::Add(inputs),
+                    _ => Op::Mul(inputs),
+                }
+            }
+            DensityNode::Clamp { input, min, max } => {
+                // f64::clamp panics on these
+                if min.is_nan() || max.is_nan() || min > max {
+                    return Err(DensityError::InvalidClamp(function.to_string()));
+                }
+                Op::Clamp {
+                    input: self.node(input, function)?,
+                    min: *min,
+                    max: *max,
+                }
+            }
+            DensityNode::Spline { input, points } => {
+                if points.is_empty() || points.windows(2).any(|pair| pair[0][0] >= pair[1][0]) {
+                    return Err(DensityError::InvalidSpline(function.to_string()));
+                }
+                Op::Spline {
+                    input: self.node(input, function)?,
+                    points: points.clone(),
+                }
+            }
+        };
+        self.ops.push(op);
+        Ok(self.ops.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(json: &str) -> Result<DensityGraph, DensityError> {
+        DensityGraph::new(&DensityConfig::from_json(json)?, 7)
+    }
+
+    #[test]
+    fn test_gradient_spline_and_clamp() {
+        let graph = graph(
+            r#"{
+                "final_density": "out",
+                "functions": {
+                    "height": { "type": "y_gradient", "from_y": 0, "to_y": 100, "from_value": -1.0, "to_value": 1.0 },
+                    "out": {
+                        "type": "clamp",
+                        "min": 0.0,
+                        "max": 5.0,
+                        "input": {
+                            "type": "spline",
+                            "input": { "type": "ref", "name": "height" },
+                            "points": [[-1.0, 10.0], [0.0, 2.0], [1.0, -4.0]]
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(graph.density_at(0, -50, 0), 5.0);
+        assert_eq!(graph.density_at(0, 25, 0), 5.0);
+        assert_eq!(graph.density_at(3, 50, 9), 2.0);
+        assert_eq!(graph.density_at(0, 75, 0), 0.0);
+        assert!((graph.density_at(0, 60, 0) - 0.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_surface_height_of_flat_ground() {
+        // Solid up to y = 70 plus a column-only offset of 0.5
+        let graph = graph(
+            r#"{
+                "final_density": "ground",
+                "functions": {
+                    "ground": {
+                        "type": "add",
+                        "inputs": [
+                            { "type": "y_gradient", "from_y": 0, "to_y": 100, "from_value": 70.0, "to_value": -30.0 },
+                            { "type": "constant", "value": 0.5 }
+                        ]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(graph.surface_height(12, -40), 70);
+        assert!(graph.column_only[1]);
+        assert!(!graph.column_only[graph.root]);
+    }
+
+    #[test]
+    fn test_shared_functions_are_compiled_once() {
+        let config = DensityConfig::from_json(EXAMPLE_DENSITY).unwrap();
+        let graph = DensityGraph::new(&config, 3).unwrap();
+        // Continents are referenced through base height and surface but
+        // compiled into one node
+        let noise_nodes = graph
+            .ops
+            .iter()
+            .filter(|op| matches!(op, Op::Noise { .. }))
+            .count();
+        assert_eq!(noise_nodes, 3);
+
+        // 2D noise is column-only; the final density varies with height
+        let mut column = graph.column(100, 200);
+        let surface = graph.surface_height(100, 200);
+        assert!(column.at(surface as i64) > 0.0);
+        assert!(column.at(surface as i64 + 1) <= 0.0);
+        assert!((config.min_y..config.max_y).contains(&surface));
+
+        // Same seed, same terrain; another seed differs somewhere
+        let again = DensityGraph::new(&config, 3).unwrap();
+        let other = DensityGraph::new(&config, 4).unwrap();
+        assert_eq!(again.surface_height(100, 200), surface);
+        assert!((0..16).any(|i| other.surface_height(i * 97, 0) != graph.surface_height(i * 97, 0)));
+    }
+
+    #[test]
+    fn test_invalid_graphs() {
+        let function = |node: &str| {
+            graph(&format!(
+                r#"{{"final_density": "a", "functions": {{"a": {}, "b": {{"type": "ref", "name": "a"}}}}}}"#,
+                node
+            ))
+            .err()
+        };
+        assert_eq!(
+            function(r#"{"type": "ref", "name": "missing"}"#),
+            Some(DensityError::UnknownFunction("missing".into()))
+        );
+        assert_eq!(
+            function(r#"{"type": "ref", "name": "b"}"#),
+            Some(DensityError::Cycle("a".into()))
+        );
+        assert_eq!(
+            function(r#"{"type": "add", "inputs": []}"#),
+            Some(DensityError::NoInputs("a".into()))
+        );
+        assert_eq!(
+            function(
+                r#"{"type": "spline", "input": {"type": "constant", "value": 0}, "points": [[1, 0], [0, 1]]}"#
+            ),
+            Some(DensityError::InvalidSpline("a".into()))
+        );
+        assert_eq!(
+            function(
+                r#"{"type": "clamp", "input": {"type": "constant", "value": 0}, "min": 1, "max": 0}"#
+            ),
+            Some(DensityError::InvalidClamp("a".into()))
+        );
+        assert!(matches!(
+            function(r#"{"type": "cube"}"#),
+            Some(DensityError::FormatError(_))
+        ));
+    }
+}
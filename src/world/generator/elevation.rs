@@ -0,0 +1,476 @@
+use crate::world::geo::GeoProjection;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+
+/// SRTM void marker
+const HGT_VOID: i16 = -32768;
+
+#[derive(Debug, Error)]
+pub enum ElevationError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("TIFF error: {0}")]
+    Tiff(#[from] tiff::TiffError),
+    #[error("Cannot read tile position from file name '{0}'")]
+    InvalidFileName(String),
+    #[error("Tile '{name}' has {len} bytes, which is not a square grid of 16-bit samples")]
+    InvalidSize { name: String, len: usize },
+    #[error("GeoTIFF '{0}' has no ModelPixelScale/ModelTiepoint georeference")]
+    MissingGeoreference(String),
+    #[error("Unsupported elevation format in '{0}'")]
+    UnsupportedFormat(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    Bilinear,
+    Bicubic,
+}
+
+/// A grid of elevation samples in metres on a regular lon/lat raster.
+/// Row 0 is the northern edge; missing samples are NaN.
+#[derive(Debug, Clone)]
+pub struct ElevationTile {
+    /// Longitude of column 0 and latitude of row 0, in degrees
+    pub origin_lon: f64,
+    pub origin_lat: f64,
+    /// Degrees between neighbouring samples
+    pub step_lon: f64,
+    pub step_lat: f64,
+    pub width: usize,
+    pub height: usize,
+    samples: Vec<f32>,
+}
+
+impl ElevationTile {
+    pub fn new(
+        origin_lon: f64,
+        origin_lat: f64,
+        step_lon: f64,
+        step_lat: f64,
+        width: usize,
+        height: usize,
+        samples: Vec<f32>,
+    ) -> Self {
+        debug_assert_eq!(samples.len(), width * height);
+        Self {
+            origin_lon,
+            origin_lat,
+            step_lon,
+            step_lat,
+            width,
+            height,
+            samples,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ElevationError> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("hgt") => Self::from_hgt(path),
+            Some("tif") | Some("tiff") => Self::from_geotiff(path),
+            _ => Err(ElevationError::UnsupportedFormat(
+                path.display().to_string(),
+            )),
+        }
+    }
+
+    /// Reads an SRTM `.hgt` tile. The file name (e.g. `N37W122.hgt`) gives
+    /// the south-west corner of the one-degree tile.
+    pub fn from_hgt(path: &Path) -> Result<Self, ElevationError> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        Self::from_hgt_bytes(name, &fs::read(path)?)
+    }
+
+    pub fn from_hgt_bytes(name: &str, bytes: &[u8]) -> Result<Self, ElevationError> {
+        let (south, west) = parse_hgt_name(name)
+            .ok_or_else(|| ElevationError::InvalidFileName(name.to_string()))?;
+
+        let count = bytes.len() / 2;
+        let size = (count as f64).sqrt() as usize;
+        if bytes.len() != count * 2 || size < 2 || size * size != count {
+            return Err(ElevationError::InvalidSize {
+                name: name.to_string(),
+                len: bytes.len(),
+            });
+        }
+
+        let samples = bytes
+            .chunks_exact(2)
+            .map(|pair| match i16::from_be_bytes([pair[0], pair[1]]) {
+                HGT_VOID => f32::NAN,
+                value => value as f32,
+            })
+            .collect();
+        let step = 1.0 / (size - 1) as f64;
+        Ok(Self::new(
+            west as f64,
+            south as f64 + 1.0,
+            step,
+            step,
+            size,
+            size,
+            samples,
+        ))
+    }
+
+    /// Reads a single-band GeoTIFF in geographic (EPSG:4326) coordinates,
+    /// georeferenced by its ModelPixelScale and ModelTiepoint tags
+    pub fn from_geotiff(path: &Path) -> Result<Self, ElevationError> {
+        let name = path.display().to_string();
+        let mut decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+        let (width, height) = decoder.dimensions()?;
+
+        let scale = decoder
+            .find_tag(Tag::ModelPixelScaleTag)?
+            .map(|value| value.into_f64_vec())
+            .transpose()?;
+        let tiepoint = decoder
+            .find_tag(Tag::ModelTiepointTag)?
+            .map(|value| value.into_f64_vec())
+            .transpose()?;
+        let (Some(scale), Some(tiepoint)) = (scale, tiepoint) else {
+            return Err(ElevationError::MissingGeoreference(name));
+        };
+        if scale.len() < 2 || tiepoint.len() < 6 {
+            return Err(ElevationError::MissingGeoreference(name));
+        }
+        let nodata = decoder
+            .get_tag_ascii_string(Tag::GdalNodata)
+            .ok()
+            .and_then(|value| value.trim_matches(char::from(0)).trim().parse::<f32>().ok());
+
+        let samples: Vec<f32> = match decoder.read_image()? {
+            DecodingResult::U8(data) => data.into_iter().map(f32::from).collect(),
+            DecodingResult::I8(data) => data.into_iter().map(f32::from).collect(),
+            DecodingResult::U16(data) => data.into_iter().map(f32::from).collect(),
+            DecodingResult::I16(data) => data.into_iter().map(f32::from).collect(),
+            DecodingResult::U32(data) => data.into_iter().map(|v| v as f32).collect(),
+            DecodingResult::I32(data) => data.into_iter().map(|v| v as f32).collect(),
+            DecodingResult::F32(data) => data,
+            DecodingResult::F64(data) => data.into_iter().map(|v| v as f32).collect(),
+            _ => return Err(ElevationError::UnsupportedFormat(name)),
+        };
+        if samples.len() != width as usize * height as usize {
+            return Err(ElevationError::UnsupportedFormat(name));
+        }
+        let samples = samples
+            .into_iter()
+            .map(|value| match nodata {
+                Some(nodata) if value == nodata => f32::NAN,
+                _ => value,
+            })
+            .collect();
+
+        // Tiepoint maps raster (i, j) to model (x, y)
+        let (i, j, x, y) = (tiepoint[0], tiepoint[1], tiepoint[3], tiepoint[4]);
+        Ok(Self::new(
+            x - i * scale[0],
+            y + j * scale[1],
+            scale[0],
+            scale[1],
+            width as usize,
+            height as usize,
+            samples,
+        ))
+    }
+
+    pub fn contains(&self, lon: f64, lat: f64) -> bool {
+        let (column, row) = self.raster_position(lon, lat);
+        column >= 0.0
+            && row >= 0.0
+            && column <= (self.width - 1) as f64
+            && row <= (self.height - 1) as f64
+    }
+
+    /// Elevation in metres, or `None` outside the tile or next to voids
+    pub fn sample(&self, lon: f64, lat: f64, interpolation: Interpolation) -> Option<f32> {
+        if !self.contains(lon, lat) {
+            return None;
+        }
+        let (column, row) = self.raster_position(lon, lat);
+        match interpolation {
+            Interpolation::Bicubic => self
+                .bicubic(column, row)
+                .or_else(|| self.bilinear(column, row)),
+            Interpolation::Bilinear => self.bilinear(column, row),
+        }
+    }
+
+    /// Value of the cell containing the position, without interpolation.
+    /// For categorical rasters such as land cover.
+    pub fn sample_nearest(&self, lon: f64, lat: f64) -> Option<f32> {
+        let (column, row) = self.raster_position(lon, lat);
+        if column < 0.0 || row < 0.0 || column >= self.width as f64 || row >= self.height as f64 {
+            return None;
+        }
+        self.get(column as isize, row as isize)
+    }
+
+    fn raster_position(&self, lon: f64, lat: f64) -> (f64, f64) {
+        (
+            (lon - self.origin_lon) / self.step_lon,
+            (self.origin_lat - lat) / self.step_lat,
+        )
+    }
+
+    fn get(&self, column: isize, row: isize) -> Option<f32> {
+        let column = column.clamp(0, self.width as isize - 1) as usize;
+        let row = row.clamp(0, self.height as isize - 1) as usize;
+        let value = self.samples[row * self.width + column];
+        (!value.is_nan()).then_some(value)
+    }
+
+    fn bilinear(&self, column: f64, row: f64) -> Option<f32> {
+        let (c0, r0) = (column.floor() as isize, row.floor() as isize);
+        let (tx, ty) = ((column - c0 as f64) as f32, (row - r0 as f64) as f32);
+
+        let top = lerp(self.get(c0, r0)?, self.get(c0 + 1, r0)?, tx);
+        let bottom = lerp(self.get(c0, r0 + 1)?, self.get(c0 + 1, r0 + 1)?, tx);
+        Some(lerp(top, bottom, ty))
+    }
+
+    fn bicubic(&self, column: f64, row: f64) -> Option<f32> {
+        let (c0, r0) = (column.floor() as isize, row.floor() as isize);
+        let (tx, ty) = ((column - c0 as f64) as f32, (row - r0 as f64) as f32);
+
+        let mut rows = [0.0; 4];
+        for (i, value) in rows.iter_mut().enumerate() {
+            let r = r0 + i as isize - 1;
+            *value = catmull_rom(
+                [
+                    self.get(c0 - 1, r)?,
+                    self.get(c0, r)?,
+                    self.get(c0 + 1, r)?,
+                    self.get(c0 + 2, r)?,
+                ],
+                tx,
+            );
+        }
+        Some(catmull_rom(rows, ty))
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn catmull_rom(p: [f32; 4], t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * ((2.0 * p[1])
+        + (-p[0] + p[2]) * t
+        + (2.0 * p[0] - 5.0 * p[1] + 4.0 * p[2] - p[3]) * t2
+        + (-p[0] + 3.0 * p[1] - 3.0 * p[2] + p[3]) * t3)
+}
+
+/// Parses `N37W122`-style tile names into the (latitude, longitude) of the
+/// south-west corner
+fn parse_hgt_name(name: &str) -> Option<(i32, i32)> {
+    let stem = name.split('.').next()?.to_ascii_uppercase();
+    if stem.len() != 7 {
+        return None;
+    }
+    let lat: i32 = stem.get(1..3)?.parse().ok()?;
+    let lon: i32 = stem.get(4..7)?.parse().ok()?;
+    let lat = match &stem[0..1] {
+        "N" => lat,
+        "S" => -lat,
+        _ => return None,
+    };
+    let lon = match &stem[3..4] {
+        "E" => lon,
+        "W" => -lon,
+        _ => return None,
+    };
+    Some((lat, lon))
+}
+
+/// Where DEM tiles are read from. Placement on the block grid comes from
+/// the world's [`GeoProjection`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElevationConfig {
+    /// Directory scanned for `.hgt`, `.tif` and `.tiff` tiles
+    pub directory: PathBuf,
+    pub interpolation: Interpolation,
+}
+
+impl Default for ElevationConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("elevation"),
+            interpolation: Interpolation::Bilinear,
+        }
+    }
+}
+
+/// Terrain heights from a set of DEM tiles
+#[derive(Debug, Clone)]
+pub struct DemElevationSource {
+    config: ElevationConfig,
+    projection: GeoProjection,
+    tiles: Vec<ElevationTile>,
+}
+
+impl DemElevationSource {
+    pub fn new(
+        config: ElevationConfig,
+        projection: GeoProjection,
+        tiles: Vec<ElevationTile>,
+    ) -> Self {
+        Self {
+            config,
+            projection,
+            tiles,
+        }
+    }
+
+    /// Loads every supported tile in the configured directory. Unreadable
+    /// tiles are skipped with a warning so one bad file does not disable the rest.
+    pub fn load(
+        config: ElevationConfig,
+        projection: GeoProjection,
+    ) -> Result<Self, ElevationError> {
+        let mut tiles = Vec::new();
+        for entry in fs::read_dir(&config.directory)? {
+            let path = entry?.path();
+            match ElevationTile::load(&path) {
+                Ok(tile) => tiles.push(tile),
+                Err(ElevationError::UnsupportedFormat(_)) => {}
+                Err(e) => log::warn!("Skipping elevation tile {}: {}", path.display(), e),
+            }
+        }
+        Ok(Self::new(config, projection, tiles))
+    }
+
+    pub fn tiles(&self) -> &[ElevationTile] {
+        &self.tiles
+    }
+
+    /// Elevation in metres at a geographic position, if any tile covers it
+    pub fn elevation_at(&self, lon: f64, lat: f64) -> Option<f32> {
+        self.tiles
+            .iter()
+            .find_map(|tile| tile.sample(lon, lat, self.config.interpolation))
+    }
+
+    /// Terrain height in blocks for a column, or `None` where no tile has data
+    pub fn height_at(&self, x: i64, z: i64) -> Option<i32> {
+        let mut pos = self.projection.column_to_geo(x, z);
+        pos.alt = self.elevation_at(pos.lon, pos.lat)? as f64;
+        Some(self.projection.to_block(pos).y.round() as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hgt_bytes(samples: &[i16]) -> Vec<u8> {
+        samples.iter().flat_map(|s| s.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn test_parse_hgt_name() {
+        assert_eq!(parse_hgt_name("N37W122.hgt"), Some((37, -122)));
+        assert_eq!(parse_hgt_name("s05e010.HGT"), Some((-5, 10)));
+        assert_eq!(parse_hgt_name("tile.hgt"), None);
+    }
+
+    #[test]
+    fn test_hgt_bilinear_sampling() {
+        // 3x3 tile covering 10..11 N, 20..21 E, rising towards the east
+        let bytes = hgt_bytes(&[0, 100, 200, 0, 100, 200, 0, 100, 200]);
+        let tile = ElevationTile::from_hgt_bytes("N10E020.hgt", &bytes).unwrap();
+
+        assert_eq!(tile.sample(20.0, 11.0, Interpolation::Bilinear), Some(0.0));
+        assert_eq!(
+            tile.sample(20.25, 10.5, Interpolation::Bilinear),
+            Some(50.0)
+        );
+        assert_eq!(
+            tile.sample(21.0, 10.0, Interpolation::Bilinear),
+            Some(200.0)
+        );
+        assert_eq!(tile.sample(21.5, 10.5, Interpolation::Bilinear), None);
+
+        // Away from the edges a linear ramp is reproduced exactly by Catmull-Rom too
+        let ramp: Vec<i16> = (0..25).map(|i| (i % 5) * 50).collect();
+        let tile = ElevationTile::from_hgt_bytes("N10E020.hgt", &hgt_bytes(&ramp)).unwrap();
+        let bicubic = tile.sample(20.375, 10.5, Interpolation::Bicubic).unwrap();
+        assert!((bicubic - 75.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_voids_and_bad_sizes() {
+        let bytes = hgt_bytes(&[0, 0, 0, HGT_VOID]);
+        let tile = ElevationTile::from_hgt_bytes("N00E000.hgt", &bytes).unwrap();
+        assert_eq!(tile.sample(0.5, 0.5, Interpolation::Bilinear), None);
+
+        assert!(matches!(
+            ElevationTile::from_hgt_bytes("N00E000.hgt", &[0, 1, 2]),
+            Err(ElevationError::InvalidSize { .. })
+        ));
+    }
+
+    #[test]
+    fn test_geotiff_tile() {
+        use tiff::encoder::{colortype, TiffEncoder};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dem.tif");
+        {
+            let mut encoder = TiffEncoder::new(File::create(&path).unwrap()).unwrap();
+            let mut image = encoder.new_image::<colortype::Gray32Float>(2, 2).unwrap();
+            image
+                .encoder()
+                .write_tag(Tag::ModelPixelScaleTag, &[0.5, 0.5, 0.0][..])
+                .unwrap();
+            image
+                .encoder()
+                .write_tag(Tag::ModelTiepointTag, &[0.0, 0.0, 0.0, 5.0, 45.0, 0.0][..])
+                .unwrap();
+            image.write_data(&[10.0, 20.0, 30.0, 40.0]).unwrap();
+        }
+
+        let tile = ElevationTile::load(&path).unwrap();
+        assert_eq!(tile.width, 2);
+        assert_eq!(tile.sample(5.0, 45.0, Interpolation::Bilinear), Some(10.0));
+        assert_eq!(
+            tile.sample(5.25, 44.75, Interpolation::Bilinear),
+            Some(25.0)
+        );
+    }
+
+    #[test]
+    fn test_height_at_maps_blocks_to_tiles() {
+        let bytes = hgt_bytes(&[100; 9]);
+        let tile = ElevationTile::from_hgt_bytes("N10E020.hgt", &bytes).unwrap();
+        let source = DemElevationSource::new(
+            ElevationConfig::default(),
+            GeoProjection {
+                origin_lat: 10.5,
+                origin_lon: 20.5,
+                vertical_scale: 0.5,
+                sea_level: 60,
+                ..Default::default()
+            },
+            vec![tile],
+        );
+
+        assert_eq!(source.height_at(0, 0), Some(110));
+        // Two degrees north of the origin is outside every tile
+        assert_eq!(source.height_at(0, -250_000), None);
+    }
+}
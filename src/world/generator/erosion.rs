@@ -0,0 +1,367 @@
+use crate::world::generator::rivers::{cached, RegionCache};
+use parking_lot::RwLock;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Parameters of the erosion stage. Heights and distances are in blocks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErosionConfig {
+    /// Side length of the tiles the heightfield is eroded in
+    pub tile_size: u32,
+    /// Extra border simulated around each tile. Neighbouring tiles overlap
+    /// by this much and are cross-faded there, so tiles meet seamlessly.
+    pub margin: u32,
+    /// Rain droplets per column of a tile, including its margin
+    pub droplets_per_block: f32,
+    pub max_droplet_steps: u32,
+    /// How much a droplet keeps its direction instead of following the slope
+    pub inertia: f32,
+    pub sediment_capacity: f32,
+    pub min_sediment_capacity: f32,
+    pub erode_speed: f32,
+    pub deposit_speed: f32,
+    pub evaporate_speed: f32,
+    pub gravity: f32,
+    /// Steepest stable height difference between neighbouring columns
+    pub talus: f32,
+    /// Share of the excess over `talus` that slides per iteration
+    pub thermal_rate: f32,
+    pub thermal_iterations: u32,
+    /// Eroded tiles kept in memory
+    pub cached_tiles: usize,
+}
+
+impl Default for ErosionConfig {
+    fn default() -> Self {
+        Self {
+            tile_size: 128,
+            margin: 32,
+            droplets_per_block: 1.0,
+            max_droplet_steps: 64,
+            inertia: 0.05,
+            sediment_capacity: 4.0,
+            min_sediment_capacity: 0.01,
+            erode_speed: 0.3,
+            deposit_speed: 0.3,
+            evaporate_speed: 0.01,
+            gravity: 4.0,
+            talus: 1.5,
+            thermal_rate: 0.5,
+            thermal_iterations: 8,
+            cached_tiles: 256,
+        }
+    }
+}
+
+/// A square grid of heights, row-major by z
+#[derive(Debug, Clone)]
+struct Heightfield {
+    size: usize,
+    heights: Vec<f32>,
+}
+
+impl Heightfield {
+    fn get(&self, x: usize, z: usize) -> f32 {
+        self.heights[z * self.size + x]
+    }
+
+    /// Bilinear height and gradient at a position inside the grid
+    fn sample(&self, x: f32, z: f32) -> (f32, f32, f32) {
+        let (cx, cz) = (x as usize, z as usize);
+        let (u, v) = (x - cx as f32, z - cz as f32);
+        let h00 = self.get(cx, cz);
+        let h10 = self.get(cx + 1, cz);
+        let h01 = self.get(cx, cz + 1);
+        let h11 = self.get(cx + 1, cz + 1);
+
+        let gradient_x = (h10 - h00) * (1.0 - v) + (h11 - h01) * v;
+        let gradient_z = (h01 - h00) * (1.0 - u) + (h11 - h10) * u;
+        let height =
+            h00 * (1.0 - u) * (1.0 - v) + h10 * u * (1.0 - v) + h01 * (1.0 - u) * v + h11 * u * v;
+        (height, gradient_x, gradient_z)
+    }
+
+    /// Adds `amount` to the four corners around a position, bilinearly weighted
+    fn add(&mut self, x: f32, z: f32, amount: f32) {
+        let (cx, cz) = (x as usize, z as usize);
+        let (u, v) = (x - cx as f32, z - cz as f32);
+        let size = self.size;
+        self.heights[cz * size + cx] += amount * (1.0 - u) * (1.0 - v);
+        self.heights[cz * size + cx + 1] += amount * u * (1.0 - v);
+        self.heights[(cz + 1) * size + cx] += amount * (1.0 - u) * v;
+        self.heights[(cz + 1) * size + cx + 1] += amount * u * v;
+    }
+
+    fn in_bounds(&self, x: f32, z: f32) -> bool {
+        let max = (self.size - 1) as f32;
+        x >= 0.0 && z >= 0.0 && x < max && z < max
+    }
+
+    /// Simulates rain droplets that pick up sediment running downhill and
+    /// drop it where they slow down
+    fn erode_hydraulic(&mut self, config: &ErosionConfig, rng: &mut ChaCha12Rng) {
+        let droplets = (config.droplets_per_block * (self.size * self.size) as f32) as usize;
+        let max = (self.size - 1) as f32;
+
+        for _ in 0..droplets {
+            let (mut x, mut z) = (rng.gen_range(0.0..max), rng.gen_range(0.0..max));
+            let (mut dir_x, mut dir_z) = (0.0f32, 0.0f32);
+            let (mut speed, mut water, mut sediment) = (1.0f32, 1.0f32, 0.0f32);
+
+            for _ in 0..config.max_droplet_steps {
+                let (height, gradient_x, gradient_z) = self.sample(x, z);
+                dir_x = dir_x * config.inertia - gradient_x * (1.0 - config.inertia);
+                dir_z = dir_z * config.inertia - gradient_z * (1.0 - config.inertia);
+                let length = (dir_x * dir_x + dir_z * dir_z).sqrt();
+                if length < f32::EPSILON {
+                    break;
+                }
+                (dir_x, dir_z) = (dir_x / length, dir_z / length);
+
+                let (old_x, old_z) = (x, z);
+                x += dir_x;
+                z += dir_z;
+                if !self.in_bounds(x, z) {
+                    break;
+                }
+
+                let delta = self.sample(x, z).0 - height;
+                let capacity = (-delta * speed * water * config.sediment_capacity)
+                    .max(config.min_sediment_capacity);
+
+                if delta > 0.0 || sediment > capacity {
+                    // Fill the pit uphill, or drop what the droplet can no longer carry
+                    let deposit = if delta > 0.0 {
+                        delta.min(sediment)
+                    } else {
+                        (sediment - capacity) * config.deposit_speed
+                    };
+                    sediment -= deposit;
+                    self.add(old_x, old_z, deposit);
+                } else {
+                    let erode = ((capacity - sediment) * config.erode_speed).min(-delta);
+                    sediment += erode;
+                    self.add(old_x, old_z, -erode);
+                }
+
+                speed = (speed * speed - delta * config.gravity).max(0.0).sqrt();
+                water *= 1.0 - config.evaporate_speed;
+            }
+        }
+    }
+
+    /// Moves material from columns steeper than the talus threshold to
+    /// their lower neighbours
+    fn erode_thermal(&mut self, config: &ErosionConfig) {
+        let size = self.size;
+        let mut delta = vec![0.0f32; self.heights.len()];
+        for _ in 0..config.thermal_iterations {
+            delta.iter_mut().for_each(|d| *d = 0.0);
+            for z in 0..size {
+                for x in 0..size {
+                    let height = self.get(x, z);
+                    let neighbours = [
+                        (x + 1 < size).then(|| (x + 1, z)),
+                        (z + 1 < size).then(|| (x, z + 1)),
+                    ];
+                    for (nx, nz) in neighbours.into_iter().flatten() {
+                        let diff = height - self.get(nx, nz);
+                        let excess = diff.abs() - config.talus;
+                        if excess <= 0.0 {
+                            continue;
+                        }
+                        // Half the excess levels both columns out completely
+                        let moved = excess * 0.5 * config.thermal_rate * diff.signum();
+                        delta[z * size + x] -= moved;
+                        delta[nz * size + nx] += moved;
+                    }
+                }
+            }
+            for (height, d) in self.heights.iter_mut().zip(&delta) {
+                *height += d;
+            }
+        }
+    }
+}
+
+/// Erodes the terrain heightfield tile by tile and caches the results.
+/// Tiles are eroded with their margins and cross-faded where they overlap,
+/// so heights are continuous and independent of generation order.
+#[derive(Debug)]
+pub struct HeightfieldEroder {
+    config: ErosionConfig,
+    seed: u64,
+    cache: RwLock<RegionCache<(i64, i64), Heightfield>>,
+}
+
+impl HeightfieldEroder {
+    pub fn new(config: ErosionConfig, seed: u64) -> Self {
+        let mut config = config;
+        config.tile_size = config.tile_size.max(1);
+        config.margin = config.margin.clamp(1, config.tile_size);
+        Self {
+            config,
+            seed,
+            cache: RwLock::new(RegionCache::new()),
+        }
+    }
+
+    pub fn config(&self) -> &ErosionConfig {
+        &self.config
+    }
+
+    /// Eroded height of a column. `base` gives the uneroded height of any column.
+    pub fn height_at(&self, x: i64, z: i64, base: &impl Fn(i64, i64) -> f64) -> f64 {
+        let size = self.config.tile_size as i64;
+        let margin = self.config.margin as i64;
+        let (tile_x, tile_z) = (x.div_euclid(size), z.div_euclid(size));
+
+        let mut total = 0.0;
+        let mut weights = 0.0;
+        for tx in tile_x - 1..=tile_x + 1 {
+            for tz in tile_z - 1..=tile_z + 1 {
+                let (local_x, local_z) = (x - tx * size, z - tz * size);
+                let weight = self.overlap_weight(local_x) * self.overlap_weight(local_z);
+                if weight <= 0.0 {
+                    continue;
+                }
+                let tile = self.tile(tx, tz, base);
+                let height = tile.get((local_x + margin) as usize, (local_z + margin) as usize);
+                total += weight * height as f64;
+                weights += weight;
+            }
+        }
+        total / weights
+    }
+
+    /// Cross-fade weight of a tile along one axis: 1 over the tile itself,
+    /// falling smoothly to 0 across the margin
+    fn overlap_weight(&self, local: i64) -> f64 {
+        let size = self.config.tile_size as i64;
+        let margin = self.config.margin as i64;
+        let inside = (local + margin).min(size - 1 + margin - local);
+        if inside < 0 {
+            return 0.0;
+        }
+        let t = ((inside as f64 + 0.5) / margin as f64).min(1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    fn tile(&self, tx: i64, tz: i64, base: &impl Fn(i64, i64) -> f64) -> Arc<Heightfield> {
+        let capacity = self.config.cached_tiles.max(9);
+        cached(&self.cache, (tx, tz), capacity, || self.erode_tile(tx, tz, base))
+    }
+
+    fn erode_tile(&self, tx: i64, tz: i64, base: &impl Fn(i64, i64) -> f64) -> Heightfield {
+        let margin = self.config.margin as i64;
+        let size = self.config.tile_size as usize + 2 * self.config.margin as usize;
+        let (min_x, min_z) = (
+            tx * self.config.tile_size as i64 - margin,
+            tz * self.config.tile_size as i64 - margin,
+        );
+
+        let mut heightfield = Heightfield {
+            size,
+            heights: (0..size * size)
+                .map(|i| base(min_x + (i % size) as i64, min_z + (i / size) as i64) as f32)
+                .collect(),
+        };
+
+        let hash = (tx as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (tz as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+        let mut rng = ChaCha12Rng::seed_from_u64(self.seed ^ hash);
+        heightfield.erode_hydraulic(&self.config, &mut rng);
+        heightfield.erode_thermal(&self.config);
+        heightfield
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ErosionConfig {
+        ErosionConfig {
+            tile_size: 32,
+            margin: 8,
+            ..Default::default()
+        }
+    }
+
+    /// Rolling hills with a steep ridge along x = 20
+    fn hills(x: i64, z: i64) -> f64 {
+        let ridge = (12.0 - (x as f64 - 20.0).abs() * 3.0).max(0.0);
+        64.0 + (x as f64 * 0.15).sin() * 6.0 + (z as f64 * 0.1).cos() * 4.0 + ridge
+    }
+
+    #[test]
+    fn test_thermal_erosion_limits_slopes() {
+        let eroder = HeightfieldEroder::new(
+            ErosionConfig {
+                droplets_per_block: 0.0,
+                thermal_iterations: 64,
+                ..config()
+            },
+            1,
+        );
+        let steepest = |height: &dyn Fn(i64) -> f64| {
+            (0..40)
+                .map(|x| (height(x + 1) - height(x)).abs())
+                .fold(0.0, f64::max)
+        };
+        let before = steepest(&|x| hills(x, 5));
+        let after = steepest(&|x| eroder.height_at(x, 5, &hills));
+        assert!(before >= 3.0);
+        assert!(after < before * 0.75, "{} -> {}", before, after);
+    }
+
+    #[test]
+    fn test_hydraulic_erosion_lowers_peaks() {
+        let eroder = HeightfieldEroder::new(
+            ErosionConfig {
+                thermal_iterations: 0,
+                ..config()
+            },
+            1,
+        );
+        let (mut before, mut after, mut changed) = (0.0f64, 0.0f64, 0);
+        for z in 0..32 {
+            before = before.max(hills(20, z));
+            let eroded = eroder.height_at(20, z, &hills);
+            after = after.max(eroded);
+            if (eroded - hills(20, z)).abs() > 0.01 {
+                changed += 1;
+            }
+        }
+        assert!(after < before, "{} -> {}", before, after);
+        assert!(changed > 16);
+    }
+
+    #[test]
+    fn test_tiles_join_seamlessly() {
+        let eroder = HeightfieldEroder::new(config(), 9);
+        // Steps across the tile borders at x = 0 and x = 32 look like any other step
+        for z in -40..40 {
+            let row: Vec<f64> = (-8..72).map(|x| eroder.height_at(x, z, &hills)).collect();
+            for pair in row.windows(2) {
+                assert!((pair[1] - pair[0]).abs() < 4.0, "z = {}: {:?}", z, pair);
+            }
+        }
+    }
+
+    #[test]
+    fn test_erosion_is_deterministic() {
+        let a = HeightfieldEroder::new(config(), 5);
+        let b = HeightfieldEroder::new(config(), 5);
+        // Query b in a different order, so its tiles are built in another order
+        let first: Vec<f64> = (0..64).map(|i| a.height_at(i * 3, -i, &hills)).collect();
+        let second: Vec<f64> = (0..64)
+            .rev()
+            .map(|i| b.height_at(i * 3, -i, &hills))
+            .collect();
+        assert!(first.iter().eq(second.iter().rev()));
+    }
+}
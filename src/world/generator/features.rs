@@ -0,0 +1,254 @@
+use crate::world::block::Block;
+use crate::world::block_id::BlockId;
+use crate::world::chunk::{Chunk, CHUNK_SIZE};
+use crate::world::chunk_coord::ChunkCoord;
+use crate::world::generator::terrain::BiomeType;
+use glam::{I64Vec3, IVec3};
+use std::collections::HashMap;
+
+/// Placement attempts per chunk column for each feature kind. Each attempt
+/// succeeds with probability `density / attempts`, using the biome at the
+/// attempted position.
+pub const TREE_ATTEMPTS: u32 = 16;
+pub const BOULDER_ATTEMPTS: u32 = 4;
+pub const FLOWER_ATTEMPTS: u32 = 16;
+pub const GRASS_ATTEMPTS: u32 = 48;
+
+/// Expected number of each decoration per 32x32 chunk column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiomeDecoration {
+    pub trees: f32,
+    pub boulders: f32,
+    pub flowers: f32,
+    pub grass: f32,
+}
+
+impl BiomeDecoration {
+    pub fn for_biome(biome: BiomeType) -> Self {
+        let (trees, boulders, flowers, grass) = match biome {
+            BiomeType::Plains => (1.0, 0.3, 6.0, 32.0),
+            BiomeType::Forest => (12.0, 0.2, 3.0, 16.0),
+            BiomeType::Mountains => (0.5, 2.0, 0.0, 4.0),
+            BiomeType::Desert => (0.0, 0.5, 0.0, 0.0),
+            BiomeType::Ocean | BiomeType::River => (0.0, 0.0, 0.0, 0.0),
+            BiomeType::Tundra => (0.5, 1.0, 0.0, 2.0),
+            BiomeType::Swamp => (3.0, 0.0, 1.0, 24.0),
+        };
+        Self {
+            trees,
+            boulders,
+            flowers,
+            grass,
+        }
+    }
+}
+
+/// Blocks of a roughly spherical boulder resting on `base`
+pub fn generate_boulder(base: I64Vec3, radius: i64, block: BlockId) -> HashMap<I64Vec3, Block> {
+    let mut blocks = HashMap::new();
+    let center = base + I64Vec3::new(0, radius - 1, 0);
+    for dx in -radius..=radius {
+        for dy in -radius..=radius {
+            for dz in -radius..=radius {
+                if dx * dx + dy * dy + dz * dz <= radius * radius {
+                    blocks.insert(center + I64Vec3::new(dx, dy, dz), Block::new(block));
+                }
+            }
+        }
+    }
+    blocks
+}
+
+/// Converts a world position to its chunk and the position inside it
+pub fn split_world_pos(pos: I64Vec3) -> (ChunkCoord, IVec3) {
+    let chunk = ChunkCoord::from_block_pos(pos, CHUNK_SIZE as i32);
+    (
+        chunk,
+        (pos - chunk.to_block_pos(CHUNK_SIZE as i32)).as_ivec3(),
+    )
+}
+
+/// Writes `block` at a chunk-local position if the current block may be
+/// replaced by features. Returns whether the block was written.
+pub fn write_feature_block(
+    chunk: &mut Chunk,
+    local: IVec3,
+    block: Block,
+    replaceable: impl Fn(BlockId) -> bool,
+) -> bool {
+    let (x, y, z) = (local.x as u32, local.y as u32, local.z as u32);
+    let can_write = chunk
+        .get_block(x, y, z)
+        .is_none_or(|existing| replaceable(existing.id));
+    if can_write {
+        chunk.set_block(x, y, z, Some(block));
+    }
+    can_write
+}
+
+/// A feature block waiting for its chunk, with the chunk whose decoration
+/// produced it
+#[derive(Debug, Clone)]
+pub struct FeatureWrite {
+    pub local: IVec3,
+    pub block: Block,
+    pub source: ChunkCoord,
+}
+
+/// Feature blocks waiting for their chunk. Decorating a chunk can reach into
+/// chunks that are not decorated yet; those writes wait here until every
+/// chunk that may write into the target has been decorated. They are then
+/// applied in order of their source chunk, so where two features overlap the
+/// one from the lowest source coordinate wins whatever order the chunks were
+/// generated in.
+#[derive(Debug, Default)]
+pub struct PendingFeatureWrites {
+    pending: HashMap<ChunkCoord, Vec<FeatureWrite>>,
+}
+
+impl PendingFeatureWrites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a block at a world position, spilled by the chunk `source`
+    pub fn push(&mut self, source: ChunkCoord, pos: I64Vec3, block: Block) {
+        let (coord, local) = split_world_pos(pos);
+        self.pending.entry(coord).or_default().push(FeatureWrite {
+            local,
+            block,
+            source,
+        });
+    }
+
+    /// Queues writes taken earlier for a chunk, e.g. read back from disk
+    pub fn extend(&mut self, coord: ChunkCoord, writes: Vec<FeatureWrite>) {
+        if !writes.is_empty() {
+            self.pending.entry(coord).or_default().extend(writes);
+        }
+    }
+
+    /// Removes and returns the writes queued for a chunk
+    pub fn take(&mut self, coord: ChunkCoord) -> Vec<FeatureWrite> {
+        self.pending.remove(&coord).unwrap_or_default()
+    }
+
+    /// Removes and returns the writes of every chunk `evict` returns true
+    /// for, e.g. chunks too far away to be generated soon
+    pub fn take_where(
+        &mut self,
+        evict: impl Fn(ChunkCoord) -> bool,
+    ) -> Vec<(ChunkCoord, Vec<FeatureWrite>)> {
+        let coords: Vec<_> = self.chunks().filter(|coord| evict(*coord)).collect();
+        coords
+            .into_iter()
+            .map(|coord| (coord, self.take(coord)))
+            .collect()
+    }
+
+    /// Applies and clears the writes queued for `coord`, lowest source chunk
+    /// first
+    pub fn apply(
+        &mut self,
+        coord: ChunkCoord,
+        chunk: &mut Chunk,
+        replaceable: impl Fn(BlockId) -> bool,
+    ) -> usize {
+        let mut writes = self.take(coord);
+        // Stable, so each source keeps its own order
+        writes.sort_by_key(|write| write.source);
+        let count = writes.len();
+        for write in writes {
+            write_feature_block(chunk, write.local, write.block, &replaceable);
+        }
+        count
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = ChunkCoord> + '_ {
+        self.pending.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_world_pos() {
+        let (coord, local) = split_world_pos(I64Vec3::new(-1, 33, 64));
+        assert_eq!(coord, ChunkCoord::new(-1, 1, 2));
+        assert_eq!(local, IVec3::new(31, 1, 0));
+    }
+
+    #[test]
+    fn test_pending_writes_apply_to_their_chunk() {
+        let leaves = BlockId::new(16, 0, 0);
+        let stone = BlockId::new(1, 0, 0);
+        let source = ChunkCoord::new(0, 0, 0);
+        let mut pending = PendingFeatureWrites::new();
+        pending.push(source, I64Vec3::new(32, 5, 0), Block::new(leaves));
+        pending.push(source, I64Vec3::new(33, 5, 0), Block::new(leaves));
+        assert_eq!(pending.len(), 2);
+
+        let coord = ChunkCoord::new(1, 0, 0);
+        let mut chunk = Chunk::new(coord);
+        chunk.set_block(1, 5, 0, Some(Block::new(stone)));
+
+        let applied = pending.apply(coord, &mut chunk, |id| id == BlockId::AIR);
+        assert_eq!(applied, 2);
+        assert!(pending.is_empty());
+        assert_eq!(chunk.get_block(0, 5, 0).map(|b| b.id), Some(leaves));
+        // Terrain is never overwritten by features
+        assert_eq!(chunk.get_block(1, 5, 0).map(|b| b.id), Some(stone));
+    }
+
+    #[test]
+    fn test_overlapping_writes_keep_the_lowest_source() {
+        let log = BlockId::new(15, 0, 0);
+        let leaves = BlockId::new(16, 0, 0);
+        let low = ChunkCoord::new(0, 0, 0);
+        let high = ChunkCoord::new(2, 0, 0);
+        let pos = I64Vec3::new(40, 5, 3);
+        let coord = ChunkCoord::new(1, 0, 0);
+
+        let mut ids = Vec::new();
+        for sources in [[low, high], [high, low]] {
+            let mut pending = PendingFeatureWrites::new();
+            for source in sources {
+                let id = if source == low { log } else { leaves };
+                pending.push(source, pos, Block::new(id));
+            }
+            let mut chunk = Chunk::new(coord);
+            pending.apply(coord, &mut chunk, |id| id == BlockId::AIR);
+            ids.push(chunk.get_block(8, 5, 3).map(|b| b.id));
+        }
+        assert_eq!(ids, [Some(log), Some(log)]);
+    }
+
+    #[test]
+    fn test_far_writes_are_taken_out() {
+        let leaves = Block::new(BlockId::new(16, 0, 0));
+        let source = ChunkCoord::new(0, 0, 0);
+        let mut pending = PendingFeatureWrites::new();
+        pending.push(source, I64Vec3::new(32, 0, 0), leaves.clone());
+        pending.push(source, I64Vec3::new(-1, 0, 0), leaves.clone());
+
+        let taken = pending.take_where(|coord| coord.x() > 0);
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].0, ChunkCoord::new(1, 0, 0));
+        assert_eq!(pending.len(), 1);
+
+        // Writes put back apply like any other
+        let (coord, writes) = taken.into_iter().next().unwrap();
+        pending.extend(coord, writes);
+        assert_eq!(pending.len(), 2);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::world::generator::elevation::{ElevationError, ElevationTile};
+use crate::world::generator::terrain::BiomeType;
+use crate::world::geo::GeoProjection;
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Frequency of the noise displacing biome borders
+const BORDER_WARP_SCALE: f64 = 1.0 / 24.0;
+
+/// ESA WorldCover classes
+pub fn default_land_cover_classes() -> HashMap<u32, BiomeType> {
+    HashMap::from([
+        (10, BiomeType::Forest),     // Tree cover
+        (20, BiomeType::Plains),     // Shrubland
+        (30, BiomeType::Plains),     // Grassland
+        (40, BiomeType::Plains),     // Cropland
+        (50, BiomeType::Plains),     // Built-up
+        (60, BiomeType::Desert),     // Bare / sparse vegetation
+        (70, BiomeType::Tundra),     // Snow and ice
+        (80, BiomeType::Ocean),      // Permanent water bodies
+        (90, BiomeType::Swamp),      // Herbaceous wetland
+        (95, BiomeType::Swamp),      // Mangroves
+        (100, BiomeType::Mountains), // Moss and lichen
+    ])
+}
+
+fn default_blend_radius() -> f64 {
+    6.0
+}
+
+/// Where classified land-cover rasters are read from and how their classes
+/// map to biomes. Placement on the block grid comes from the world's
+/// [`GeoProjection`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LandCoverConfig {
+    /// Directory scanned for single-band `.tif` and `.tiff` rasters
+    pub directory: PathBuf,
+    /// Biome for each raster class. Unlisted classes use noise biomes.
+    #[serde(default = "default_land_cover_classes")]
+    pub classes: HashMap<u32, BiomeType>,
+    /// Blocks by which biome borders are displaced, so raster cells do not
+    /// show up as straight steps
+    #[serde(default = "default_blend_radius")]
+    pub blend_radius: f64,
+}
+
+impl Default for LandCoverConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("landcover"),
+            classes: default_land_cover_classes(),
+            blend_radius: default_blend_radius(),
+        }
+    }
+}
+
+/// Biomes from land-cover rasters
+#[derive(Debug, Clone)]
+pub struct LandCoverSource {
+    config: LandCoverConfig,
+    projection: GeoProjection,
+    tiles: Vec<ElevationTile>,
+    warp: Perlin,
+}
+
+impl LandCoverSource {
+    pub fn new(
+        config: LandCoverConfig,
+        projection: GeoProjection,
+        tiles: Vec<ElevationTile>,
+        seed: u64,
+    ) -> Self {
+        Self {
+            config,
+            projection,
+            tiles,
+            warp: Perlin::new((seed as u32).wrapping_add(0x4C43)),
+        }
+    }
+
+    /// Loads every GeoTIFF in the configured directory. Unreadable rasters
+    /// are skipped with a warning.
+    pub fn load(
+        config: LandCoverConfig,
+        projection: GeoProjection,
+        seed: u64,
+    ) -> Result<Self, ElevationError> {
+        let mut tiles = Vec::new();
+        for entry in fs::read_dir(&config.directory)? {
+            let path = entry?.path();
+            let extension = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(str::to_ascii_lowercase);
+            if !matches!(extension.as_deref(), Some("tif") | Some("tiff")) {
+                continue;
+            }
+            match ElevationTile::from_geotiff(&path) {
+                Ok(tile) => tiles.push(tile),
+                Err(e) => log::warn!("Skipping land-cover raster {}: {}", path.display(), e),
+            }
+        }
+        Ok(Self::new(config, projection, tiles, seed))
+    }
+
+    /// Raster class of a block column, or `None` outside every raster
+    pub fn class_at(&self, x: i64, z: i64) -> Option<u32> {
+        let pos = self.projection.column_to_geo(x, z);
+        self.tiles
+            .iter()
+            .find_map(|tile| tile.sample_nearest(pos.lon, pos.lat))
+            .map(|class| class as u32)
+    }
+
+    /// Biome of a block column, or `None` where the raster has no data or
+    /// the class is not mapped. The lookup position is displaced by smooth
+    /// noise, which turns cell edges into natural-looking borders.
+    pub fn biome_at(&self, x: i64, z: i64) -> Option<BiomeType> {
+        let (nx, nz) = (x as f64 * BORDER_WARP_SCALE, z as f64 * BORDER_WARP_SCALE);
+        let radius = self.config.blend_radius;
+        let dx = (self.warp.get([nx, nz, 0.5]) * radius).round() as i64;
+        let dz = (self.warp.get([nx, nz, 17.5]) * radius).round() as i64;
+        let class = self.class_at(x + dx, z + dz)?;
+        self.config.classes.get(&class).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4x2 raster of 0.001° cells at the projection origin: forest in the
+    /// west half, water in the east, one nodata cell
+    fn source(blend_radius: f64) -> LandCoverSource {
+        let tile = ElevationTile::new(
+            8.0,
+            47.0,
+            0.001,
+            0.001,
+            4,
+            2,
+            vec![10.0, 10.0, 80.0, 80.0, 10.0, 10.0, 80.0, f32::NAN],
+        );
+        let config = LandCoverConfig {
+            blend_radius,
+            ..Default::default()
+        };
+        let projection = GeoProjection {
+            origin_lat: 47.0,
+            origin_lon: 8.0,
+            ..Default::default()
+        };
+        LandCoverSource::new(config, projection, vec![tile], 7)
+    }
+
+    #[test]
+    fn test_classes_map_to_biomes() {
+        let source = source(0.0);
+        // One cell is about 76 m east-west and 111 m north-south
+        assert_eq!(source.class_at(10, 10), Some(10));
+        assert_eq!(source.biome_at(10, 10), Some(BiomeType::Forest));
+        assert_eq!(source.biome_at(200, 10), Some(BiomeType::Ocean));
+        // Nodata and areas outside the raster fall back to noise
+        assert_eq!(source.class_at(250, 150), None);
+        assert_eq!(source.biome_at(-10, 10), None);
+        assert_eq!(source.biome_at(10, 400), None);
+    }
+
+    #[test]
+    fn test_borders_are_blended() {
+        let sharp = source(0.0);
+        let blended = source(6.0);
+
+        // Far from the border the biome is unchanged
+        for z in 20..90 {
+            assert_eq!(blended.biome_at(40, z), Some(BiomeType::Forest));
+        }
+
+        // Along the forest/water border the cell edge is a straight line
+        // without blending, and wanders with it
+        let border = |source: &LandCoverSource, z: i64| {
+            (100..200)
+                .find(|&x| source.biome_at(x, z) == Some(BiomeType::Ocean))
+                .unwrap()
+        };
+        let straight: Vec<_> = (10..100).map(|z| border(&sharp, z)).collect();
+        assert!(straight.windows(2).all(|w| w[0] == w[1]));
+        let wandering: Vec<_> = (10..100).map(|z| border(&blended, z)).collect();
+        assert!(wandering.windows(2).any(|w| w[0] != w[1]));
+        assert!(wandering.iter().all(|x| (*x - straight[0]).abs() <= 6));
+    }
+}
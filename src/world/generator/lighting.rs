@@ -0,0 +1,205 @@
+use crate::world::block_id::{BlockCategory, BlockId};
+use crate::world::block_tech::BlockFlags;
+use crate::world::blocks_data::BlockRegistry;
+use crate::world::chunk::{Chunk, CHUNK_SIZE, CHUNK_VOLUME};
+use std::collections::VecDeque;
+
+/// Brightest sky or block light level
+pub const MAX_LIGHT: u8 = 15;
+
+const SIZE: i32 = CHUNK_SIZE as i32;
+
+fn index(x: i32, y: i32, z: i32) -> usize {
+    (x + y * SIZE + z * SIZE * SIZE) as usize
+}
+
+/// Whether light stops at the block. Air, liquids, flora and transparent
+/// blocks such as glass let it through.
+fn is_opaque(registry: &BlockRegistry, id: BlockId) -> bool {
+    id != BlockId::AIR
+        && registry.get_by_id(id).is_some_and(|def| {
+            def.flags.contains(BlockFlags::SOLID)
+                && def.category != BlockCategory::Transparent
+                && !def.properties.is_transparent()
+        })
+}
+
+/// Fills the sky and block light of a chunk. `ground` gives the world Y of
+/// the highest sky-blocking block of any column, so the chunk knows where
+/// the sky reaches it without its neighbours being generated: sky light
+/// falls in through the top face of columns whose ground is below the chunk,
+/// and in through the side faces wherever the neighbouring column is open
+/// to the sky at that height. Block light stays inside the chunk.
+pub fn light_chunk(chunk: &mut Chunk, registry: &BlockRegistry, ground: impl Fn(i64, i64) -> i64) {
+    let ids: Vec<BlockId> = chunk
+        .blocks
+        .iter()
+        .map(|block| block.as_ref().map_or(BlockId::AIR, |block| block.id))
+        .collect();
+    let opaque: Vec<bool> = ids.iter().map(|id| is_opaque(registry, *id)).collect();
+
+    // Ground of the chunk's columns and the ring of columns around it
+    let base = chunk.position.to_block_pos(SIZE);
+    let side = SIZE + 2;
+    let grounds: Vec<i64> = (0..side * side)
+        .map(|i| ground(base.x + (i / side - 1) as i64, base.z + (i % side - 1) as i64))
+        .collect();
+    let open_sky = |x: i32, y: i32, z: i32| {
+        base.y + y as i64 > grounds[((x + 1) * side + z + 1) as usize]
+    };
+
+    // Full sky light falls straight down each open column to the first
+    // opaque block
+    let mut sky = vec![0; CHUNK_VOLUME];
+    let mut sky_queue = VecDeque::new();
+    for x in 0..SIZE {
+        for z in 0..SIZE {
+            if !open_sky(x, SIZE, z) {
+                continue;
+            }
+            for y in (0..SIZE).rev() {
+                if opaque[index(x, y, z)] {
+                    break;
+                }
+                sky[index(x, y, z)] = MAX_LIGHT;
+                sky_queue.push_back((x, y, z));
+            }
+        }
+    }
+
+    // Open sky beside the chunk lights the cells on its side faces
+    for x in 0..SIZE {
+        for z in 0..SIZE {
+            for (dx, dz) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let (nx, nz) = (x + dx, z + dz);
+                if (0..SIZE).contains(&nx) && (0..SIZE).contains(&nz) {
+                    continue;
+                }
+                for y in 0..SIZE {
+                    let i = index(x, y, z);
+                    if !opaque[i] && sky[i] < MAX_LIGHT - 1 && open_sky(nx, y, nz) {
+                        sky[i] = MAX_LIGHT - 1;
+                        sky_queue.push_back((x, y, z));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut block = vec![0; CHUNK_VOLUME];
+    let mut block_queue = VecDeque::new();
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                let i = index(x, y, z);
+                let emitted = registry
+                    .get_by_id(ids[i])
+                    .map_or(0, |def| def.properties.light_level().min(MAX_LIGHT));
+                if emitted > 0 {
+                    block[i] = emitted;
+                    block_queue.push_back((x, y, z));
+                }
+            }
+        }
+    }
+
+    spread(&mut sky, &opaque, sky_queue);
+    spread(&mut block, &opaque, block_queue);
+    chunk.light = sky
+        .iter()
+        .zip(&block)
+        .map(|(sky, block)| sky << 4 | block)
+        .collect();
+}
+
+/// Breadth-first flood fill, losing one level per block travelled
+fn spread(levels: &mut [u8], opaque: &[bool], mut queue: VecDeque<(i32, i32, i32)>) {
+    const OFFSETS: [(i32, i32, i32); 6] = [
+        (0, -1, 0),
+        (0, 1, 0),
+        (0, 0, -1),
+        (0, 0, 1),
+        (-1, 0, 0),
+        (1, 0, 0),
+    ];
+
+    while let Some((x, y, z)) = queue.pop_front() {
+        let level = levels[index(x, y, z)];
+        if level <= 1 {
+            continue;
+        }
+        for (dx, dy, dz) in OFFSETS {
+            let (nx, ny, nz) = (x + dx, y + dy, z + dz);
+            if !(0..SIZE).contains(&nx) || !(0..SIZE).contains(&ny) || !(0..SIZE).contains(&nz) {
+                continue;
+            }
+            let neighbor = index(nx, ny, nz);
+            if !opaque[neighbor] && levels[neighbor] < level - 1 {
+                levels[neighbor] = level - 1;
+                queue.push_back((nx, ny, nz));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block::Block;
+    use crate::world::block_flags::BlockFlags as BlockProperties;
+    use crate::world::chunk_coord::ChunkCoord;
+
+    #[test]
+    fn test_roof_shades_and_lamp_lights() {
+        let mut registry = BlockRegistry::default();
+        let stone = registry.get_by_name("stone").unwrap().id;
+        let mut lamp = registry.get_by_name("glass").unwrap().clone();
+        lamp.id = BlockId::new(200, 0, 0);
+        lamp.name = "lamp".to_string();
+        lamp.properties = BlockProperties::empty().with_light_level(12);
+        registry.register(lamp.clone());
+
+        // A stone roof over the whole chunk at y = 20, with a lamp under it
+        let mut chunk = Chunk::new(ChunkCoord::new(0, 0, 0));
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_block(x, 20, z, Some(Block::new(stone)));
+            }
+        }
+        chunk.set_block(10, 10, 10, Some(Block::new(lamp.id)));
+        light_chunk(&mut chunk, &registry, |_, _| 20);
+
+        assert_eq!(chunk.sky_light(5, 25, 5), MAX_LIGHT);
+        assert_eq!(chunk.sky_light(5, 19, 5), 0);
+        assert_eq!(chunk.block_light(10, 10, 10), 12);
+        assert_eq!(chunk.block_light(13, 10, 10), 9);
+        assert_eq!(chunk.block_light(10, 25, 10), 0);
+    }
+
+    #[test]
+    fn test_sky_reaches_through_open_faces_only() {
+        let registry = BlockRegistry::default();
+        let stone = registry.get_by_name("stone").unwrap().id;
+        // A chunk of stone 32 blocks under the ground, with a tunnel along x
+        // at y = 5 that opens into a valley to the west
+        let mut chunk = Chunk::new(ChunkCoord::new(0, 0, 0));
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    if y != 5 || z != 5 {
+                        chunk.set_block(x, y, z, Some(Block::new(stone)));
+                    }
+                }
+            }
+        }
+        let buried = |_: i64, _: i64| 64;
+        light_chunk(&mut chunk, &registry, buried);
+        assert!((0..CHUNK_SIZE).all(|x| chunk.sky_light(x, 5, 5) == 0));
+
+        let valley = |x: i64, _: i64| if x < 0 { 0 } else { 64 };
+        light_chunk(&mut chunk, &registry, valley);
+        assert_eq!(chunk.sky_light(0, 5, 5), MAX_LIGHT - 1);
+        assert_eq!(chunk.sky_light(4, 5, 5), MAX_LIGHT - 5);
+        assert_eq!(chunk.sky_light(20, 5, 5), 0);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::world::block_id::BlockId;
+use crate::world::blocks_data::BlockRegistry;
+use crate::world::chunk::{Chunk, CHUNK_SIZE};
+use crate::world::chunk_coord::ChunkCoord;
+use serde::{Deserialize, Serialize};
+
+/// Colour of cells whose block has no material
+const MISSING_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+
+/// Blocks per cell edge of a chunk summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LodScale {
+    X2,
+    X4,
+    X8,
+}
+
+impl LodScale {
+    pub const ALL: [LodScale; 3] = [LodScale::X2, LodScale::X4, LodScale::X8];
+
+    pub fn factor(self) -> usize {
+        match self {
+            LodScale::X2 => 2,
+            LodScale::X4 => 4,
+            LodScale::X8 => 8,
+        }
+    }
+
+    /// Cells per chunk edge
+    pub fn cells(self) -> usize {
+        CHUNK_SIZE as usize / self.factor()
+    }
+
+    /// The scale drawn at a spatial LOD level. Level 0 is full detail.
+    pub fn from_level(level: u32) -> Option<Self> {
+        match level {
+            0 => None,
+            1 => Some(LodScale::X2),
+            2 => Some(LodScale::X4),
+            _ => Some(LodScale::X8),
+        }
+    }
+}
+
+/// A chunk downsampled to one block per cell, indexed
+/// `(x * cells + y) * cells + z`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodGrid {
+    pub coord: ChunkCoord,
+    pub scale: LodScale,
+    pub cells: Vec<BlockId>,
+}
+
+impl LodGrid {
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        let cells = self.scale.cells();
+        self.cells[(x * cells + y) * cells + z]
+    }
+
+    /// Whether every cell is air, so there is nothing to draw
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(|id| *id == BlockId::AIR)
+    }
+
+    /// Downsamples a generated chunk, keeping the most common block of each
+    /// cell. Used where the generator has no cheaper way to summarise.
+    pub fn from_chunk(chunk: &Chunk, coord: ChunkCoord, scale: LodScale) -> Self {
+        let factor = scale.factor();
+        let cells = scale.cells();
+        let mut grid = Vec::with_capacity(cells * cells * cells);
+        for cx in 0..cells {
+            for cy in 0..cells {
+                for cz in 0..cells {
+                    let mut blocks = Vec::with_capacity(factor * factor * factor);
+                    for x in cx * factor..(cx + 1) * factor {
+                        for y in cy * factor..(cy + 1) * factor {
+                            for z in cz * factor..(cz + 1) * factor {
+                                let id = chunk
+                                    .get_block(x as u32, y as u32, z as u32)
+                                    .map_or(BlockId::AIR, |block| block.id);
+                                blocks.push(id);
+                            }
+                        }
+                    }
+                    grid.push(dominant(blocks));
+                }
+            }
+        }
+        Self {
+            coord,
+            scale,
+            cells: grid,
+        }
+    }
+}
+
+/// One cell of a surface heightfield tile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodCell {
+    /// Mean ground height of the cell
+    pub height: i32,
+    /// Highest water surface over the cell. Only above `height` where the
+    /// cell is under water.
+    pub water_level: i32,
+    /// Most common visible block: the fluid over submerged ground
+    pub block: BlockId,
+    /// Albedo of `block`
+    pub color: [f32; 4],
+}
+
+/// Surface heightfield of a chunk column, indexed `x * cells + z`. The
+/// coordinate's y is always 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodTile {
+    pub coord: ChunkCoord,
+    pub scale: LodScale,
+    pub cells: Vec<LodCell>,
+}
+
+impl LodTile {
+    pub fn get(&self, x: usize, z: usize) -> &LodCell {
+        &self.cells[x * self.scale.cells() + z]
+    }
+
+    /// Lowest and highest ground height, for the tile's bounding box
+    pub fn height_range(&self) -> (i32, i32) {
+        self.cells
+            .iter()
+            .fold((i32::MAX, i32::MIN), |(min, max), cell| {
+                (
+                    min.min(cell.height),
+                    max.max(cell.height.max(cell.water_level)),
+                )
+            })
+    }
+}
+
+/// Albedo of a block for summary cells
+pub fn block_color(registry: &BlockRegistry, id: BlockId) -> [f32; 4] {
+    registry
+        .get_block_material(id)
+        .map_or(MISSING_COLOR, |material| material.albedo)
+}
+
+/// Most common block among the samples. Ties go to the block seen first.
+pub fn dominant(samples: impl IntoIterator<Item = BlockId>) -> BlockId {
+    let mut counts: Vec<(BlockId, u32)> = Vec::new();
+    for id in samples {
+        match counts.iter_mut().find(|(seen, _)| *seen == id) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((id, 1)),
+        }
+    }
+    // max_by_key keeps the last maximum, so search backwards
+    counts
+        .iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map_or(BlockId::AIR, |(id, _)| *id)
+}
+
+/// Offsets within a cell of the 2x2 columns sampled for it, or of every
+/// column when cells are two blocks wide
+pub fn sample_offsets(scale: LodScale) -> [i64; 2] {
+    let factor = scale.factor() as i64;
+    [factor / 4, factor * 3 / 4]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block::Block;
+
+    #[test]
+    fn test_dominant_prefers_the_most_common_block() {
+        let stone = BlockId::new(1, 0, 0);
+        let grass = BlockId::new(2, 0, 0);
+        assert_eq!(dominant([grass, stone, stone, BlockId::AIR]), stone);
+        // Ties go to the first block
+        assert_eq!(dominant([grass, stone]), grass);
+        assert_eq!(dominant([]), BlockId::AIR);
+    }
+
+    #[test]
+    fn test_grid_downsamples_chunks() {
+        let coord = ChunkCoord::new(0, 0, 0);
+        let stone = BlockId::new(1, 0, 0);
+        let mut chunk = Chunk::new(coord);
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let id = if y < 12 { stone } else { BlockId::AIR };
+                    chunk.set_block(x, y, z, Some(Block::new(id)));
+                }
+            }
+        }
+
+        let grid = LodGrid::from_chunk(&chunk, coord, LodScale::X8);
+        assert_eq!(grid.cells.len(), 4 * 4 * 4);
+        assert_eq!(grid.get(0, 0, 0), stone);
+        // The second layer of cells is half stone, and stone is seen first
+        assert_eq!(grid.get(3, 1, 2), stone);
+        assert_eq!(grid.get(1, 2, 1), BlockId::AIR);
+        assert!(!grid.is_empty());
+        assert!(LodGrid::from_chunk(&Chunk::new(coord), coord, LodScale::X2).is_empty());
+    }
+}
@@ -1,5 +1,57 @@
+pub mod caves;
+pub mod climate;
 pub mod core;
+pub mod density;
+pub mod elevation;
+pub mod erosion;
+pub mod features;
+pub mod landcover;
+pub mod lighting;
+pub mod lod;
+pub mod ores;
+pub mod osm;
+pub mod osm_pbf;
+pub mod preview;
+pub mod rivers;
+pub mod structures;
+pub mod superflat;
 pub mod terrain;
+pub mod water;
 
-pub use core::ChunkGenerator;
+pub use caves::CaveCarver;
+pub use climate::{ClimateConfig, ClimateModel};
+pub use core::{ChunkGenerator, GenerationPipeline, GenerationStage, TerrainStage, WorldGenerator};
+pub use density::{DensityConfig, DensityGraph};
+pub use elevation::{DemElevationSource, ElevationConfig};
+pub use erosion::{ErosionConfig, HeightfieldEroder};
+pub use features::PendingFeatureWrites;
+pub use landcover::{LandCoverConfig, LandCoverSource};
+pub use lod::{LodCell, LodGrid, LodScale, LodTile};
+pub use ores::{OreFeature, OrePlacer};
+pub use osm::{OsmConfig, OsmData, OsmOverlay};
+pub use preview::{PreviewLayer, PreviewRegion};
+pub use rivers::{RiverConfig, RiverNetwork};
+pub use structures::{StructurePlacer, StructureSet, StructureStart};
+pub use superflat::{SuperflatError, SuperflatPreset};
 pub use terrain::TerrainGenerator;
+pub use water::{WaterBodies, WaterConfig};
+
+/// Chunk filled with `stone`, for tests of stages that carve or replace rock
+#[cfg(test)]
+pub(crate) fn stone_chunk(
+    coord: crate::world::chunk_coord::ChunkCoord,
+    stone: crate::world::block_id::BlockId,
+) -> crate::world::chunk::Chunk {
+    use crate::world::block::Block;
+    use crate::world::chunk::{Chunk, CHUNK_SIZE};
+
+    let mut chunk = Chunk::new(coord);
+    for x in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_block(x, y, z, Some(Block::new(stone)));
+            }
+        }
+    }
+    chunk
+}
@@ -0,0 +1,336 @@
+use crate::world::block::Block;
+use crate::world::block_id::BlockId;
+use crate::world::block_tags::{BlockIdSet, TAG_REFERENCE_PREFIX};
+use crate::world::blocks_data::BlockRegistry;
+use crate::world::chunk::{Chunk, CHUNK_SIZE};
+use crate::world::chunk_coord::ChunkCoord;
+use glam::IVec3;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Built-in ore features shipped with the engine
+pub const DEFAULT_ORES: &str = include_str!("../../assets/ores.json");
+
+/// Steps a vein may take from its starting block
+const VEIN_DIRECTIONS: [IVec3; 6] = [
+    IVec3::new(1, 0, 0),
+    IVec3::new(-1, 0, 0),
+    IVec3::new(0, 1, 0),
+    IVec3::new(0, -1, 0),
+    IVec3::new(0, 0, 1),
+    IVec3::new(0, 0, -1),
+];
+
+#[derive(Debug, Error)]
+pub enum OreError {
+    #[error("Unknown block '{block}' in ore feature '{feature}'")]
+    UnknownBlock { feature: String, block: String },
+    #[error("Unknown tag '{tag}' in ore feature '{feature}'")]
+    UnknownTag { feature: String, tag: String },
+    #[error("Invalid depth range {min_y}..={max_y} in ore feature '{feature}'")]
+    InvalidRange {
+        feature: String,
+        min_y: i32,
+        max_y: i32,
+    },
+    #[error("Invalid ore file format: {0}")]
+    FormatError(#[from] serde_json::Error),
+}
+
+/// How vein heights are spread over the depth range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OreDistribution {
+    /// Every height in the range is equally likely
+    Uniform,
+    /// Most veins near the middle of the range, tapering to both ends
+    Triangular,
+}
+
+impl OreDistribution {
+    fn sample(&self, rng: &mut ChaCha12Rng, min_y: i32, max_y: i32) -> i32 {
+        match self {
+            OreDistribution::Uniform => rng.gen_range(min_y..=max_y),
+            OreDistribution::Triangular => {
+                let half = (max_y - min_y) / 2;
+                min_y + rng.gen_range(0..=half) + rng.gen_range(0..=(max_y - min_y - half))
+            }
+        }
+    }
+}
+
+fn default_replaceable() -> Vec<String> {
+    vec![format!("{}replaceable/ore", TAG_REFERENCE_PREFIX)]
+}
+
+/// One ore feature as read from a data file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OreFeature {
+    pub name: String,
+    pub block: String,
+    pub vein_size: u32,
+    pub attempts_per_chunk: u32,
+    pub min_y: i32,
+    pub max_y: i32,
+    pub distribution: OreDistribution,
+    /// Block names or `#tag` references the ore may replace
+    #[serde(default = "default_replaceable")]
+    pub replaceable: Vec<String>,
+}
+
+impl OreFeature {
+    pub fn list_from_json(json: &str) -> Result<Vec<OreFeature>, OreError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// The built-in ore features
+pub fn default_ore_features() -> Vec<OreFeature> {
+    OreFeature::list_from_json(DEFAULT_ORES).unwrap_or_else(|e| {
+        log::error!("Failed to load default ore features: {}", e);
+        Vec::new()
+    })
+}
+
+/// An ore feature with its block and replaceable set looked up in the registry
+#[derive(Debug, Clone)]
+pub struct ResolvedOre {
+    pub feature: OreFeature,
+    pub block: BlockId,
+    pub replaceable: BlockIdSet,
+}
+
+impl ResolvedOre {
+    pub fn resolve(feature: &OreFeature, registry: &BlockRegistry) -> Result<Self, OreError> {
+        if feature.min_y > feature.max_y {
+            return Err(OreError::InvalidRange {
+                feature: feature.name.clone(),
+                min_y: feature.min_y,
+                max_y: feature.max_y,
+            });
+        }
+
+        let block = registry
+            .get_by_name(&feature.block)
+            .map(|def| def.id)
+            .ok_or_else(|| OreError::UnknownBlock {
+                feature: feature.name.clone(),
+                block: feature.block.clone(),
+            })?;
+
+        let mut replaceable = BlockIdSet::new();
+        for entry in &feature.replaceable {
+            if let Some(tag) = entry.strip_prefix(TAG_REFERENCE_PREFIX) {
+                let set = registry.tag(tag).ok_or_else(|| OreError::UnknownTag {
+                    feature: feature.name.clone(),
+                    tag: tag.to_string(),
+                })?;
+                replaceable.union_with(set);
+            } else {
+                let def = registry
+                    .get_by_name(entry)
+                    .ok_or_else(|| OreError::UnknownBlock {
+                        feature: feature.name.clone(),
+                        block: entry.clone(),
+                    })?;
+                replaceable.insert(def.id);
+            }
+        }
+
+        Ok(Self {
+            feature: feature.clone(),
+            block,
+            replaceable,
+        })
+    }
+}
+
+/// Places ore veins into generated chunks. Veins are laid out per chunk
+/// column from the world seed, so every vertical chunk of a column agrees
+/// and regenerating a chunk gives the same ores.
+pub struct OrePlacer {
+    seed: u64,
+    ores: Vec<ResolvedOre>,
+}
+
+impl OrePlacer {
+    pub fn new(
+        seed: u64,
+        features: &[OreFeature],
+        registry: &BlockRegistry,
+    ) -> Result<Self, OreError> {
+        let ores = features
+            .iter()
+            .map(|feature| ResolvedOre::resolve(feature, registry))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { seed, ores })
+    }
+
+    /// A placer that generates no ores
+    pub fn empty(seed: u64) -> Self {
+        Self {
+            seed,
+            ores: Vec::new(),
+        }
+    }
+
+    pub fn ores(&self) -> &[ResolvedOre] {
+        &self.ores
+    }
+
+    pub fn place(&self, chunk: &mut Chunk, coord: ChunkCoord) {
+        let base_y = coord.to_block_pos(CHUNK_SIZE as i32).y;
+
+        for (index, ore) in self.ores.iter().enumerate() {
+            let mut rng = self.feature_rng(coord.x(), coord.z(), index);
+            let feature = &ore.feature;
+
+            for _ in 0..feature.attempts_per_chunk {
+                let start = IVec3::new(
+                    rng.gen_range(0..CHUNK_SIZE as i32),
+                    feature
+                        .distribution
+                        .sample(&mut rng, feature.min_y, feature.max_y),
+                    rng.gen_range(0..CHUNK_SIZE as i32),
+                );
+
+                // Walk the whole vein even when it misses this chunk so the
+                // RNG stream stays identical for every chunk in the column
+                let mut position = start;
+                for _ in 0..feature.vein_size {
+                    let local_y = position.y as i64 - base_y;
+                    let local = IVec3::new(position.x, local_y as i32, position.z);
+                    if (0..CHUNK_SIZE as i64).contains(&local_y)
+                        && local.cmpge(IVec3::ZERO).all()
+                        && local.cmplt(IVec3::splat(CHUNK_SIZE as i32)).all()
+                    {
+                        Self::replace(chunk, local, ore);
+                    }
+                    position += VEIN_DIRECTIONS[rng.gen_range(0..VEIN_DIRECTIONS.len())];
+                }
+            }
+        }
+    }
+
+    fn replace(chunk: &mut Chunk, local: IVec3, ore: &ResolvedOre) {
+        let (x, y, z) = (local.x as u32, local.y as u32, local.z as u32);
+        let replaceable = chunk
+            .get_block(x, y, z)
+            .is_some_and(|block| ore.replaceable.contains(block.id));
+        if replaceable {
+            chunk.set_block(x, y, z, Some(Block::new(ore.block)));
+        }
+    }
+
+    fn feature_rng(&self, chunk_x: i64, chunk_z: i64, feature: usize) -> ChaCha12Rng {
+        let hash = (chunk_x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (chunk_z as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ (feature as u64 + 1).wrapping_mul(0x1656_67B1_9E37_79F9);
+        ChaCha12Rng::seed_from_u64(self.seed ^ hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::generator::stone_chunk;
+
+    fn count(chunk: &Chunk, id: BlockId) -> usize {
+        (0..32 * 32 * 32)
+            .filter(|i| {
+                chunk
+                    .get_block(i % 32, (i / 32) % 32, i / 1024)
+                    .map_or(false, |block| block.id == id)
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_default_ores_resolve() {
+        let registry = BlockRegistry::default();
+        let placer = OrePlacer::new(1, &default_ore_features(), &registry).unwrap();
+        assert_eq!(placer.ores().len(), 4);
+    }
+
+    #[test]
+    fn test_ore_placement_is_reproducible() {
+        let registry = BlockRegistry::default();
+        let features = default_ore_features();
+        let coord = ChunkCoord::new(2, 0, -3);
+        let coal = registry.get_by_name("coal_ore").unwrap().id;
+        let stone = registry.get_by_name("stone").unwrap().id;
+
+        let mut a = stone_chunk(coord, stone);
+        OrePlacer::new(9, &features, &registry)
+            .unwrap()
+            .place(&mut a, coord);
+        let mut b = stone_chunk(coord, stone);
+        OrePlacer::new(9, &features, &registry)
+            .unwrap()
+            .place(&mut b, coord);
+
+        let ids = |chunk: &Chunk| {
+            (0..32u32)
+                .flat_map(|x| (0..32u32).map(move |y| (x, y)))
+                .map(|(x, y)| chunk.get_block(x, y, 4).map(|block| block.id))
+                .collect::<Vec<_>>()
+        };
+        assert!(count(&a, coal) > 0);
+        assert_eq!(ids(&a), ids(&b));
+    }
+
+    #[test]
+    fn test_depth_range_and_replaceable() {
+        let registry = BlockRegistry::default();
+        let diamond = registry.get_by_name("diamond_ore").unwrap().id;
+        let stone = registry.get_by_name("stone").unwrap().id;
+        let features = OreFeature::list_from_json(
+            r#"[{
+                "name": "deep",
+                "block": "diamond_ore",
+                "vein_size": 4,
+                "attempts_per_chunk": 64,
+                "min_y": 0,
+                "max_y": 8,
+                "distribution": "triangular"
+            }]"#,
+        )
+        .unwrap();
+        let placer = OrePlacer::new(5, &features, &registry).unwrap();
+
+        // Veins can wander a few blocks, but never reach a chunk starting at y=32
+        let mut high = stone_chunk(ChunkCoord::new(0, 1, 0), stone);
+        placer.place(&mut high, ChunkCoord::new(0, 1, 0));
+        assert_eq!(count(&high, diamond), 0);
+
+        // Only replaceable blocks are turned into ore
+        let mut air = Chunk::new(ChunkCoord::new(0, 0, 0));
+        placer.place(&mut air, ChunkCoord::new(0, 0, 0));
+        assert_eq!(count(&air, diamond), 0);
+
+        let mut low = stone_chunk(ChunkCoord::new(0, 0, 0), stone);
+        placer.place(&mut low, ChunkCoord::new(0, 0, 0));
+        assert!(count(&low, diamond) > 0);
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let registry = BlockRegistry::default();
+        let mut feature = default_ore_features().remove(0);
+        feature.block = "mythril_ore".into();
+        assert!(matches!(
+            ResolvedOre::resolve(&feature, &registry),
+            Err(OreError::UnknownBlock { .. })
+        ));
+
+        let mut feature = default_ore_features().remove(0);
+        feature.min_y = 10;
+        feature.max_y = 0;
+        assert!(matches!(
+            ResolvedOre::resolve(&feature, &registry),
+            Err(OreError::InvalidRange { .. })
+        ));
+    }
+}
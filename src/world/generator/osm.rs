@@ -0,0 +1,712 @@
+use crate::world::block::Block;
+use crate::world::block_id::BlockId;
+use crate::world::blocks_data::BlockRegistry;
+use crate::world::chunk::Chunk;
+use crate::world::chunk_coord::ChunkCoord;
+use crate::world::generator::osm_pbf;
+use crate::world::geo::{GeoPosition, GeoProjection};
+use glam::DVec2;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Storey height in metres used for `building:levels`
+const LEVEL_HEIGHT: f64 = 3.0;
+/// Levels assumed for buildings without height information
+const DEFAULT_LEVELS: f64 = 2.0;
+const LAKE_DEPTH: i64 = 3;
+const RIVER_DEPTH: i64 = 2;
+
+#[derive(Debug, Error)]
+pub enum OsmError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("XML error: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("XML attribute error: {0}")]
+    Attribute(#[from] quick_xml::events::attributes::AttrError),
+    #[error("Invalid PBF data: {0}")]
+    Pbf(String),
+    #[error("Unsupported OSM format in '{0}'")]
+    UnsupportedFormat(String),
+    #[error("Unknown block '{0}'")]
+    UnknownBlock(String),
+}
+
+/// Where the OSM extract is read from. Placement on the block grid comes
+/// from the world's [`GeoProjection`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OsmConfig {
+    /// `.osm` XML or `.osm.pbf` extract
+    pub path: PathBuf,
+}
+
+impl Default for OsmConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("map.osm.pbf"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsmWay {
+    pub id: i64,
+    pub nodes: Vec<i64>,
+    pub tags: HashMap<String, String>,
+}
+
+impl OsmWay {
+    pub fn is_closed(&self) -> bool {
+        self.nodes.len() > 3 && self.nodes.first() == self.nodes.last()
+    }
+}
+
+/// Nodes and ways of an OSM extract. Relations are not used.
+#[derive(Debug, Clone, Default)]
+pub struct OsmData {
+    /// Node positions as (latitude, longitude) in degrees
+    pub nodes: HashMap<i64, (f64, f64)>,
+    pub ways: Vec<OsmWay>,
+}
+
+impl OsmData {
+    pub fn load(path: &Path) -> Result<Self, OsmError> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("osm") | Some("xml") => Self::from_xml(BufReader::new(File::open(path)?)),
+            Some("pbf") => osm_pbf::read(&fs::read(path)?),
+            _ => Err(OsmError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+
+    /// Reads the `node`, `way`, `nd` and `tag` elements of an OSM XML document
+    pub fn from_xml<R: BufRead>(input: R) -> Result<Self, OsmError> {
+        let mut reader = Reader::from_reader(input);
+        let mut buf = Vec::new();
+        let mut data = Self::default();
+        let mut way: Option<OsmWay> = None;
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(element) | Event::Empty(element) => match element.name().as_ref() {
+                    b"node" => {
+                        let id = parse_attribute(&element, b"id")?;
+                        let lat = parse_attribute(&element, b"lat")?;
+                        let lon = parse_attribute(&element, b"lon")?;
+                        if let (Some(id), Some(lat), Some(lon)) = (id, lat, lon) {
+                            data.nodes.insert(id, (lat, lon));
+                        }
+                    }
+                    b"way" => {
+                        way = parse_attribute(&element, b"id")?.map(|id| OsmWay {
+                            id,
+                            ..Default::default()
+                        });
+                    }
+                    b"nd" => {
+                        if let (Some(way), Some(node)) =
+                            (way.as_mut(), parse_attribute(&element, b"ref")?)
+                        {
+                            way.nodes.push(node);
+                        }
+                    }
+                    b"tag" => {
+                        if let (Some(way), Some(key), Some(value)) = (
+                            way.as_mut(),
+                            attribute(&element, b"k")?,
+                            attribute(&element, b"v")?,
+                        ) {
+                            way.tags.insert(key, value);
+                        }
+                    }
+                    _ => {}
+                },
+                Event::End(element) if element.name().as_ref() == b"way" => {
+                    data.ways.extend(way.take());
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(data)
+    }
+}
+
+fn attribute(element: &BytesStart, name: &[u8]) -> Result<Option<String>, OsmError> {
+    Ok(match element.try_get_attribute(name)? {
+        Some(attr) => Some(attr.unescape_value()?.into_owned()),
+        None => None,
+    })
+}
+
+fn parse_attribute<T: std::str::FromStr>(
+    element: &BytesStart,
+    name: &[u8],
+) -> Result<Option<T>, OsmError> {
+    Ok(attribute(element, name)?.and_then(|value| value.parse().ok()))
+}
+
+/// What a way is rasterized as. Widths and heights are in metres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OsmFeatureKind {
+    Water,
+    Waterway { width: f64 },
+    Road { width: f64 },
+    Path { width: f64 },
+    Building { height: f64 },
+}
+
+impl OsmFeatureKind {
+    /// Classifies a way from its tags, or `None` for ways that are not drawn.
+    /// Areas are only recognised on closed ways.
+    pub fn from_way(way: &OsmWay) -> Option<Self> {
+        let tag = |key: &str| way.tags.get(key).map(String::as_str);
+        let width = tag("width").and_then(parse_metres);
+
+        if way.is_closed() {
+            if tag("building").is_some_and(|value| value != "no") {
+                let levels = tag("building:levels").and_then(|value| value.parse::<f64>().ok());
+                let height = tag("height")
+                    .and_then(parse_metres)
+                    .unwrap_or(levels.unwrap_or(DEFAULT_LEVELS) * LEVEL_HEIGHT);
+                return Some(Self::Building { height });
+            }
+            if tag("natural") == Some("water")
+                || tag("waterway") == Some("riverbank")
+                || matches!(tag("landuse"), Some("reservoir") | Some("basin"))
+            {
+                return Some(Self::Water);
+            }
+        }
+
+        let default_width = match tag("waterway") {
+            Some("river") => Some(8.0),
+            Some("canal") => Some(6.0),
+            Some("stream") => Some(2.0),
+            Some("ditch") | Some("drain") => Some(1.0),
+            _ => None,
+        };
+        if let Some(default_width) = default_width {
+            return Some(Self::Waterway {
+                width: width.unwrap_or(default_width),
+            });
+        }
+
+        Some(match tag("highway")? {
+            "motorway" | "trunk" | "primary" => Self::Road {
+                width: width.unwrap_or(10.0),
+            },
+            "secondary" | "tertiary" => Self::Road {
+                width: width.unwrap_or(7.0),
+            },
+            "residential" | "unclassified" | "living_street" | "service" => Self::Road {
+                width: width.unwrap_or(5.0),
+            },
+            "footway" | "path" | "cycleway" | "track" | "pedestrian" | "bridleway" | "steps" => {
+                Self::Path {
+                    width: width.unwrap_or(2.0),
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    /// Later layers are drawn over earlier ones where features overlap
+    fn layer(&self) -> u8 {
+        match self {
+            Self::Water | Self::Waterway { .. } => 0,
+            Self::Path { .. } => 1,
+            Self::Road { .. } => 2,
+            Self::Building { .. } => 3,
+        }
+    }
+}
+
+/// Parses `"12"`, `"12.5"` or `"12 m"`
+fn parse_metres(value: &str) -> Option<f64> {
+    value.trim().trim_end_matches('m').trim().parse().ok()
+}
+
+/// A classified way with its nodes projected onto the block grid (x, z)
+#[derive(Debug, Clone)]
+pub struct OsmFeature {
+    pub kind: OsmFeatureKind,
+    pub points: Vec<DVec2>,
+    /// Half width in blocks for line features
+    half_width: f64,
+    min: DVec2,
+    max: DVec2,
+}
+
+impl OsmFeature {
+    fn new(kind: OsmFeatureKind, points: Vec<DVec2>, blocks_per_metre: f64) -> Self {
+        let half_width = match kind {
+            OsmFeatureKind::Waterway { width }
+            | OsmFeatureKind::Road { width }
+            | OsmFeatureKind::Path { width } => (width * blocks_per_metre * 0.5).max(0.5),
+            _ => 0.0,
+        };
+        let min = points.iter().fold(DVec2::splat(f64::MAX), |a, b| a.min(*b));
+        let max = points.iter().fold(DVec2::splat(f64::MIN), |a, b| a.max(*b));
+        Self {
+            kind,
+            points,
+            half_width,
+            min: min - half_width,
+            max: max + half_width,
+        }
+    }
+
+    fn is_area(&self) -> bool {
+        matches!(
+            self.kind,
+            OsmFeatureKind::Water | OsmFeatureKind::Building { .. }
+        )
+    }
+
+    /// Whether the centre of a block column lies on the feature
+    fn covers(&self, point: DVec2) -> bool {
+        if point.cmplt(self.min).any() || point.cmpgt(self.max).any() {
+            return false;
+        }
+        if self.is_area() {
+            point_in_polygon(&self.points, point)
+        } else {
+            distance_to_polyline(&self.points, point) <= self.half_width
+        }
+    }
+
+    /// Column whose surface height sets a building's floor level
+    fn anchor(&self) -> (i64, i64) {
+        let centre = (self.min + self.max) * 0.5;
+        (centre.x.floor() as i64, centre.y.floor() as i64)
+    }
+}
+
+fn point_in_polygon(polygon: &[DVec2], point: DVec2) -> bool {
+    let mut inside = false;
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn distance_to_polyline(points: &[DVec2], point: DVec2) -> f64 {
+    if points.len() == 1 {
+        return points[0].distance(point);
+    }
+    points
+        .windows(2)
+        .map(|segment| {
+            let (a, b) = (segment[0], segment[1]);
+            let ab = b - a;
+            let t = ((point - a).dot(ab) / ab.length_squared().max(f64::EPSILON)).clamp(0.0, 1.0);
+            (a + ab * t).distance(point)
+        })
+        .fold(f64::MAX, f64::min)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OsmBlocks {
+    water: BlockId,
+    road: BlockId,
+    path: BlockId,
+    wall: BlockId,
+    window: BlockId,
+    roof: BlockId,
+}
+
+impl OsmBlocks {
+    fn resolve(registry: &BlockRegistry) -> Result<Self, OsmError> {
+        let id = |name: &str| {
+            registry
+                .get_by_name(name)
+                .map(|def| def.id)
+                .ok_or_else(|| OsmError::UnknownBlock(name.to_string()))
+        };
+        Ok(Self {
+            water: id("water")?,
+            road: id("asphalt")?,
+            path: id("gravel")?,
+            wall: id("bricks")?,
+            window: id("glass")?,
+            roof: id("stone")?,
+        })
+    }
+}
+
+/// OSM features on the block grid, bucketed by the chunk columns they touch.
+/// Drawing only depends on the column being generated and the terrain
+/// height function, so chunks can be generated in any order.
+#[derive(Debug, Clone)]
+pub struct OsmOverlay {
+    features: Vec<OsmFeature>,
+    columns: HashMap<(i64, i64), Vec<usize>>,
+    blocks: OsmBlocks,
+    /// Blocks per storey
+    level_blocks: i64,
+    vertical_scale: f64,
+}
+
+impl OsmOverlay {
+    pub fn new(
+        data: &OsmData,
+        projection: GeoProjection,
+        registry: &BlockRegistry,
+    ) -> Result<Self, OsmError> {
+        let mut features = Vec::new();
+        for way in &data.ways {
+            let Some(kind) = OsmFeatureKind::from_way(way) else {
+                continue;
+            };
+            let points: Vec<DVec2> = way
+                .nodes
+                .iter()
+                .filter_map(|id| data.nodes.get(id))
+                .map(|&(lat, lon)| {
+                    let block = projection.to_block(GeoPosition::new(lat, lon, 0.0));
+                    DVec2::new(block.x, block.z)
+                })
+                .collect();
+            // Ways cut off at the extract boundary keep the nodes that are present
+            if points.len() < 2 {
+                continue;
+            }
+            features.push(OsmFeature::new(kind, points, projection.blocks_per_metre));
+        }
+        // Stable draw order within a column
+        features.sort_by_key(|feature| feature.kind.layer());
+
+        let mut columns: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (i, feature) in features.iter().enumerate() {
+            let min = (feature.min / 32.0).floor();
+            let max = (feature.max / 32.0).floor();
+            for cx in min.x as i64..=max.x as i64 {
+                for cz in min.y as i64..=max.y as i64 {
+                    columns.entry((cx, cz)).or_default().push(i);
+                }
+            }
+        }
+
+        Ok(Self {
+            features,
+            columns,
+            blocks: OsmBlocks::resolve(registry)?,
+            level_blocks: (LEVEL_HEIGHT * projection.vertical_scale).round().max(2.0) as i64,
+            vertical_scale: projection.vertical_scale,
+        })
+    }
+
+    pub fn load(
+        config: &OsmConfig,
+        projection: GeoProjection,
+        registry: &BlockRegistry,
+    ) -> Result<Self, OsmError> {
+        Self::new(&OsmData::load(&config.path)?, projection, registry)
+    }
+
+    pub fn features(&self) -> &[OsmFeature] {
+        &self.features
+    }
+
+    /// Whether any feature covers the block column, e.g. to keep trees off roads
+    pub fn covers(&self, x: i64, z: i64) -> bool {
+        let point = DVec2::new(x as f64 + 0.5, z as f64 + 0.5);
+        self.features_near(x.div_euclid(32), z.div_euclid(32))
+            .any(|feature| feature.covers(point))
+    }
+
+    fn features_near(&self, cx: i64, cz: i64) -> impl Iterator<Item = &OsmFeature> {
+        self.columns
+            .get(&(cx, cz))
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.features[i])
+    }
+
+    /// Draws water, roads and buildings into a generated chunk. `surface`
+    /// gives the terrain height of a column.
+    pub fn apply(&self, chunk: &mut Chunk, coord: ChunkCoord, surface: impl Fn(i64, i64) -> i32) {
+        let base = coord.to_block_pos(32);
+        if !self.columns.contains_key(&(coord.x(), coord.z())) {
+            return;
+        }
+
+        for x in 0..32 {
+            for z in 0..32 {
+                let (world_x, world_z) = (base.x + x, base.z + z);
+                let point = DVec2::new(world_x as f64 + 0.5, world_z as f64 + 0.5);
+                let mut set = |y: i64, id: BlockId| {
+                    let local_y = y - base.y;
+                    if (0..32).contains(&local_y) {
+                        let block = (id != BlockId::AIR).then(|| Block::new(id));
+                        chunk.set_block(x as u32, local_y as u32, z as u32, block);
+                    }
+                };
+
+                for feature in self.features_near(coord.x(), coord.z()) {
+                    if !feature.covers(point) {
+                        continue;
+                    }
+                    let ground = surface(world_x, world_z) as i64;
+                    match feature.kind {
+                        OsmFeatureKind::Water | OsmFeatureKind::Waterway { .. } => {
+                            let depth = if feature.kind == OsmFeatureKind::Water {
+                                LAKE_DEPTH
+                            } else {
+                                RIVER_DEPTH
+                            };
+                            for y in ground - depth + 1..=ground {
+                                set(y, self.blocks.water);
+                            }
+                        }
+                        OsmFeatureKind::Road { .. } => set(ground, self.blocks.road),
+                        OsmFeatureKind::Path { .. } => set(ground, self.blocks.path),
+                        OsmFeatureKind::Building { height } => {
+                            let (anchor_x, anchor_z) = feature.anchor();
+                            let floor = surface(anchor_x, anchor_z) as i64;
+                            let top =
+                                floor + (height * self.vertical_scale).round().max(2.0) as i64;
+                            let wall = distance_to_polyline(&feature.points, point) < 1.0;
+
+                            // Foundation down to the terrain, then the building itself
+                            for y in ground.min(floor)..floor {
+                                set(y, self.blocks.wall);
+                            }
+                            set(floor, self.blocks.wall);
+                            for y in floor + 1..top {
+                                let row = (y - floor) % self.level_blocks;
+                                let id = if !wall {
+                                    BlockId::AIR
+                                } else if row == self.level_blocks - 1
+                                    && (world_x + world_z).rem_euclid(2) == 0
+                                {
+                                    self.blocks.window
+                                } else {
+                                    self.blocks.wall
+                                };
+                                set(y, id);
+                            }
+                            set(top, self.blocks.roof);
+                            // Clear terrain rising above the roof
+                            for y in top + 1..=ground {
+                                set(y, BlockId::AIR);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6">
+  <node id="1" lat="47.0" lon="8.0"/>
+  <node id="2" lat="47.0" lon="8.001">
+    <tag k="highway" v="crossing"/>
+  </node>
+  <node id="3" lat="47.001" lon="8.001"/>
+  <way id="10">
+    <nd ref="1"/>
+    <nd ref="2"/>
+    <tag k="highway" v="residential"/>
+    <tag k="name" v="Bahnhof&amp;strasse"/>
+  </way>
+  <way id="11">
+    <nd ref="1"/>
+    <nd ref="2"/>
+    <nd ref="3"/>
+    <nd ref="1"/>
+    <tag k="building" v="yes"/>
+    <tag k="building:levels" v="4"/>
+  </way>
+</osm>"#;
+
+    fn way(nodes: &[i64], tags: &[(&str, &str)]) -> OsmWay {
+        OsmWay {
+            id: 0,
+            nodes: nodes.to_vec(),
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_xml() {
+        let data = OsmData::from_xml(XML.as_bytes()).unwrap();
+        assert_eq!(data.nodes.len(), 3);
+        assert_eq!(data.nodes[&3], (47.001, 8.001));
+        assert_eq!(data.ways.len(), 2);
+        assert_eq!(data.ways[0].nodes, vec![1, 2]);
+        assert_eq!(data.ways[0].tags["name"], "Bahnhof&strasse");
+        // Node tags are not attached to ways
+        assert_eq!(data.ways[0].tags.len(), 2);
+        assert!(data.ways[1].is_closed());
+    }
+
+    #[test]
+    fn test_classify_ways() {
+        let ring = [1, 2, 3, 1];
+        assert_eq!(
+            OsmFeatureKind::from_way(&way(
+                &ring,
+                &[("building", "yes"), ("building:levels", "4")]
+            )),
+            Some(OsmFeatureKind::Building { height: 12.0 })
+        );
+        assert_eq!(
+            OsmFeatureKind::from_way(&way(&ring, &[("building", "house"), ("height", "7.5 m")])),
+            Some(OsmFeatureKind::Building { height: 7.5 })
+        );
+        assert_eq!(
+            OsmFeatureKind::from_way(&way(&ring, &[("natural", "water")])),
+            Some(OsmFeatureKind::Water)
+        );
+        // Areas need a closed ring
+        assert_eq!(
+            OsmFeatureKind::from_way(&way(&[1, 2], &[("natural", "water")])),
+            None
+        );
+        assert_eq!(
+            OsmFeatureKind::from_way(&way(&[1, 2], &[("highway", "primary"), ("width", "14")])),
+            Some(OsmFeatureKind::Road { width: 14.0 })
+        );
+        assert_eq!(
+            OsmFeatureKind::from_way(&way(&[1, 2], &[("highway", "footway")])),
+            Some(OsmFeatureKind::Path { width: 2.0 })
+        );
+        assert_eq!(
+            OsmFeatureKind::from_way(&way(&[1, 2], &[("waterway", "river")])),
+            Some(OsmFeatureKind::Waterway { width: 8.0 })
+        );
+        assert_eq!(
+            OsmFeatureKind::from_way(&way(&[1, 2], &[("highway", "proposed")])),
+            None
+        );
+    }
+
+    /// Projection with the origin on node 1 at one block per metre
+    fn projection() -> GeoProjection {
+        GeoProjection {
+            origin_lat: 47.0,
+            origin_lon: 8.0,
+            vertical_scale: 1.0,
+            ..Default::default()
+        }
+    }
+
+    fn block_at(chunk: &Chunk, x: u32, y: u32, z: u32) -> BlockId {
+        chunk
+            .get_block(x, y, z)
+            .map_or(BlockId::AIR, |block| block.id)
+    }
+
+    #[test]
+    fn test_overlay_draws_roads_water_and_buildings() {
+        let registry = BlockRegistry::default();
+        let id = |name: &str| registry.get_by_name(name).unwrap().id;
+        let projection = projection();
+        let node = |x: f64, z: f64| {
+            let pos = projection.to_geo(glam::DVec3::new(x, 0.0, z));
+            (pos.lat, pos.lon)
+        };
+
+        let data = OsmData {
+            nodes: HashMap::from([
+                (1, node(0.0, 2.5)),
+                (2, node(31.0, 2.5)),
+                (3, node(4.0, 10.0)),
+                (4, node(12.0, 10.0)),
+                (5, node(12.0, 18.0)),
+                (6, node(4.0, 18.0)),
+                (7, node(20.0, 20.0)),
+                (8, node(30.0, 20.0)),
+                (9, node(30.0, 30.0)),
+                (10, node(20.0, 30.0)),
+            ]),
+            ways: vec![
+                way(&[1, 2], &[("highway", "residential")]),
+                way(
+                    &[3, 4, 5, 6, 3],
+                    &[("building", "yes"), ("building:levels", "2")],
+                ),
+                way(&[7, 8, 9, 10, 7], &[("natural", "water")]),
+                // References to nodes outside the extract are dropped
+                way(&[99, 98], &[("highway", "primary")]),
+            ],
+        };
+        let overlay = OsmOverlay::new(&data, projection, &registry).unwrap();
+        assert_eq!(overlay.features().len(), 3);
+
+        let coord = ChunkCoord::new(0, 1, 0);
+        let mut chunk = Chunk::new(coord);
+        let ground = 40;
+        overlay.apply(&mut chunk, coord, |_, _| ground);
+        let local = (ground - 32) as u32;
+
+        // A 5 m road along z = 2.5
+        assert_eq!(block_at(&chunk, 15, local, 2), id("asphalt"));
+        assert_eq!(block_at(&chunk, 15, local, 4), id("asphalt"));
+        assert_eq!(block_at(&chunk, 15, local, 6), BlockId::AIR);
+        assert!(overlay.covers(15, 2));
+        assert!(!overlay.covers(15, 8));
+
+        // Two storeys: floor, walls up to 6 blocks, roof, hollow inside
+        assert_eq!(block_at(&chunk, 4, local + 1, 14), id("bricks"));
+        assert_eq!(block_at(&chunk, 8, local, 14), id("bricks"));
+        assert_eq!(block_at(&chunk, 8, local + 3, 14), BlockId::AIR);
+        assert_eq!(block_at(&chunk, 8, local + 6, 14), id("stone"));
+        assert!((1..6).any(|y| block_at(&chunk, 4, local + y, 14) == id("glass")));
+
+        // The lake is three blocks deep below the surface
+        assert_eq!(block_at(&chunk, 25, local, 25), id("water"));
+        assert_eq!(block_at(&chunk, 25, local - 2, 25), id("water"));
+        assert_eq!(block_at(&chunk, 25, local - 3, 25), BlockId::AIR);
+
+        // Other chunk columns are untouched
+        let far = ChunkCoord::new(5, 1, 5);
+        let mut empty = Chunk::new(far);
+        overlay.apply(&mut empty, far, |_, _| ground);
+        assert!(!overlay.covers(5 * 32, 5 * 32));
+    }
+
+    #[test]
+    fn test_polygon_helpers() {
+        let square = [
+            DVec2::new(0.0, 0.0),
+            DVec2::new(4.0, 0.0),
+            DVec2::new(4.0, 4.0),
+            DVec2::new(0.0, 4.0),
+            DVec2::new(0.0, 0.0),
+        ];
+        assert!(point_in_polygon(&square, DVec2::new(2.0, 2.0)));
+        assert!(!point_in_polygon(&square, DVec2::new(5.0, 2.0)));
+        assert_eq!(distance_to_polyline(&square, DVec2::new(2.0, 1.0)), 1.0);
+        assert_eq!(
+            distance_to_polyline(&square[..2], DVec2::new(6.0, 0.0)),
+            2.0
+        );
+    }
+}
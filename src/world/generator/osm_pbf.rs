@@ -0,0 +1,389 @@
+//! Minimal reader for the OSM PBF format: a sequence of length-prefixed
+//! blobs holding protobuf `PrimitiveBlock`s. Only nodes, dense nodes and
+//! ways are decoded; uncompressed and zlib blobs are supported.
+
+use crate::world::generator::osm::{OsmData, OsmError, OsmWay};
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Iterates over the (field number, value) pairs of a protobuf message
+struct Message<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Message<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, OsmError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.data.get(self.pos).ok_or_else(truncated)?;
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(OsmError::Pbf("varint too long".to_string()))
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], OsmError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn field(&mut self) -> Result<(u64, Value<'a>), OsmError> {
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.read_bytes(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.read_bytes(len)?)
+            }
+            5 => {
+                self.read_bytes(4)?;
+                Value::Fixed
+            }
+            wire => return Err(OsmError::Pbf(format!("unsupported wire type {}", wire))),
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+impl<'a> Iterator for Message<'a> {
+    type Item = Result<(u64, Value<'a>), OsmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        (self.pos < self.data.len()).then(|| self.field())
+    }
+}
+
+fn truncated() -> OsmError {
+    OsmError::Pbf("unexpected end of data".to_string())
+}
+
+fn packed(bytes: &[u8]) -> Result<Vec<u64>, OsmError> {
+    let mut message = Message::new(bytes);
+    let mut values = Vec::new();
+    while message.pos < bytes.len() {
+        values.push(message.varint()?);
+    }
+    Ok(values)
+}
+
+fn zigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// Undoes the delta coding of packed `sint64` fields
+fn deltas(bytes: &[u8]) -> Result<Vec<i64>, OsmError> {
+    let mut current = 0i64;
+    Ok(packed(bytes)?
+        .into_iter()
+        .map(|value| {
+            current += zigzag(value);
+            current
+        })
+        .collect())
+}
+
+pub fn read(bytes: &[u8]) -> Result<OsmData, OsmError> {
+    let mut data = OsmData::default();
+    let mut file = Message::new(bytes);
+
+    while file.pos < bytes.len() {
+        let header_len = u32::from_be_bytes(file.read_bytes(4)?.try_into().unwrap()) as usize;
+        let mut kind = "";
+        let mut size = 0;
+        for field in Message::new(file.read_bytes(header_len)?) {
+            match field? {
+                (1, Value::Bytes(value)) => {
+                    kind = std::str::from_utf8(value)
+                        .map_err(|_| OsmError::Pbf("invalid blob type".to_string()))?;
+                }
+                (3, Value::Varint(value)) => size = value as usize,
+                _ => {}
+            }
+        }
+
+        let blob = file.read_bytes(size)?;
+        // OSMHeader blocks only list required features, which are not checked
+        if kind == "OSMData" {
+            read_block(&decode_blob(blob)?, &mut data)?;
+        }
+    }
+
+    Ok(data)
+}
+
+fn decode_blob(blob: &[u8]) -> Result<Vec<u8>, OsmError> {
+    for field in Message::new(blob) {
+        match field? {
+            (1, Value::Bytes(raw)) => return Ok(raw.to_vec()),
+            (3, Value::Bytes(zlib)) => {
+                let mut out = Vec::new();
+                ZlibDecoder::new(zlib).read_to_end(&mut out)?;
+                return Ok(out);
+            }
+            (4..=7, Value::Bytes(_)) => {
+                return Err(OsmError::Pbf("unsupported blob compression".to_string()))
+            }
+            _ => {}
+        }
+    }
+    Err(OsmError::Pbf("blob without data".to_string()))
+}
+
+/// Decodes a `PrimitiveBlock` into `data`
+fn read_block(block: &[u8], data: &mut OsmData) -> Result<(), OsmError> {
+    let mut strings = Vec::new();
+    let mut groups = Vec::new();
+    let mut granularity = 100i64;
+    let mut lat_offset = 0i64;
+    let mut lon_offset = 0i64;
+
+    for field in Message::new(block) {
+        match field? {
+            (1, Value::Bytes(table)) => {
+                for entry in Message::new(table) {
+                    if let (1, Value::Bytes(s)) = entry? {
+                        strings.push(String::from_utf8_lossy(s).into_owned());
+                    }
+                }
+            }
+            (2, Value::Bytes(group)) => groups.push(group),
+            (17, Value::Varint(value)) => granularity = value as i64,
+            (19, Value::Varint(value)) => lat_offset = value as i64,
+            (20, Value::Varint(value)) => lon_offset = value as i64,
+            _ => {}
+        }
+    }
+
+    let degrees = |offset: i64, value: i64| 1e-9 * (offset + granularity * value) as f64;
+    let string = |index: u64| {
+        strings
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| OsmError::Pbf(format!("string index {} out of range", index)))
+    };
+
+    for group in groups {
+        for field in Message::new(group) {
+            match field? {
+                (1, Value::Bytes(node)) => {
+                    let (mut id, mut lat, mut lon) = (0, 0, 0);
+                    for field in Message::new(node) {
+                        match field? {
+                            (1, Value::Varint(value)) => id = zigzag(value),
+                            (8, Value::Varint(value)) => lat = zigzag(value),
+                            (9, Value::Varint(value)) => lon = zigzag(value),
+                            _ => {}
+                        }
+                    }
+                    data.nodes
+                        .insert(id, (degrees(lat_offset, lat), degrees(lon_offset, lon)));
+                }
+                (2, Value::Bytes(dense)) => {
+                    let (mut ids, mut lats, mut lons) = (Vec::new(), Vec::new(), Vec::new());
+                    for field in Message::new(dense) {
+                        match field? {
+                            (1, Value::Bytes(bytes)) => ids = deltas(bytes)?,
+                            (8, Value::Bytes(bytes)) => lats = deltas(bytes)?,
+                            (9, Value::Bytes(bytes)) => lons = deltas(bytes)?,
+                            _ => {}
+                        }
+                    }
+                    if ids.len() != lats.len() || ids.len() != lons.len() {
+                        return Err(OsmError::Pbf(
+                            "dense node arrays differ in length".to_string(),
+                        ));
+                    }
+                    for ((id, lat), lon) in ids.into_iter().zip(lats).zip(lons) {
+                        data.nodes
+                            .insert(id, (degrees(lat_offset, lat), degrees(lon_offset, lon)));
+                    }
+                }
+                (3, Value::Bytes(way)) => {
+                    let mut result = OsmWay::default();
+                    let (mut keys, mut values) = (Vec::new(), Vec::new());
+                    for field in Message::new(way) {
+                        match field? {
+                            (1, Value::Varint(value)) => result.id = value as i64,
+                            (2, Value::Bytes(bytes)) => keys = packed(bytes)?,
+                            (3, Value::Bytes(bytes)) => values = packed(bytes)?,
+                            (8, Value::Bytes(bytes)) => result.nodes = deltas(bytes)?,
+                            _ => {}
+                        }
+                    }
+                    for (key, value) in keys.into_iter().zip(values) {
+                        result.tags.insert(string(key)?, string(value)?);
+                    }
+                    data.ways.push(result);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn bytes_field(number: u64, bytes: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        varint(number << 3 | 2, &mut out);
+        varint(bytes.len() as u64, &mut out);
+        out.extend_from_slice(bytes);
+        out
+    }
+
+    fn varint_field(number: u64, value: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        varint(number << 3, &mut out);
+        varint(value, &mut out);
+        out
+    }
+
+    fn packed_field(number: u64, values: &[u64]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for value in values {
+            varint(*value, &mut bytes);
+        }
+        bytes_field(number, &bytes)
+    }
+
+    fn zigzag_encode(value: i64) -> u64 {
+        ((value << 1) ^ (value >> 63)) as u64
+    }
+
+    fn delta_field(number: u64, values: &[i64]) -> Vec<u8> {
+        let mut previous = 0;
+        let encoded: Vec<u64> = values
+            .iter()
+            .map(|value| {
+                let delta = value - previous;
+                previous = *value;
+                zigzag_encode(delta)
+            })
+            .collect();
+        packed_field(number, &encoded)
+    }
+
+    fn file_block(kind: &str, blob: Vec<u8>) -> Vec<u8> {
+        let header = [
+            bytes_field(1, kind.as_bytes()),
+            varint_field(3, blob.len() as u64),
+        ]
+        .concat();
+        [(header.len() as u32).to_be_bytes().to_vec(), header, blob].concat()
+    }
+
+    /// A primitive block with two dense nodes, one plain node and a tagged way
+    fn primitive_block() -> Vec<u8> {
+        let strings = [
+            bytes_field(1, b""),
+            bytes_field(1, b"highway"),
+            bytes_field(1, b"residential"),
+        ]
+        .concat();
+        let dense = [
+            delta_field(1, &[100, 101]),
+            delta_field(8, &[470_000_000, 470_010_000]),
+            delta_field(9, &[80_000_000, 80_010_000]),
+        ]
+        .concat();
+        let node = [
+            varint_field(1, zigzag_encode(-5)),
+            varint_field(8, zigzag_encode(-335_000_000)),
+            varint_field(9, zigzag_encode(-702_500_000)),
+        ]
+        .concat();
+        let way = [
+            varint_field(1, 7),
+            packed_field(2, &[1]),
+            packed_field(3, &[2]),
+            delta_field(8, &[100, 101, -5]),
+        ]
+        .concat();
+        let group = [
+            bytes_field(2, &dense),
+            bytes_field(1, &node),
+            bytes_field(3, &way),
+        ]
+        .concat();
+        [bytes_field(1, &strings), bytes_field(2, &group)].concat()
+    }
+
+    fn check(data: &OsmData) {
+        assert_eq!(data.nodes.len(), 3);
+        let (lat, lon) = data.nodes[&101];
+        assert!((lat - 47.001).abs() < 1e-12 && (lon - 8.001).abs() < 1e-12);
+        let (lat, lon) = data.nodes[&-5];
+        assert!((lat + 33.5).abs() < 1e-12 && (lon + 70.25).abs() < 1e-12);
+
+        assert_eq!(data.ways.len(), 1);
+        assert_eq!(data.ways[0].id, 7);
+        assert_eq!(data.ways[0].nodes, vec![100, 101, -5]);
+        assert_eq!(data.ways[0].tags["highway"], "residential");
+    }
+
+    #[test]
+    fn test_read_raw_blocks() {
+        let header = file_block("OSMHeader", bytes_field(1, b""));
+        let data = file_block("OSMData", bytes_field(1, &primitive_block()));
+        check(&read(&[header, data].concat()).unwrap());
+    }
+
+    #[test]
+    fn test_read_zlib_blocks() {
+        let block = primitive_block();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&block).unwrap();
+        let blob = [
+            varint_field(2, block.len() as u64),
+            bytes_field(3, &encoder.finish().unwrap()),
+        ]
+        .concat();
+        check(&read(&file_block("OSMData", blob)).unwrap());
+    }
+
+    #[test]
+    fn test_truncated_data() {
+        let data = file_block("OSMData", bytes_field(1, &primitive_block()));
+        assert!(matches!(
+            read(&data[..data.len() - 3]),
+            Err(OsmError::Pbf(_))
+        ));
+    }
+}
@@ -0,0 +1,406 @@
+use crate::world::block_id::BlockId;
+use crate::world::chunk::Chunk;
+use crate::world::chunk_coord::ChunkCoord;
+use crate::world::generator::lod;
+use crate::world::generator::terrain::{BiomeType, TerrainGenerator};
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Width of the black bars between the panels of a comparison
+const PANEL_GAP: u32 = 4;
+
+/// Height the hill shading light comes from, relative to the slope
+const LIGHT_HEIGHT: f64 = 2.0;
+
+const SKY_COLOR: [u8; 3] = [235, 240, 255];
+const CAVE_COLOR: [u8; 3] = [220, 40, 40];
+const CHANGED_COLOR: [u8; 3] = [255, 0, 255];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PreviewError {
+    #[error("Preview region is empty: {width}x{depth} blocks at {scale} blocks per pixel")]
+    EmptyRegion { width: u32, depth: u32, scale: u32 },
+    #[error("Cross-section range {min_y}..{max_y} is empty")]
+    InvalidRange { min_y: i64, max_y: i64 },
+}
+
+/// What a preview image shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewLayer {
+    /// Top-down map coloured by biome
+    Biomes,
+    /// Top-down map of surface block colours with hill shading
+    Height,
+    /// Vertical slice along x through the middle of the region, with caves
+    /// and aquifers carved
+    Caves,
+}
+
+/// Area of the world a preview covers. Maps are `width / scale` by
+/// `depth / scale` pixels; cross-sections are `width / scale` pixels wide
+/// and one pixel per `scale` blocks of `min_y..max_y` high.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreviewRegion {
+    pub x: i64,
+    pub z: i64,
+    pub width: u32,
+    pub depth: u32,
+    /// Blocks per pixel edge
+    pub scale: u32,
+    pub min_y: i64,
+    pub max_y: i64,
+}
+
+impl Default for PreviewRegion {
+    fn default() -> Self {
+        Self {
+            x: -256,
+            z: -256,
+            width: 512,
+            depth: 512,
+            scale: 1,
+            min_y: 0,
+            max_y: 128,
+        }
+    }
+}
+
+impl PreviewRegion {
+    fn validate(&self, layer: PreviewLayer) -> Result<(), PreviewError> {
+        if self.scale == 0 || self.width < self.scale || self.depth < self.scale {
+            return Err(PreviewError::EmptyRegion {
+                width: self.width,
+                depth: self.depth,
+                scale: self.scale,
+            });
+        }
+        if layer == PreviewLayer::Caves && self.max_y - self.min_y < self.scale as i64 {
+            return Err(PreviewError::InvalidRange {
+                min_y: self.min_y,
+                max_y: self.max_y,
+            });
+        }
+        Ok(())
+    }
+
+    fn size(&self, layer: PreviewLayer) -> (u32, u32) {
+        let height = match layer {
+            PreviewLayer::Caves => (self.max_y - self.min_y) as u32 / self.scale,
+            _ => self.depth / self.scale,
+        };
+        (self.width / self.scale, height)
+    }
+
+    /// z of the cross-section slice
+    fn slice_z(&self) -> i64 {
+        self.z + self.depth as i64 / 2
+    }
+}
+
+/// Two previews side by side with a third panel marking where they differ
+pub struct PreviewComparison {
+    pub image: RgbaImage,
+    pub changed_pixels: u64,
+    pub total_pixels: u64,
+}
+
+/// Renders one layer of a region, a row of pixels per task on the rayon
+/// pool the call runs in
+pub fn render_preview(
+    generator: &TerrainGenerator,
+    layer: PreviewLayer,
+    region: &PreviewRegion,
+) -> Result<RgbaImage, PreviewError> {
+    region.validate(layer)?;
+    let (width, height) = region.size(layer);
+    let slice = match layer {
+        PreviewLayer::Caves => Slice::generate(generator, region),
+        _ => Slice::default(),
+    };
+
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    pixels
+        .par_chunks_mut(width as usize * 4)
+        .enumerate()
+        .for_each(|(row, pixels)| {
+            for (column, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+                let [r, g, b] = match layer {
+                    PreviewLayer::Biomes => {
+                        let (x, z) = map_position(region, column, row);
+                        biome_color(generator.biome_at(x, z))
+                    }
+                    PreviewLayer::Height => {
+                        let (x, z) = map_position(region, column, row);
+                        shaded_color(generator, x, z, region.scale as i64)
+                    }
+                    PreviewLayer::Caves => slice.color(generator, region, column, row),
+                };
+                pixel.copy_from_slice(&[r, g, b, 255]);
+            }
+        });
+    Ok(RgbaImage::from_raw(width, height, pixels).expect("buffer matches image size"))
+}
+
+/// Renders the layer for two generators, typically one config before and
+/// after a change, next to a panel of the first with changed pixels marked
+pub fn render_comparison(
+    before: &TerrainGenerator,
+    after: &TerrainGenerator,
+    layer: PreviewLayer,
+    region: &PreviewRegion,
+) -> Result<PreviewComparison, PreviewError> {
+    let (a, b) = rayon::join(
+        || render_preview(before, layer, region),
+        || render_preview(after, layer, region),
+    );
+    let (a, b) = (a?, b?);
+    let (width, height) = a.dimensions();
+
+    let mut image = RgbaImage::from_pixel(width * 3 + PANEL_GAP * 2, height, Rgba([0, 0, 0, 255]));
+    let mut changed_pixels = 0;
+    for (x, y, before) in a.enumerate_pixels() {
+        let after = b.get_pixel(x, y);
+        image.put_pixel(x, y, *before);
+        image.put_pixel(x + width + PANEL_GAP, y, *after);
+        let marked = if before == after {
+            // Faded, so the changes stand out
+            let [r, g, b, a] = before.0;
+            Rgba([r / 3 + 170, g / 3 + 170, b / 3 + 170, a])
+        } else {
+            changed_pixels += 1;
+            let [r, g, b] = CHANGED_COLOR;
+            Rgba([r, g, b, 255])
+        };
+        image.put_pixel(x + (width + PANEL_GAP) * 2, y, marked);
+    }
+    Ok(PreviewComparison {
+        image,
+        changed_pixels,
+        total_pixels: width as u64 * height as u64,
+    })
+}
+
+pub fn biome_color(biome: BiomeType) -> [u8; 3] {
+    match biome {
+        BiomeType::Plains => [141, 179, 96],
+        BiomeType::Mountains => [128, 128, 128],
+        BiomeType::Desert => [250, 148, 24],
+        BiomeType::Forest => [5, 102, 33],
+        BiomeType::Ocean => [0, 0, 112],
+        BiomeType::Tundra => [200, 220, 230],
+        BiomeType::Swamp => [7, 249, 178],
+        BiomeType::River => [48, 96, 255],
+    }
+}
+
+/// Block column at the middle of a map pixel
+fn map_position(region: &PreviewRegion, column: usize, row: usize) -> (i64, i64) {
+    let scale = region.scale as i64;
+    (
+        region.x + column as i64 * scale + scale / 2,
+        region.z + row as i64 * scale + scale / 2,
+    )
+}
+
+/// Surface block colour lit from the north-west by the slope to the
+/// neighbouring pixels. Water is flat and darkens with depth.
+fn shaded_color(generator: &TerrainGenerator, x: i64, z: i64, step: i64) -> [u8; 3] {
+    let (column, block) = generator.surface_at(x, z);
+    let color = albedo(generator, block);
+    if column.water_level > column.height {
+        let depth = (column.water_level - column.height) as f64;
+        return scale_color(color, (1.0 - depth / 48.0).max(0.35));
+    }
+
+    let height = |x, z| generator.surface_at(x, z).0.height as f64;
+    let dx = (height(x + step, z) - column.height as f64) / step as f64;
+    let dz = (height(x, z + step) - column.height as f64) / step as f64;
+    // Lambert term for a light at (-1, LIGHT_HEIGHT, -1)
+    let normal = [-dx, 1.0, -dz];
+    let light = [-1.0, LIGHT_HEIGHT, -1.0];
+    let dot: f64 = normal.iter().zip(light).map(|(n, l)| n * l).sum();
+    let length = |v: [f64; 3]| v.iter().map(|c| c * c).sum::<f64>().sqrt();
+    let lambert = (dot / (length(normal) * length(light))).max(0.0);
+    scale_color(color, 0.3 + 0.7 * lambert)
+}
+
+/// Chunks and ground heights along a cross-section
+#[derive(Default)]
+struct Slice {
+    chunks: HashMap<ChunkCoord, Chunk>,
+    /// Ground height under each pixel column
+    heights: Vec<i64>,
+}
+
+impl Slice {
+    /// Generates, in parallel, every chunk the cross-section passes through
+    fn generate(generator: &TerrainGenerator, region: &PreviewRegion) -> Self {
+        let z = region.slice_z();
+        let chunk_z = z.div_euclid(32);
+        let xs = region.x.div_euclid(32)..=(region.x + region.width as i64 - 1).div_euclid(32);
+        let ys = region.min_y.div_euclid(32)..=(region.max_y - 1).div_euclid(32);
+        let coords: Vec<_> = xs
+            .flat_map(|x| ys.clone().map(move |y| ChunkCoord::new(x, y, chunk_z)))
+            .collect();
+        let chunks = coords
+            .into_par_iter()
+            .map(|coord| (coord, generator.generate_chunk(coord)))
+            .collect();
+        let heights = (0..region.width / region.scale)
+            .into_par_iter()
+            .map(|column| {
+                let (x, _) = map_position(region, column as usize, 0);
+                generator.surface_at(x, z).0.height as i64
+            })
+            .collect();
+        Self { chunks, heights }
+    }
+
+    /// Block colour of a pixel. Open space below the ground is a cave;
+    /// above it, sky.
+    fn color(
+        &self,
+        generator: &TerrainGenerator,
+        region: &PreviewRegion,
+        column: usize,
+        row: usize,
+    ) -> [u8; 3] {
+        let scale = region.scale as i64;
+        let (x, _) = map_position(region, column, 0);
+        let y = region.max_y - 1 - row as i64 * scale - scale / 2;
+        let z = region.slice_z();
+        let block = self
+            .chunks
+            .get(&ChunkCoord::new(
+                x.div_euclid(32),
+                y.div_euclid(32),
+                z.div_euclid(32),
+            ))
+            .and_then(|chunk| chunk.get_block_at(x, y, z))
+            .map_or(BlockId::AIR, |block| block.id);
+
+        let height = self.heights[column];
+        if block != BlockId::AIR {
+            // Darken with depth a little, so layers read as a section
+            let depth = (height - y).max(0) as f64;
+            scale_color(albedo(generator, block), (1.0 - depth / 256.0).max(0.6))
+        } else if y <= height {
+            CAVE_COLOR
+        } else {
+            SKY_COLOR
+        }
+    }
+}
+
+fn albedo(generator: &TerrainGenerator, block: BlockId) -> [u8; 3] {
+    if block == BlockId::AIR {
+        return SKY_COLOR;
+    }
+    let [r, g, b, _] = lod::block_color(generator.block_registry(), block);
+    [r, g, b].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+fn scale_color(color: [u8; 3], factor: f64) -> [u8; 3] {
+    color.map(|c| (c as f64 * factor).round().clamp(0.0, 255.0) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::blocks_data::BlockRegistry;
+    use crate::world::generator::terrain::WorldGenConfig;
+    use std::sync::Arc;
+
+    fn generator(seed: u64) -> TerrainGenerator {
+        TerrainGenerator::new(
+            WorldGenConfig {
+                world_seed: seed,
+                terrain_height: 64,
+                height_multiplier: 16.0,
+                ..Default::default()
+            },
+            Arc::new(BlockRegistry::default()),
+        )
+    }
+
+    fn region() -> PreviewRegion {
+        PreviewRegion {
+            x: 100,
+            z: -40,
+            width: 96,
+            depth: 64,
+            scale: 2,
+            min_y: 0,
+            max_y: 96,
+        }
+    }
+
+    #[test]
+    fn test_maps_cover_the_region() {
+        let generator = generator(7);
+        let region = region();
+        let biomes = render_preview(&generator, PreviewLayer::Biomes, &region).unwrap();
+        assert_eq!(biomes.dimensions(), (48, 32));
+        let (x, z) = map_position(&region, 10, 20);
+        let [r, g, b] = biome_color(generator.biome_at(x, z));
+        assert_eq!(biomes.get_pixel(10, 20).0, [r, g, b, 255]);
+
+        let height = render_preview(&generator, PreviewLayer::Height, &region).unwrap();
+        assert_eq!(height.dimensions(), (48, 32));
+        assert_ne!(height.get_pixel(0, 0).0, [0, 0, 0, 0]);
+
+        assert_eq!(
+            render_preview(
+                &generator,
+                PreviewLayer::Biomes,
+                &PreviewRegion { scale: 0, ..region }
+            )
+            .unwrap_err(),
+            PreviewError::EmptyRegion {
+                width: 96,
+                depth: 64,
+                scale: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_cross_section_shows_ground_and_sky() {
+        let generator = generator(7);
+        let region = region();
+        let section = render_preview(&generator, PreviewLayer::Caves, &region).unwrap();
+        assert_eq!(section.dimensions(), (48, 48));
+        // Sky above the surface at the top row, solid ground at the bottom
+        let [r, g, b] = SKY_COLOR;
+        assert_eq!(section.get_pixel(5, 0).0, [r, g, b, 255]);
+        let [r, g, b] = CAVE_COLOR;
+        assert_ne!(section.get_pixel(5, 47).0, [r, g, b, 255]);
+        assert_ne!(section.get_pixel(5, 47).0, section.get_pixel(5, 0).0);
+    }
+
+    #[test]
+    fn test_comparison_marks_changed_pixels() {
+        let region = PreviewRegion {
+            scale: 4,
+            ..region()
+        };
+        let same =
+            render_comparison(&generator(7), &generator(7), PreviewLayer::Biomes, &region).unwrap();
+        assert_eq!(same.changed_pixels, 0);
+        assert_eq!(same.total_pixels, 24 * 16);
+        assert_eq!(same.image.dimensions(), (24 * 3 + PANEL_GAP * 2, 16));
+
+        let changed =
+            render_comparison(&generator(7), &generator(8), PreviewLayer::Height, &region).unwrap();
+        assert!(changed.changed_pixels > 0);
+        let [r, g, b] = CHANGED_COLOR;
+        assert!(changed
+            .image
+            .pixels()
+            .any(|pixel| pixel.0 == [r, g, b, 255]));
+    }
+}
@@ -0,0 +1,538 @@
+use glam::DVec2;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::Arc;
+
+/// Height added per cell along filled depressions, so every cell drains
+const FILL_EPSILON: f64 = 1e-3;
+
+pub(crate) const NEIGHBOURS: [(i64, i64); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// Parameters of the river network. Distances are in blocks unless noted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RiverConfig {
+    /// Spacing of the flow grid the terrain is sampled on
+    pub cell_size: u32,
+    /// Flow grid cells per side of a cached region
+    pub region_cells: u32,
+    /// Cells sampled around each region, so flow directions agree with
+    /// the neighbouring regions
+    pub margin_cells: u32,
+    /// How many regions upstream inflow is followed. Catchments reaching
+    /// further are cut off there.
+    pub upstream_regions: u32,
+    /// Upstream cells needed before a river appears
+    pub min_catchment: u32,
+    /// River width is `width_scale * sqrt(catchment - min_catchment + 1)`
+    pub width_scale: f64,
+    pub min_width: f64,
+    pub max_width: f64,
+    /// Bed depth at the centre as a share of the half width
+    pub depth_ratio: f64,
+    pub max_depth: f64,
+    /// Flow regions kept in memory
+    pub cached_regions: usize,
+}
+
+impl Default for RiverConfig {
+    fn default() -> Self {
+        Self {
+            cell_size: 16,
+            region_cells: 64,
+            margin_cells: 8,
+            upstream_regions: 4,
+            min_catchment: 64,
+            width_scale: 0.75,
+            min_width: 3.0,
+            max_width: 28.0,
+            depth_ratio: 0.35,
+            max_depth: 6.0,
+            cached_regions: 256,
+        }
+    }
+}
+
+/// A block column lying in a river
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RiverSample {
+    /// Distance from the river's centre line
+    pub distance: f64,
+    pub width: f64,
+    /// Terrain height along the river, decreasing downstream
+    pub level: f64,
+    /// Bed depth below the water surface at this column
+    pub depth: f64,
+}
+
+/// Drained heights and flow directions of one region and its margin
+#[derive(Debug)]
+struct Routing {
+    /// Flow grid cell of index 0, at the corner of the margin
+    min: (i64, i64),
+    /// Cells per side including the margins
+    size: usize,
+    filled: Vec<f64>,
+    downstream: Vec<Option<usize>>,
+    /// Core cells from the highest to the lowest drained height
+    order: Vec<usize>,
+}
+
+impl Routing {
+    fn cell(&self, i: usize) -> (i64, i64) {
+        (
+            self.min.0 + (i % self.size) as i64,
+            self.min.1 + (i / self.size) as i64,
+        )
+    }
+
+    fn index(&self, cell: (i64, i64)) -> Option<usize> {
+        let (x, z) = (cell.0 - self.min.0, cell.1 - self.min.1);
+        let size = self.size as i64;
+        (x >= 0 && z >= 0 && x < size && z < size).then(|| (z * size + x) as usize)
+    }
+}
+
+/// Bounded cache of per-region results, evicting the oldest first
+#[derive(Debug)]
+pub(crate) struct RegionCache<K, V> {
+    entries: HashMap<K, Arc<V>>,
+    order: VecDeque<K>,
+}
+
+impl<K: Copy + Eq + Hash, V> RegionCache<K, V> {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// The cached value of `key`, without computing it
+    pub(crate) fn get(&self, key: &K) -> Option<Arc<V>> {
+        self.entries.get(key).cloned()
+    }
+}
+
+/// Looks a value up in `cache`, computing it outside the lock if missing.
+/// A value computed twice by racing threads is identical.
+pub(crate) fn cached<K: Copy + Eq + Hash, V>(
+    cache: &RwLock<RegionCache<K, V>>,
+    key: K,
+    capacity: usize,
+    compute: impl FnOnce() -> V,
+) -> Arc<V> {
+    if let Some(value) = cache.read().entries.get(&key) {
+        return value.clone();
+    }
+    let value = Arc::new(compute());
+    let mut cache = cache.write();
+    if cache.entries.insert(key, value.clone()).is_none() {
+        cache.order.push_back(key);
+    }
+    while cache.order.len() > capacity {
+        if let Some(oldest) = cache.order.pop_front() {
+            cache.entries.remove(&oldest);
+        }
+    }
+    value
+}
+
+#[derive(PartialEq)]
+pub(crate) struct Queued {
+    pub(crate) height: f64,
+    pub(crate) index: usize,
+}
+
+impl Eq for Queued {}
+
+impl Ord for Queued {
+    /// Lowest first for `BinaryHeap`, ties broken by index for determinism
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .height
+            .total_cmp(&self.height)
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Rivers from flow accumulation on a coarse grid over the terrain height.
+/// Flow is routed per region, and catchments are summed across regions by
+/// following inflow up to `upstream_regions` regions upstream. Everything
+/// is cached per region and only depends on the region coordinates, so
+/// any chunk can ask for its rivers without generating its neighbours.
+#[derive(Debug)]
+pub struct RiverNetwork {
+    config: RiverConfig,
+    sea_level: i32,
+    routing: RwLock<RegionCache<(i64, i64), Routing>>,
+    catchment: RwLock<RegionCache<(i64, i64, u32), Vec<u32>>>,
+}
+
+impl RiverNetwork {
+    pub fn new(config: RiverConfig, sea_level: i32) -> Self {
+        let mut config = config;
+        config.cell_size = config.cell_size.max(1);
+        config.region_cells = config.region_cells.max(1);
+        config.margin_cells = config.margin_cells.max(1);
+        Self {
+            config,
+            sea_level,
+            routing: RwLock::new(RegionCache::new()),
+            catchment: RwLock::new(RegionCache::new()),
+        }
+    }
+
+    pub fn config(&self) -> &RiverConfig {
+        &self.config
+    }
+
+    /// The river at a block column, if any. `height` gives the terrain
+    /// height of a column before rivers are carved.
+    pub fn sample(&self, x: i64, z: i64, height: &impl Fn(i64, i64) -> f64) -> Option<RiverSample> {
+        let cell_size = self.config.cell_size as f64;
+        let point = DVec2::new(x as f64 + 0.5, z as f64 + 0.5);
+        let (cell_x, cell_z) = (
+            (point.x / cell_size).floor() as i64,
+            (point.y / cell_size).floor() as i64,
+        );
+        // Segments reach the next cell centre, plus half the widest river
+        let reach = 1 + (self.config.max_width * 0.5 / cell_size).ceil() as i64;
+
+        let mut best: Option<RiverSample> = None;
+        for cz in cell_z - reach..=cell_z + reach {
+            for cx in cell_x - reach..=cell_x + reach {
+                let Some(sample) = self.sample_segment((cx, cz), point, height) else {
+                    continue;
+                };
+                if best.is_none_or(|best| sample.distance < best.distance) {
+                    best = Some(sample);
+                }
+            }
+        }
+        best
+    }
+
+    /// Samples the river leaving a flow cell towards its downstream cell
+    fn sample_segment(
+        &self,
+        cell: (i64, i64),
+        point: DVec2,
+        height: &impl Fn(i64, i64) -> f64,
+    ) -> Option<RiverSample> {
+        let depth = self.config.upstream_regions;
+        let region = self.region_of(cell);
+        let routing = self.routing(region, height);
+        let catchment = self.catchment(region, depth, height);
+
+        let i = routing.index(cell)?;
+        let j = routing.downstream[i]?;
+        let from_catchment = catchment[self.core_index(region, cell)];
+        if from_catchment < self.config.min_catchment {
+            return None;
+        }
+        let to_cell = routing.cell(j);
+        let to_catchment = if self.region_of(to_cell) == region {
+            catchment[self.core_index(region, to_cell)]
+        } else {
+            self.catchment(self.region_of(to_cell), depth, height)
+                [self.core_index(self.region_of(to_cell), to_cell)]
+        };
+
+        let (from, to) = (self.cell_centre(cell), self.cell_centre(to_cell));
+        let along = to - from;
+        let t = ((point - from).dot(along) / along.length_squared()).clamp(0.0, 1.0);
+        let distance = (from + along * t).distance(point);
+        let width = self.width(from_catchment)
+            + (self.width(to_catchment.max(from_catchment)) - self.width(from_catchment)) * t;
+        let half = width * 0.5;
+        if distance > half {
+            return None;
+        }
+
+        let centre_depth = (half * self.config.depth_ratio).clamp(1.0, self.config.max_depth);
+        Some(RiverSample {
+            distance,
+            width,
+            level: routing.filled[i] + (routing.filled[j] - routing.filled[i]) * t,
+            depth: 1.0 + centre_depth * (1.0 - (distance / half).powi(2)),
+        })
+    }
+
+    fn width(&self, catchment: u32) -> f64 {
+        let excess = (catchment.saturating_sub(self.config.min_catchment) + 1) as f64;
+        (self.config.width_scale * excess.sqrt())
+            .clamp(self.config.min_width, self.config.max_width)
+    }
+
+    fn region_of(&self, cell: (i64, i64)) -> (i64, i64) {
+        let size = self.config.region_cells as i64;
+        (cell.0.div_euclid(size), cell.1.div_euclid(size))
+    }
+
+    fn core_index(&self, region: (i64, i64), cell: (i64, i64)) -> usize {
+        let size = self.config.region_cells as i64;
+        let (x, z) = (cell.0 - region.0 * size, cell.1 - region.1 * size);
+        (z * size + x) as usize
+    }
+
+    fn cell_centre(&self, cell: (i64, i64)) -> DVec2 {
+        let cell_size = self.config.cell_size as f64;
+        DVec2::new(cell.0 as f64 + 0.5, cell.1 as f64 + 0.5) * cell_size
+    }
+
+    fn routing(&self, region: (i64, i64), height: &impl Fn(i64, i64) -> f64) -> Arc<Routing> {
+        cached(
+            &self.routing,
+            region,
+            self.config.cached_regions.max(9),
+            || self.route_region(region, height),
+        )
+    }
+
+    /// Upstream cell count of every core cell of a region, including inflow
+    /// from neighbouring regions followed `depth` regions upstream
+    fn catchment(
+        &self,
+        region: (i64, i64),
+        depth: u32,
+        height: &impl Fn(i64, i64) -> f64,
+    ) -> Arc<Vec<u32>> {
+        let key = (region.0, region.1, depth);
+        cached(
+            &self.catchment,
+            key,
+            self.config.cached_regions.max(9),
+            || {
+                let routing = self.routing(region, height);
+                let core = self.config.region_cells as usize;
+                let mut catchment = vec![1u32; core * core];
+
+                if depth > 0 {
+                    for (dx, dz) in NEIGHBOURS {
+                        let neighbour = (region.0 + dx, region.1 + dz);
+                        let neighbour_routing = self.routing(neighbour, height);
+                        let mut upstream = None;
+                        for &i in &neighbour_routing.order {
+                            let Some(j) = neighbour_routing.downstream[i] else {
+                                continue;
+                            };
+                            let target = neighbour_routing.cell(j);
+                            if self.region_of(target) != region {
+                                continue;
+                            }
+                            let upstream = upstream.get_or_insert_with(|| {
+                                self.catchment(neighbour, depth - 1, height)
+                            });
+                            let source = neighbour_routing.cell(i);
+                            catchment[self.core_index(region, target)] +=
+                                upstream[self.core_index(neighbour, source)];
+                        }
+                    }
+                }
+
+                for &i in &routing.order {
+                    let Some(j) = routing.downstream[i] else {
+                        continue;
+                    };
+                    let (source, target) = (routing.cell(i), routing.cell(j));
+                    if self.region_of(target) == region {
+                        catchment[self.core_index(region, target)] +=
+                            catchment[self.core_index(region, source)];
+                    }
+                }
+                catchment
+            },
+        )
+    }
+
+    /// Samples the region and its margin, drains depressions by priority
+    /// flood and routes every cell to its steepest lower neighbour
+    fn route_region(&self, region: (i64, i64), height: &impl Fn(i64, i64) -> f64) -> Routing {
+        let core = self.config.region_cells as i64;
+        let margin = self.config.margin_cells as i64;
+        let n = (core + 2 * margin) as usize;
+        let min = (region.0 * core - margin, region.1 * core - margin);
+        let mut routing = Routing {
+            min,
+            size: n,
+            filled: Vec::new(),
+            downstream: Vec::new(),
+            order: Vec::new(),
+        };
+
+        let heights: Vec<f64> = (0..n * n)
+            .map(|i| {
+                let centre = self.cell_centre(routing.cell(i));
+                height(centre.x.floor() as i64, centre.y.floor() as i64)
+            })
+            .collect();
+        let neighbours = |i: usize| {
+            let (x, z) = ((i % n) as i64, (i / n) as i64);
+            NEIGHBOURS.iter().filter_map(move |(dx, dz)| {
+                let (nx, nz) = (x + dx, z + dz);
+                (nx >= 0 && nz >= 0 && nx < n as i64 && nz < n as i64)
+                    .then(|| nz as usize * n + nx as usize)
+            })
+        };
+
+        // Water leaves at the sea and across the outer edge of the margin
+        let sea = self.sea_level as f64;
+        let mut filled = heights.clone();
+        let mut visited = vec![false; n * n];
+        let mut queue = BinaryHeap::new();
+        for i in 0..n * n {
+            let (x, z) = (i % n, i / n);
+            if heights[i] <= sea || x == 0 || z == 0 || x == n - 1 || z == n - 1 {
+                visited[i] = true;
+                queue.push(Queued {
+                    height: heights[i],
+                    index: i,
+                });
+            }
+        }
+        while let Some(Queued { height, index }) = queue.pop() {
+            for j in neighbours(index) {
+                if !visited[j] {
+                    visited[j] = true;
+                    filled[j] = filled[j].max(height + FILL_EPSILON);
+                    queue.push(Queued {
+                        height: filled[j],
+                        index: j,
+                    });
+                }
+            }
+        }
+
+        routing.downstream = (0..n * n)
+            .map(|i| {
+                if heights[i] <= sea {
+                    return None;
+                }
+                let here = self.cell_centre(routing.cell(i));
+                neighbours(i)
+                    .filter(|&j| filled[j] < filled[i])
+                    .map(|j| {
+                        let distance = here.distance(self.cell_centre(routing.cell(j)));
+                        (j, (filled[i] - filled[j]) / distance)
+                    })
+                    .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+                    .map(|(j, _)| j)
+            })
+            .collect();
+
+        routing.order = (0..n * n)
+            .filter(|&i| self.region_of(routing.cell(i)) == region)
+            .collect();
+        routing
+            .order
+            .sort_by(|&a, &b| filled[b].total_cmp(&filled[a]).then(a.cmp(&b)));
+        routing.filled = filled;
+        routing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RiverConfig {
+        RiverConfig {
+            cell_size: 8,
+            region_cells: 16,
+            margin_cells: 4,
+            min_catchment: 40,
+            ..Default::default()
+        }
+    }
+
+    /// A valley between ridges at x = -28 and x = 228, rising from z = 600
+    /// to a pass at z = 0
+    fn valley(x: i64, z: i64) -> f64 {
+        let across = (128.0 - ((x as f64 - 100.0).abs() - 128.0).abs()) * 0.3;
+        let along = (600.0 - (z as f64).abs()) * 0.1;
+        40.0 + across + along
+    }
+
+    fn river_columns(network: &RiverNetwork, z: i64) -> Vec<(i64, RiverSample)> {
+        (0..200)
+            .filter_map(|x| network.sample(x, z, &valley).map(|river| (x, river)))
+            .collect()
+    }
+
+    #[test]
+    fn test_river_follows_valley_floor_and_widens() {
+        let network = RiverNetwork::new(config(), 30);
+
+        let upstream = river_columns(&network, 100);
+        let downstream = river_columns(&network, 450);
+        assert!(!upstream.is_empty() && !downstream.is_empty());
+        for (x, _) in upstream.iter().chain(&downstream) {
+            assert!((x - 100).abs() < 16, "river at x = {}", x);
+        }
+
+        // More catchment further down
+        let widest = |columns: &[(i64, RiverSample)]| {
+            columns.iter().map(|(_, r)| r.width).fold(0.0, f64::max)
+        };
+        assert!(widest(&downstream) > widest(&upstream));
+
+        // The water level falls towards the sea
+        let level = |columns: &[(i64, RiverSample)]| columns[columns.len() / 2].1.level;
+        assert!(level(&downstream) < level(&upstream));
+
+        // The bed is deepest in the middle
+        let middle = downstream
+            .iter()
+            .min_by(|a, b| a.1.distance.total_cmp(&b.1.distance))
+            .unwrap();
+        assert!(downstream.iter().all(|(_, r)| r.depth <= middle.1.depth));
+    }
+
+    #[test]
+    fn test_no_rivers_on_ridges_or_in_the_sea() {
+        let network = RiverNetwork::new(config(), 30);
+        // The valley sides are dry
+        assert!(network.sample(20, 300, &valley).is_none());
+        assert!(network.sample(180, 300, &valley).is_none());
+        // Flat ground below sea level has no river
+        let network = RiverNetwork::new(config(), 30);
+        let sea = |_: i64, _: i64| 10.0;
+        assert!((0..100).all(|x| network.sample(x, x, &sea).is_none()));
+    }
+
+    #[test]
+    fn test_rivers_are_deterministic_across_regions() {
+        let a = RiverNetwork::new(config(), 30);
+        let b = RiverNetwork::new(config(), 30);
+        // Query in opposite orders, crossing region borders every 128 blocks
+        let zs: Vec<i64> = (0..600).step_by(7).collect();
+        let first: Vec<_> = zs.iter().map(|&z| a.sample(100, z, &valley)).collect();
+        let second: Vec<_> = zs
+            .iter()
+            .rev()
+            .map(|&z| b.sample(100, z, &valley))
+            .collect();
+        assert!(first.iter().eq(second.iter().rev()));
+        // The river is continuous along the valley floor across region borders
+        assert!(first.iter().take(80).skip(10).all(Option::is_some));
+    }
+}
@@ -0,0 +1,1086 @@
+use crate::world::block::Block;
+use crate::world::block_id::BlockId;
+use crate::world::blocks_data::BlockRegistry;
+use crate::world::chunk::{Chunk, CHUNK_SIZE};
+use crate::world::chunk_coord::ChunkCoord;
+use crate::world::generator::rivers::{cached, RegionCache};
+use crate::world::generator::terrain::BiomeType;
+use glam::{I64Vec3, IVec3};
+use parking_lot::RwLock;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Built-in structure templates, pools and spawn rules
+pub const DEFAULT_STRUCTURES: &str = include_str!("../../assets/structures.json");
+
+/// Assembled structure starts kept in memory
+const CACHED_STARTS: usize = 512;
+
+/// Template character that leaves the terrain as it is
+const KEEP: char = ' ';
+
+/// Template character that clears the terrain to air
+const CLEAR: char = '.';
+
+#[derive(Debug, Error)]
+pub enum StructureError {
+    #[error("Unknown block '{block}' in structure template '{template}'")]
+    UnknownBlock { template: String, block: String },
+    #[error("Character '{symbol}' in structure template '{template}' is not in its palette")]
+    UnknownSymbol { template: String, symbol: char },
+    #[error("Connector {index} of structure template '{template}' is outside the template")]
+    InvalidConnector { template: String, index: usize },
+    #[error("Unknown template '{template}' in pool '{pool}'")]
+    UnknownTemplate { pool: String, template: String },
+    #[error("Unknown pool '{pool}' referenced by '{by}'")]
+    UnknownPool { by: String, pool: String },
+    #[error("Structure '{structure}' needs a spacing above its separation")]
+    InvalidSpacing { structure: String },
+    #[error("Invalid height range {min_y}..={max_y} in structure '{structure}'")]
+    InvalidRange {
+        structure: String,
+        min_y: i32,
+        max_y: i32,
+    },
+    #[error("Failed to read structure file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid structure file format: {0}")]
+    FormatError(#[from] serde_json::Error),
+}
+
+/// Horizontal direction a connector faces, out of its template
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+
+    pub fn offset(self) -> IVec3 {
+        match self {
+            Direction::North => IVec3::new(0, 0, -1),
+            Direction::East => IVec3::new(1, 0, 0),
+            Direction::South => IVec3::new(0, 0, 1),
+            Direction::West => IVec3::new(-1, 0, 0),
+        }
+    }
+
+    /// The direction after `turns` clockwise quarter turns, seen from above
+    pub fn rotated(self, turns: u8) -> Self {
+        Self::ALL[(self as usize + turns as usize) % 4]
+    }
+
+    pub fn opposite(self) -> Self {
+        self.rotated(2)
+    }
+}
+
+/// Position of a template cell after `turns` clockwise quarter turns of a
+/// template of `size`, keeping the template's minimum corner at the origin
+fn rotate(pos: IVec3, size: IVec3, turns: u8) -> IVec3 {
+    match turns % 4 {
+        0 => pos,
+        1 => IVec3::new(size.z - 1 - pos.z, pos.y, pos.x),
+        2 => IVec3::new(size.x - 1 - pos.x, pos.y, size.z - 1 - pos.z),
+        _ => IVec3::new(pos.z, pos.y, size.x - 1 - pos.x),
+    }
+}
+
+fn rotated_size(size: IVec3, turns: u8) -> IVec3 {
+    match turns % 2 {
+        0 => size,
+        _ => IVec3::new(size.z, size.y, size.x),
+    }
+}
+
+/// Point where another piece may be joined to a template. The joined piece
+/// is placed against the cell in front of the connector, with one of its own
+/// connectors facing back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Connector {
+    /// Template cell of the connector
+    pub pos: [i32; 3],
+    pub facing: Direction,
+    /// Pool to join a piece from; connectors without one only receive pieces
+    #[serde(default)]
+    pub pool: Option<String>,
+}
+
+/// A saved structure piece as read from a data file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StructureTemplate {
+    pub name: String,
+    /// Block name of each template character. A space keeps the terrain and
+    /// `.` clears it to air.
+    pub palette: HashMap<char, String>,
+    /// Horizontal slices from the bottom up, each a list of rows along +Z
+    /// of characters along +X
+    pub layers: Vec<Vec<String>>,
+    /// Layer set at ground level when the structure follows the surface
+    #[serde(default)]
+    pub ground: i32,
+    #[serde(default)]
+    pub connectors: Vec<Connector>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolEntry {
+    pub template: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+/// Templates a connector picks its piece from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplatePool {
+    pub name: String,
+    pub templates: Vec<PoolEntry>,
+    /// Template tried last when no other fits, to close off the structure
+    #[serde(default)]
+    pub fallback: Option<String>,
+}
+
+/// Vertical placement of a structure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StructureHeight {
+    /// Every piece sits on the ground under its centre, with foundations
+    /// below and the terrain above cleared
+    Surface,
+    /// The start piece is buried at a height in the range and the other
+    /// pieces keep their height relative to it
+    Range { min_y: i32, max_y: i32 },
+}
+
+fn default_max_distance() -> u32 {
+    48
+}
+
+/// Spawn rules of one structure. Starts are spread on a grid of `spacing`
+/// chunks, one per cell at a seeded chunk that keeps `separation` chunks
+/// clear of the next cell.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StructureConfig {
+    pub name: String,
+    pub start_pool: String,
+    /// Biomes the structure may start in; every biome when empty
+    #[serde(default)]
+    pub biomes: Vec<BiomeType>,
+    pub spacing: u32,
+    pub separation: u32,
+    /// Varies the grid between structures with the same spacing
+    pub salt: u64,
+    pub height: StructureHeight,
+    /// Pieces joined outwards from the start piece, at most
+    #[serde(default)]
+    pub max_depth: u32,
+    /// Blocks from the start a piece may reach
+    #[serde(default = "default_max_distance")]
+    pub max_distance: u32,
+    /// Block filled under surface pieces down to the ground
+    #[serde(default)]
+    pub foundation: Option<String>,
+}
+
+/// Templates, pools and structures of one data file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StructureSet {
+    #[serde(default)]
+    pub templates: Vec<StructureTemplate>,
+    #[serde(default)]
+    pub pools: Vec<TemplatePool>,
+    #[serde(default)]
+    pub structures: Vec<StructureConfig>,
+}
+
+impl StructureSet {
+    pub fn from_json(json: &str) -> Result<Self, StructureError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Reads every `.json` file of a directory, in name order
+    pub fn load_dir(dir: &Path) -> Result<Self, StructureError> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+        paths.sort();
+
+        let mut set = Self::default();
+        for path in paths {
+            set.merge(Self::from_json(&fs::read_to_string(path)?)?);
+        }
+        Ok(set)
+    }
+
+    /// Adds the entries of another set, replacing those with the same name
+    pub fn merge(&mut self, other: StructureSet) {
+        fn merge_named<T>(into: &mut Vec<T>, from: Vec<T>, name: impl Fn(&T) -> &str) {
+            for item in from {
+                match into
+                    .iter()
+                    .position(|existing| name(existing) == name(&item))
+                {
+                    Some(i) => into[i] = item,
+                    None => into.push(item),
+                }
+            }
+        }
+        merge_named(&mut self.templates, other.templates, |t| &t.name);
+        merge_named(&mut self.pools, other.pools, |p| &p.name);
+        merge_named(&mut self.structures, other.structures, |s| &s.name);
+    }
+}
+
+/// The built-in structures
+pub fn default_structure_set() -> StructureSet {
+    StructureSet::from_json(DEFAULT_STRUCTURES).unwrap_or_else(|e| {
+        log::error!("Failed to load default structures: {}", e);
+        StructureSet::default()
+    })
+}
+
+struct ResolvedConnector {
+    pos: IVec3,
+    facing: Direction,
+    pool: Option<usize>,
+}
+
+struct ResolvedTemplate {
+    size: IVec3,
+    ground: i32,
+    /// Blocks of every non-space cell; cleared cells are air
+    blocks: Vec<(IVec3, BlockId)>,
+    connectors: Vec<ResolvedConnector>,
+}
+
+impl ResolvedTemplate {
+    fn resolve(
+        template: &StructureTemplate,
+        registry: &BlockRegistry,
+    ) -> Result<Self, StructureError> {
+        let mut palette = HashMap::from([(CLEAR, BlockId::AIR)]);
+        for (symbol, block) in &template.palette {
+            let def = registry
+                .get_by_name(block)
+                .ok_or_else(|| StructureError::UnknownBlock {
+                    template: template.name.clone(),
+                    block: block.clone(),
+                })?;
+            palette.insert(*symbol, def.id);
+        }
+
+        let mut size = IVec3::new(0, template.layers.len() as i32, 0);
+        let mut blocks = Vec::new();
+        for (y, layer) in template.layers.iter().enumerate() {
+            size.z = size.z.max(layer.len() as i32);
+            for (z, row) in layer.iter().enumerate() {
+                size.x = size.x.max(row.chars().count() as i32);
+                for (x, symbol) in row.chars().enumerate() {
+                    if symbol == KEEP {
+                        continue;
+                    }
+                    let id = palette
+                        .get(&symbol)
+                        .ok_or_else(|| StructureError::UnknownSymbol {
+                            template: template.name.clone(),
+                            symbol,
+                        })?;
+                    blocks.push((IVec3::new(x as i32, y as i32, z as i32), *id));
+                }
+            }
+        }
+
+        let connectors = template
+            .connectors
+            .iter()
+            .enumerate()
+            .map(|(index, connector)| {
+                let pos = IVec3::from_array(connector.pos);
+                if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(size).any() {
+                    return Err(StructureError::InvalidConnector {
+                        template: template.name.clone(),
+                        index,
+                    });
+                }
+                Ok(ResolvedConnector {
+                    pos,
+                    facing: connector.facing,
+                    pool: None,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            size,
+            ground: template.ground,
+            blocks,
+            connectors,
+        })
+    }
+}
+
+struct ResolvedPool {
+    /// Template indices with their weights
+    entries: Vec<(usize, u32)>,
+    fallback: Option<usize>,
+}
+
+struct ResolvedStructure {
+    config: StructureConfig,
+    start_pool: usize,
+    foundation: Option<BlockId>,
+}
+
+/// A template placed in the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StructurePiece {
+    pub template: usize,
+    /// Clockwise quarter turns
+    pub rotation: u8,
+    /// Minimum corner
+    pub min: I64Vec3,
+    /// Size after rotation
+    pub size: IVec3,
+}
+
+impl StructurePiece {
+    pub fn max(&self) -> I64Vec3 {
+        self.min + self.size.as_i64vec3()
+    }
+
+    /// Whether the footprints of two pieces overlap. Surface pieces follow
+    /// the ground, so pieces are kept apart horizontally whatever their height.
+    pub fn overlaps(&self, other: &StructurePiece) -> bool {
+        let (a, b) = (self.max(), other.max());
+        self.min.x < b.x && other.min.x < a.x && self.min.z < b.z && other.min.z < a.z
+    }
+
+    fn cell(&self, template_size: IVec3, pos: IVec3) -> I64Vec3 {
+        self.min + rotate(pos, template_size, self.rotation).as_i64vec3()
+    }
+}
+
+/// An assembled structure
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureStart {
+    pub structure: String,
+    /// Chunk column the structure starts in
+    pub chunk: (i64, i64),
+    /// Centre of the start piece at its ground layer
+    pub position: I64Vec3,
+    /// The start piece first, then joined pieces outwards
+    pub pieces: Vec<StructurePiece>,
+}
+
+/// Places template structures into generated chunks. Every structure is
+/// assembled whole from its start chunk and the world seed, and each chunk
+/// writes only its own share of the pieces, so a structure comes out the
+/// same across chunk borders whatever order the chunks are generated in.
+pub struct StructurePlacer {
+    seed: u64,
+    templates: Vec<ResolvedTemplate>,
+    pools: Vec<ResolvedPool>,
+    structures: Vec<ResolvedStructure>,
+    /// Start of each structure index and grid cell
+    starts: RwLock<RegionCache<(usize, i64, i64), Option<StructureStart>>>,
+}
+
+impl StructurePlacer {
+    pub fn new(
+        seed: u64,
+        set: &StructureSet,
+        registry: &BlockRegistry,
+    ) -> Result<Self, StructureError> {
+        let mut templates = set
+            .templates
+            .iter()
+            .map(|template| ResolvedTemplate::resolve(template, registry))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let pool_index = |pool: &str, by: &str| {
+            set.pools
+                .iter()
+                .position(|p| p.name == pool)
+                .ok_or_else(|| StructureError::UnknownPool {
+                    by: by.to_string(),
+                    pool: pool.to_string(),
+                })
+        };
+        for (template, resolved) in set.templates.iter().zip(&mut templates) {
+            for (connector, resolved) in template.connectors.iter().zip(&mut resolved.connectors) {
+                if let Some(pool) = &connector.pool {
+                    resolved.pool = Some(pool_index(pool, &template.name)?);
+                }
+            }
+        }
+
+        let template_index = |template: &str, pool: &str| {
+            set.templates
+                .iter()
+                .position(|t| t.name == template)
+                .ok_or_else(|| StructureError::UnknownTemplate {
+                    pool: pool.to_string(),
+                    template: template.to_string(),
+                })
+        };
+        let pools = set
+            .pools
+            .iter()
+            .map(|pool| {
+                Ok(ResolvedPool {
+                    entries: pool
+                        .templates
+                        .iter()
+                        .map(|entry| {
+                            Ok((template_index(&entry.template, &pool.name)?, entry.weight))
+                        })
+                        .collect::<Result<_, StructureError>>()?,
+                    fallback: pool
+                        .fallback
+                        .as_ref()
+                        .map(|fallback| template_index(fallback, &pool.name))
+                        .transpose()?,
+                })
+            })
+            .collect::<Result<_, StructureError>>()?;
+
+        let structures = set
+            .structures
+            .iter()
+            .map(|config| {
+                if config.separation >= config.spacing {
+                    return Err(StructureError::InvalidSpacing {
+                        structure: config.name.clone(),
+                    });
+                }
+                if let StructureHeight::Range { min_y, max_y } = config.height {
+                    if min_y > max_y {
+                        return Err(StructureError::InvalidRange {
+                            structure: config.name.clone(),
+                            min_y,
+                            max_y,
+                        });
+                    }
+                }
+                let foundation = config
+                    .foundation
+                    .as_ref()
+                    .map(|block| {
+                        registry
+                            .get_by_name(block)
+                            .map(|def| def.id)
+                            .ok_or_else(|| StructureError::UnknownBlock {
+                                template: config.name.clone(),
+                                block: block.clone(),
+                            })
+                    })
+                    .transpose()?;
+                Ok(ResolvedStructure {
+                    config: config.clone(),
+                    start_pool: pool_index(&config.start_pool, &config.name)?,
+                    foundation,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            seed,
+            templates,
+            pools,
+            structures,
+            starts: RwLock::new(RegionCache::new()),
+        })
+    }
+
+    /// A placer that generates no structures
+    pub fn empty(seed: u64) -> Self {
+        Self {
+            seed,
+            templates: Vec::new(),
+            pools: Vec::new(),
+            structures: Vec::new(),
+            starts: RwLock::new(RegionCache::new()),
+        }
+    }
+
+    pub fn structure_names(&self) -> impl Iterator<Item = &str> {
+        self.structures.iter().map(|s| s.config.name.as_str())
+    }
+
+    /// Writes the parts of every structure reaching into the chunk.
+    /// `biome` and `height` give the biome and ground height of a column.
+    pub fn place(
+        &self,
+        chunk: &mut Chunk,
+        coord: ChunkCoord,
+        biome: &impl Fn(i64, i64) -> BiomeType,
+        height: &impl Fn(i64, i64) -> i32,
+    ) {
+        let base = coord.to_block_pos(CHUNK_SIZE as i32);
+        for (index, structure) in self.structures.iter().enumerate() {
+            let spacing = structure.config.spacing as i64;
+            let reach = structure.config.max_distance.div_ceil(CHUNK_SIZE) as i64 + 1;
+            let (cells_x, cells_z) = (
+                (coord.x() - reach).div_euclid(spacing)..=(coord.x() + reach).div_euclid(spacing),
+                (coord.z() - reach).div_euclid(spacing)..=(coord.z() + reach).div_euclid(spacing),
+            );
+            for cell_x in cells_x {
+                for cell_z in cells_z.clone() {
+                    let (start_x, start_z) = self.start_chunk(index, (cell_x, cell_z));
+                    if (start_x - coord.x()).abs() > reach || (start_z - coord.z()).abs() > reach {
+                        continue;
+                    }
+                    if let Some(start) = self.start(index, (cell_x, cell_z), biome, height).as_ref()
+                    {
+                        self.place_start(start, structure, chunk, base, height);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The start of a named structure nearest to a block column, searching
+    /// grid cells up to `max_cells` away
+    pub fn locate(
+        &self,
+        name: &str,
+        x: i64,
+        z: i64,
+        max_cells: i64,
+        biome: &impl Fn(i64, i64) -> BiomeType,
+        height: &impl Fn(i64, i64) -> i32,
+    ) -> Option<StructureStart> {
+        let index = self.structures.iter().position(|s| s.config.name == name)?;
+        let spacing = self.structures[index].config.spacing as i64;
+        let cell = (
+            x.div_euclid(CHUNK_SIZE as i64).div_euclid(spacing),
+            z.div_euclid(CHUNK_SIZE as i64).div_euclid(spacing),
+        );
+
+        // A start in a nearer ring of cells can still be further away than
+        // one in the next ring, but never by more than a cell
+        for ring in 0..=max_cells {
+            let nearest = (-ring..=ring)
+                .flat_map(|dx| (-ring..=ring).map(move |dz| (dx, dz)))
+                .filter(|(dx, dz)| dx.abs().max(dz.abs()) == ring)
+                .filter_map(|(dx, dz)| {
+                    self.start(index, (cell.0 + dx, cell.1 + dz), biome, height)
+                        .as_ref()
+                        .clone()
+                })
+                .min_by_key(|start| {
+                    let (dx, dz) = (start.position.x - x, start.position.z - z);
+                    dx * dx + dz * dz
+                });
+            if nearest.is_some() {
+                return nearest;
+            }
+        }
+        None
+    }
+
+    /// Start chunk of a grid cell. Starts keep `separation` chunks away from
+    /// the start of the next cell.
+    fn start_chunk(&self, index: usize, cell: (i64, i64)) -> (i64, i64) {
+        let config = &self.structures[index].config;
+        let spacing = config.spacing as i64;
+        let spread = (config.spacing - config.separation) as i64;
+        let hash = (cell.0 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (cell.1 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ config.salt.wrapping_mul(0x1656_67B1_9E37_79F9);
+        let mut rng = ChaCha12Rng::seed_from_u64(self.seed ^ hash);
+        (
+            cell.0 * spacing + rng.gen_range(0..spread),
+            cell.1 * spacing + rng.gen_range(0..spread),
+        )
+    }
+
+    /// The structure of a grid cell, or None where its biome rules it out
+    fn start(
+        &self,
+        index: usize,
+        cell: (i64, i64),
+        biome: &impl Fn(i64, i64) -> BiomeType,
+        height: &impl Fn(i64, i64) -> i32,
+    ) -> Arc<Option<StructureStart>> {
+        cached(&self.starts, (index, cell.0, cell.1), CACHED_STARTS, || {
+            let config = &self.structures[index].config;
+            let chunk = self.start_chunk(index, cell);
+            let half = CHUNK_SIZE as i64 / 2;
+            let center = (
+                chunk.0 * CHUNK_SIZE as i64 + half,
+                chunk.1 * CHUNK_SIZE as i64 + half,
+            );
+            let allowed =
+                config.biomes.is_empty() || config.biomes.contains(&biome(center.0, center.1));
+            allowed
+                .then(|| self.assemble(index, chunk, height))
+                .flatten()
+        })
+    }
+
+    /// Picks the start piece from the structure's start pool and joins pieces
+    /// to its connectors, breadth first so the depth limit cuts evenly
+    fn assemble(
+        &self,
+        index: usize,
+        chunk: (i64, i64),
+        height: &impl Fn(i64, i64) -> i32,
+    ) -> Option<StructureStart> {
+        let structure = &self.structures[index];
+        let config = &structure.config;
+        let hash = (chunk.0 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (chunk.1 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ config.salt.rotate_left(17);
+        let mut rng = ChaCha12Rng::seed_from_u64(self.seed ^ hash);
+
+        let template_index =
+            *Self::weighted_order(&self.pools[structure.start_pool], &mut rng).first()?;
+        let template = &self.templates[template_index];
+        let rotation = rng.gen_range(0..4);
+        let size = rotated_size(template.size, rotation);
+        let half = CHUNK_SIZE as i64 / 2;
+        let center = I64Vec3::new(
+            chunk.0 * CHUNK_SIZE as i64 + half,
+            0,
+            chunk.1 * CHUNK_SIZE as i64 + half,
+        );
+        let ground = match config.height {
+            StructureHeight::Surface => height(center.x, center.z) as i64,
+            StructureHeight::Range { min_y, max_y } => rng.gen_range(min_y..=max_y) as i64,
+        };
+        let min = I64Vec3::new(
+            center.x - size.x as i64 / 2,
+            ground - template.ground as i64,
+            center.z - size.z as i64 / 2,
+        );
+        let mut pieces = vec![StructurePiece {
+            template: template_index,
+            rotation,
+            min,
+            size,
+        }];
+
+        // Piece, its depth, and the connector it was joined by
+        let mut queue = VecDeque::from([(0, 0, None)]);
+        while let Some((parent, depth, joined_by)) = queue.pop_front() {
+            if depth >= config.max_depth {
+                continue;
+            }
+            let piece = pieces[parent];
+            let template = &self.templates[piece.template];
+            for (i, connector) in template.connectors.iter().enumerate() {
+                let Some(pool) = connector.pool else {
+                    continue;
+                };
+                if joined_by == Some(i) {
+                    continue;
+                }
+                let facing = connector.facing.rotated(piece.rotation);
+                let target =
+                    piece.cell(template.size, connector.pos) + facing.offset().as_i64vec3();
+                if let Some((child, used)) = self.join(
+                    structure,
+                    pool,
+                    target,
+                    facing.opposite(),
+                    &pieces,
+                    center,
+                    height,
+                    &mut rng,
+                ) {
+                    pieces.push(child);
+                    queue.push_back((pieces.len() - 1, depth + 1, Some(used)));
+                }
+            }
+        }
+
+        Some(StructureStart {
+            structure: config.name.clone(),
+            chunk,
+            position: I64Vec3::new(center.x, ground, center.z),
+            pieces,
+        })
+    }
+
+    /// A piece from the pool with a connector on `target` facing `facing`,
+    /// clear of the other pieces and within the structure's reach, with the
+    /// index of that connector
+    #[allow(clippy::too_many_arguments)]
+    fn join(
+        &self,
+        structure: &ResolvedStructure,
+        pool: usize,
+        target: I64Vec3,
+        facing: Direction,
+        pieces: &[StructurePiece],
+        center: I64Vec3,
+        height: &impl Fn(i64, i64) -> i32,
+        rng: &mut ChaCha12Rng,
+    ) -> Option<(StructurePiece, usize)> {
+        let pool = &self.pools[pool];
+        let mut candidates = Self::weighted_order(pool, rng);
+        candidates.extend(pool.fallback);
+        let first_turn = rng.gen_range(0..4u8);
+        let reach = structure.config.max_distance as i64;
+
+        for template_index in candidates {
+            let template = &self.templates[template_index];
+            for turn in 0..4 {
+                let rotation = (first_turn + turn) % 4;
+                for (i, connector) in template.connectors.iter().enumerate() {
+                    if connector.facing.rotated(rotation) != facing {
+                        continue;
+                    }
+                    let size = rotated_size(template.size, rotation);
+                    let mut min =
+                        target - rotate(connector.pos, template.size, rotation).as_i64vec3();
+                    if structure.config.height == StructureHeight::Surface {
+                        let ground = height(min.x + size.x as i64 / 2, min.z + size.z as i64 / 2);
+                        min.y = (ground - template.ground) as i64;
+                    }
+                    let piece = StructurePiece {
+                        template: template_index,
+                        rotation,
+                        min,
+                        size,
+                    };
+                    let max = piece.max();
+                    let within = (min.x - center.x).abs().max((max.x - center.x).abs()) <= reach
+                        && (min.z - center.z).abs().max((max.z - center.z).abs()) <= reach;
+                    if within && !pieces.iter().any(|other| other.overlaps(&piece)) {
+                        return Some((piece, i));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Templates of a pool in a weighted random order, each once
+    fn weighted_order(pool: &ResolvedPool, rng: &mut ChaCha12Rng) -> Vec<usize> {
+        let mut entries: Vec<_> = pool
+            .entries
+            .iter()
+            .filter(|(_, weight)| *weight > 0)
+            .copied()
+            .collect();
+        let mut order = Vec::with_capacity(entries.len());
+        while !entries.is_empty() {
+            let total: u32 = entries.iter().map(|(_, weight)| weight).sum();
+            let mut roll = rng.gen_range(0..total);
+            let picked = entries
+                .iter()
+                .position(|(_, weight)| match roll.checked_sub(*weight) {
+                    Some(rest) => {
+                        roll = rest;
+                        false
+                    }
+                    None => true,
+                })
+                .unwrap_or(0);
+            order.push(entries.remove(picked).0);
+        }
+        order
+    }
+
+    fn place_start(
+        &self,
+        start: &StructureStart,
+        structure: &ResolvedStructure,
+        chunk: &mut Chunk,
+        base: I64Vec3,
+        height: &impl Fn(i64, i64) -> i32,
+    ) {
+        let chunk_max = base + I64Vec3::splat(CHUNK_SIZE as i64);
+        for piece in &start.pieces {
+            let max = piece.max();
+            if piece.min.x >= chunk_max.x
+                || max.x <= base.x
+                || piece.min.z >= chunk_max.z
+                || max.z <= base.z
+            {
+                continue;
+            }
+            let template = &self.templates[piece.template];
+            if structure.config.height == StructureHeight::Surface {
+                Self::adapt_terrain(piece, structure.foundation, chunk, base, height);
+            }
+            for (pos, id) in &template.blocks {
+                Self::set(chunk, base, piece.cell(template.size, *pos), *id);
+            }
+        }
+    }
+
+    /// Fills the ground up to the bottom of a surface piece with its
+    /// foundation and clears terrain rising above the piece
+    fn adapt_terrain(
+        piece: &StructurePiece,
+        foundation: Option<BlockId>,
+        chunk: &mut Chunk,
+        base: I64Vec3,
+        height: &impl Fn(i64, i64) -> i32,
+    ) {
+        let max = piece.max();
+        for x in piece.min.x.max(base.x)..max.x.min(base.x + CHUNK_SIZE as i64) {
+            for z in piece.min.z.max(base.z)..max.z.min(base.z + CHUNK_SIZE as i64) {
+                let ground = height(x, z) as i64;
+                if let Some(foundation) = foundation {
+                    for y in ground + 1..piece.min.y {
+                        Self::set(chunk, base, I64Vec3::new(x, y, z), foundation);
+                    }
+                }
+                for y in max.y..=ground {
+                    Self::set(chunk, base, I64Vec3::new(x, y, z), BlockId::AIR);
+                }
+            }
+        }
+    }
+
+    fn set(chunk: &mut Chunk, base: I64Vec3, pos: I64Vec3, id: BlockId) {
+        let local = pos - base;
+        if local.cmpge(I64Vec3::ZERO).all() && local.cmplt(I64Vec3::splat(CHUNK_SIZE as i64)).all()
+        {
+            let block = (id != BlockId::AIR).then(|| Block::new(id));
+            chunk.set_block(local.x as u32, local.y as u32, local.z as u32, block);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    const GROUND: i32 = 40;
+
+    fn placer(seed: u64) -> StructurePlacer {
+        StructurePlacer::new(seed, &default_structure_set(), &BlockRegistry::default()).unwrap()
+    }
+
+    /// A placer for villages alone, so no other structure overwrites them
+    fn village_placer(seed: u64) -> StructurePlacer {
+        let mut set = default_structure_set();
+        set.structures.retain(|s| s.name == "village");
+        StructurePlacer::new(seed, &set, &BlockRegistry::default()).unwrap()
+    }
+
+    fn plains(_: i64, _: i64) -> BiomeType {
+        BiomeType::Plains
+    }
+
+    fn flat(_: i64, _: i64) -> i32 {
+        GROUND
+    }
+
+    /// Chunks covering the pieces of a start, generated in the given order
+    fn place_all(
+        placer: &StructurePlacer,
+        start: &StructureStart,
+        reverse: bool,
+    ) -> HashMap<ChunkCoord, Chunk> {
+        let mut coords = HashSet::new();
+        for piece in &start.pieces {
+            let max = piece.max() - I64Vec3::ONE;
+            let (a, b) = (
+                ChunkCoord::from_block_pos(piece.min, 32),
+                ChunkCoord::from_block_pos(max, 32),
+            );
+            for x in a.x()..=b.x() {
+                for y in a.y()..=b.y() {
+                    for z in a.z()..=b.z() {
+                        coords.insert(ChunkCoord::new(x, y, z));
+                    }
+                }
+            }
+        }
+        let mut coords: Vec<_> = coords.into_iter().collect();
+        coords.sort_by_key(|c| (c.x(), c.y(), c.z()));
+        if reverse {
+            coords.reverse();
+        }
+        coords
+            .into_iter()
+            .map(|coord| {
+                let mut chunk = Chunk::new(coord);
+                placer.place(&mut chunk, coord, &plains, &flat);
+                (coord, chunk)
+            })
+            .collect()
+    }
+
+    fn block_at(chunks: &HashMap<ChunkCoord, Chunk>, pos: I64Vec3) -> BlockId {
+        let coord = ChunkCoord::from_block_pos(pos, 32);
+        let local = pos - coord.to_block_pos(32);
+        chunks[&coord]
+            .get_block(local.x as u32, local.y as u32, local.z as u32)
+            .map_or(BlockId::AIR, |block| block.id)
+    }
+
+    #[test]
+    fn test_rotation() {
+        let size = IVec3::new(3, 2, 5);
+        for pos in [
+            IVec3::new(0, 0, 0),
+            IVec3::new(2, 1, 4),
+            IVec3::new(1, 0, 3),
+        ] {
+            let mut turned = pos;
+            let mut turned_size = size;
+            for _ in 0..4 {
+                turned = rotate(turned, turned_size, 1);
+                turned_size = rotated_size(turned_size, 1);
+            }
+            assert_eq!(turned, pos);
+            assert_eq!(rotate(rotate(pos, size, 1), rotated_size(size, 1), 3), pos);
+        }
+
+        // A connector on the north edge faces east after a clockwise turn
+        let north = IVec3::new(1, 0, 0);
+        assert_eq!(rotate(north, size, 1).x, rotated_size(size, 1).x - 1);
+        assert_eq!(Direction::North.rotated(1), Direction::East);
+        assert_eq!(Direction::West.opposite(), Direction::East);
+    }
+
+    #[test]
+    fn test_village_assembly() {
+        let placer = placer(3);
+        let village = placer.locate("village", 0, 0, 8, &plains, &flat).unwrap();
+        assert!(village.pieces.len() > 3);
+
+        let reach = default_structure_set().structures[0].max_distance as i64;
+        for (i, piece) in village.pieces.iter().enumerate() {
+            for other in &village.pieces[i + 1..] {
+                assert!(!piece.overlaps(other));
+            }
+            assert!((piece.min.x - village.position.x).abs() <= reach);
+            assert!((piece.max().z - village.position.z).abs() <= reach);
+        }
+
+        // Every joined piece touches an earlier one
+        for (i, piece) in village.pieces.iter().enumerate().skip(1) {
+            let touches = village.pieces[..i].iter().any(|other| {
+                let grown = StructurePiece {
+                    min: other.min - I64Vec3::new(1, 0, 1),
+                    size: other.size + IVec3::new(2, 0, 2),
+                    ..*other
+                };
+                grown.overlaps(piece)
+            });
+            assert!(touches);
+        }
+    }
+
+    #[test]
+    fn test_placement_across_chunk_borders() {
+        let village = placer(3)
+            .locate("village", 0, 0, 8, &plains, &flat)
+            .unwrap();
+        let forward = place_all(&village_placer(3), &village, false);
+        let backward = place_all(&village_placer(3), &village, true);
+        assert!(forward.len() > 1);
+
+        let placer = village_placer(3);
+        for piece in &village.pieces {
+            let template = &placer.templates[piece.template];
+            for (pos, id) in &template.blocks {
+                let world = piece.cell(template.size, *pos);
+                assert_eq!(block_at(&forward, world), *id);
+                assert_eq!(block_at(&backward, world), *id);
+            }
+        }
+
+        // Surface pieces sit on the ground
+        assert_eq!(village.position.y, GROUND as i64);
+    }
+
+    #[test]
+    fn test_locate_respects_biomes() {
+        let placer = placer(11);
+        let ocean = |_: i64, _: i64| BiomeType::Ocean;
+        assert!(placer.locate("village", 0, 0, 4, &ocean, &flat).is_none());
+        assert!(placer.locate("castle", 0, 0, 4, &plains, &flat).is_none());
+
+        // Dungeons start in every biome, buried in their height range
+        let dungeon = placer
+            .locate("dungeon", 500, -300, 4, &ocean, &flat)
+            .unwrap();
+        assert!((8..=40).contains(&dungeon.position.y));
+        let cell = |v: i64| v.div_euclid(32).div_euclid(8);
+        assert!((cell(dungeon.position.x) - cell(500)).abs() <= 1);
+        assert!((cell(dungeon.position.z) - cell(-300)).abs() <= 1);
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let registry = BlockRegistry::default();
+        let invalid = |edit: fn(&mut StructureSet)| {
+            let mut set = default_structure_set();
+            edit(&mut set);
+            StructurePlacer::new(1, &set, &registry).err().unwrap()
+        };
+        assert!(matches!(
+            invalid(|set| {
+                set.templates[0].palette.insert('s', "mythril".into());
+            }),
+            StructureError::UnknownBlock { .. }
+        ));
+        assert!(matches!(
+            invalid(|set| set.templates[0].layers[0][0].push('?')),
+            StructureError::UnknownSymbol { symbol: '?', .. }
+        ));
+        assert!(matches!(
+            invalid(|set| set.templates[0].connectors[0].pool = Some("nowhere".into())),
+            StructureError::UnknownPool { .. }
+        ));
+        assert!(matches!(
+            invalid(|set| set.pools[0].templates[0].template = "nothing".into()),
+            StructureError::UnknownTemplate { .. }
+        ));
+        assert!(matches!(
+            invalid(|set| set.structures[0].separation = set.structures[0].spacing),
+            StructureError::InvalidSpacing { .. }
+        ));
+    }
+
+    #[test]
+    fn test_merge_replaces_by_name() {
+        let mut set = default_structure_set();
+        let extra = StructureSet::from_json(
+            r#"{"structures": [{
+                "name": "dungeon",
+                "start_pool": "dungeon/start",
+                "spacing": 4,
+                "separation": 1,
+                "salt": 1,
+                "height": { "type": "range", "min_y": 0, "max_y": 4 }
+            }]}"#,
+        )
+        .unwrap();
+        let count = set.structures.len();
+        set.merge(extra);
+        assert_eq!(set.structures.len(), count);
+        assert_eq!(set.structures.last().unwrap().spacing, 4);
+    }
+}
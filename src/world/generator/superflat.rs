@@ -0,0 +1,237 @@
+use crate::world::block_id::BlockId;
+use crate::world::blocks_data::BlockRegistry;
+use crate::world::generator::terrain::BiomeType;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Preset used when a superflat world is created without one
+pub const DEFAULT_SUPERFLAT_PRESET: &str = "4*stone,2*dirt,grass;plains";
+
+/// Tallest stack of layers a preset may describe
+const MAX_SUPERFLAT_HEIGHT: u64 = 4096;
+
+const BIOMES: [(&str, BiomeType); 8] = [
+    ("plains", BiomeType::Plains),
+    ("mountains", BiomeType::Mountains),
+    ("desert", BiomeType::Desert),
+    ("forest", BiomeType::Forest),
+    ("ocean", BiomeType::Ocean),
+    ("tundra", BiomeType::Tundra),
+    ("swamp", BiomeType::Swamp),
+    ("river", BiomeType::River),
+];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SuperflatError {
+    #[error("Superflat preset has no layers")]
+    Empty,
+    #[error("Invalid superflat layer '{0}', expected 'count*block'")]
+    InvalidLayer(String),
+    #[error("Unknown biome '{0}' in superflat preset")]
+    UnknownBiome(String),
+    #[error("Unknown block '{0}' in superflat preset")]
+    UnknownBlock(String),
+    #[error("Superflat layers are {0} blocks thick, at most {MAX_SUPERFLAT_HEIGHT} are allowed")]
+    TooThick(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuperflatLayer {
+    pub block: String,
+    pub thickness: u32,
+}
+
+/// Layers of a superflat world from the bottom up, and its biome. Written as
+/// `"1*stone,3*dirt,grass;plains"`: comma-separated `count*block` layers,
+/// where a missing count means one block, then optionally `;` and a biome.
+/// Stored with the world in this textual form.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SuperflatPreset {
+    pub layers: Vec<SuperflatLayer>,
+    pub biome: BiomeType,
+}
+
+impl SuperflatPreset {
+    /// Block of each layer with its thickness, failing on blocks the
+    /// registry does not know
+    pub fn resolve(&self, registry: &BlockRegistry) -> Result<Vec<(BlockId, u32)>, SuperflatError> {
+        self.layers
+            .iter()
+            .map(|layer| {
+                registry
+                    .get_by_name(&layer.block)
+                    .map(|def| (def.id, layer.thickness))
+                    .ok_or_else(|| SuperflatError::UnknownBlock(layer.block.clone()))
+            })
+            .collect()
+    }
+
+    /// Total thickness of the layers
+    pub fn height(&self) -> u64 {
+        self.layers.iter().map(|layer| layer.thickness as u64).sum()
+    }
+}
+
+impl Default for SuperflatPreset {
+    fn default() -> Self {
+        DEFAULT_SUPERFLAT_PRESET
+            .parse()
+            .expect("default superflat preset is valid")
+    }
+}
+
+impl FromStr for SuperflatPreset {
+    type Err = SuperflatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (layers, biome) = match s.split_once(';') {
+            Some((layers, biome)) => (layers, Some(biome.trim())),
+            None => (s, None),
+        };
+
+        let layers = layers
+            .split(',')
+            .map(str::trim)
+            .filter(|layer| !layer.is_empty())
+            .map(|layer| {
+                let (thickness, block) = match layer.split_once('*') {
+                    Some((count, block)) => (count.trim().parse().ok(), block.trim()),
+                    None => (Some(1), layer),
+                };
+                match thickness {
+                    Some(thickness) if thickness > 0 && !block.is_empty() => Ok(SuperflatLayer {
+                        block: block.to_ascii_lowercase(),
+                        thickness,
+                    }),
+                    _ => Err(SuperflatError::InvalidLayer(layer.to_string())),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if layers.is_empty() {
+            return Err(SuperflatError::Empty);
+        }
+
+        let biome = match biome {
+            Some(name) => BIOMES
+                .iter()
+                .find(|(biome, _)| biome.eq_ignore_ascii_case(name))
+                .map(|(_, biome)| *biome)
+                .ok_or_else(|| SuperflatError::UnknownBiome(name.to_string()))?,
+            None => BiomeType::Plains,
+        };
+
+        let preset = Self { layers, biome };
+        match preset.height() {
+            height if height > MAX_SUPERFLAT_HEIGHT => Err(SuperflatError::TooThick(height)),
+            _ => Ok(preset),
+        }
+    }
+}
+
+impl TryFrom<String> for SuperflatPreset {
+    type Error = SuperflatError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<SuperflatPreset> for String {
+    fn from(preset: SuperflatPreset) -> Self {
+        preset.to_string()
+    }
+}
+
+impl fmt::Display for SuperflatPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, layer) in self.layers.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            match layer.thickness {
+                1 => write!(f, "{}", layer.block)?,
+                thickness => write!(f, "{}*{}", thickness, layer.block)?,
+            }
+        }
+        let biome = BIOMES
+            .iter()
+            .find(|(_, biome)| *biome == self.biome)
+            .map_or("plains", |(name, _)| name);
+        write!(f, ";{}", biome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        let preset: SuperflatPreset = " 2*stone, 3 * Dirt ,grass ;Desert".parse().unwrap();
+        assert_eq!(preset.height(), 6);
+        assert_eq!(preset.biome, BiomeType::Desert);
+        assert_eq!(
+            preset.layers[1],
+            SuperflatLayer {
+                block: "dirt".to_string(),
+                thickness: 3
+            }
+        );
+        assert_eq!(preset.to_string(), "2*stone,3*dirt,grass;desert");
+        assert_eq!(preset.to_string().parse::<SuperflatPreset>(), Ok(preset));
+
+        // The biome is optional
+        let preset: SuperflatPreset = "stone".parse().unwrap();
+        assert_eq!(preset.biome, BiomeType::Plains);
+    }
+
+    #[test]
+    fn test_invalid_presets() {
+        let parse = |s: &str| s.parse::<SuperflatPreset>().unwrap_err();
+        assert_eq!(parse(""), SuperflatError::Empty);
+        assert_eq!(parse(";plains"), SuperflatError::Empty);
+        assert_eq!(
+            parse("x*stone"),
+            SuperflatError::InvalidLayer("x*stone".into())
+        );
+        assert_eq!(
+            parse("0*stone"),
+            SuperflatError::InvalidLayer("0*stone".into())
+        );
+        assert_eq!(parse("3*"), SuperflatError::InvalidLayer("3*".into()));
+        assert_eq!(
+            parse("stone;moon"),
+            SuperflatError::UnknownBiome("moon".into())
+        );
+        assert_eq!(parse("5000*stone"), SuperflatError::TooThick(5000));
+    }
+
+    #[test]
+    fn test_resolve_against_registry() {
+        let registry = BlockRegistry::default();
+        let layers = SuperflatPreset::default().resolve(&registry).unwrap();
+        assert_eq!(layers.len(), 3);
+        assert_eq!(layers[0], (registry.get_by_name("stone").unwrap().id, 4));
+
+        let preset: SuperflatPreset = "1*bedrock,3*stone".parse().unwrap();
+        assert_eq!(
+            preset.resolve(&registry),
+            Err(SuperflatError::UnknownBlock("bedrock".into()))
+        );
+    }
+
+    #[test]
+    fn test_serialized_as_text() {
+        let preset = SuperflatPreset::default();
+        let json = serde_json::to_string(&preset).unwrap();
+        assert_eq!(json, format!("\"{}\"", DEFAULT_SUPERFLAT_PRESET));
+        assert_eq!(
+            serde_json::from_str::<SuperflatPreset>(&json).unwrap(),
+            preset
+        );
+        assert!(serde_json::from_str::<SuperflatPreset>("\"3*\"").is_err());
+    }
+}
@@ -1,27 +1,66 @@
 use crate::world::BlockOrientation;
-use crate::world::block::{Block, SubBlock};
+use crate::world::block::{Block, SubBlock, DEFAULT_SUB_RESOLUTION, MAX_SUB_RESOLUTION};
 use crate::world::block_facing::BlockFacing;
 use crate::world::block_id::BlockId;
 use crate::world::block_visual::ConnectedDirections;
 use crate::world::blocks_data::BlockRegistry;
-use crate::world::chunk::Chunk;
+use crate::world::chunk::{Chunk, ChunkStatus, CHUNK_SIZE};
 use crate::world::chunk_coord::ChunkCoord;
-use glam::IVec3;
+use crate::world::generator::caves::CaveCarver;
+use crate::world::generator::climate::{ClimateConfig, ClimateModel};
+use crate::world::generator::density::{DensityConfig, DensityGraph};
+use crate::world::generator::elevation::{DemElevationSource, ElevationConfig};
+use crate::world::geo::GeoProjection;
+use crate::world::generator::erosion::{ErosionConfig, HeightfieldEroder};
+use crate::world::generator::features::{
+    self, BiomeDecoration, BOULDER_ATTEMPTS, FLOWER_ATTEMPTS, GRASS_ATTEMPTS, TREE_ATTEMPTS,
+};
+use crate::world::generator::lighting;
+use crate::world::generator::landcover::{LandCoverConfig, LandCoverSource};
+use crate::world::generator::lod::{self, LodCell, LodGrid, LodScale, LodTile};
+use crate::world::generator::ores::{default_ore_features, OreFeature, OrePlacer};
+use crate::world::generator::osm::{OsmConfig, OsmOverlay};
+use crate::world::generator::rivers::{cached, RegionCache, RiverConfig, RiverNetwork, RiverSample};
+use crate::world::generator::structures::{
+    default_structure_set, StructurePlacer, StructureSet, StructureStart,
+};
+use crate::world::generator::superflat::SuperflatPreset;
+use crate::world::generator::water::{resolve_fluid, WaterBodies, WaterConfig};
+use glam::I64Vec3;
 use noise::{NoiseFn, Perlin};
 use parking_lot::RwLock;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha12Rng;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::f32::consts::PI;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 // Constants
-const CHUNK_SIZE: usize = 16;
-const SUB_RESOLUTION: usize = 4;
-const SEA_LEVEL: i32 = 64;
-const BASE_TERRAIN_HEIGHT: f64 = 64.0;
 const FLAT_WORLD_HEIGHT: i32 = 64;
+/// Half-width of the stone platform void worlds are spawned on
+const VOID_PLATFORM_RADIUS: i64 = 2;
+
+/// Independent noise layers, each seeded from the world seed and its index
+const NOISE_LAYERS: [&str; 4] = ["terrain", "detail", "temperature", "moisture"];
+
+/// Spacing of the lattice biomes are sampled on. Each lattice point
+/// averages the height modifiers of its 3x3 neighbourhood, and columns
+/// interpolate between the points around them, so biome borders slope.
+const BIOME_BLEND_SPACING: i64 = 4;
+
+/// Lattice points per edge of a cached tile, one tile per chunk column
+const BLEND_TILE: i64 = CHUNK_SIZE as i64 / BIOME_BLEND_SPACING;
+
+/// Chunk columns whose surface samples are kept, so every vertical chunk
+/// and generation stage of a column shares one set of height lookups
+const CACHED_COLUMNS: usize = 256;
+
+/// Blocks of biome soil below the top block
+const SURFACE_DEPTH: i64 = 3;
+
+/// Height above its water that a column still counts as shore
+const SHORE_HEIGHT: i32 = 1;
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum BiomeType {
@@ -32,6 +71,7 @@ pub enum BiomeType {
     Ocean,
     Tundra,
     Swamp,
+    River,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -39,6 +79,17 @@ pub enum WorldType {
     Normal,
     Flat,
     Superflat,
+    Void,
+}
+
+impl From<crate::ui::menu::WorldType> for WorldType {
+    fn from(world_type: crate::ui::menu::WorldType) -> Self {
+        match world_type {
+            crate::ui::menu::WorldType::Normal => WorldType::Normal,
+            crate::ui::menu::WorldType::Superflat => WorldType::Superflat,
+            crate::ui::menu::WorldType::Void => WorldType::Void,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,7 +129,47 @@ pub struct WorldGenConfig {
     pub world_type: WorldType,
     pub terrain_amplitude: f64,
     pub cave_threshold: f64,
-    pub flat_world_layers: Vec<(BlockId, i32)>,
+    pub cave_density: f64,
+    /// Layers and biome of superflat worlds
+    #[serde(default)]
+    pub superflat: SuperflatPreset,
+    pub sub_resolution: u8,
+    #[serde(default = "default_ore_features")]
+    pub ore_features: Vec<OreFeature>,
+    /// Real-world elevation tiles; columns without data use noise terrain
+    #[serde(default)]
+    pub elevation: Option<ElevationConfig>,
+    /// Placement of real-world data on the block grid
+    #[serde(default)]
+    pub projection: GeoProjection,
+    /// OpenStreetMap extract drawn over the terrain
+    #[serde(default)]
+    pub osm: Option<OsmConfig>,
+    /// Land-cover rasters overriding noise biomes where they have data
+    #[serde(default)]
+    pub land_cover: Option<LandCoverConfig>,
+    /// Hydraulic and thermal erosion of the noise heightfield
+    #[serde(default)]
+    pub erosion: Option<ErosionConfig>,
+    /// Rivers from flow accumulation on the terrain height
+    #[serde(default)]
+    pub rivers: Option<RiverConfig>,
+    /// Temperature and precipitation model biomes are picked from
+    #[serde(default)]
+    pub climate: ClimateConfig,
+    /// Terrain shape as a graph of density functions, replacing the noise
+    /// heightfield
+    #[serde(default)]
+    pub density: Option<DensityConfig>,
+    /// Fluid block, lakes and aquifers
+    #[serde(default)]
+    pub water: WaterConfig,
+    /// Structure templates, pools and spawn rules
+    #[serde(default = "default_structure_set")]
+    pub structures: StructureSet,
+    /// Directory of extra structure files, merged over `structures`
+    #[serde(default)]
+    pub structure_directory: Option<PathBuf>,
 }
 
 impl Default for WorldGenConfig {
@@ -96,12 +187,138 @@ impl Default for WorldGenConfig {
             world_type: WorldType::Normal,
             terrain_amplitude: 1.0,
             cave_threshold: 0.5,
-            flat_world_layers: vec![
-                (BlockId::new(1, 0, 0), 1), // Bedrock
-                (BlockId::new(2, 0, 0), 3), // Stone
-                (BlockId::new(3, 0, 0), 1), // Dirt
-                (BlockId::new(4, 0, 0), 1), // Grass
-            ],
+            cave_density: 0.3,
+            superflat: SuperflatPreset::default(),
+            sub_resolution: DEFAULT_SUB_RESOLUTION,
+            ore_features: default_ore_features(),
+            elevation: None,
+            projection: GeoProjection::default(),
+            osm: None,
+            land_cover: None,
+            erosion: None,
+            rivers: None,
+            climate: ClimateConfig::default(),
+            density: None,
+            water: WaterConfig::default(),
+            structures: default_structure_set(),
+            structure_directory: None,
+        }
+    }
+}
+
+impl From<&crate::config::WorldGenConfig> for WorldGenConfig {
+    /// Engine world settings over the defaults of every option they lack
+    fn from(config: &crate::config::WorldGenConfig) -> Self {
+        let defaults = Self::default();
+        Self {
+            world_seed: config.world_seed,
+            terrain_height: config.terrain_height as i32,
+            water_level: config.water_level as i32,
+            biome_scale: frequency(config.biome_scale, defaults.biome_scale),
+            noise_scale: frequency(config.noise_scale, defaults.noise_scale),
+            octaves: config.octaves,
+            persistence: config.persistence as f64,
+            lacunarity: config.lacunarity as f64,
+            world_type: config.world_type.into(),
+            cave_threshold: config.cave_threshold as f64,
+            cave_density: config.cave_density as f64,
+            ore_features: config.ore_features.clone(),
+            superflat: config.superflat.clone(),
+            sub_resolution: sub_resolution(config.sub_resolution),
+            elevation: config.elevation.clone(),
+            projection: config.projection,
+            osm: config.osm.clone(),
+            land_cover: config.land_cover.clone(),
+            erosion: config.erosion.clone(),
+            rivers: config.rivers.clone(),
+            climate: config.climate.clone(),
+            density: config.density.clone(),
+            water: config.water.clone(),
+            structures: config.structures.clone(),
+            structure_directory: config.structure_directory.clone(),
+            ..defaults
+        }
+    }
+}
+
+/// Sub-blocks per block edge, clamped to the supported range. Zero would
+/// leave no cell to place decorations in.
+fn sub_resolution(resolution: u32) -> u8 {
+    let clamped = u8::try_from(resolution)
+        .unwrap_or(MAX_SUB_RESOLUTION)
+        .clamp(1, MAX_SUB_RESOLUTION);
+    if u32::from(clamped) != resolution {
+        log::warn!(
+            "Sub-block resolution {} is outside 1..={}, using {}",
+            resolution,
+            MAX_SUB_RESOLUTION,
+            clamped
+        );
+    }
+    clamped
+}
+
+/// Noise frequency of features `scale` blocks across. Sampling at integer
+/// coordinates times a whole-number scale would land on Perlin lattice
+/// points, where the noise is always zero.
+fn frequency(scale: f32, default: f64) -> f64 {
+    if scale > 0.0 {
+        1.0 / scale as f64
+    } else {
+        default
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Biome, ground height and water level of one block column
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ColumnSample {
+    pub(crate) biome: BiomeType,
+    pub(crate) height: i32,
+    pub(crate) water_level: i32,
+}
+
+/// Blocks the terrain is built from, looked up by name once
+#[derive(Debug, Clone, Copy)]
+struct TerrainBlocks {
+    stone: BlockId,
+    dirt: BlockId,
+    grass: BlockId,
+    snow: BlockId,
+    sand: BlockId,
+    gravel: BlockId,
+    flower: BlockId,
+    tall_grass: BlockId,
+    log: BlockId,
+    leaves: BlockId,
+    water: BlockId,
+}
+
+impl TerrainBlocks {
+    /// Looks up every terrain block, reporting the ones the registry lacks.
+    /// Those generate as air.
+    fn resolve(registry: &BlockRegistry) -> Self {
+        let id = |name: &str| {
+            registry.get_by_name(name).map(|def| def.id).unwrap_or_else(|| {
+                log::error!("Unknown terrain block {}, generating air instead", name);
+                BlockId::AIR
+            })
+        };
+        Self {
+            stone: id("stone"),
+            dirt: id("dirt"),
+            grass: id("grass"),
+            snow: id("snow"),
+            sand: id("sand"),
+            gravel: id("gravel"),
+            flower: id("flower"),
+            tall_grass: id("tall_grass"),
+            log: id("log"),
+            leaves: id("leaves"),
+            water: id("water"),
         }
     }
 }
@@ -109,99 +326,685 @@ impl Default for WorldGenConfig {
 pub struct TerrainGenerator {
     config: WorldGenConfig,
     block_registry: Arc<BlockRegistry>,
+    blocks: TerrainBlocks,
     noise: Perlin,
-    rng: ChaCha12Rng,
+    noise_layers: HashMap<&'static str, Perlin>,
+    caves: CaveCarver,
+    ores: OrePlacer,
+    structures: StructurePlacer,
+    density: Option<DensityGraph>,
+    water: WaterBodies,
+    /// Liquid block of the sea, lakes and aquifers
+    fluid: BlockId,
+    elevation: Option<DemElevationSource>,
+    osm: Option<OsmOverlay>,
+    land_cover: Option<LandCoverSource>,
+    erosion: Option<HeightfieldEroder>,
+    rivers: Option<RiverNetwork>,
+    climate: ClimateModel,
+    flat_layers: Vec<(BlockId, u32)>,
+    columns: RwLock<RegionCache<(i64, i64), Vec<ColumnSample>>>,
+    /// Blended biome height modifiers of the lattice points in each tile,
+    /// indexed `x * BLEND_TILE + z`
+    blend: RwLock<RegionCache<(i64, i64), Vec<f64>>>,
 }
 
 impl TerrainGenerator {
     pub fn new(config: WorldGenConfig, block_registry: Arc<BlockRegistry>) -> Self {
         let noise = Perlin::new(config.world_seed as u32);
-        let rng = ChaCha12Rng::seed_from_u64(config.world_seed);
+        let noise_layers = NOISE_LAYERS
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                let seed = (config.world_seed as u32)
+                    .wrapping_add((i as u32 + 1).wrapping_mul(0x9E37_79B9));
+                (*layer, Perlin::new(seed))
+            })
+            .collect();
+        let caves = CaveCarver::new(
+            config.world_seed,
+            config.cave_threshold,
+            config.cave_density,
+            config.water_level,
+        );
+        let ores = OrePlacer::new(config.world_seed, &config.ore_features, &block_registry)
+            .unwrap_or_else(|e| {
+                log::error!("Invalid ore features, generating without ores: {}", e);
+                OrePlacer::empty(config.world_seed)
+            });
+        let mut structure_set = config.structures.clone();
+        if let Some(dir) = &config.structure_directory {
+            match StructureSet::load_dir(dir) {
+                Ok(extra) => structure_set.merge(extra),
+                Err(e) => log::error!("Failed to load structure files: {}", e),
+            }
+        }
+        let structures = StructurePlacer::new(config.world_seed, &structure_set, &block_registry)
+            .unwrap_or_else(|e| {
+                log::error!("Invalid structures, generating without structures: {}", e);
+                StructurePlacer::empty(config.world_seed)
+            });
+        let density = config.density.as_ref().and_then(|density| {
+            DensityGraph::new(density, config.world_seed)
+                .map_err(|e| log::error!("Invalid density graph, using noise terrain: {}", e))
+                .ok()
+        });
+        let water = WaterBodies::new(config.water.clone(), config.water_level, config.world_seed);
+        let fluid = resolve_fluid(&config.water.fluid, &block_registry)
+            .or_else(|e| {
+                log::error!("Invalid fluid, using water: {}", e);
+                resolve_fluid("water", &block_registry)
+            })
+            .unwrap_or(BlockId::AIR);
+        let elevation = config.elevation.clone().and_then(|elevation| {
+            DemElevationSource::load(elevation, config.projection)
+                .map_err(|e| log::error!("Failed to load elevation tiles: {}", e))
+                .ok()
+        });
+        let osm = config.osm.as_ref().and_then(|osm| {
+            OsmOverlay::load(osm, config.projection, &block_registry)
+                .map_err(|e| log::error!("Failed to load OSM data: {}", e))
+                .ok()
+        });
+        let land_cover = config.land_cover.clone().and_then(|land_cover| {
+            LandCoverSource::load(land_cover, config.projection, config.world_seed)
+                .map_err(|e| log::error!("Failed to load land-cover rasters: {}", e))
+                .ok()
+        });
+        let erosion = config
+            .erosion
+            .clone()
+            .map(|erosion| HeightfieldEroder::new(erosion, config.world_seed));
+        let rivers = config
+            .rivers
+            .clone()
+            .map(|rivers| RiverNetwork::new(rivers, config.water_level));
+        let climate = ClimateModel::new(config.climate.clone(), config.projection);
+        let flat_layers = config
+            .superflat
+            .resolve(&block_registry)
+            .unwrap_or_else(|e| {
+                log::error!("Invalid superflat preset, using the default: {}", e);
+                SuperflatPreset::default()
+                    .resolve(&block_registry)
+                    .unwrap_or_default()
+            });
+        let blocks = TerrainBlocks::resolve(&block_registry);
         Self {
             config,
             block_registry,
+            blocks,
             noise,
-            rng,
+            noise_layers,
+            caves,
+            ores,
+            structures,
+            density,
+            water,
+            fluid,
+            elevation,
+            osm,
+            land_cover,
+            erosion,
+            rivers,
+            climate,
+            flat_layers,
+            columns: RwLock::new(RegionCache::new()),
+            blend: RwLock::new(RegionCache::new()),
         }
     }
 
+    /// Runs every stage that needs no neighbouring chunks: base density,
+    /// surface and carvers
     pub fn generate_chunk(&self, coord: ChunkCoord) -> Chunk {
         let mut chunk = Chunk::new(coord);
+        self.generate_base(&mut chunk, coord);
+        self.generate_surface(&mut chunk, coord);
+        self.carve(&mut chunk, coord);
+        chunk.status = ChunkStatus::Carvers;
+        chunk
+    }
+
+    /// Fills the chunk with stone below the ground and water up to the
+    /// water level. Flat, superflat and void worlds are finished here.
+    pub fn generate_base(&self, chunk: &mut Chunk, coord: ChunkCoord) {
+        chunk.sub_resolution = self.config.sub_resolution;
         match self.config.world_type {
-            WorldType::Normal => self.generate_normal_chunk(&mut chunk, coord),
-            WorldType::Flat => self.generate_flat_chunk(&mut chunk, coord),
-            WorldType::Superflat => self.generate_superflat_chunk(&mut chunk, coord),
+            WorldType::Normal => {}
+            WorldType::Flat => return self.generate_flat_chunk(chunk, coord),
+            WorldType::Superflat => return self.generate_superflat_chunk(chunk, coord),
+            WorldType::Void => return self.generate_void_chunk(chunk, coord),
+        }
+
+        let chunk_base = coord.to_block_pos(CHUNK_SIZE as i32);
+        let stone = self.blocks.stone;
+        let columns = self.column_samples(coord);
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let column = columns[(x * CHUNK_SIZE + z) as usize];
+                let mut density = self
+                    .density
+                    .as_ref()
+                    .map(|graph| graph.column(chunk_base.x + x as i64, chunk_base.z + z as i64));
+                for y in 0..CHUNK_SIZE {
+                    let world_y = chunk_base.y + y as i64;
+                    // Density terrain keeps its overhangs, except where a
+                    // river has cut the column down
+                    let solid = match &mut density {
+                        Some(density) => {
+                            density.at(world_y) > 0.0
+                                && (column.biome != BiomeType::River
+                                    || world_y <= column.height as i64)
+                        }
+                        None => world_y <= column.height as i64,
+                    };
+                    let block = if solid {
+                        Block::new(stone)
+                    } else if world_y <= column.water_level as i64 {
+                        Block::new(self.fluid)
+                    } else {
+                        Block::new(BlockId::AIR)
+                    };
+                    chunk.set_block(x, y, z, Some(block));
+                }
+            }
         }
-        chunk
     }
 
-    fn generate_normal_chunk(&self, chunk: &mut Chunk, coord: ChunkCoord) {
-        let base_x = coord.x() * 32;
-        let base_y = coord.y() * 32;
-        let base_z = coord.z() * 32;
+    /// Replaces the top layers of the ground with the blocks of each
+    /// column's biome
+    pub fn generate_surface(&self, chunk: &mut Chunk, coord: ChunkCoord) {
+        if self.config.world_type != WorldType::Normal {
+            return;
+        }
 
-        for x in 0..32 {
-            for y in 0..32 {
-                for z in 0..32 {
-                    let world_x = base_x + x as i32;
-                    let world_y = base_y + y as i32;
-                    let world_z = base_z + z as i32;
-
-                    let height = self.get_height(world_x, world_z);
-                    if world_y < height {
-                        chunk.set_block(
-                            x as u32,
-                            y as u32,
-                            z as u32,
-                            Some(Block::new(BlockId::new(1, 0, 0))),
-                        ); // Stone
-                    } else if world_y == height {
-                        chunk.set_block(
-                            x as u32,
-                            y as u32,
-                            z as u32,
-                            Some(Block::new(BlockId::new(2, 0, 0))),
-                        ); // Grass
+        let chunk_base = coord.to_block_pos(CHUNK_SIZE as i32);
+        let mut rng = self.chunk_rng(coord);
+        rng.set_stream(1);
+        let columns = self.column_samples(coord);
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let column = columns[(x * CHUNK_SIZE + z) as usize];
+                let ColumnSample { biome, height, .. } = column;
+                let (base, top) = self.surface_blocks(&column);
+                let bottom = (height as i64 - SURFACE_DEPTH).max(chunk_base.y);
+                let surface = (height as i64).min(chunk_base.y + CHUNK_SIZE as i64 - 1);
+                for world_y in bottom..=surface {
+                    let id = self.get_block_for_depth(world_y, height, base, top, biome);
+                    let y = (world_y - chunk_base.y) as u32;
+                    chunk.set_block(x, y, z, Some(Block::new(id)));
+                }
+
+                // Decoration stays out of the ground, in the air cell above it
+                let above = height as i64 + 1 - chunk_base.y;
+                if !(1..CHUNK_SIZE as i64).contains(&above) {
+                    continue;
+                }
+                let y = above as u32;
+                if chunk.get_block(x, y, z).is_some_and(|block| block.id != BlockId::AIR) {
+                    continue;
+                }
+                let ground = self.get_block_for_depth(height as i64, height, base, top, biome);
+                if let Some(block) = self.decoration_above(ground, biome, &mut rng) {
+                    chunk.set_block(x, y, z, Some(block));
+                }
+            }
+        }
+    }
+
+    /// Cuts caves out of the terrain and lets aquifers flood them
+    pub fn carve(&self, chunk: &mut Chunk, coord: ChunkCoord) {
+        if self.config.world_type != WorldType::Normal {
+            return;
+        }
+        self.caves.carve_chunk_under_water(
+            chunk,
+            coord,
+            |x, z| self.column_sample(x, z).height,
+            |x, z| self.column_sample(x, z).water_level,
+            |id| self.block_registry.has_tag(id, "replaceable/carver"),
+        );
+        self.water
+            .fill_aquifers(chunk, coord, self.fluid, self.blocks.stone, |x, z| {
+                self.column_sample(x, z).height
+            });
+    }
+
+    /// Places ores, then decorates the chunk. Blocks landing outside the
+    /// chunk are returned as in [`Self::decorate_chunk`].
+    pub fn place_features(&self, chunk: &mut Chunk, coord: ChunkCoord) -> Vec<(I64Vec3, Block)> {
+        if self.config.world_type != WorldType::Normal {
+            return Vec::new();
+        }
+        self.ores.place(chunk, coord);
+        self.decorate_chunk(chunk, coord)
+    }
+
+    /// Lights the chunk, judging where the sky reaches it from the ground
+    /// height of the columns in and around it
+    pub fn light(&self, chunk: &mut Chunk) {
+        lighting::light_chunk(chunk, &self.block_registry, |x, z| {
+            self.surface_at(x, z).0.height as i64
+        });
+    }
+
+    /// Places template structures, then draws the OpenStreetMap overlay
+    /// over them if one is loaded
+    pub fn place_structures(&self, chunk: &mut Chunk, coord: ChunkCoord) {
+        if self.config.world_type == WorldType::Normal {
+            self.structures.place(
+                chunk,
+                coord,
+                &|x, z| self.column_sample(x, z).biome,
+                &|x, z| self.column_sample(x, z).height,
+            );
+        }
+        if let Some(osm) = &self.osm {
+            osm.apply(chunk, coord, |x, z| self.column_sample(x, z).height);
+        }
+    }
+
+    /// The nearest structure with the given name, searching `max_cells` grid
+    /// cells of its spawn rules around `near`
+    pub fn locate_structure(
+        &self,
+        name: &str,
+        near: I64Vec3,
+        max_cells: i64,
+    ) -> Option<StructureStart> {
+        if self.config.world_type != WorldType::Normal {
+            return None;
+        }
+        self.structures.locate(
+            name,
+            near.x,
+            near.z,
+            max_cells,
+            &|x, z| self.column_sample(x, z).biome,
+            &|x, z| self.column_sample(x, z).height,
+        )
+    }
+
+    /// A chunk downsampled to one block per cell for far terrain. Normal
+    /// worlds are read from the column samples and density graph without
+    /// generating the chunk, so caves, ores and features are left out.
+    pub fn lod_grid(&self, coord: ChunkCoord, scale: LodScale) -> LodGrid {
+        if self.config.world_type != WorldType::Normal {
+            let mut chunk = Chunk::new(coord);
+            self.generate_base(&mut chunk, coord);
+            return LodGrid::from_chunk(&chunk, coord, scale);
+        }
+
+        let factor = scale.factor() as i64;
+        let cells = scale.cells();
+        let base = coord.to_block_pos(CHUNK_SIZE as i32);
+        let mut grid = vec![BlockId::AIR; cells * cells * cells];
+        for cx in 0..cells {
+            for cz in 0..cells {
+                let samples = self
+                    .lod_positions(base, cx, cz, scale)
+                    .map(|(x, z)| (x, z, self.column_sample(x, z)));
+                let mut densities: Vec<_> = samples
+                    .iter()
+                    .map(|(x, z, _)| self.density.as_ref().map(|graph| graph.column(*x, *z)))
+                    .collect();
+                for cy in 0..cells {
+                    let bottom = base.y + cy as i64 * factor;
+                    let centre = bottom + factor / 2;
+                    let blocks = samples.iter().zip(densities.iter_mut()).map(
+                        |((_, _, column), density)| {
+                            let height = column.height as i64;
+                            let solid = match density {
+                                Some(density) => {
+                                    density.at(centre) > 0.0
+                                        && (column.biome != BiomeType::River || centre <= height)
+                                }
+                                None => centre <= height,
+                            };
+                            let (soil, top) = self.surface_blocks(column);
+                            // The cell holding the surface shows it, however
+                            // little of the cell is ground
+                            if (bottom..bottom + factor).contains(&height) {
+                                top
+                            } else if solid {
+                                self.get_block_for_depth(
+                                    centre,
+                                    column.height,
+                                    soil,
+                                    top,
+                                    column.biome,
+                                )
+                            } else if centre <= column.water_level as i64 {
+                                self.fluid
+                            } else {
+                                BlockId::AIR
+                            }
+                        },
+                    );
+                    grid[(cx * cells + cy) * cells + cz] = lod::dominant(blocks);
+                }
+            }
+        }
+        LodGrid {
+            coord,
+            scale,
+            cells: grid,
+        }
+    }
+
+    /// Surface heightfield of a chunk column for far terrain, sampled on a
+    /// 2x2 grid per cell without generating any chunk
+    pub fn lod_tile(&self, coord: ChunkCoord, scale: LodScale) -> LodTile {
+        let coord = ChunkCoord::new(coord.x(), 0, coord.z());
+        let cells = scale.cells();
+        let base = coord.to_block_pos(CHUNK_SIZE as i32);
+        let tile = (0..cells * cells)
+            .map(|i| {
+                let samples = self
+                    .lod_positions(base, i / cells, i % cells, scale)
+                    .map(|(x, z)| self.surface_at(x, z));
+                let height = samples
+                    .iter()
+                    .map(|(column, _)| column.height as f64)
+                    .sum::<f64>()
+                    / samples.len() as f64;
+                let water_level = samples
+                    .iter()
+                    .map(|(column, _)| column.water_level)
+                    .max()
+                    .unwrap_or(self.config.water_level);
+                let block = lod::dominant(samples.iter().map(|(_, block)| *block));
+                LodCell {
+                    height: height.round() as i32,
+                    water_level,
+                    block,
+                    color: lod::block_color(&self.block_registry, block),
+                }
+            })
+            .collect();
+        LodTile {
+            coord,
+            scale,
+            cells: tile,
+        }
+    }
+
+    /// Columns sampled to summarise one cell of a chunk summary
+    fn lod_positions(
+        &self,
+        base: I64Vec3,
+        cx: usize,
+        cz: usize,
+        scale: LodScale,
+    ) -> [(i64, i64); 4] {
+        let factor = scale.factor() as i64;
+        let [near, far] = lod::sample_offsets(scale);
+        let x = base.x + cx as i64 * factor;
+        let z = base.z + cz as i64 * factor;
+        [
+            (x + near, z + near),
+            (x + near, z + far),
+            (x + far, z + near),
+            (x + far, z + far),
+        ]
+    }
+
+    /// Column sample and visible top block of any column, the fluid over
+    /// submerged ground. Flat world types report their fixed surface.
+    pub(crate) fn surface_at(&self, x: i64, z: i64) -> (ColumnSample, BlockId) {
+        let flat = |biome, height, block| {
+            let column = ColumnSample {
+                biome,
+                height,
+                water_level: height,
+            };
+            (column, block)
+        };
+        match self.config.world_type {
+            WorldType::Normal => {
+                let column = self.column_sample(x, z);
+                let block = if column.water_level > column.height {
+                    self.fluid
+                } else {
+                    self.surface_blocks(&column).1
+                };
+                (column, block)
+            }
+            WorldType::Flat => flat(
+                BiomeType::Plains,
+                self.config.terrain_height,
+                BlockId::new(2, 0, 0),
+            ),
+            WorldType::Superflat => {
+                let height = self.flat_layers.iter().map(|(_, t)| *t as i32).sum::<i32>();
+                let top = self.flat_layers.last().map_or(BlockId::AIR, |(id, _)| *id);
+                flat(self.config.superflat.biome, height - 1, top)
+            }
+            // Nothing to draw; the spawn platform is too small to matter
+            WorldType::Void => flat(BiomeType::Plains, 0, BlockId::AIR),
+        }
+    }
+
+    pub(crate) fn block_registry(&self) -> &Arc<BlockRegistry> {
+        &self.block_registry
+    }
+
+    pub(crate) fn sub_resolution(&self) -> u8 {
+        self.config.sub_resolution
+    }
+
+    /// Projection placing the terrain on the earth: the one its real-world
+    /// data is read through, or the one its climate takes latitude from.
+    /// None for worlds with no geography, such as superflat or void worlds.
+    pub fn geo_projection(&self) -> Option<&GeoProjection> {
+        let config = &self.config;
+        let real_world =
+            config.elevation.is_some() || config.osm.is_some() || config.land_cover.is_some();
+        let climate = config.world_type == WorldType::Normal
+            && config.climate.blocks_per_degree.is_none();
+        (real_world || climate).then_some(&config.projection)
+    }
+
+    /// Surface samples of the columns of a chunk, indexed `x * CHUNK_SIZE + z`
+    fn column_samples(&self, coord: ChunkCoord) -> Arc<Vec<ColumnSample>> {
+        let size = CHUNK_SIZE as i64;
+        cached(
+            &self.columns,
+            (coord.x(), coord.z()),
+            CACHED_COLUMNS,
+            || {
+                let base = coord.to_block_pos(CHUNK_SIZE as i32);
+                (0..size * size)
+                    .map(|i| self.sample_column(base.x + i / size, base.z + i % size))
+                    .collect()
+            },
+        )
+    }
+
+    /// Surface sample of any column, read from the cache when its chunk
+    /// column has been sampled
+    fn column_sample(&self, x: i64, z: i64) -> ColumnSample {
+        let coord = ChunkCoord::from_block_pos(I64Vec3::new(x, 0, z), CHUNK_SIZE as i32);
+        let size = CHUNK_SIZE as i64;
+        let local = (x.rem_euclid(size) * size + z.rem_euclid(size)) as usize;
+        match self.columns.read().get(&(coord.x(), coord.z())) {
+            Some(columns) => columns[local],
+            None => self.sample_column(x, z),
+        }
+    }
+
+    fn sample_column(&self, x: i64, z: i64) -> ColumnSample {
+        let (mut column, river) = self.sample_ground(x, z);
+        if river {
+            return column;
+        }
+        let lake = self
+            .water
+            .lake_level(x, z, &|x, z| self.calculate_height(x, z));
+        if let Some(level) = lake {
+            column.water_level = column.water_level.max(level);
+        }
+        column
+    }
+
+    /// A column with its river but without lakes, and whether a river
+    /// crosses it
+    fn sample_ground(&self, x: i64, z: i64) -> (ColumnSample, bool) {
+        let mut column = ColumnSample {
+            biome: self.calculate_biome(x, z),
+            height: self.calculate_height(x, z),
+            water_level: self.config.water_level,
+        };
+        if let Some(river) = self.river_at(x, z) {
+            // Water one block below the banks, over a bed deepest mid-stream.
+            // Rivers end where they reach the sea.
+            let surface = river.level.min(column.height as f64).floor() as i32 - 1;
+            if surface > self.config.water_level {
+                column.biome = BiomeType::River;
+                column.water_level = surface;
+                column.height = surface - river.depth.round() as i32;
+                return (column, true);
+            }
+        }
+        (column, false)
+    }
+
+    /// Biome of any column as generated, rivers included. Skips the lake
+    /// search, which only moves the water level.
+    pub(crate) fn biome_at(&self, x: i64, z: i64) -> BiomeType {
+        match self.config.world_type {
+            WorldType::Normal => self.sample_ground(x, z).0.biome,
+            _ => self.surface_at(x, z).0.biome,
+        }
+    }
+
+    /// Places trees, boulders, flowers and grass whose origin lies in this
+    /// chunk. Run once the neighbouring chunks have terrain. Blocks landing
+    /// outside the chunk are returned as world positions for the caller to
+    /// apply to, or queue for, the chunks they fall in.
+    pub fn decorate_chunk(&self, chunk: &mut Chunk, coord: ChunkCoord) -> Vec<(I64Vec3, Block)> {
+        if self.config.world_type != WorldType::Normal {
+            return Vec::new();
+        }
+
+        let base = coord.to_block_pos(CHUNK_SIZE as i32);
+        // Seeded per column, so every vertical chunk rolls the same features
+        // and only keeps those rooted inside it
+        let mut rng = self.chunk_rng(ChunkCoord::new(coord.x(), 0, coord.z()));
+        let mut spill = Vec::new();
+        let replaceable = |id: BlockId| self.block_registry.has_tag(id, "replaceable/feature");
+
+        type Density = fn(&BiomeDecoration) -> f32;
+        let kinds: [(u32, Density); 4] = [
+            (TREE_ATTEMPTS, |d| d.trees),
+            (BOULDER_ATTEMPTS, |d| d.boulders),
+            (FLOWER_ATTEMPTS, |d| d.flowers),
+            (GRASS_ATTEMPTS, |d| d.grass),
+        ];
+        for (kind, (attempts, density)) in kinds.iter().enumerate() {
+            for _ in 0..*attempts {
+                let world_x = base.x + rng.gen_range(0..CHUNK_SIZE as i64);
+                let world_z = base.z + rng.gen_range(0..CHUNK_SIZE as i64);
+                let roll: f32 = rng.gen();
+                let feature_seed: u64 = rng.gen();
+
+                let column = self.column_sample(world_x, world_z);
+                let chance = density(&BiomeDecoration::for_biome(column.biome)) / *attempts as f32;
+                let ground = column.height;
+                let origin = I64Vec3::new(world_x, ground as i64 + 1, world_z);
+                if roll >= chance || origin.y < base.y || origin.y >= base.y + CHUNK_SIZE as i64 {
+                    continue;
+                }
+                if ground < column.water_level || !self.is_feature_ground(chunk, base, origin) {
+                    continue;
+                }
+                // Roads, water and buildings stay clear of vegetation
+                if self
+                    .osm
+                    .as_ref()
+                    .is_some_and(|osm| osm.covers(world_x, world_z))
+                {
+                    continue;
+                }
+
+                let mut feature_rng = ChaCha12Rng::seed_from_u64(feature_seed);
+                let blocks = match kind {
+                    0 => self.generate_tree(origin),
+                    1 => features::generate_boulder(
+                        origin,
+                        feature_rng.gen_range(1..=2),
+                        self.blocks.stone,
+                    ),
+                    2 => HashMap::from([(origin, Block::new(self.blocks.flower))]),
+                    _ => HashMap::from([(origin, Block::new(self.blocks.tall_grass))]),
+                };
+
+                let mut blocks: Vec<_> = blocks.into_iter().collect();
+                blocks.sort_by_key(|(pos, _)| (pos.y, pos.x, pos.z));
+                for (pos, block) in blocks {
+                    let local = pos - base;
+                    if local.cmpge(I64Vec3::ZERO).all()
+                        && local.cmplt(I64Vec3::splat(CHUNK_SIZE as i64)).all()
+                    {
+                        features::write_feature_block(chunk, local.as_ivec3(), block, replaceable);
                     } else {
-                        chunk.set_block(
-                            x as u32,
-                            y as u32,
-                            z as u32,
-                            Some(Block::new(BlockId::new(0, 0, 0))),
-                        ); // Air
+                        spill.push((pos, block));
                     }
                 }
             }
         }
+
+        spill
+    }
+
+    /// Whether a feature may root at `origin`: the cell is free and, when the
+    /// ground below is inside this chunk, that ground is soil and not carved away
+    fn is_feature_ground(&self, chunk: &Chunk, base: I64Vec3, origin: I64Vec3) -> bool {
+        let local = (origin - base).as_ivec3();
+        let free = chunk
+            .get_block(local.x as u32, local.y as u32, local.z as u32)
+            .is_none_or(|block| block.id == BlockId::AIR);
+        if !free || local.y == 0 {
+            return free;
+        }
+        chunk
+            .get_block(local.x as u32, local.y as u32 - 1, local.z as u32)
+            .is_some_and(|block| {
+                block.id != BlockId::AIR && !self.block_registry.has_tag(block.id, "liquids")
+            })
+    }
+
+    /// Deterministic per-chunk RNG, independent of generation order
+    fn chunk_rng(&self, coord: ChunkCoord) -> ChaCha12Rng {
+        let hash = (coord.x() as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (coord.y() as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ (coord.z() as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+        ChaCha12Rng::seed_from_u64(self.config.world_seed ^ hash)
+    }
+
+    #[cfg(test)]
+    fn block_id(&self, name: &str) -> BlockId {
+        self.block_registry
+            .get_by_name(name)
+            .map(|def| def.id)
+            .expect("block is registered")
     }
 
     fn generate_flat_chunk(&self, chunk: &mut Chunk, coord: ChunkCoord) {
-        let base_y = coord.y() * 32;
-        for x in 0..32 {
-            for y in 0..32 {
-                for z in 0..32 {
-                    let world_y = base_y + y as i32;
-                    if world_y < self.config.terrain_height {
-                        chunk.set_block(
-                            x as u32,
-                            y as u32,
-                            z as u32,
-                            Some(Block::new(BlockId::new(1, 0, 0))),
-                        ); // Stone
-                    } else if world_y == self.config.terrain_height {
-                        chunk.set_block(
-                            x as u32,
-                            y as u32,
-                            z as u32,
-                            Some(Block::new(BlockId::new(2, 0, 0))),
-                        ); // Grass
+        let base_y = coord.y() * CHUNK_SIZE as i64;
+        let terrain_height = self.config.terrain_height as i64;
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let world_y = base_y + y as i64;
+                    if world_y < terrain_height {
+                        chunk.set_block(x, y, z, Some(Block::new(self.blocks.stone)));
+                    } else if world_y == terrain_height {
+                        chunk.set_block(x, y, z, Some(Block::new(self.blocks.grass)));
                     } else {
-                        chunk.set_block(
-                            x as u32,
-                            y as u32,
-                            z as u32,
-                            Some(Block::new(BlockId::new(0, 0, 0))),
-                        ); // Air
+                        chunk.set_block(x, y, z, Some(Block::new(BlockId::AIR)));
                     }
                 }
             }
@@ -209,21 +1012,17 @@ impl TerrainGenerator {
     }
 
     fn generate_superflat_chunk(&self, chunk: &mut Chunk, coord: ChunkCoord) {
-        let base_y = coord.y() * 32;
+        let base_y = coord.y() * CHUNK_SIZE as i64;
         let mut current_height = 0;
 
-        for (block_id, thickness) in &self.config.flat_world_layers {
-            for y in 0..32 {
-                let world_y = base_y + y as i32;
+        for (block_id, thickness) in &self.flat_layers {
+            let thickness = *thickness as i64;
+            for y in 0..CHUNK_SIZE {
+                let world_y = base_y + y as i64;
                 if world_y >= current_height && world_y < current_height + thickness {
-                    for x in 0..32 {
-                        for z in 0..32 {
-                            chunk.set_block(
-                                x as u32,
-                                y as u32,
-                                z as u32,
-                                Some(Block::new(*block_id)),
-                            );
+                    for x in 0..CHUNK_SIZE {
+                        for z in 0..CHUNK_SIZE {
+                            chunk.set_block(x, y, z, Some(Block::new(*block_id)));
                         }
                     }
                 }
@@ -232,24 +1031,41 @@ impl TerrainGenerator {
         }
 
         // Fill remaining space with air
-        for x in 0..32 {
-            for y in 0..32 {
-                for z in 0..32 {
-                    let world_y = base_y + y as i32;
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let world_y = base_y + y as i64;
                     if world_y >= current_height {
-                        chunk.set_block(
-                            x as u32,
-                            y as u32,
-                            z as u32,
-                            Some(Block::new(BlockId::new(0, 0, 0))),
-                        ); // Air
+                        chunk.set_block(x, y, z, Some(Block::new(BlockId::AIR)));
                     }
                 }
             }
         }
     }
 
-    fn get_height(&self, x: i32, z: i32) -> i32 {
+    /// Nothing but a small stone platform at the origin to spawn on
+    fn generate_void_chunk(&self, chunk: &mut Chunk, coord: ChunkCoord) {
+        let base = coord.to_block_pos(CHUNK_SIZE as i32);
+        let platform = I64Vec3::new(0, FLAT_WORLD_HEIGHT as i64, 0);
+        let stone = self.blocks.stone;
+        for x in -VOID_PLATFORM_RADIUS..=VOID_PLATFORM_RADIUS {
+            for z in -VOID_PLATFORM_RADIUS..=VOID_PLATFORM_RADIUS {
+                let local = platform + I64Vec3::new(x, 0, z) - base;
+                if local.cmpge(I64Vec3::ZERO).all()
+                    && local.cmplt(I64Vec3::splat(CHUNK_SIZE as i64)).all()
+                {
+                    chunk.set_block(
+                        local.x as u32,
+                        local.y as u32,
+                        local.z as u32,
+                        Some(Block::new(stone)),
+                    );
+                }
+            }
+        }
+    }
+
+    fn get_height(&self, x: i64, z: i64) -> i32 {
         let mut amplitude = 1.0;
         let mut frequency = self.config.noise_scale;
         let mut height = 0.0;
@@ -265,33 +1081,112 @@ impl TerrainGenerator {
         (height * self.config.height_multiplier) as i32 + self.config.terrain_height
     }
 
-    pub fn get_chunk(&self, coord: ChunkCoord) -> Option<Arc<Chunk>> {
-        let chunk = self.generate_chunk(coord);
-        Some(Arc::new(chunk))
+    fn calculate_height(&self, x: i64, z: i64) -> i32 {
+        if let Some(height) = self.elevation.as_ref().and_then(|dem| dem.height_at(x, z)) {
+            return height;
+        }
+        if let Some(density) = &self.density {
+            return density.surface_height(x, z);
+        }
+
+        let height = match &self.erosion {
+            Some(erosion) => erosion.height_at(x, z, &|x, z| self.noise_height(x, z)),
+            None => self.noise_height(x, z),
+        };
+        height.clamp(self.config.water_level as f64 - 8.0, 256.0) as i32
+    }
+
+    /// The river crossing a column, if rivers are enabled
+    fn river_at(&self, x: i64, z: i64) -> Option<RiverSample> {
+        self.rivers
+            .as_ref()?
+            .sample(x, z, &|x, z| self.calculate_height(x, z) as f64)
     }
 
-    fn calculate_height(&self, x: i32, z: i32, biome: BiomeType) -> i32 {
+    /// Terrain height from the noise layers, before erosion and clamping
+    fn noise_height(&self, x: i64, z: i64) -> f64 {
+        let biome_mod = self.blended_height_modifier(x, z);
+        match self.config.world_type {
+            WorldType::Superflat => self.relief_height(x, z) + biome_mod * 1.5,
+            _ => self.relief_height(x, z) + biome_mod,
+        }
+    }
+
+    /// Noise terrain height without biome modifiers. The climate reads
+    /// this, since biomes cannot depend on a height that depends on them.
+    fn relief_height(&self, x: i64, z: i64) -> f64 {
         let base_noise = self.sample_noise("terrain", x, z);
         let detail_noise = self.sample_noise("detail", x, z);
-        let biome_mod = self.biome_height_modifier(biome);
+        let base_height = self.get_height(x, z) as f64;
 
-        let height = match self.config.world_type {
+        match self.config.world_type {
             WorldType::Superflat => {
-                BASE_TERRAIN_HEIGHT
+                base_height
                     + (base_noise * self.config.terrain_amplitude * 2.0).abs()
                     + (detail_noise * 12.0)
-                    + biome_mod * 1.5
             }
             _ => {
-                BASE_TERRAIN_HEIGHT
+                base_height
                     + (base_noise * self.config.terrain_amplitude).abs()
                     + (detail_noise * 6.0)
-                    + biome_mod
             }
-        };
+        }
+    }
 
-        let final_height = height.clamp(SEA_LEVEL as f64 - 8.0, 256.0) as i32;
-        final_height
+    /// Biome height modifier interpolated between the blended lattice
+    /// points around a column, so biome borders slope instead of forming
+    /// cliffs
+    fn blended_height_modifier(&self, x: i64, z: i64) -> f64 {
+        let lx = x.div_euclid(BIOME_BLEND_SPACING);
+        let lz = z.div_euclid(BIOME_BLEND_SPACING);
+        let tx = x.rem_euclid(BIOME_BLEND_SPACING) as f64 / BIOME_BLEND_SPACING as f64;
+        let tz = z.rem_euclid(BIOME_BLEND_SPACING) as f64 / BIOME_BLEND_SPACING as f64;
+
+        let near = lerp(
+            self.lattice_height_modifier(lx, lz),
+            self.lattice_height_modifier(lx + 1, lz),
+            tx,
+        );
+        let far = lerp(
+            self.lattice_height_modifier(lx, lz + 1),
+            self.lattice_height_modifier(lx + 1, lz + 1),
+            tx,
+        );
+        lerp(near, far, tz)
+    }
+
+    /// Biome height modifier averaged over a lattice point and its eight
+    /// neighbours, read from the tile cache
+    fn lattice_height_modifier(&self, lx: i64, lz: i64) -> f64 {
+        let key = (lx.div_euclid(BLEND_TILE), lz.div_euclid(BLEND_TILE));
+        let local = (lx.rem_euclid(BLEND_TILE) * BLEND_TILE + lz.rem_euclid(BLEND_TILE)) as usize;
+        let tile = cached(&self.blend, key, CACHED_COLUMNS, || {
+            // Each biome on the tile and its border is sampled once
+            let (base_x, base_z) = (key.0 * BLEND_TILE - 1, key.1 * BLEND_TILE - 1);
+            let side = BLEND_TILE + 2;
+            let modifiers: Vec<f64> = (0..side * side)
+                .map(|i| {
+                    let biome = self.calculate_biome(
+                        (base_x + i / side) * BIOME_BLEND_SPACING,
+                        (base_z + i % side) * BIOME_BLEND_SPACING,
+                    );
+                    self.biome_height_modifier(biome)
+                })
+                .collect();
+            (0..BLEND_TILE * BLEND_TILE)
+                .map(|i| {
+                    let (x, z) = (i / BLEND_TILE + 1, i % BLEND_TILE + 1);
+                    let mut total = 0.0;
+                    for dx in -1..=1 {
+                        for dz in -1..=1 {
+                            total += modifiers[((x + dx) * side + z + dz) as usize];
+                        }
+                    }
+                    total / 9.0
+                })
+                .collect()
+        });
+        tile[local]
     }
 
     fn biome_height_modifier(&self, biome: BiomeType) -> f64 {
@@ -303,251 +1198,152 @@ impl TerrainGenerator {
             BiomeType::Ocean => -8.0,
             BiomeType::Tundra => 6.0,
             BiomeType::Swamp => -2.0,
+            BiomeType::River => -4.0,
         }
     }
 
-    fn calculate_biome(&self, x: i32, z: i32) -> BiomeType {
-        let temp = self.sample_noise("biome", x, z);
-        let moisture = self.sample_noise("biome", x + 1000, z + 1000);
+    fn calculate_biome(&self, x: i64, z: i64) -> BiomeType {
+        if self.config.world_type == WorldType::Superflat {
+            return self.config.superflat.biome;
+        }
+        if let Some(biome) = self
+            .land_cover
+            .as_ref()
+            .and_then(|land_cover| land_cover.biome_at(x, z))
+        {
+            return biome;
+        }
 
-        let biome = match (temp, moisture) {
-            (t, _) if t < -0.5 => BiomeType::Mountains,
-            (t, m) if t > 0.5 && m < 0.0 => BiomeType::Desert,
-            (t, m) if t > 0.3 && m > 0.4 => BiomeType::Forest,
-            (_, m) if m > 0.7 => BiomeType::Ocean,
-            (t, _) if t < -0.3 => BiomeType::Tundra,
-            (_, m) if m > 0.5 => BiomeType::Swamp,
-            _ => BiomeType::Plains,
+        let height = |x, z| match self.elevation.as_ref().and_then(|dem| dem.height_at(x, z)) {
+            Some(height) => height as f64,
+            None => self.relief_height(x, z),
         };
-
-        biome
+        let climate = self.climate.sample(
+            x,
+            z,
+            &height,
+            self.sample_noise("temperature", x, z),
+            self.sample_noise("moisture", x, z),
+        );
+        self.climate.biome(&climate)
     }
 
     fn get_biome_blocks(&self, biome: BiomeType) -> (BlockId, BlockId) {
+        let blocks = &self.blocks;
         match biome {
-            BiomeType::Plains | BiomeType::Swamp => (
-                self.block_registry
-                    .get_by_name("dirt")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0)),
-                self.block_registry
-                    .get_by_name("grass")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0)),
-            ),
-            BiomeType::Mountains | BiomeType::Tundra => (
-                self.block_registry
-                    .get_by_name("stone")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0)),
-                self.block_registry
-                    .get_by_name("snow")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0)),
-            ),
-            BiomeType::Desert => (
-                self.block_registry
-                    .get_by_name("sand")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0)),
-                self.block_registry
-                    .get_by_name("sand")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0)),
-            ),
-            BiomeType::Forest => (
-                self.block_registry
-                    .get_by_name("dirt")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0)),
-                self.block_registry
-                    .get_by_name("grass")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0)),
-            ),
-            BiomeType::Ocean => (
-                self.block_registry
-                    .get_by_name("sand")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0)),
-                self.block_registry
-                    .get_by_name("gravel")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0)),
-            ),
+            BiomeType::Plains | BiomeType::Swamp => (blocks.dirt, blocks.grass),
+            BiomeType::Mountains | BiomeType::Tundra => (blocks.stone, blocks.snow),
+            BiomeType::Desert => (blocks.sand, blocks.sand),
+            BiomeType::Forest => (blocks.dirt, blocks.grass),
+            BiomeType::Ocean | BiomeType::River => (blocks.sand, blocks.gravel),
+        }
+    }
+
+    /// Soil and top block of a column. Beaches and lake beds take the
+    /// biome's shore block, and grass and snow do not form under water.
+    fn surface_blocks(&self, column: &ColumnSample) -> (BlockId, BlockId) {
+        let (mut base, mut top) = self.get_biome_blocks(column.biome);
+        if column.height <= column.water_level + SHORE_HEIGHT
+            && !matches!(column.biome, BiomeType::Ocean | BiomeType::River)
+        {
+            match self.shore_block(column.biome) {
+                Some(shore) => (base, top) = (shore, shore),
+                None if column.height < column.water_level => top = base,
+                None => {}
+            }
+        }
+        (base, top)
+    }
+
+    /// Surface block of a biome's beaches and lake beds, or None where the
+    /// biome keeps its own soil
+    fn shore_block(&self, biome: BiomeType) -> Option<BlockId> {
+        match biome {
+            BiomeType::Plains | BiomeType::Forest | BiomeType::Desert => {
+                Some(self.blocks.sand)
+            }
+            BiomeType::Mountains | BiomeType::Tundra => Some(self.blocks.gravel),
+            BiomeType::Swamp | BiomeType::Ocean | BiomeType::River => None,
         }
     }
 
     fn get_block_for_depth(
         &self,
-        y: i32,
+        y: i64,
         height: i32,
         base: BlockId,
         top: BlockId,
         biome: BiomeType,
     ) -> BlockId {
+        let height = height as i64;
         match biome {
-            BiomeType::Ocean if y <= SEA_LEVEL - 8 => self
-                .block_registry
-                .get_by_name("stone")
-                .map(|def| def.id)
-                .unwrap_or(BlockId::new(10, 0, 0)),
+            BiomeType::Ocean if y <= self.config.water_level as i64 - 8 => self.blocks.stone,
             _ if y == height => top,
-            _ if y > height - 4 => base,
-            _ => self
-                .block_registry
-                .get_by_name("stone")
-                .map(|def| def.id)
-                .unwrap_or(BlockId::new(10, 0, 0)),
+            _ if y >= height - SURFACE_DEPTH => base,
+            _ => self.blocks.stone,
         }
     }
 
-    fn create_block(&self, id: BlockId, biome: BiomeType, rng: &mut ChaCha12Rng) -> Block {
-        let mut block = Block::new(id.into());
-
-        // Add biome-specific features
+    /// Air block holding the biome's sub-block decoration for the cell above
+    /// a surface block, if it gets one
+    fn decoration_above(
+        &self,
+        surface: BlockId,
+        biome: BiomeType,
+        rng: &mut ChaCha12Rng,
+    ) -> Option<Block> {
         match biome {
-            BiomeType::Forest
-                if id
-                    == self
-                        .block_registry
-                        .get_by_name("grass")
-                        .map(|def| def.id)
-                        .unwrap_or(BlockId::new(10, 0, 0)) =>
-            {
-                if rng.gen_ratio(1, 10) {
-                    block.place_sub_block(
-                        (
-                            rng.gen_range(0..SUB_RESOLUTION as u8),
-                            rng.gen_range(0..SUB_RESOLUTION as u8),
-                            rng.gen_range(0..SUB_RESOLUTION as u8),
-                        ),
-                        SubBlock {
-                            id: self
-                                .block_registry
-                                .get_by_name("grass")
-                                .map(|def| def.id)
-                                .unwrap_or(BlockId::new(10, 0, 0))
-                                .into(),
-                            facing: BlockFacing::PosZ,
-                            orientation: BlockOrientation::North,
-                            connections: ConnectedDirections::default(),
-                        },
-                    );
-                }
-            }
-            BiomeType::Swamp
-                if id
-                    == self
-                        .block_registry
-                        .get_by_name("water")
-                        .map(|def| def.id)
-                        .unwrap_or(BlockId::new(10, 0, 0)) =>
-            {
+            BiomeType::Forest if surface == self.blocks.grass && rng.gen_ratio(1, 10) => {
+                let mut block = Block::new(BlockId::AIR);
+                // Tufts stand on the floor of the cell
                 block.place_sub_block(
                     (
-                        rng.gen_range(0..SUB_RESOLUTION as u8),
-                        rng.gen_range(0..SUB_RESOLUTION as u8),
-                        rng.gen_range(0..SUB_RESOLUTION as u8),
+                        rng.gen_range(0..self.config.sub_resolution),
+                        0,
+                        rng.gen_range(0..self.config.sub_resolution),
                     ),
                     SubBlock {
-                        id: self
-                            .block_registry
-                            .get_by_name("water")
-                            .map(|def| def.id)
-                            .unwrap_or(BlockId::new(10, 0, 0))
-                            .into(),
+                        id: self.blocks.tall_grass.into(),
                         facing: BlockFacing::PosZ,
                         orientation: BlockOrientation::North,
                         connections: ConnectedDirections::default(),
                     },
                 );
+                Some(block)
             }
-            _ => {}
+            _ => None,
         }
-
-        block
     }
 
-    fn add_strata_details(&self, block: &mut Block, world_y: i32, rng: &mut ChaCha12Rng) {
-        if world_y < SEA_LEVEL - 8 && rng.gen_ratio(1, 10) {
-            let ore_type = match rng.gen_range(0..100) {
-                0..=5 => "coal_ore",
-                6..=8 => "iron_ore",
-                9..=10 => "gold_ore",
-                11..=12 => "diamond_ore",
-                _ => "stone",
-            };
-
-            for _ in 0..rng.gen_range(1..=3) {
-                block.place_sub_block(
-                    (
-                        rng.gen_range(0..SUB_RESOLUTION as u8),
-                        rng.gen_range(0..SUB_RESOLUTION as u8),
-                        rng.gen_range(0..SUB_RESOLUTION as u8),
-                    ),
-                    SubBlock {
-                        id: self
-                            .block_registry
-                            .get_by_name(ore_type)
-                            .map(|def| def.id)
-                            .unwrap_or(BlockId::new(10, 0, 0))
-                            .into(),
-                        facing: BlockFacing::PosZ,
-                        orientation: BlockOrientation::North,
-                        connections: ConnectedDirections::default(),
-                    },
-                );
-            }
-        }
-    }
-
-    fn should_add_cave(&self, x: i32, y: i32, z: i32) -> bool {
-        if self.config.world_type == WorldType::Flat {
-            return false;
-        }
-
-        let cave_noise = self.sample_noise("caves", x, z);
-        let y_factor = 1.0 - (y as f64 / 128.0).abs();
-        (cave_noise * y_factor).abs() > self.config.cave_threshold
-    }
-
-    fn sample_noise(&self, layer: &str, x: i32, z: i32) -> f64 {
-        let noise = self.noise.get([
-            x as f64 / self.config.noise_scale,
-            z as f64 / self.config.noise_scale,
-        ]);
-        noise
+    fn sample_noise(&self, layer: &str, x: i64, z: i64) -> f64 {
+        let scale = match layer {
+            "temperature" | "moisture" => self.config.biome_scale,
+            _ => self.config.noise_scale,
+        };
+        let noise = self.noise_layers.get(layer).unwrap_or(&self.noise);
+        noise.get([x as f64 * scale, z as f64 * scale])
     }
 
-    pub fn generate_tree(&self, base_pos: IVec3) -> HashMap<IVec3, Block> {
+    pub fn generate_tree(&self, base_pos: I64Vec3) -> HashMap<I64Vec3, Block> {
         let mut blocks = HashMap::new();
-        let trunk_id = self
-            .block_registry
-            .get_by_name("log")
-            .map(|def| def.id)
-            .unwrap_or(BlockId::new(10, 0, 0));
-        let leaves_id = self
-            .block_registry
-            .get_by_name("leaves")
-            .map(|def| def.id)
-            .unwrap_or(BlockId::new(10, 0, 0));
-        let height = 4 + (base_pos.x.abs() % 3) as i32;
+        let trunk_id = self.blocks.log;
+        let leaves_id = self.blocks.leaves;
+        let height = 4 + base_pos.x.abs() % 3;
 
         // Generate trunk
         for y in 0..height {
-            let pos = IVec3::new(base_pos.x, base_pos.y + y, base_pos.z);
-            blocks.insert(pos, Block::new(trunk_id.into()));
+            let pos = I64Vec3::new(base_pos.x, base_pos.y + y, base_pos.z);
+            blocks.insert(pos, Block::new(trunk_id));
         }
 
         // Generate leaves
-        let center = IVec3::new(base_pos.x, base_pos.y + height - 2, base_pos.z);
+        let center = I64Vec3::new(base_pos.x, base_pos.y + height - 2, base_pos.z);
         for dx in -2..=2 {
             for dz in -2..=2 {
                 for dy in -1..=1 {
                     if dx * dx + dz * dz + dy * dy <= 4 {
-                        let pos = center + IVec3::new(dx, dy, dz);
-                        blocks.insert(pos, Block::new(leaves_id.into()));
+                        let pos = center + I64Vec3::new(dx, dy, dz);
+                        blocks.insert(pos, Block::new(leaves_id));
                     }
                 }
             }
@@ -559,17 +1355,12 @@ impl TerrainGenerator {
     pub fn generate_grass(&mut self, block: &mut Block, rng: &mut ChaCha12Rng) {
         block.place_sub_block(
             (
-                rng.gen_range(0..SUB_RESOLUTION as u8),
-                rng.gen_range(0..SUB_RESOLUTION as u8),
-                rng.gen_range(0..SUB_RESOLUTION as u8),
+                rng.gen_range(0..self.config.sub_resolution),
+                rng.gen_range(0..self.config.sub_resolution),
+                rng.gen_range(0..self.config.sub_resolution),
             ),
             SubBlock {
-                id: self
-                    .block_registry
-                    .get_by_name("grass")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0))
-                    .into(),
+                id: self.blocks.grass.into(),
                 facing: BlockFacing::PosZ,
                 orientation: BlockOrientation::North,
                 connections: ConnectedDirections::empty(),
@@ -580,17 +1371,12 @@ impl TerrainGenerator {
     pub fn generate_water(&mut self, block: &mut Block, rng: &mut ChaCha12Rng) {
         block.place_sub_block(
             (
-                rng.gen_range(0..SUB_RESOLUTION as u8),
-                rng.gen_range(0..SUB_RESOLUTION as u8),
-                rng.gen_range(0..SUB_RESOLUTION as u8),
+                rng.gen_range(0..self.config.sub_resolution),
+                rng.gen_range(0..self.config.sub_resolution),
+                rng.gen_range(0..self.config.sub_resolution),
             ),
             SubBlock {
-                id: self
-                    .block_registry
-                    .get_by_name("water")
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0))
-                    .into(),
+                id: self.blocks.water.into(),
                 facing: BlockFacing::PosZ,
                 orientation: BlockOrientation::North,
                 connections: ConnectedDirections::empty(),
@@ -599,19 +1385,18 @@ impl TerrainGenerator {
     }
 
     pub fn generate_ore(&mut self, block: &mut Block, rng: &mut ChaCha12Rng, ore_type: &str) {
+        let Some(ore) = self.block_registry.get_by_name(ore_type) else {
+            log::error!("Unknown ore block {}", ore_type);
+            return;
+        };
         block.place_sub_block(
             (
-                rng.gen_range(0..SUB_RESOLUTION as u8),
-                rng.gen_range(0..SUB_RESOLUTION as u8),
-                rng.gen_range(0..SUB_RESOLUTION as u8),
+                rng.gen_range(0..self.config.sub_resolution),
+                rng.gen_range(0..self.config.sub_resolution),
+                rng.gen_range(0..self.config.sub_resolution),
             ),
             SubBlock {
-                id: self
-                    .block_registry
-                    .get_by_name(ore_type)
-                    .map(|def| def.id)
-                    .unwrap_or(BlockId::new(10, 0, 0))
-                    .into(),
+                id: ore.id.into(),
                 facing: BlockFacing::PosZ,
                 orientation: BlockOrientation::North,
                 connections: ConnectedDirections::empty(),
@@ -619,3 +1404,577 @@ impl TerrainGenerator {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn generator() -> TerrainGenerator {
+        TerrainGenerator::new(
+            WorldGenConfig {
+                world_seed: 42,
+                terrain_height: 64,
+                height_multiplier: 16.0,
+                // Keep surfaces intact for the surface checks
+                cave_density: 0.0,
+                cave_threshold: 10.0,
+                ..Default::default()
+            },
+            Arc::new(BlockRegistry::default()),
+        )
+    }
+
+    #[test]
+    fn test_engine_config_terrain_is_not_flat() {
+        let config = crate::config::WorldGenConfig::default();
+        let generator = TerrainGenerator::new((&config).into(), Arc::new(BlockRegistry::default()));
+        let heights: HashSet<i32> = (0..64)
+            .map(|i| generator.sample_column(i * 13, i * 7).height)
+            .collect();
+        assert!(heights.len() > 1);
+    }
+
+    #[test]
+    fn test_engine_config_forwards_generation_options() {
+        let projection = GeoProjection {
+            origin_lat: 47.0,
+            origin_lon: 8.0,
+            ..Default::default()
+        };
+        let config = crate::config::WorldGenConfig {
+            elevation: Some(ElevationConfig::default()),
+            projection,
+            osm: Some(OsmConfig::default()),
+            land_cover: Some(LandCoverConfig::default()),
+            erosion: Some(ErosionConfig::default()),
+            rivers: Some(RiverConfig::default()),
+            climate: ClimateConfig {
+                blocks_per_degree: Some(100.0),
+                ..Default::default()
+            },
+            density: Some(
+                DensityConfig::from_json(crate::world::generator::density::EXAMPLE_DENSITY)
+                    .unwrap(),
+            ),
+            octaves: 6,
+            persistence: 0.25,
+            lacunarity: 3.0,
+            cave_threshold: 0.75,
+            ore_features: default_ore_features()[..1].to_vec(),
+            water: WaterConfig {
+                fluid: "lava".to_string(),
+                lakes: false,
+                ..Default::default()
+            },
+            structures: StructureSet::default(),
+            structure_directory: Some(PathBuf::from("structures")),
+            ..Default::default()
+        };
+        let terrain = WorldGenConfig::from(&config);
+        assert_eq!(terrain.octaves, 6);
+        assert_eq!(terrain.persistence, 0.25);
+        assert_eq!(terrain.lacunarity, 3.0);
+        assert_eq!(terrain.cave_threshold, 0.75);
+        assert_eq!(terrain.ore_features, config.ore_features);
+        assert!(terrain.elevation.is_some());
+        assert_eq!(terrain.projection, projection);
+        assert!(terrain.osm.is_some());
+        assert!(terrain.land_cover.is_some());
+        assert!(terrain.erosion.is_some());
+        assert!(terrain.rivers.is_some());
+        assert_eq!(terrain.climate.blocks_per_degree, Some(100.0));
+        assert_eq!(terrain.density, config.density);
+        assert_eq!(terrain.water.fluid, "lava");
+        assert!(!terrain.water.lakes);
+        assert_eq!(terrain.structures, StructureSet::default());
+        assert_eq!(terrain.structure_directory, config.structure_directory);
+    }
+
+    #[test]
+    fn test_engine_sub_resolution_is_clamped() {
+        for (resolution, expected) in [(0, 1), (4, 4), (256, MAX_SUB_RESOLUTION)] {
+            let config = crate::config::WorldGenConfig {
+                sub_resolution: resolution,
+                ..Default::default()
+            };
+            assert_eq!(WorldGenConfig::from(&config).sub_resolution, expected);
+        }
+    }
+
+    #[test]
+    fn test_terrain_blocks_resolve_from_the_default_registry() {
+        let registry = BlockRegistry::default();
+        let blocks = TerrainBlocks::resolve(&registry);
+        assert_eq!(Some(blocks.stone), registry.get_by_name("stone").map(|def| def.id));
+        for id in [
+            blocks.stone,
+            blocks.dirt,
+            blocks.grass,
+            blocks.snow,
+            blocks.sand,
+            blocks.gravel,
+            blocks.flower,
+            blocks.tall_grass,
+            blocks.log,
+            blocks.leaves,
+            blocks.water,
+        ] {
+            assert_ne!(id, BlockId::AIR);
+        }
+    }
+
+    #[test]
+    fn test_forest_grass_tufts_stand_above_the_ground() {
+        let generator = generator();
+        let registry = BlockRegistry::default();
+        let resolution = generator.config.sub_resolution;
+        let tufts: Vec<Block> = (0..64)
+            .filter_map(|seed| {
+                let mut rng = ChaCha12Rng::seed_from_u64(seed);
+                generator.decoration_above(generator.blocks.grass, BiomeType::Forest, &mut rng)
+            })
+            .collect();
+        assert!(!tufts.is_empty());
+
+        for tuft in tufts {
+            assert_eq!(tuft.id, BlockId::AIR);
+            let mut chunk = Chunk::new(ChunkCoord::new(0, 0, 0));
+            chunk.sub_resolution = resolution;
+            chunk.set_block(0, 0, 0, Some(Block::new(generator.blocks.grass)));
+            chunk.set_block(0, 1, 0, Some(tuft));
+            for i in 0..(resolution as u32).pow(3) {
+                let cell = glam::UVec3::new(
+                    i % resolution as u32,
+                    i / resolution as u32 % resolution as u32,
+                    i / (resolution as u32).pow(2),
+                );
+                let point = (cell.as_vec3() + 0.5) / resolution as f32;
+                assert!(chunk.is_solid_at_point(point, &registry), "open at {}", point);
+                let above = point + glam::Vec3::Y;
+                assert!(!chunk.is_solid_at_point(above, &registry), "solid at {}", above);
+            }
+        }
+    }
+
+    #[test]
+    fn test_climate_layers_are_independent() {
+        let generator = generator();
+        let differs = (0..64).any(|i| {
+            let (x, z) = (i * 37, i * 53);
+            generator.sample_noise("temperature", x, z) != generator.sample_noise("moisture", x, z)
+        });
+        assert!(differs);
+    }
+
+    #[test]
+    fn test_biome_blend_averages_lattice_neighbours() {
+        let generator = generator();
+        // Inside a tile, on a tile border and at negative coordinates
+        for (lx, lz) in [(3, 3), (BLEND_TILE, -1), (-BLEND_TILE - 3, 5)] {
+            let mut total = 0.0;
+            for dx in -1..=1 {
+                for dz in -1..=1 {
+                    let biome = generator.calculate_biome(
+                        (lx + dx) * BIOME_BLEND_SPACING,
+                        (lz + dz) * BIOME_BLEND_SPACING,
+                    );
+                    total += generator.biome_height_modifier(biome);
+                }
+            }
+            let blended = generator
+                .blended_height_modifier(lx * BIOME_BLEND_SPACING, lz * BIOME_BLEND_SPACING);
+            assert!((blended - total / 9.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_polar_biomes_are_tundra() {
+        let generator = TerrainGenerator::new(
+            WorldGenConfig {
+                climate: ClimateConfig {
+                    blocks_per_degree: Some(100.0),
+                    ..Default::default()
+                },
+                ..generator().config
+            },
+            Arc::new(BlockRegistry::default()),
+        );
+        let generator = &generator;
+        let biomes = |z: i64| (0..32).map(move |i| generator.calculate_biome(i * 41, z + i));
+        // Land near the pole is all tundra, the equator has none
+        let polar: Vec<_> = biomes(-8800).filter(|b| *b != BiomeType::Ocean).collect();
+        assert!(!polar.is_empty());
+        assert!(polar.iter().all(|biome| *biome == BiomeType::Tundra));
+        assert!(biomes(0).all(|biome| biome != BiomeType::Tundra));
+    }
+
+    #[test]
+    fn test_geo_projection_of_world_types() {
+        assert!(generator().geo_projection().is_some());
+        let config = generator().config;
+        let with = |config| TerrainGenerator::new(config, Arc::new(BlockRegistry::default()));
+        let superflat = with(WorldGenConfig {
+            world_type: WorldType::Superflat,
+            ..config.clone()
+        });
+        assert!(superflat.geo_projection().is_none());
+        // A latitude scale of its own detaches the climate from the projection
+        let small = with(WorldGenConfig {
+            climate: ClimateConfig {
+                blocks_per_degree: Some(100.0),
+                ..Default::default()
+            },
+            ..config
+        });
+        assert!(small.geo_projection().is_none());
+    }
+
+    #[test]
+    fn test_superflat_layers_and_void() {
+        let registry = Arc::new(BlockRegistry::default());
+        let id = |name| registry.get_by_name(name).unwrap().id;
+        let superflat = TerrainGenerator::new(
+            WorldGenConfig {
+                world_type: WorldType::Superflat,
+                superflat: "2*stone,dirt,grass;desert".parse().unwrap(),
+                ..Default::default()
+            },
+            registry.clone(),
+        );
+        let chunk = superflat.generate_chunk(ChunkCoord::new(5, 0, -3));
+        let column: Vec<_> = (0..5)
+            .map(|y| {
+                chunk
+                    .get_block(7, y, 9)
+                    .map_or(BlockId::AIR, |block| block.id)
+            })
+            .collect();
+        assert_eq!(
+            column,
+            [
+                id("stone"),
+                id("stone"),
+                id("dirt"),
+                id("grass"),
+                BlockId::AIR
+            ]
+        );
+        assert_eq!(superflat.calculate_biome(100, 100), BiomeType::Desert);
+
+        let void = TerrainGenerator::new(
+            WorldGenConfig {
+                world_type: WorldType::Void,
+                ..Default::default()
+            },
+            registry.clone(),
+        );
+        let solid = |coord: ChunkCoord| {
+            let chunk = void.generate_chunk(coord);
+            (0..32 * 32 * 32)
+                .filter(|i| {
+                    chunk
+                        .get_block(i % 32, i / 32 % 32, i / 1024)
+                        .is_some_and(|block| block.id != BlockId::AIR)
+                })
+                .count()
+        };
+        // Only the 5x5 spawn platform, split over the chunks around the origin
+        let platform: usize = [(-1, -1), (-1, 0), (0, -1), (0, 0)]
+            .into_iter()
+            .map(|(x, z)| solid(ChunkCoord::new(x, 2, z)))
+            .sum();
+        assert_eq!(platform, 25);
+        assert_eq!(solid(ChunkCoord::new(0, 1, 0)), 0);
+        assert_eq!(solid(ChunkCoord::new(4, 2, 4)), 0);
+    }
+
+    #[test]
+    fn test_surface_uses_biome_blocks() {
+        let generator = generator();
+        let mut checked = 0;
+
+        for i in 0..64 {
+            let (x, z) = (i * 97, i * 61);
+            let ColumnSample {
+                biome,
+                height,
+                water_level,
+            } = generator.column_sample(x, z);
+            // Shores are checked on their own
+            if height <= water_level + SHORE_HEIGHT {
+                continue;
+            }
+
+            let coord = ChunkCoord::from_block_pos(I64Vec3::new(x, height as i64, z), 32);
+            let chunk = generator.generate_chunk(coord);
+            let top = chunk
+                .get_block(
+                    x.rem_euclid(32) as u32,
+                    height.rem_euclid(32) as u32,
+                    z.rem_euclid(32) as u32,
+                )
+                .unwrap();
+            assert_eq!(top.id, generator.get_biome_blocks(biome).1, "{:?}", biome);
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn test_density_graph_shapes_terrain() {
+        let density =
+            DensityConfig::from_json(crate::world::generator::density::EXAMPLE_DENSITY).unwrap();
+        let graph = DensityGraph::new(&density, 42).unwrap();
+        let generator = TerrainGenerator::new(
+            WorldGenConfig {
+                world_seed: 42,
+                density: Some(density),
+                cave_density: 0.0,
+                cave_threshold: 10.0,
+                ..Default::default()
+            },
+            Arc::new(BlockRegistry::default()),
+        );
+
+        let coord = ChunkCoord::new(3, 2, -1);
+        let base = coord.to_block_pos(32);
+        let chunk = generator.generate_chunk(coord);
+        for (x, z) in [(0, 0), (13, 7), (31, 31)] {
+            let mut column = graph.column(base.x + x, base.z + z);
+            for y in 0..32 {
+                let block = chunk.get_block(x as u32, y as u32, z as u32).unwrap();
+                let solid = block.id != BlockId::AIR && block.id != generator.block_id("water");
+                assert_eq!(solid, column.at(base.y + y) > 0.0);
+            }
+        }
+        assert_eq!(
+            generator.calculate_height(base.x, base.z),
+            graph.surface_height(base.x, base.z)
+        );
+    }
+
+    #[test]
+    fn test_lakes_fill_basins_with_fluid() {
+        let generator = TerrainGenerator::new(
+            WorldGenConfig {
+                water: WaterConfig {
+                    fluid: "lava".into(),
+                    ..Default::default()
+                },
+                ..generator().config
+            },
+            Arc::new(BlockRegistry::default()),
+        );
+        let lava = generator.block_id("lava");
+        assert_eq!(generator.fluid, lava);
+
+        let (x, z, column) = (0..64)
+            .flat_map(|i| (0..64).map(move |j| (i * 24, j * 24)))
+            .map(|(x, z)| (x, z, generator.column_sample(x, z)))
+            .find(|(_, _, column)| {
+                column.water_level > generator.config.water_level
+                    && column.height < column.water_level - 1
+                    && column.biome != BiomeType::River
+            })
+            .expect("no lake above sea level");
+
+        let level = column.water_level as i64;
+        let coord = ChunkCoord::from_block_pos(I64Vec3::new(x, level, z), 32);
+        let chunk = generator.generate_chunk(coord);
+        let block_at = |y: i64| {
+            let local = I64Vec3::new(x, y, z) - coord.to_block_pos(32);
+            (0..32).contains(&local.y).then(|| {
+                chunk
+                    .get_block(local.x as u32, local.y as u32, local.z as u32)
+                    .map_or(BlockId::AIR, |block| block.id)
+            })
+        };
+        assert_eq!(block_at(level), Some(lava));
+        assert_ne!(block_at(level + 1), Some(lava));
+
+        // An unknown or solid fluid falls back to water
+        let fallback = TerrainGenerator::new(
+            WorldGenConfig {
+                water: WaterConfig {
+                    fluid: "sand".into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            Arc::new(BlockRegistry::default()),
+        );
+        assert_eq!(fallback.fluid, fallback.block_id("water"));
+    }
+
+    #[test]
+    fn test_rivers_carve_beds_and_hold_water() {
+        let generator = TerrainGenerator::new(
+            WorldGenConfig {
+                rivers: Some(RiverConfig {
+                    cell_size: 8,
+                    region_cells: 16,
+                    upstream_regions: 2,
+                    min_catchment: 24,
+                    ..Default::default()
+                }),
+                ..generator().config
+            },
+            Arc::new(BlockRegistry::default()),
+        );
+        let water = generator.block_id("water");
+
+        let (x, z, river) = (0..256)
+            .flat_map(|x| (0..256).step_by(16).map(move |z| (x, z)))
+            .find_map(|(x, z)| {
+                let river = generator.river_at(x, z)?;
+                let bank = generator.calculate_height(x, z);
+                let surface = river.level.min(bank as f64).floor() as i32 - 1;
+                (surface > generator.config.water_level).then_some((x, z, surface))
+            })
+            .expect("no river above sea level");
+
+        let coord = ChunkCoord::from_block_pos(I64Vec3::new(x, river as i64, z), 32);
+        let chunk = generator.generate_chunk(coord);
+        let block_at = |y: i32| {
+            let local = I64Vec3::new(x, y as i64, z) - coord.to_block_pos(32);
+            (0..32).contains(&local.y).then(|| {
+                chunk
+                    .get_block(local.x as u32, local.y as u32, local.z as u32)
+                    .map_or(BlockId::AIR, |block| block.id)
+            })
+        };
+        // Water from the surface down to a solid bed, air above
+        assert_eq!(block_at(river), Some(water));
+        assert_ne!(block_at(river + 1), Some(water));
+        let bed = (river - 8..river)
+            .rev()
+            .find(|&y| block_at(y) != Some(water));
+        assert!(bed.is_none_or(|y| block_at(y) != Some(BlockId::AIR)));
+    }
+
+    #[test]
+    fn test_decoration_spills_across_chunks() {
+        let generator = generator();
+        let mut spilled = Vec::new();
+
+        for cx in 0..8 {
+            for cy in 1..4 {
+                let coord = ChunkCoord::new(cx, cy, 0);
+                let mut chunk = generator.generate_chunk(coord);
+                spilled.extend(generator.decorate_chunk(&mut chunk, coord));
+            }
+        }
+        assert!(!spilled.is_empty());
+
+        // Every spilled block lies outside the chunk that produced it
+        let coord = ChunkCoord::new(0, 2, 0);
+        let mut chunk = generator.generate_chunk(coord);
+        let first = generator.decorate_chunk(&mut chunk, coord);
+        let base = coord.to_block_pos(32);
+        assert!(first.iter().all(|(pos, _)| {
+            let local = *pos - base;
+            local.cmplt(I64Vec3::ZERO).any() || local.cmpge(I64Vec3::splat(32)).any()
+        }));
+
+        let mut again = generator.generate_chunk(coord);
+        let second = generator.decorate_chunk(&mut again, coord);
+        assert_eq!(
+            first
+                .iter()
+                .map(|(pos, block)| (*pos, block.id))
+                .collect::<Vec<_>>(),
+            second
+                .iter()
+                .map(|(pos, block)| (*pos, block.id))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_lod_summaries_follow_generated_terrain() {
+        let generator = generator();
+        let coord = ChunkCoord::new(3, 0, -2);
+        let tile = generator.lod_tile(coord, LodScale::X2);
+        assert_eq!(tile.cells.len(), 16 * 16);
+
+        // At 2x every column is sampled, so cell heights are column means
+        let base = coord.to_block_pos(32);
+        let mean = (0..2)
+            .flat_map(|x| (0..2).map(move |z| (x, z)))
+            .map(|(x, z)| {
+                generator
+                    .sample_column(base.x + 10 + x, base.z + 6 + z)
+                    .height
+            })
+            .sum::<i32>() as f64
+            / 4.0;
+        let cell = tile.get(5, 3);
+        assert_eq!(cell.height, mean.round() as i32);
+        assert_eq!(
+            cell.color,
+            generator
+                .block_registry
+                .get_block_material(cell.block)
+                .unwrap()
+                .albedo
+        );
+
+        // The voxel summary agrees with the full chunk on what is solid
+        let coord = ChunkCoord::new(3, (cell.height / 32) as i64, -2);
+        let chunk = generator.generate_chunk(coord);
+        let full = LodGrid::from_chunk(&chunk, coord, LodScale::X4);
+        let grid = generator.lod_grid(coord, LodScale::X4);
+        let agree = full
+            .cells
+            .iter()
+            .zip(&grid.cells)
+            .filter(|(a, b)| (**a == BlockId::AIR) == (**b == BlockId::AIR))
+            .count();
+        assert!(
+            agree * 10 >= grid.cells.len() * 9,
+            "{} of 512 cells agree",
+            agree
+        );
+        assert!(!grid.is_empty());
+
+        let flat = TerrainGenerator::new(
+            WorldGenConfig {
+                world_type: WorldType::Flat,
+                terrain_height: 40,
+                ..Default::default()
+            },
+            Arc::new(BlockRegistry::default()),
+        );
+        let cell = *flat.lod_tile(coord, LodScale::X8).get(1, 2);
+        assert_eq!((cell.height, cell.block), (40, BlockId::new(2, 0, 0)));
+    }
+
+    #[test]
+    fn test_terrain_is_continuous_far_from_origin() {
+        // 10,000 km out in both directions at one block per metre
+        let generator = generator();
+        for far in [10_000_000_i64, -10_000_000] {
+            let heights: Vec<i32> = (0..256)
+                .map(|i| generator.calculate_height(far + i, far))
+                .collect();
+            assert!(heights
+                .windows(2)
+                .all(|pair| (pair[0] - pair[1]).abs() <= 3));
+            assert!(heights.iter().any(|h| *h != heights[0]));
+        }
+    }
+
+    #[test]
+    fn test_generation_is_deterministic() {
+        let coord = ChunkCoord::new(3, 2, -5);
+        let a = generator().generate_chunk(coord);
+        let b = generator().generate_chunk(coord);
+        let ids = |chunk: &Chunk| {
+            (0..32u32)
+                .map(|y| chunk.get_block(5, y, 7).map(|block| block.id))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&a), ids(&b));
+    }
+}
@@ -0,0 +1,520 @@
+use crate::world::block::Block;
+use crate::world::block_id::{BlockCategory, BlockId};
+use crate::world::blocks_data::BlockRegistry;
+use crate::world::chunk::{Chunk, CHUNK_SIZE};
+use crate::world::chunk_coord::ChunkCoord;
+use crate::world::generator::rivers::{cached, Queued, RegionCache, NEIGHBOURS};
+use parking_lot::RwLock;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BinaryHeap, VecDeque};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Blocks along an aquifer cell edge turned to rock where the neighbouring
+/// aquifer has another level, so the two do not spill into each other
+const AQUIFER_BARRIER: i64 = 2;
+
+/// Water level of every lake cell of a region, None where the cell is dry
+type LakeLevels = Vec<Option<i32>>;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum WaterError {
+    #[error("Unknown fluid block '{0}'")]
+    UnknownFluid(String),
+    #[error("Block '{0}' is not a liquid")]
+    NotLiquid(String),
+}
+
+/// Where generated water goes besides the sea. Distances are in blocks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WaterConfig {
+    /// Liquid block filling the sea, lakes and aquifers
+    pub fluid: String,
+    /// Fill basins above the sea level as lakes
+    pub lakes: bool,
+    /// Spacing of the grid basins are found on
+    pub lake_cell_size: u32,
+    /// Lake grid cells per side of a cached region
+    pub lake_region_cells: u32,
+    /// Cells sampled around each region. Basins reaching past the margin
+    /// are left dry, so every region agrees on the lakes it shares.
+    pub lake_margin_cells: u32,
+    /// Deepest a lake fills its basin
+    pub max_lake_depth: u32,
+    /// Give caves water tables of their own
+    pub aquifers: bool,
+    /// Side of the square areas sharing one aquifer level
+    pub aquifer_cell_size: u32,
+    /// Share of aquifer cells holding water
+    pub aquifer_chance: f64,
+    pub aquifer_min_y: i32,
+    pub aquifer_max_y: i32,
+    /// Rock kept between an aquifer and the surface
+    pub aquifer_seal: u32,
+    /// Lake regions kept in memory
+    pub cached_regions: usize,
+}
+
+impl Default for WaterConfig {
+    fn default() -> Self {
+        Self {
+            fluid: "water".to_string(),
+            lakes: true,
+            lake_cell_size: 8,
+            lake_region_cells: 32,
+            lake_margin_cells: 8,
+            max_lake_depth: 10,
+            aquifers: true,
+            aquifer_cell_size: 48,
+            aquifer_chance: 0.4,
+            aquifer_min_y: 4,
+            aquifer_max_y: 40,
+            aquifer_seal: 8,
+            cached_regions: 64,
+        }
+    }
+}
+
+/// The liquid block named by `name`
+pub fn resolve_fluid(name: &str, registry: &BlockRegistry) -> Result<BlockId, WaterError> {
+    let def = registry
+        .get_by_name(name)
+        .ok_or_else(|| WaterError::UnknownFluid(name.to_string()))?;
+    match def.category {
+        BlockCategory::Liquid => Ok(def.id),
+        _ => Err(WaterError::NotLiquid(name.to_string())),
+    }
+}
+
+/// Lakes and aquifers. Lakes fill the basins of the terrain height by
+/// priority flood on a coarse grid, cached per region like river routing.
+/// Aquifers give each cell of a horizontal grid a seeded water table
+/// that caves below it fill up to.
+#[derive(Debug)]
+pub struct WaterBodies {
+    config: WaterConfig,
+    sea_level: i32,
+    seed: u64,
+    lakes: RwLock<RegionCache<(i64, i64), LakeLevels>>,
+}
+
+impl WaterBodies {
+    pub fn new(config: WaterConfig, sea_level: i32, seed: u64) -> Self {
+        let mut config = config;
+        config.lake_cell_size = config.lake_cell_size.max(1);
+        config.lake_region_cells = config.lake_region_cells.max(1);
+        config.lake_margin_cells = config.lake_margin_cells.max(1);
+        config.aquifer_cell_size = config.aquifer_cell_size.max(1);
+        Self {
+            config,
+            sea_level,
+            seed,
+            lakes: RwLock::new(RegionCache::new()),
+        }
+    }
+
+    pub fn config(&self) -> &WaterConfig {
+        &self.config
+    }
+
+    /// Surface of the lake at a block column. Lakes are found on a coarse
+    /// grid, so a column takes the highest lake among the grid cells around
+    /// it and the shore follows the terrain between grid points. The level
+    /// is returned for shore columns above the water too.
+    pub fn lake_level(&self, x: i64, z: i64, height: &impl Fn(i64, i64) -> i32) -> Option<i32> {
+        if !self.config.lakes {
+            return None;
+        }
+        let cell_size = self.config.lake_cell_size as i64;
+        let cell = (x.div_euclid(cell_size), z.div_euclid(cell_size));
+        let mut level = None;
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                let neighbour = (cell.0 + dx, cell.1 + dz);
+                for region in self.regions_covering(neighbour) {
+                    let lakes = self.lakes(region, height);
+                    level = level.max(lakes[self.sampled_index(region, neighbour)]);
+                }
+            }
+        }
+        level
+    }
+
+    /// Water table of the aquifer under a column, or None where it is dry
+    pub fn aquifer_level(&self, x: i64, z: i64) -> Option<i32> {
+        let cell_size = self.config.aquifer_cell_size as i64;
+        self.aquifer_cell_level((x.div_euclid(cell_size), z.div_euclid(cell_size)))
+    }
+
+    /// Fills carved air in the chunk with `fluid` up to each column's
+    /// aquifer level, keeping `aquifer_seal` blocks below `surface` dry.
+    /// Air along the edge to an aquifer with another level becomes `barrier`.
+    pub fn fill_aquifers(
+        &self,
+        chunk: &mut Chunk,
+        coord: ChunkCoord,
+        fluid: BlockId,
+        barrier: BlockId,
+        surface: impl Fn(i64, i64) -> i32,
+    ) {
+        if !self.config.aquifers {
+            return;
+        }
+        let base = coord.to_block_pos(CHUNK_SIZE as i32);
+        let cell_size = self.config.aquifer_cell_size as i64;
+        let barrier_width = AQUIFER_BARRIER.min(cell_size / 2);
+
+        for x in 0..CHUNK_SIZE as i64 {
+            for z in 0..CHUNK_SIZE as i64 {
+                let (world_x, world_z) = (base.x + x, base.z + z);
+                let cell = (world_x.div_euclid(cell_size), world_z.div_euclid(cell_size));
+                let level = self.aquifer_cell_level(cell);
+
+                // The highest level among neighbouring aquifers this column
+                // borders on, where it differs from its own
+                let (edge_x, edge_z) =
+                    (world_x.rem_euclid(cell_size), world_z.rem_euclid(cell_size));
+                let mut walled = None;
+                for (dx, dz) in NEIGHBOURS {
+                    let near_x = match dx {
+                        -1 => edge_x < barrier_width,
+                        1 => edge_x >= cell_size - barrier_width,
+                        _ => true,
+                    };
+                    let near_z = match dz {
+                        -1 => edge_z < barrier_width,
+                        1 => edge_z >= cell_size - barrier_width,
+                        _ => true,
+                    };
+                    if !(near_x && near_z) {
+                        continue;
+                    }
+                    let other = self.aquifer_cell_level((cell.0 + dx, cell.1 + dz));
+                    if other != level {
+                        walled = walled.max(other.max(level));
+                    }
+                }
+
+                let Some(top) = walled.or(level) else {
+                    continue;
+                };
+                let top = (top as i64)
+                    .min(surface(world_x, world_z) as i64 - self.config.aquifer_seal as i64);
+                let fill = match walled {
+                    Some(_) => barrier,
+                    None => fluid,
+                };
+                for y in 0..(CHUNK_SIZE as i64).min(top - base.y + 1).max(0) {
+                    let (lx, ly, lz) = (x as u32, y as u32, z as u32);
+                    if chunk
+                        .get_block(lx, ly, lz)
+                        .is_some_and(|block| block.id == BlockId::AIR)
+                    {
+                        chunk.set_block(lx, ly, lz, Some(Block::new(fill)));
+                    }
+                }
+            }
+        }
+    }
+
+    fn aquifer_cell_level(&self, cell: (i64, i64)) -> Option<i32> {
+        let config = &self.config;
+        if !config.aquifers || config.aquifer_min_y > config.aquifer_max_y {
+            return None;
+        }
+        let hash = (cell.0 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (cell.1 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ 0xA001_F3E5_u64.wrapping_mul(0x1656_67B1_9E37_79F9);
+        let mut rng = ChaCha12Rng::seed_from_u64(self.seed ^ hash);
+        rng.gen_bool(config.aquifer_chance.clamp(0.0, 1.0))
+            .then(|| rng.gen_range(config.aquifer_min_y..=config.aquifer_max_y))
+    }
+
+    /// Regions whose core or margin holds the lake cell
+    fn regions_covering(&self, cell: (i64, i64)) -> impl Iterator<Item = (i64, i64)> {
+        let core = self.config.lake_region_cells as i64;
+        let margin = self.config.lake_margin_cells as i64;
+        let range = |c: i64| (c - margin).div_euclid(core)..=(c + margin).div_euclid(core);
+        let xs = range(cell.0);
+        range(cell.1).flat_map(move |z| xs.clone().map(move |x| (x, z)))
+    }
+
+    fn sampled_index(&self, region: (i64, i64), cell: (i64, i64)) -> usize {
+        let core = self.config.lake_region_cells as i64;
+        let margin = self.config.lake_margin_cells as i64;
+        let n = core + 2 * margin;
+        let (x, z) = (
+            cell.0 - region.0 * core + margin,
+            cell.1 - region.1 * core + margin,
+        );
+        (z * n + x) as usize
+    }
+
+    fn lakes(&self, region: (i64, i64), height: &impl Fn(i64, i64) -> i32) -> Arc<LakeLevels> {
+        cached(
+            &self.lakes,
+            region,
+            self.config.cached_regions.max(9),
+            || self.find_lakes(region, height),
+        )
+    }
+
+    /// Lake level of every cell the region samples, its core and margin.
+    /// Floods depressions up to their spill height and keeps the basins
+    /// clear of the margin's outer edge whose lowest cell lies in the core,
+    /// so each lake is reported whole by exactly one region.
+    fn find_lakes(&self, region: (i64, i64), height: &impl Fn(i64, i64) -> i32) -> LakeLevels {
+        let core = self.config.lake_region_cells as i64;
+        let margin = self.config.lake_margin_cells as i64;
+        let cell_size = self.config.lake_cell_size as i64;
+        let n = (core + 2 * margin) as usize;
+        let min = (region.0 * core - margin, region.1 * core - margin);
+
+        let heights: Vec<f64> = (0..n * n)
+            .map(|i| {
+                let (x, z) = (min.0 + (i % n) as i64, min.1 + (i / n) as i64);
+                height(x * cell_size + cell_size / 2, z * cell_size + cell_size / 2) as f64
+            })
+            .collect();
+        let neighbours = |i: usize| {
+            let (x, z) = ((i % n) as i64, (i / n) as i64);
+            NEIGHBOURS.iter().filter_map(move |(dx, dz)| {
+                let (nx, nz) = (x + dx, z + dz);
+                (nx >= 0 && nz >= 0 && nx < n as i64 && nz < n as i64)
+                    .then(|| nz as usize * n + nx as usize)
+            })
+        };
+        let on_edge = |i: usize| {
+            let (x, z) = (i % n, i / n);
+            x == 0 || z == 0 || x == n - 1 || z == n - 1
+        };
+        let in_core = |i: usize| {
+            let core = margin as usize..(core + margin) as usize;
+            core.contains(&(i % n)) && core.contains(&(i / n))
+        };
+
+        // Water leaves at the sea and across the outer edge of the margin.
+        // No slope is added, so lakes come out flat.
+        let sea = self.sea_level as f64;
+        let mut filled = heights.clone();
+        let mut visited = vec![false; n * n];
+        let mut queue = BinaryHeap::new();
+        for i in 0..n * n {
+            if heights[i] <= sea || on_edge(i) {
+                visited[i] = true;
+                queue.push(Queued {
+                    height: heights[i],
+                    index: i,
+                });
+            }
+        }
+        while let Some(Queued { height, index }) = queue.pop() {
+            for j in neighbours(index) {
+                if !visited[j] {
+                    visited[j] = true;
+                    filled[j] = filled[j].max(height);
+                    queue.push(Queued {
+                        height: filled[j],
+                        index: j,
+                    });
+                }
+            }
+        }
+
+        // Each flooded basin keeps at most `max_lake_depth` of water, and is
+        // dropped when it reaches the cells next to the edge, where it may
+        // continue beyond what this region sampled
+        let mut levels = vec![None; n * n];
+        let mut seen = vec![false; n * n];
+        for start in 0..n * n {
+            if seen[start] || filled[start] <= heights[start] {
+                continue;
+            }
+            let mut basin = vec![start];
+            let mut open = VecDeque::from([start]);
+            seen[start] = true;
+            while let Some(i) = open.pop_front() {
+                for j in neighbours(i) {
+                    if !seen[j] && filled[j] > heights[j] {
+                        seen[j] = true;
+                        basin.push(j);
+                        open.push_back(j);
+                    }
+                }
+            }
+            if basin.iter().any(|&i| neighbours(i).any(on_edge)) {
+                continue;
+            }
+            // The lowest cell, first in world order on ties, decides which
+            // region owns the basin
+            let lowest = basin
+                .iter()
+                .copied()
+                .min_by(|&a, &b| heights[a].total_cmp(&heights[b]).then(a.cmp(&b)))
+                .unwrap_or(start);
+            if !in_core(lowest) {
+                continue;
+            }
+
+            let bottom = heights[lowest];
+            let cap = bottom + self.config.max_lake_depth as f64;
+            for &i in &basin {
+                let level = filled[i].min(cap);
+                if level > heights[i] && level > sea {
+                    levels[i] = Some(level as i32);
+                }
+            }
+        }
+
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bowl 20 blocks deep around (0, 0) on ground at y = 100, with a
+    /// notch in its rim at y = 94 to the east
+    fn bowl(x: i64, z: i64) -> i32 {
+        let distance = ((x * x + z * z) as f64).sqrt();
+        let rim = if x > 0 && z.abs() < 16 { 94.0 } else { 100.0 };
+        (80.0 + distance * 0.5).min(rim) as i32
+    }
+
+    #[test]
+    fn test_basin_fills_to_its_spill_height() {
+        let water = WaterBodies::new(
+            WaterConfig {
+                max_lake_depth: 40,
+                ..Default::default()
+            },
+            62,
+            1,
+        );
+        assert_eq!(water.lake_level(0, 0, &bowl), Some(94));
+        assert_eq!(water.lake_level(-20, 10, &bowl), Some(94));
+        // Far outside the bowl the ground is dry
+        assert_eq!(water.lake_level(400, 400, &bowl), None);
+    }
+
+    #[test]
+    fn test_lake_depth_is_capped() {
+        let water = WaterBodies::new(WaterConfig::default(), 62, 1);
+        // The bowl bottoms out at 82 on the lake grid
+        assert_eq!(water.lake_level(0, 0, &bowl), Some(92));
+
+        // Nothing is filled below the sea. Regions are cached, so another
+        // terrain needs its own water bodies.
+        let water = WaterBodies::new(WaterConfig::default(), 62, 1);
+        let low = |x: i64, z: i64| bowl(x, z) - 60;
+        assert_eq!(water.lake_level(0, 0, &low), None);
+    }
+
+    #[test]
+    fn test_lakes_agree_across_regions() {
+        // The same bowl centred on a region corner
+        let corner = 32 * 8;
+        let shifted = |x: i64, z: i64| bowl(x - corner, z - corner);
+        let water = WaterBodies::new(WaterConfig::default(), 62, 1);
+        for (x, z) in [(-6, -6), (6, 6), (-6, 6), (6, -6)] {
+            assert_eq!(water.lake_level(corner + x, corner + z, &shifted), Some(92));
+        }
+    }
+
+    #[test]
+    fn test_lakes_are_not_cut_at_region_edges() {
+        // A bowl flooded 40 blocks out from x = 276. It reaches the outer
+        // edge of region 0's margin, but lies whole in region 1.
+        let bowl = |x: i64, z: i64| {
+            let distance = (((x - 276) * (x - 276) + z * z) as f64).sqrt();
+            (80.0 + distance * 0.5).min(100.0) as i32
+        };
+        let water = WaterBodies::new(
+            WaterConfig {
+                max_lake_depth: 40,
+                ..Default::default()
+            },
+            62,
+            1,
+        );
+        assert_eq!(water.lake_level(276, 0, &bowl), Some(100));
+        // Inside region 0, two lake cells away from region 1
+        assert_eq!(water.lake_level(240, 0, &bowl), Some(100));
+    }
+
+    #[test]
+    fn test_aquifers_fill_caves_below_their_level() {
+        let registry = BlockRegistry::default();
+        let water_id = resolve_fluid("water", &registry).unwrap();
+        let stone = registry.get_by_name("stone").unwrap().id;
+        let water = WaterBodies::new(
+            WaterConfig {
+                aquifer_chance: 1.0,
+                ..Default::default()
+            },
+            62,
+            5,
+        );
+
+        let coord = ChunkCoord::new(0, 0, 0);
+        let mut chunk = Chunk::new(coord);
+        for i in 0..32 * 32 * 32 {
+            chunk.set_block(
+                i % 32,
+                (i / 32) % 32,
+                i / 1024,
+                Some(Block::new(BlockId::AIR)),
+            );
+        }
+        water.fill_aquifers(&mut chunk, coord, water_id, stone, |_, _| 100);
+
+        // Away from cell edges, water reaches exactly the aquifer level
+        let level = water.aquifer_level(20, 20).unwrap() as u32;
+        let id = |y: u32| chunk.get_block(20, y, 20).unwrap().id;
+        assert_eq!(id(0), water_id);
+        assert_eq!(id(level.min(31)), water_id);
+        if level < 31 {
+            assert_eq!(id(level + 1), BlockId::AIR);
+        }
+
+        // Nothing is filled within the seal below the surface
+        let mut sealed = Chunk::new(coord);
+        for i in 0..32 * 32 * 32 {
+            sealed.set_block(
+                i % 32,
+                (i / 32) % 32,
+                i / 1024,
+                Some(Block::new(BlockId::AIR)),
+            );
+        }
+        water.fill_aquifers(&mut sealed, coord, water_id, stone, |_, _| 4);
+        assert!((0..32 * 32 * 32).all(|i| {
+            sealed
+                .get_block(i % 32, (i / 32) % 32, i / 1024)
+                .unwrap()
+                .id
+                == BlockId::AIR
+        }));
+    }
+
+    #[test]
+    fn test_fluid_must_be_liquid() {
+        let registry = BlockRegistry::default();
+        assert_eq!(
+            resolve_fluid("lava", &registry),
+            Ok(registry.get_by_name("lava").unwrap().id)
+        );
+        assert_eq!(
+            resolve_fluid("sand", &registry),
+            Err(WaterError::NotLiquid("sand".into()))
+        );
+        assert_eq!(
+            resolve_fluid("oil", &registry),
+            Err(WaterError::UnknownFluid("oil".into()))
+        );
+    }
+}
@@ -0,0 +1,838 @@
+use crate::config::ChunkSysConfig;
+use crate::world::chunk::{Chunk, Frustum, CHUNK_SIZE};
+use crate::world::chunk_coord::ChunkCoord;
+use crate::world::core::World;
+use crate::world::generator::core::{GenerationPipeline, WorldGenerator};
+use crate::world::origin::FloatingOrigin;
+use crate::world::storage::chunk_file;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use glam::{DVec3, Vec3};
+use log::warn;
+use parking_lot::Mutex;
+use rayon::ThreadPool;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Distance multiplier of chunks outside the view, so the ones in view load
+/// first without starving those right behind the player
+const OFF_SCREEN_PENALTY: f64 = 2.0;
+
+/// Where the player is and what they see, for ordering chunk requests
+#[derive(Debug, Clone, Default)]
+pub struct LoadFocus {
+    pub position: DVec3,
+    /// View frustum and the origin its coordinates are relative to. Without
+    /// one every chunk counts as visible.
+    pub view: Option<(Frustum, FloatingOrigin)>,
+}
+
+impl LoadFocus {
+    pub fn is_visible(&self, coord: ChunkCoord) -> bool {
+        self.view.as_ref().is_none_or(|(frustum, origin)| {
+            let min = origin.chunk_to_local(coord);
+            frustum.intersects_aabb(min, min + Vec3::splat(CHUNK_SIZE as f32))
+        })
+    }
+
+    /// Distance from the player to the centre of a chunk
+    pub fn distance(&self, coord: ChunkCoord) -> f64 {
+        self.position.distance(coord.to_world_center(CHUNK_SIZE as i32))
+    }
+
+    /// Priority of a chunk request. Lower loads first.
+    pub fn priority(&self, coord: ChunkCoord) -> f64 {
+        let distance = self.distance(coord);
+        if self.is_visible(coord) {
+            distance
+        } else {
+            distance * OFF_SCREEN_PENALTY
+        }
+    }
+}
+
+/// A chunk generated off the main thread, up to the first stage that needs
+/// its neighbours
+pub struct GeneratedChunk {
+    pub coord: ChunkCoord,
+    pub chunk: Chunk,
+}
+
+/// A queued request. Requests order so that the heap pops the lowest
+/// priority first, then the oldest.
+#[derive(Debug)]
+struct Request {
+    priority: f64,
+    ticket: u64,
+    coord: ChunkCoord,
+}
+
+impl PartialEq for Request {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Request {}
+
+impl PartialOrd for Request {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Request {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .total_cmp(&self.priority)
+            .then(other.ticket.cmp(&self.ticket))
+    }
+}
+
+#[derive(Default)]
+struct LoadQueue {
+    heap: BinaryHeap<Request>,
+    /// Ticket of the live request for each queued or generating chunk. Heap
+    /// entries and results holding any other ticket are stale.
+    wanted: HashMap<ChunkCoord, u64>,
+    next_ticket: u64,
+    /// Unloaded chunks whose save has not finished. Requests for them start
+    /// from these instead of the possibly older file.
+    saving: HashMap<ChunkCoord, Arc<Chunk>>,
+}
+
+impl LoadQueue {
+    fn is_live(&self, coord: ChunkCoord, ticket: u64) -> bool {
+        self.wanted.get(&coord) == Some(&ticket)
+    }
+}
+
+/// Generates requested chunks on a thread pool, most urgent first. Each
+/// chunk runs the stages that only read itself; the main thread takes the
+/// results with [`ChunkLoader::receive`] and runs the stages that need
+/// neighbours once it integrates them. Chunks saved in the save directory
+/// are read back instead of generated from scratch.
+pub struct ChunkLoader {
+    pool: Arc<ThreadPool>,
+    pipeline: Arc<GenerationPipeline>,
+    save_dir: Option<Arc<PathBuf>>,
+    queue: Arc<Mutex<LoadQueue>>,
+    sender: Sender<(u64, GeneratedChunk)>,
+    receiver: Receiver<(u64, GeneratedChunk)>,
+}
+
+impl ChunkLoader {
+    pub fn new(pool: Arc<ThreadPool>, pipeline: GenerationPipeline) -> Self {
+        let (sender, receiver) = unbounded();
+        Self {
+            pool,
+            pipeline: Arc::new(pipeline),
+            save_dir: None,
+            queue: Arc::new(Mutex::new(LoadQueue::default())),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Directory unloaded chunks are saved to and loaded back from
+    pub fn with_save_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.save_dir = Some(Arc::new(dir.into()));
+        self
+    }
+
+    pub fn save_dir(&self) -> Option<&Path> {
+        self.save_dir.as_deref().map(PathBuf::as_path)
+    }
+
+    /// Queues a chunk unless it is already queued or generating. Returns
+    /// whether it was queued.
+    pub fn request(&self, coord: ChunkCoord, priority: f64) -> bool {
+        {
+            let mut queue = self.queue.lock();
+            if queue.wanted.contains_key(&coord) {
+                return false;
+            }
+            let ticket = queue.next_ticket;
+            queue.next_ticket += 1;
+            queue.wanted.insert(coord, ticket);
+            queue.heap.push(Request {
+                priority,
+                ticket,
+                coord,
+            });
+        }
+
+        // One job per request, each taking whichever request is most urgent
+        // when a thread becomes free
+        let queue = self.queue.clone();
+        let pipeline = self.pipeline.clone();
+        let save_dir = self.save_dir.clone();
+        let sender = self.sender.clone();
+        self.pool
+            .spawn(move || generate_next(&queue, &pipeline, save_dir.as_deref(), &sender));
+        true
+    }
+
+    /// Saves an unloaded chunk to the save directory on the pool, if the
+    /// loader has one
+    pub fn save(&self, coord: ChunkCoord, chunk: Arc<Chunk>) {
+        let Some(dir) = self.save_dir.clone() else {
+            return;
+        };
+        self.queue.lock().saving.insert(coord, chunk.clone());
+        let queue = self.queue.clone();
+        self.pool.spawn(move || {
+            if let Err(e) = fs::create_dir_all(&*dir).and_then(|()| chunk.save_world(&dir)) {
+                warn!("Failed to save chunk {:?}: {}", coord, e);
+            }
+            let mut queue = queue.lock();
+            // A later unload of the same chunk may have replaced this one
+            if queue.saving.get(&coord).is_some_and(|saving| Arc::ptr_eq(saving, &chunk)) {
+                queue.saving.remove(&coord);
+            }
+        });
+    }
+
+    /// Whether any unloaded chunk is still being saved
+    pub fn is_saving(&self) -> bool {
+        !self.queue.lock().saving.is_empty()
+    }
+
+    /// Drops a queued or generating chunk. Returns whether it was pending.
+    pub fn cancel(&self, coord: ChunkCoord) -> bool {
+        self.queue.lock().wanted.remove(&coord).is_some()
+    }
+
+    /// Recomputes the priority of every pending chunk, cancelling those
+    /// `priority` returns `None` for
+    pub fn reprioritize(&self, priority: impl Fn(ChunkCoord) -> Option<f64>) {
+        let mut queue = self.queue.lock();
+        let LoadQueue { heap, wanted, .. } = &mut *queue;
+        let priorities: HashMap<ChunkCoord, f64> = wanted
+            .keys()
+            .filter_map(|coord| priority(*coord).map(|priority| (*coord, priority)))
+            .collect();
+        wanted.retain(|coord, _| priorities.contains_key(coord));
+        *heap = heap
+            .drain()
+            .filter(|request| wanted.get(&request.coord) == Some(&request.ticket))
+            .map(|request| Request {
+                priority: priorities[&request.coord],
+                ..request
+            })
+            .collect();
+    }
+
+    /// Whether a chunk is queued, generating or generated but not received
+    pub fn is_pending(&self, coord: ChunkCoord) -> bool {
+        self.queue.lock().wanted.contains_key(&coord)
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.queue.lock().wanted.len()
+    }
+
+    /// Takes up to `max` generated chunks in the order they finished,
+    /// dropping any cancelled since they were requested
+    pub fn receive(&self, max: usize) -> Vec<GeneratedChunk> {
+        let mut chunks = Vec::new();
+        while chunks.len() < max {
+            let Ok((ticket, generated)) = self.receiver.try_recv() else {
+                break;
+            };
+            let mut queue = self.queue.lock();
+            if queue.is_live(generated.coord, ticket) {
+                queue.wanted.remove(&generated.coord);
+                chunks.push(generated);
+            }
+        }
+        chunks
+    }
+}
+
+/// Keeps the chunks around the player loaded. Whenever the player enters
+/// another chunk it requests the missing chunks within the load distance,
+/// nearest and visible first, and cancels requests and unloads chunks beyond
+/// the unload distance. Each frame it adds a bounded number of generated
+/// chunks to the world.
+pub struct ChunkStreamer {
+    loader: ChunkLoader,
+    focus: LoadFocus,
+    /// Chunk the player was in when requests were last reordered
+    focus_chunk: Option<ChunkCoord>,
+    config: ChunkSysConfig,
+}
+
+impl ChunkStreamer {
+    pub fn new(loader: ChunkLoader, config: ChunkSysConfig) -> Self {
+        Self {
+            loader,
+            focus: LoadFocus::default(),
+            focus_chunk: None,
+            config,
+        }
+    }
+
+    pub fn loader(&self) -> &ChunkLoader {
+        &self.loader
+    }
+
+    pub fn focus(&self) -> &LoadFocus {
+        &self.focus
+    }
+
+    /// Moves the focus to the player, then integrates finished chunks.
+    /// Returns how many chunks were added to the world.
+    pub fn update(
+        &mut self,
+        world: &mut World,
+        position: DVec3,
+        view: Option<(Frustum, FloatingOrigin)>,
+    ) -> usize {
+        self.set_focus(world, position, view);
+        self.integrate(world)
+    }
+
+    /// Moves the focus to `position`. Once it is in another chunk, requests
+    /// the missing chunks within the load distance, reorders pending ones
+    /// and drops pending and loaded chunks beyond the unload distance.
+    /// Without a view every direction counts as visible.
+    pub fn set_focus(
+        &mut self,
+        world: &mut World,
+        position: DVec3,
+        view: Option<(Frustum, FloatingOrigin)>,
+    ) {
+        let chunk_size = CHUNK_SIZE as f64;
+        self.focus = LoadFocus { position, view };
+        let center = ChunkCoord::from_world_pos(position, CHUNK_SIZE as i32);
+        if self.focus_chunk == Some(center) {
+            return;
+        }
+        self.focus_chunk = Some(center);
+
+        // Pending chunks the player has moved away from are cancelled
+        let unload_distance = self.config.unload_distance as f64 * chunk_size;
+        let focus = &self.focus;
+        self.loader.reprioritize(|coord| {
+            (focus.distance(coord) <= unload_distance).then(|| focus.priority(coord))
+        });
+        self.unload_where(world, |coord| focus.distance(coord) > unload_distance);
+
+        let load_distance = self.config.load_distance as i64;
+        for dx in -load_distance..=load_distance {
+            for dy in -load_distance..=load_distance {
+                for dz in -load_distance..=load_distance {
+                    if dx * dx + dy * dy + dz * dz > load_distance * load_distance {
+                        continue;
+                    }
+                    let coord = ChunkCoord::new(center.x() + dx, center.y() + dy, center.z() + dz);
+                    if !world.has_chunk(&coord) {
+                        self.loader.request(coord, self.focus.priority(coord));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes every loaded chunk and drops every pending one, saving the
+    /// loaded chunks and waiting feature writes as when they fall out of
+    /// range. Saves finish on the pool, see [`ChunkLoader::is_saving`].
+    pub fn unload_all(&mut self, world: &mut World) {
+        self.loader.reprioritize(|_| None);
+        self.unload_where(world, |_| true);
+        self.focus_chunk = None;
+    }
+
+    /// Removes the loaded chunks `unload` returns true for, saving them on
+    /// the pool if the loader has a save directory. Feature writes waiting
+    /// for those chunks are saved beside them.
+    fn unload_where(&self, world: &mut World, unload: impl Fn(ChunkCoord) -> bool) {
+        for coord in world.chunk_coords() {
+            if !unload(coord) {
+                continue;
+            }
+            if let Some(chunk) = world.remove_chunk(coord) {
+                self.loader.save(coord, chunk);
+            }
+        }
+
+        let evicted = world.take_feature_writes(&unload);
+        let Some(dir) = self.loader.save_dir() else {
+            return;
+        };
+        // A few blocks per chunk, appended where integrating reads them
+        for (coord, writes) in evicted {
+            let saved = fs::create_dir_all(dir)
+                .and_then(|()| chunk_file::append_feature_writes(dir, coord, &writes));
+            if let Err(e) = saved {
+                warn!("Failed to save feature writes for {:?}: {}", coord, e);
+            }
+        }
+    }
+
+    /// Adds generated chunks to the world and runs the stages they make
+    /// possible on their neighbours, at most `max_chunks_per_frame` of both
+    /// together. Stages left over run on the next call. Returns how many
+    /// chunks were added.
+    pub fn integrate(&mut self, world: &mut World) -> usize {
+        let mut budget = self.config.max_chunks_per_frame;
+        budget -= world.run_stages(budget);
+        let mut added = 0;
+        while budget > 0 {
+            let Some(generated) = self.loader.receive(1).pop() else {
+                break;
+            };
+            // The world may have generated the chunk itself meanwhile
+            if world.has_chunk(&generated.coord) {
+                continue;
+            }
+            world.insert_chunk(generated.coord, generated.chunk);
+            if let Some(dir) = self.loader.save_dir() {
+                match chunk_file::take_feature_writes(dir, generated.coord) {
+                    Ok(writes) => world.queue_feature_writes(generated.coord, writes),
+                    Err(e) => warn!("Failed to load feature writes for {:?}: {}", generated.coord, e),
+                }
+            }
+            added += 1;
+            budget -= 1;
+            budget -= world.run_stages(budget);
+        }
+        added
+    }
+}
+
+/// Generates the most urgent live request, if any is left
+fn generate_next(
+    queue: &Mutex<LoadQueue>,
+    pipeline: &GenerationPipeline,
+    save_dir: Option<&PathBuf>,
+    sender: &Sender<(u64, GeneratedChunk)>,
+) {
+    let request = {
+        let mut queue = queue.lock();
+        loop {
+            let Some(request) = queue.heap.pop() else {
+                return;
+            };
+            if queue.is_live(request.coord, request.ticket) {
+                break request;
+            }
+        }
+    };
+
+    let coord = request.coord;
+    // A chunk saved when it was unloaded picks up where it left off
+    let saving = queue.lock().saving.get(&coord).cloned();
+    let mut chunk = match saving {
+        Some(chunk) => Chunk::clone(&chunk),
+        None => save_dir
+            .and_then(|dir| Chunk::load_world(dir, coord).ok())
+            .unwrap_or_else(|| Chunk::new(coord)),
+    };
+    while pipeline
+        .next_stage(chunk.status)
+        .is_some_and(|stage| stage.neighbor_radius() == 0)
+    {
+        // Give up between stages once the chunk is no longer wanted
+        if !queue.lock().is_live(coord, request.ticket) {
+            return;
+        }
+        // Stages without neighbours only write inside their chunk
+        pipeline.advance(&mut chunk, coord);
+    }
+    // The loader may have been dropped meanwhile
+    let _ = sender.send((request.ticket, GeneratedChunk { coord, chunk }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block::Block;
+    use crate::world::block_id::BlockId;
+    use crate::world::blocks_data::BlockRegistry;
+    use crate::world::chunk::ChunkStatus;
+    use crate::world::generator::core::{GenerationStage, StageSpill};
+    use crate::world::generator::features::FeatureWrite;
+    use crate::world::generator::terrain::TerrainGenerator;
+    use glam::{I64Vec3, Mat4};
+    use rayon::ThreadPoolBuilder;
+    use std::time::{Duration, Instant};
+
+    /// Marks block (0, 0, 0) with its status
+    struct MarkStage {
+        status: ChunkStatus,
+        radius: i32,
+    }
+
+    impl GenerationStage for MarkStage {
+        fn name(&self) -> &str {
+            "mark"
+        }
+
+        fn status(&self) -> ChunkStatus {
+            self.status
+        }
+
+        fn neighbor_radius(&self) -> i32 {
+            self.radius
+        }
+
+        fn generate(&self, chunk: &mut Chunk, _coord: ChunkCoord) -> StageSpill {
+            let mark = Block::new(BlockId::new(self.status as u16, 0, 0));
+            chunk.set_block(0, 0, 0, Some(mark));
+            Vec::new()
+        }
+    }
+
+    fn loader() -> ChunkLoader {
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let pipeline = GenerationPipeline::new()
+            .with(MarkStage {
+                status: ChunkStatus::BaseDensity,
+                radius: 0,
+            })
+            .with(MarkStage {
+                status: ChunkStatus::Surface,
+                radius: 0,
+            })
+            .with(MarkStage {
+                status: ChunkStatus::Features,
+                radius: 1,
+            });
+        ChunkLoader::new(Arc::new(pool), pipeline)
+    }
+
+    /// Holds the loader's only thread until the returned sender is dropped
+    fn block_pool(loader: &ChunkLoader) -> Sender<()> {
+        let (release, wait) = crossbeam_channel::bounded::<()>(0);
+        loader.pool.spawn(move || {
+            let _ = wait.recv();
+        });
+        release
+    }
+
+    /// Receives one chunk at a time until nothing is pending
+    fn receive_all(loader: &ChunkLoader) -> Vec<ChunkCoord> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut coords = Vec::new();
+        while loader.pending_count() > 0 {
+            assert!(Instant::now() < deadline, "chunks never arrived");
+            coords.extend(
+                loader
+                    .receive(1)
+                    .into_iter()
+                    .map(|generated| generated.coord),
+            );
+            std::thread::yield_now();
+        }
+        coords
+    }
+
+    fn coord(x: i64) -> ChunkCoord {
+        ChunkCoord::new(x, 0, 0)
+    }
+
+    #[test]
+    fn test_requests_run_neighbor_free_stages_once() {
+        let loader = loader();
+        assert!(loader.request(coord(0), 1.0));
+        assert!(!loader.request(coord(0), 0.0));
+        assert!(loader.is_pending(coord(0)));
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let generated = loop {
+            if let Some(generated) = loader.receive(4).pop() {
+                break generated;
+            }
+            assert!(Instant::now() < deadline, "chunk never arrived");
+            std::thread::yield_now();
+        };
+        assert_eq!(generated.coord, coord(0));
+        // Features read the neighbours, so they are left to the world
+        assert_eq!(generated.chunk.status, ChunkStatus::Surface);
+        assert!(!loader.is_pending(coord(0)));
+        assert!(loader.receive(4).is_empty());
+    }
+
+    #[test]
+    fn test_most_urgent_requests_generate_first() {
+        let loader = loader();
+        let release = block_pool(&loader);
+        loader.request(coord(1), 30.0);
+        loader.request(coord(2), 10.0);
+        loader.request(coord(3), 20.0);
+        // The player turned towards the first chunk
+        loader.reprioritize(|coord| Some(if coord.x() == 1 { 5.0 } else { 50.0 }));
+        drop(release);
+
+        assert_eq!(receive_all(&loader), [coord(1), coord(2), coord(3)]);
+    }
+
+    #[test]
+    fn test_cancelled_requests_are_dropped() {
+        let loader = loader();
+        let release = block_pool(&loader);
+        for x in 0..4 {
+            loader.request(coord(x), x as f64);
+        }
+        assert!(loader.cancel(coord(0)));
+        assert!(!loader.cancel(coord(0)));
+        // The player moved away from the far chunks
+        loader.reprioritize(|coord| (coord.x() < 2).then_some(0.0));
+        assert_eq!(loader.pending_count(), 1);
+        drop(release);
+
+        assert_eq!(receive_all(&loader), [coord(1)]);
+        // A cancelled chunk can be requested again
+        assert!(loader.request(coord(0), 0.0));
+        assert_eq!(receive_all(&loader), [coord(0)]);
+    }
+
+    #[test]
+    fn test_chunks_requested_while_saving_keep_their_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let loader = loader().with_save_dir(dir.path());
+        let mut chunk = Chunk::new(coord(0));
+        chunk.status = ChunkStatus::Surface;
+        let edit = Block::new(BlockId::new(7, 0, 0));
+        chunk.set_block(1, 2, 3, Some(edit.clone()));
+
+        let release = block_pool(&loader);
+        loader.save(coord(0), Arc::new(chunk));
+        assert!(loader.is_saving());
+        loader.request(coord(0), 0.0);
+        drop(release);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let generated = loop {
+            if let Some(generated) = loader.receive(1).pop() {
+                break generated;
+            }
+            assert!(Instant::now() < deadline, "chunk never arrived");
+            std::thread::yield_now();
+        };
+        assert_eq!(generated.chunk.get_block(1, 2, 3).map(|b| b.id), Some(edit.id));
+        while loader.is_saving() {
+            assert!(Instant::now() < deadline, "chunk never saved");
+            std::thread::yield_now();
+        }
+        assert!(dir.path().join("chunk_0_0_0.bin").exists());
+    }
+
+    #[test]
+    fn test_receive_is_bounded() {
+        let loader = loader();
+        for x in 0..5 {
+            loader.request(coord(x), 0.0);
+        }
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while loader.receiver.len() < 5 {
+            assert!(Instant::now() < deadline, "chunks never arrived");
+            std::thread::yield_now();
+        }
+        assert_eq!(loader.receive(2).len(), 2);
+        assert_eq!(loader.pending_count(), 3);
+        assert_eq!(loader.receive(8).len(), 3);
+    }
+
+    #[test]
+    fn test_streamer_loads_chunks_around_the_player() {
+        let terrain = TerrainGenerator::new(Default::default(), Arc::new(BlockRegistry::default()));
+        let mut world = World::new(Arc::new(terrain));
+        let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let loader = ChunkLoader::new(Arc::new(pool), world.pipeline().clone());
+        let config = ChunkSysConfig {
+            load_distance: 1,
+            unload_distance: 2,
+            max_chunks_per_frame: 2,
+            ..Default::default()
+        };
+        let mut streamer = ChunkStreamer::new(loader, config);
+
+        // The centre chunk and its six face neighbours
+        let position = DVec3::splat(16.0);
+        streamer.set_focus(&mut world, position, None);
+        assert_eq!(streamer.loader().pending_count(), 7);
+        assert!(streamer.loader().is_pending(coord(0)));
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let mut loaded = 0;
+        while loaded < 7 {
+            assert!(Instant::now() < deadline, "chunks never arrived");
+            let added = streamer.update(&mut world, position, None);
+            assert!(added <= 2);
+            loaded += added;
+            std::thread::yield_now();
+        }
+        assert!(world.has_chunk(&coord(0)));
+        assert!(world.has_chunk(&ChunkCoord::new(0, -1, 0)));
+        assert_eq!(streamer.loader().pending_count(), 0);
+
+        // Requests made far away are cancelled once the player is back
+        let far = DVec3::new(16.0, 16.0, 32.0 * 100.0);
+        let far_chunk = ChunkCoord::new(0, 0, 100);
+        streamer.set_focus(&mut world, far, None);
+        assert!(streamer.loader().is_pending(far_chunk));
+        streamer.set_focus(&mut world, position, None);
+        assert!(!streamer.loader().is_pending(far_chunk));
+    }
+
+    #[test]
+    fn test_focus_in_the_same_chunk_keeps_requests() {
+        let terrain = TerrainGenerator::new(Default::default(), Arc::new(BlockRegistry::default()));
+        let mut world = World::new(Arc::new(terrain));
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let loader = ChunkLoader::new(Arc::new(pool), world.pipeline().clone());
+        let config = ChunkSysConfig {
+            load_distance: 1,
+            unload_distance: 2,
+            ..Default::default()
+        };
+        let mut streamer = ChunkStreamer::new(loader, config);
+
+        streamer.set_focus(&mut world, DVec3::splat(16.0), None);
+        assert!(streamer.loader().cancel(coord(0)));
+        streamer.set_focus(&mut world, DVec3::splat(20.0), None);
+        assert!(!streamer.loader().is_pending(coord(0)));
+
+        // Entering the next chunk requests it again
+        streamer.set_focus(&mut world, DVec3::new(48.0, 16.0, 16.0), None);
+        assert!(streamer.loader().is_pending(coord(0)));
+    }
+
+    #[test]
+    fn test_far_chunks_are_saved_and_loaded_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let terrain = TerrainGenerator::new(Default::default(), Arc::new(BlockRegistry::default()));
+        let mut world = World::new(Arc::new(terrain));
+        let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let loader =
+            ChunkLoader::new(Arc::new(pool), world.pipeline().clone()).with_save_dir(dir.path());
+        let config = ChunkSysConfig {
+            load_distance: 1,
+            unload_distance: 2,
+            max_chunks_per_frame: 8,
+            ..Default::default()
+        };
+        let mut streamer = ChunkStreamer::new(loader, config);
+
+        let position = DVec3::splat(16.0);
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while !world.has_chunk(&coord(0)) {
+            assert!(Instant::now() < deadline, "chunk never arrived");
+            streamer.update(&mut world, position, None);
+            std::thread::yield_now();
+        }
+        // An edit that generating the chunk again would lose
+        let edited = I64Vec3::new(5, 5, 5);
+        let block = if world.block_id_at(edited) == BlockId::AIR {
+            Some(Block::new(BlockId::new(1, 0, 0)))
+        } else {
+            None
+        };
+        world.set_block_at(edited, block.clone());
+
+        let far = DVec3::new(16.0, 16.0, 32.0 * 100.0);
+        streamer.set_focus(&mut world, far, None);
+        assert!(world.chunk_coords().is_empty());
+        // Chunks are saved on the pool, not while the focus moves
+        while streamer.loader().is_saving() {
+            assert!(Instant::now() < deadline, "chunks never saved");
+            std::thread::yield_now();
+        }
+        assert!(dir.path().join("chunk_0_0_0.bin").exists());
+
+        while !world.has_chunk(&coord(0)) {
+            assert!(Instant::now() < deadline, "chunk never came back");
+            streamer.update(&mut world, position, None);
+            std::thread::yield_now();
+        }
+        assert_eq!(world.block_at(edited).map(|b| b.id), block.map(|b| b.id));
+    }
+
+    #[test]
+    fn test_unload_all_saves_chunks_and_feature_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let terrain = TerrainGenerator::new(Default::default(), Arc::new(BlockRegistry::default()));
+        let mut world = World::new(Arc::new(terrain));
+        let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let loader =
+            ChunkLoader::new(Arc::new(pool), world.pipeline().clone()).with_save_dir(dir.path());
+        let config = ChunkSysConfig {
+            load_distance: 1,
+            unload_distance: 2,
+            max_chunks_per_frame: 8,
+            ..Default::default()
+        };
+        let mut streamer = ChunkStreamer::new(loader, config);
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while !world.has_chunk(&coord(0)) {
+            assert!(Instant::now() < deadline, "chunk never arrived");
+            streamer.update(&mut world, DVec3::splat(16.0), None);
+            std::thread::yield_now();
+        }
+        let far = ChunkCoord::new(40, 0, 0);
+        let write = FeatureWrite {
+            local: glam::IVec3::new(1, 2, 3),
+            block: Block::new(BlockId::new(1, 0, 0)),
+            source: ChunkCoord::new(41, 0, 0),
+        };
+        world.queue_feature_writes(far, vec![write]);
+
+        streamer.unload_all(&mut world);
+        assert!(world.chunk_coords().is_empty());
+        assert_eq!(streamer.loader().pending_count(), 0);
+        while streamer.loader().is_saving() {
+            assert!(Instant::now() < deadline, "chunks never saved");
+            std::thread::yield_now();
+        }
+        assert!(dir.path().join("chunk_0_0_0.bin").exists());
+        let saved = chunk_file::take_feature_writes(dir.path(), far).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].local, glam::IVec3::new(1, 2, 3));
+    }
+
+    #[test]
+    fn test_stages_run_within_budget() {
+        let terrain = TerrainGenerator::new(Default::default(), Arc::new(BlockRegistry::default()));
+        let mut world = World::new(Arc::new(terrain));
+        world.insert_chunk(coord(0), Chunk::new(coord(0)));
+        world.insert_chunk(coord(1), Chunk::new(coord(1)));
+        assert_eq!(
+            world.get_chunk(&coord(0)).unwrap().status,
+            ChunkStatus::Empty
+        );
+
+        assert_eq!(world.run_stages(1), 1);
+        assert!(world.run_stages(usize::MAX) > 1);
+        assert_eq!(world.run_stages(usize::MAX), 0);
+    }
+
+    #[test]
+    fn test_visible_chunks_come_first() {
+        let projection = Mat4::perspective_rh(70f32.to_radians(), 1.0, 0.1, 1000.0);
+        let view = Mat4::look_at_rh(
+            Vec3::new(16.0, 16.0, 16.0),
+            Vec3::new(100.0, 16.0, 16.0),
+            Vec3::Y,
+        );
+        let focus = LoadFocus {
+            position: DVec3::splat(16.0),
+            view: Some((
+                Frustum::from_view_projection(&(projection * view)),
+                FloatingOrigin::default(),
+            )),
+        };
+        let ahead = coord(2);
+        let behind = coord(-2);
+        assert!(focus.is_visible(ahead));
+        assert!(!focus.is_visible(behind));
+        assert_eq!(focus.distance(ahead), focus.distance(behind));
+        assert!(focus.priority(ahead) < focus.priority(behind));
+    }
+}
@@ -5,15 +5,21 @@ pub mod block_flags;
 pub mod block_id;
 pub mod block_material;
 pub mod block_orientation;
+pub mod block_tags;
 pub mod block_tech;
 pub mod block_visual;
 pub mod blocks_data;
 pub mod chunk;
 pub mod chunk_coord;
+pub mod core;
 pub mod generator;
+pub mod geo;
+pub mod loading;
+pub mod origin;
 pub mod pool;
 pub mod spatial;
 pub mod storage;
+pub mod ticks;
 
 // Re-export commonly used types
 pub use block::Block;
@@ -23,16 +29,23 @@ pub use block_flags::BlockFlags;
 pub use block_id::{BlockCategory, BlockData, BlockDefinition, BlockId, BlockRegistry};
 pub use block_material::BlockMaterial;
 pub use block_orientation::BlockOrientation;
+pub use block_tags::{BlockIdSet, TagDefinitions, TagError};
 pub use block_tech::BlockPhysics;
 pub use block_visual::ConnectedDirections;
 pub use blocks_data::BLOCKS;
-pub use chunk::{Chunk, SerializedChunk};
+pub use chunk::{Chunk, ChunkStatus, SerializedChunk};
 pub use chunk_coord::ChunkCoord;
+pub use self::core::World;
 pub use generator::TerrainGenerator;
+pub use geo::{GeoPosition, GeoProjection, ProjectionKind};
+pub use loading::{ChunkLoader, ChunkStreamer, LoadFocus};
+pub use origin::FloatingOrigin;
 pub use pool::ChunkPool;
 pub use spatial::SpatialIndex;
 pub use storage::ChunkStorage;
-use crate::ui::menu::{Difficulty,WorldType };
+pub use ticks::{BlockBehavior, BlockBehaviors, TickScheduler};
+use crate::ui::menu::{Difficulty, WorldType};
+use crate::world::generator::superflat::SuperflatPreset;
 
 use serde::{Deserialize, Serialize};
 /*
@@ -48,6 +61,9 @@ pub enum WorldType {
 pub struct WorldMeta {
     pub name: String,
     pub world_type: WorldType,
+    /// Layers of a superflat world, in the preset's text form
+    #[serde(default)]
+    pub superflat: Option<SuperflatPreset>,
     pub seed: i64,
     pub difficulty: Difficulty,
     pub spawn_point: [f32; 3],
@@ -61,4 +77,4 @@ pub enum Difficulty {
     Normal,
     Hard,
 }
-*/  
\ No newline at end of file
+*/
@@ -0,0 +1,160 @@
+use crate::world::chunk::CHUNK_SIZE;
+use crate::world::chunk_coord::ChunkCoord;
+use glam::{DVec3, I64Vec3, Vec3};
+
+/// Distance from the origin in blocks after which the origin is moved. Well
+/// inside the range where `f32` still resolves 1/1000 of a block.
+pub const REBASE_DISTANCE: f32 = 1024.0;
+
+/// Anchor for camera-relative coordinates. World positions are `f64`/`i64`
+/// and exact anywhere on a planet-sized world; rendering and physics work in
+/// `f32` positions relative to this origin, which follows the camera in
+/// whole-chunk steps so local positions always stay small.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FloatingOrigin {
+    origin: I64Vec3,
+}
+
+impl FloatingOrigin {
+    /// An origin at the chunk corner containing `world`
+    pub fn at(world: DVec3) -> Self {
+        let chunk = ChunkCoord::from_world_pos(world, CHUNK_SIZE as i32);
+        Self {
+            origin: chunk.to_block_pos(CHUNK_SIZE as i32),
+        }
+    }
+
+    /// Block position of the origin, always a chunk corner
+    pub fn origin(&self) -> I64Vec3 {
+        self.origin
+    }
+
+    pub fn to_local(&self, world: DVec3) -> Vec3 {
+        (world - self.origin.as_dvec3()).as_vec3()
+    }
+
+    pub fn to_world(&self, local: Vec3) -> DVec3 {
+        self.origin.as_dvec3() + local.as_dvec3()
+    }
+
+    pub fn block_to_local(&self, block: I64Vec3) -> Vec3 {
+        (block - self.origin).as_vec3()
+    }
+
+    pub fn local_to_block(&self, local: Vec3) -> I64Vec3 {
+        self.origin + local.floor().as_i64vec3()
+    }
+
+    /// Local position of a chunk's minimum corner
+    pub fn chunk_to_local(&self, coord: ChunkCoord) -> Vec3 {
+        self.block_to_local(coord.to_block_pos(CHUNK_SIZE as i32))
+    }
+
+    /// Local position of `world`, first moving the origin to it if it is
+    /// further than [`REBASE_DISTANCE`] away. The distance is checked in
+    /// `f64`, so this also works for jumps across the whole world.
+    pub fn follow(&mut self, world: DVec3) -> Vec3 {
+        let offset = world - self.origin.as_dvec3();
+        if offset.abs().max_element() >= REBASE_DISTANCE as f64 {
+            *self = Self::at(world);
+        }
+        self.to_local(world)
+    }
+
+    /// Moves the origin towards `local` once it is further than
+    /// [`REBASE_DISTANCE`] away. Returns the shift that every local position
+    /// held by the caller must be reduced by. The shift is a whole number of
+    /// chunks, so the subtraction is exact and nothing visibly moves.
+    pub fn recenter(&mut self, local: Vec3) -> Option<Vec3> {
+        if local.abs().max_element() < REBASE_DISTANCE {
+            return None;
+        }
+        let chunks = (local / CHUNK_SIZE as f32).floor().as_i64vec3();
+        let shift = chunks * CHUNK_SIZE as i64;
+        self.origin += shift;
+        Some(shift.as_vec3())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 10,000 km at one block per metre
+    const FAR: f64 = 10_000_000.0;
+
+    #[test]
+    fn test_far_positions_stay_exact() {
+        for sign in [1.0, -1.0] {
+            let world = DVec3::new(sign * FAR + 0.25, 70.5, -sign * FAR + 0.75);
+            let origin = FloatingOrigin::at(world);
+            let local = origin.to_local(world);
+            assert!(local.x.abs() < CHUNK_SIZE as f32);
+            assert_eq!(origin.to_world(local), world);
+            assert_eq!(
+                origin.local_to_block(local),
+                I64Vec3::new(world.x.floor() as i64, 70, world.z.floor() as i64)
+            );
+        }
+    }
+
+    /// Walks in small per-frame steps, returning the world position after each
+    fn walk(start: DVec3, step: Vec3, frames: usize) -> Vec<DVec3> {
+        let mut origin = FloatingOrigin::at(start);
+        let mut position = origin.to_local(start);
+        (0..frames)
+            .map(|_| {
+                position += step;
+                if let Some(shift) = origin.recenter(position) {
+                    position -= shift;
+                }
+                origin.to_world(position)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_movement_is_jitter_free_at_planet_scale() {
+        let step = Vec3::new(0.013, 0.0, -0.007);
+        // ~5 km, long enough to rebase several times
+        let near = walk(DVec3::new(0.0, 64.0, 0.0), step, 400_000);
+
+        for sign in [1.0, -1.0] {
+            let offset = DVec3::new(sign * FAR, 0.0, -sign * FAR);
+            let far = walk(DVec3::new(0.0, 64.0, 0.0) + offset, step, 400_000);
+
+            // Every frame moves by the step, just as it does next to the origin
+            let mut previous = far[0] - step.as_dvec3();
+            for (far, near) in far.iter().zip(&near) {
+                assert!((*far - offset - *near).abs().max_element() < 1e-6);
+                assert!((*far - previous - step.as_dvec3()).abs().max_element() < 1e-3);
+                previous = *far;
+            }
+        }
+    }
+
+    #[test]
+    fn test_recenter_keeps_world_position() {
+        let mut origin = FloatingOrigin::at(DVec3::new(-FAR, 0.0, FAR));
+        let local = Vec3::new(1500.3, -12.5, -2000.8);
+        let before = origin.to_world(local);
+        let shift = origin.recenter(local).unwrap();
+        assert_eq!(origin.to_world(local - shift), before);
+        assert_eq!(origin.origin() % CHUNK_SIZE as i64, I64Vec3::ZERO);
+        assert_eq!(origin.recenter(local - shift), None);
+    }
+
+    #[test]
+    fn test_follow_across_the_world() {
+        let mut origin = FloatingOrigin::default();
+        let world = DVec3::new(FAR + 5.5, 80.0, -FAR - 3.25);
+        let local = origin.follow(world);
+        assert!(local.abs().max_element() < CHUNK_SIZE as f32 * 2.0);
+        assert_eq!(origin.to_world(local), world);
+
+        // Small moves keep the origin where it is
+        let before = origin.origin();
+        origin.follow(world + DVec3::new(100.0, 0.0, 0.0));
+        assert_eq!(origin.origin(), before);
+    }
+}
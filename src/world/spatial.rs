@@ -2,8 +2,9 @@ use crate::{
     config::EngineConfig,
     utils::math::{AABB as MathAABB, Plane as MathPlane, ViewFrustum as MathViewFrustum},
     world::chunk_coord::ChunkCoord,
+    world::origin::FloatingOrigin,
 };
-use glam::{Mat4, Vec3};
+use glam::{DVec3, Mat4, Vec3};
 use parking_lot::RwLock;
 use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
@@ -12,7 +13,7 @@ pub struct SpatialPartition {
     quadtree: QuadTree,
     lod_state: HashMap<ChunkCoord, u32>,
     spatial_index: BTreeMap<ChunkCoord, u32>,
-    last_player_pos: Vec3,
+    last_player_pos: DVec3,
 }
 
 impl SpatialPartition {
@@ -35,12 +36,12 @@ impl SpatialPartition {
             ),
             lod_state: HashMap::new(),
             spatial_index: BTreeMap::new(),
-            last_player_pos: Vec3::ZERO,
+            last_player_pos: DVec3::ZERO,
         }
     }
 
-    fn update(&mut self, player_pos: Vec3, view_frustum: &MathViewFrustum, config: &EngineConfig) {
-        if player_pos.distance(self.last_player_pos) > config.chunk_size as f32 * 0.5 {
+    fn update(&mut self, player_pos: DVec3, view_frustum: &MathViewFrustum, config: &EngineConfig) {
+        if player_pos.distance(self.last_player_pos) > config.chunk_size as f64 * 0.5 {
             self.rebuild_quadtree(player_pos, config);
             self.last_player_pos = player_pos;
         }
@@ -49,7 +50,7 @@ impl SpatialPartition {
         self.update_lod(player_pos, &visible, config);
     }
 
-    fn rebuild_quadtree(&mut self, center: Vec3, config: &EngineConfig) {
+    fn rebuild_quadtree(&mut self, center: DVec3, config: &EngineConfig) {
         self.quadtree = QuadTree::new(
             MathAABB {
                 min: Vec3::new(
@@ -67,7 +68,7 @@ impl SpatialPartition {
         );
         self.spatial_index.clear();
 
-        let radius = config.render_distance as i32;
+        let radius = config.render_distance as i64;
         let center_chunk = ChunkCoord::from_world_pos(center, config.chunk_size as i32);
 
         for x in -radius..=radius {
@@ -81,7 +82,7 @@ impl SpatialPartition {
         }
     }
 
-    fn update_lod(&mut self, center: Vec3, visible: &[ChunkCoord], config: &EngineConfig) {
+    fn update_lod(&mut self, center: DVec3, visible: &[ChunkCoord], config: &EngineConfig) {
         for coord in visible {
             let distance = self.calculate_distance(center, coord, config.chunk_size);
             let lod = self.calculate_lod_level(distance, config);
@@ -89,9 +90,9 @@ impl SpatialPartition {
         }
     }
 
-    fn calculate_distance(&self, pos: Vec3, coord: &ChunkCoord, chunk_size: u32) -> f32 {
+    fn calculate_distance(&self, pos: DVec3, coord: &ChunkCoord, chunk_size: u32) -> f32 {
         let chunk_center = coord.to_world_center(chunk_size as i32);
-        pos.distance(chunk_center)
+        pos.distance(chunk_center) as f32
     }
 
     fn calculate_lod_level(&self, distance: f32, config: &EngineConfig) -> u32 {
@@ -112,11 +113,11 @@ impl SpatialPartition {
         self.spatial_index.keys().cloned().collect()
     }
 
-    fn get_loading_priority(&self, player_pos: Vec3, chunk_size: u32) -> Vec<ChunkCoord> {
+    fn get_loading_priority(&self, player_pos: DVec3, chunk_size: u32) -> Vec<ChunkCoord> {
         let mut chunks: Vec<_> = self.spatial_index.keys().cloned().collect();
         chunks.sort_by_key(|c| {
             let center = c.to_world_center(chunk_size as i32);
-            (player_pos.distance(center) * 1000.0) as u32
+            (player_pos.distance(center) * 1000.0) as u64
         });
         chunks
     }
@@ -370,16 +371,16 @@ impl ViewFrustum {
 
 pub struct SpatialIndex {
     chunks: Vec<ChunkCoord>,
-    center: Vec3,
+    center: DVec3,
     radius: f32,
     chunk_size: u32,
 }
 
 impl SpatialIndex {
-    pub fn new(center: Vec3, radius: f32, chunk_size: u32) -> Self {
+    pub fn new(center: DVec3, radius: f32, chunk_size: u32) -> Self {
         let chunk_size = chunk_size as i32;
         let center_chunk = ChunkCoord::from_world_pos(center, chunk_size);
-        let radius_chunks = (radius / chunk_size as f32).ceil() as i32;
+        let radius_chunks = (radius / chunk_size as f32).ceil() as i64;
 
         let mut chunks = Vec::new();
         for x in -radius_chunks..=radius_chunks {
@@ -392,7 +393,7 @@ impl SpatialIndex {
                     );
                     let chunk_center = coord.to_world_center(chunk_size);
                     let distance = (chunk_center - center).length();
-                    if distance <= radius {
+                    if distance <= radius as f64 {
                         chunks.push(coord);
                     }
                 }
@@ -411,12 +412,17 @@ impl SpatialIndex {
         ((coord.x() as u32) << 16) | (coord.z() as u32)
     }
 
-    pub fn get_chunks_in_frustum(&self, frustum: &ViewFrustum) -> Vec<ChunkCoord> {
+    /// Chunks inside a frustum given relative to the floating origin
+    pub fn get_chunks_in_frustum(
+        &self,
+        frustum: &ViewFrustum,
+        origin: &FloatingOrigin,
+    ) -> Vec<ChunkCoord> {
         self.chunks
             .iter()
             .filter(|c| {
-                let center = c.to_world_center(self.chunk_size as i32);
                 let half_size = self.chunk_size as f32 * 0.5;
+                let center = origin.chunk_to_local(**c) + Vec3::splat(half_size);
                 let aabb = MathAABB {
                     min: center - Vec3::splat(half_size),
                     max: center + Vec3::splat(half_size),
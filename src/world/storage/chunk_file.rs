@@ -0,0 +1,212 @@
+use crate::world::block::{Block, DEFAULT_SUB_RESOLUTION};
+use crate::world::chunk::{ChunkStatus, CompressedChunk, CompressedRegion};
+use crate::world::chunk_coord::ChunkCoord;
+use crate::world::generator::features::FeatureWrite;
+use glam::IVec3;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Leads chunk files written since block IDs were packed as base, variation
+/// and colour, followed by the format version
+const CHUNK_FILE_MAGIC: [u8; 4] = *b"BKCH";
+pub const CHUNK_FORMAT_VERSION: u16 = 2;
+
+/// Chunk files from before the format version. Their IDs were stored raw,
+/// in the layout the `BlockId` accessors have always read, so the regions
+/// are kept as stored. Only IDs built with `BlockId::new` had lost their
+/// base block then.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct LegacyCompressedChunk {
+    pub(crate) coord: ChunkCoord,
+    pub(crate) regions: Vec<CompressedRegion>,
+}
+
+impl From<LegacyCompressedChunk> for CompressedChunk {
+    fn from(legacy: LegacyCompressedChunk) -> Self {
+        Self {
+            coord: legacy.coord,
+            sub_resolution: DEFAULT_SUB_RESOLUTION,
+            // Saved chunks were complete before generation had stages
+            status: unlit_status(ChunkStatus::Full),
+            regions: legacy.regions,
+            light: Vec::new(),
+        }
+    }
+}
+
+/// Version 1 chunk files, written before light was saved
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct CompressedChunkV1 {
+    pub(crate) coord: ChunkCoord,
+    pub(crate) sub_resolution: u8,
+    pub(crate) status: ChunkStatus,
+    pub(crate) regions: Vec<CompressedRegion>,
+}
+
+impl From<CompressedChunkV1> for CompressedChunk {
+    fn from(chunk: CompressedChunkV1) -> Self {
+        Self {
+            coord: chunk.coord,
+            sub_resolution: chunk.sub_resolution,
+            status: unlit_status(chunk.status),
+            regions: chunk.regions,
+            light: Vec::new(),
+        }
+    }
+}
+
+/// Status of a chunk saved without its light, moved back before lighting
+/// so the lighting stage runs on it again
+fn unlit_status(status: ChunkStatus) -> ChunkStatus {
+    status.min(ChunkStatus::Structures)
+}
+
+/// Light values as runs of (value, length)
+pub fn light_runs(light: &[u8]) -> Vec<(u8, u16)> {
+    let mut runs: Vec<(u8, u16)> = Vec::new();
+    for &value in light {
+        match runs.last_mut() {
+            Some((last, length)) if *last == value && *length < u16::MAX => *length += 1,
+            _ => runs.push((value, 1)),
+        }
+    }
+    runs
+}
+
+pub fn light_from_runs(runs: &[(u8, u16)]) -> Vec<u8> {
+    runs.iter()
+        .flat_map(|&(value, length)| std::iter::repeat_n(value, length as usize))
+        .collect()
+}
+
+pub fn write_chunk(mut writer: impl Write, compressed: &CompressedChunk) -> io::Result<()> {
+    writer.write_all(&CHUNK_FILE_MAGIC)?;
+    writer.write_all(&CHUNK_FORMAT_VERSION.to_le_bytes())?;
+    bincode::serialize_into(writer, compressed).map_err(io::Error::other)
+}
+
+/// Reads a chunk file of the current version, or an older one converted
+/// to it
+pub fn read_chunk(mut reader: impl Read) -> io::Result<CompressedChunk> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != CHUNK_FILE_MAGIC {
+        let legacy: LegacyCompressedChunk = bincode::deserialize_from(magic.chain(reader))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        return Ok(legacy.into());
+    }
+
+    let mut version = [0; 2];
+    reader.read_exact(&mut version)?;
+    match u16::from_le_bytes(version) {
+        1 => bincode::deserialize_from::<_, CompressedChunkV1>(reader)
+            .map(CompressedChunk::from)
+            .map_err(io::Error::other),
+        CHUNK_FORMAT_VERSION => bincode::deserialize_from(reader).map_err(io::Error::other),
+        version => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported chunk format version {}", version),
+        )),
+    }
+}
+
+/// Feature write as stored in a world directory
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedFeatureWrite {
+    local: [i32; 3],
+    block: Block,
+    source: ChunkCoord,
+}
+
+fn feature_writes_path(world_dir: &Path, coord: ChunkCoord) -> PathBuf {
+    world_dir.join(format!(
+        "features_{}_{}_{}.bin",
+        coord.x(),
+        coord.y(),
+        coord.z()
+    ))
+}
+
+/// Adds feature writes waiting for a chunk to its file in `world_dir`,
+/// after any saved there before
+pub fn append_feature_writes(
+    world_dir: &Path,
+    coord: ChunkCoord,
+    writes: &[FeatureWrite],
+) -> io::Result<()> {
+    let saved: Vec<_> = writes
+        .iter()
+        .map(|write| SavedFeatureWrite {
+            local: write.local.to_array(),
+            block: write.block.clone(),
+            source: write.source,
+        })
+        .collect();
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(feature_writes_path(world_dir, coord))?;
+    bincode::serialize_into(file, &saved).map_err(io::Error::other)
+}
+
+/// Removes and returns the feature writes saved for a chunk, if any
+pub fn take_feature_writes(world_dir: &Path, coord: ChunkCoord) -> io::Result<Vec<FeatureWrite>> {
+    let path = feature_writes_path(world_dir, coord);
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    // One batch per append
+    let mut reader = io::Cursor::new(bytes.as_slice());
+    let mut writes = Vec::new();
+    while (reader.position() as usize) < bytes.len() {
+        let batch: Vec<SavedFeatureWrite> = bincode::deserialize_from(&mut reader)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        writes.extend(batch.into_iter().map(|write| FeatureWrite {
+            local: IVec3::from_array(write.local),
+            block: write.block,
+            source: write.source,
+        }));
+    }
+    fs::remove_file(path)?;
+    Ok(writes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block_id::BlockId;
+
+    #[test]
+    fn test_feature_writes_are_appended_and_taken_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let coord = ChunkCoord::new(-1, 2, 3);
+        let write = |x, source| FeatureWrite {
+            local: IVec3::new(x, 4, 5),
+            block: Block::new(BlockId::new(16, 0, 0)),
+            source,
+        };
+        append_feature_writes(dir.path(), coord, &[write(1, ChunkCoord::new(0, 2, 3))]).unwrap();
+        append_feature_writes(
+            dir.path(),
+            coord,
+            &[write(2, ChunkCoord::new(-2, 2, 3)), write(3, ChunkCoord::new(-2, 2, 3))],
+        )
+        .unwrap();
+
+        let writes = take_feature_writes(dir.path(), coord).unwrap();
+        let saved: Vec<_> = writes.iter().map(|w| (w.local.x, w.source)).collect();
+        assert_eq!(
+            saved,
+            [
+                (1, ChunkCoord::new(0, 2, 3)),
+                (2, ChunkCoord::new(-2, 2, 3)),
+                (3, ChunkCoord::new(-2, 2, 3))
+            ]
+        );
+        assert!(take_feature_writes(dir.path(), coord).unwrap().is_empty());
+    }
+}
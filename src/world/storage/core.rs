@@ -22,6 +22,7 @@ pub trait ChunkStorage: Send + Sync {
     fn get_chunk_mut(&mut self, coord: ChunkCoord) -> Option<&mut Arc<Chunk>>;
     fn set_chunk(&mut self, coord: ChunkCoord, chunk: Arc<Chunk>);
     fn remove_chunk(&mut self, coord: ChunkCoord);
+    fn chunk_coords(&self) -> Vec<ChunkCoord>;
 }
 
 pub struct MemoryStorage {
@@ -52,6 +53,10 @@ impl ChunkStorage for MemoryStorage {
     fn remove_chunk(&mut self, coord: ChunkCoord) {
         self.chunks.remove(&coord);
     }
+
+    fn chunk_coords(&self) -> Vec<ChunkCoord> {
+        self.chunks.keys().copied().collect()
+    }
 }
 
 #[derive(Serialize, Deserialize)]
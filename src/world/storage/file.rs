@@ -35,4 +35,8 @@ impl ChunkStorage for FileChunkStorage {
     fn remove_chunk(&mut self, coord: ChunkCoord) {
         self.chunks.remove(&coord);
     }
+
+    fn chunk_coords(&self) -> Vec<ChunkCoord> {
+        self.chunks.keys().copied().collect()
+    }
 }
@@ -1,3 +1,4 @@
+pub mod chunk_file;
 pub mod core;
 pub mod file;
 
@@ -0,0 +1,426 @@
+use crate::world::block::Block;
+use crate::world::block_id::BlockId;
+use crate::world::blocks_data::BlockRegistry;
+use crate::world::core::World;
+use glam::I64Vec3;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+/// Game ticks per second of the fixed-timestep world update
+pub const TICKS_PER_SECOND: u32 = 20;
+pub const TICK_INTERVAL: f32 = 1.0 / TICKS_PER_SECOND as f32;
+
+/// Upper bound on neighbour updates processed per tick, so runaway chains
+/// spill into the next tick instead of stalling the frame
+pub const MAX_NEIGHBOR_UPDATES_PER_TICK: usize = 4096;
+
+/// Upper bound on game ticks run in one frame. A slower frame drops the
+/// ticks it is still behind by instead of falling further behind.
+pub const MAX_TICKS_PER_FRAME: u32 = 5;
+
+/// The six face-adjacent offsets, in the fixed order neighbours are notified
+pub const NEIGHBOR_OFFSETS: [I64Vec3; 6] = [
+    I64Vec3::new(0, -1, 0),
+    I64Vec3::new(0, 1, 0),
+    I64Vec3::new(0, 0, -1),
+    I64Vec3::new(0, 0, 1),
+    I64Vec3::new(-1, 0, 0),
+    I64Vec3::new(1, 0, 0),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScheduledTick {
+    pub position: I64Vec3,
+    pub block: BlockId,
+    pub due_tick: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NeighborUpdate {
+    pub position: I64Vec3,
+    pub source: I64Vec3,
+}
+
+/// Per-world queue of scheduled block ticks and pending neighbour updates.
+/// Ticks due on the same game tick run in the order they were scheduled.
+#[derive(Debug, Default)]
+pub struct TickScheduler {
+    current_tick: u64,
+    sequence: u64,
+    queue: BTreeMap<(u64, u64), ScheduledTick>,
+    scheduled: HashSet<(I64Vec3, BlockId)>,
+    neighbor_updates: VecDeque<NeighborUpdate>,
+}
+
+impl TickScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.current_tick
+    }
+
+    /// Schedules a tick for `block` at `position` after `delay` game ticks.
+    /// Returns false if the same block is already scheduled there.
+    pub fn schedule(&mut self, position: I64Vec3, block: BlockId, delay: u32) -> bool {
+        if !self.scheduled.insert((position, block)) {
+            return false;
+        }
+
+        let due_tick = self.current_tick + delay.max(1) as u64;
+        self.queue.insert(
+            (due_tick, self.sequence),
+            ScheduledTick {
+                position,
+                block,
+                due_tick,
+            },
+        );
+        self.sequence += 1;
+        true
+    }
+
+    pub fn is_scheduled(&self, position: I64Vec3, block: BlockId) -> bool {
+        self.scheduled.contains(&(position, block))
+    }
+
+    /// Queues notifications for a changed block itself, so newly placed
+    /// blocks can react, followed by its six neighbours
+    pub fn block_changed(&mut self, source: I64Vec3) {
+        self.neighbor_updates.push_back(NeighborUpdate {
+            position: source,
+            source,
+        });
+        for offset in NEIGHBOR_OFFSETS {
+            self.neighbor_updates.push_back(NeighborUpdate {
+                position: source + offset,
+                source,
+            });
+        }
+    }
+
+    /// Advances the game tick and removes every scheduled tick that is now due
+    pub fn advance(&mut self) -> Vec<ScheduledTick> {
+        self.current_tick += 1;
+
+        let pending = self.queue.split_off(&(self.current_tick + 1, 0));
+        let due = std::mem::replace(&mut self.queue, pending);
+
+        due.into_values()
+            .inspect(|tick| {
+                self.scheduled.remove(&(tick.position, tick.block));
+            })
+            .collect()
+    }
+
+    pub fn pop_neighbor_update(&mut self) -> Option<NeighborUpdate> {
+        self.neighbor_updates.pop_front()
+    }
+
+    pub fn pending_ticks(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn pending_neighbor_updates(&self) -> usize {
+        self.neighbor_updates.len()
+    }
+}
+
+/// Reaction of a block type to world changes
+pub trait BlockBehavior: Send + Sync {
+    /// Called when a face-adjacent block changed
+    fn neighbor_changed(&self, _world: &mut World, _position: I64Vec3, _source: I64Vec3) {}
+
+    /// Called when a tick scheduled for this block becomes due
+    fn scheduled_tick(&self, _world: &mut World, _position: I64Vec3) {}
+}
+
+/// Behaviours registered per `BlockId`. Variations fall back to the
+/// behaviour of their base block.
+#[derive(Default, Clone)]
+pub struct BlockBehaviors {
+    behaviors: HashMap<BlockId, Arc<dyn BlockBehavior>>,
+}
+
+impl BlockBehaviors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the built-in behaviours: blocks tagged `falling` fall and
+    /// blocks tagged `liquids` flow
+    pub fn with_defaults(registry: &BlockRegistry) -> Self {
+        let mut behaviors = Self::new();
+        for (tag, behavior) in [
+            ("falling", Arc::new(FallingBlock) as Arc<dyn BlockBehavior>),
+            ("liquids", Arc::new(FlowingLiquid) as Arc<dyn BlockBehavior>),
+        ] {
+            if let Some(set) = registry.tag(tag) {
+                for id in set.iter() {
+                    behaviors.register(id, behavior.clone());
+                }
+            }
+        }
+        behaviors
+    }
+
+    pub fn register(&mut self, id: BlockId, behavior: Arc<dyn BlockBehavior>) {
+        self.behaviors.insert(id, behavior);
+    }
+
+    pub fn get(&self, id: BlockId) -> Option<Arc<dyn BlockBehavior>> {
+        self.behaviors
+            .get(&id)
+            .or_else(|| self.behaviors.get(&BlockId::new(id.base_id(), 0, 0)))
+            .cloned()
+    }
+}
+
+/// Whether a block may move into `position`. Unloaded chunks never are,
+/// even though they read as air.
+fn is_replaceable(world: &World, position: I64Vec3) -> bool {
+    if !world.is_loaded(position) {
+        return false;
+    }
+    let id = world.block_id_at(position);
+    id == BlockId::AIR || world.block_registry().has_tag(id, "liquids")
+}
+
+/// Gravity-affected blocks such as sand and gravel
+pub struct FallingBlock;
+
+impl FallingBlock {
+    const FALL_DELAY: u32 = 2;
+}
+
+impl BlockBehavior for FallingBlock {
+    fn neighbor_changed(&self, world: &mut World, position: I64Vec3, _source: I64Vec3) {
+        if is_replaceable(world, position - I64Vec3::Y) {
+            let id = world.block_id_at(position);
+            world.schedule_tick(position, id, Self::FALL_DELAY);
+        }
+    }
+
+    fn scheduled_tick(&self, world: &mut World, position: I64Vec3) {
+        let below = position - I64Vec3::Y;
+        if !is_replaceable(world, below) {
+            return;
+        }
+        let block = world.take_block(position);
+        if !world.set_block_at(below, block.clone()) {
+            world.set_block_at(position, block);
+        }
+    }
+}
+
+/// Liquids spread downwards, then sideways. The variation nibble of the
+/// `BlockId` holds the flow level: 0 is a source, higher values are further away.
+/// Liquid definitions reserve that nibble, see `BlockDefinition::reserves_variation`.
+pub struct FlowingLiquid;
+
+impl FlowingLiquid {
+    const FLOW_DELAY: u32 = 5;
+    const MAX_LEVEL: u8 = 7;
+
+    /// Whether liquid can flow into `position`. Unloaded chunks read as air
+    /// but are never open.
+    fn is_open(world: &World, position: I64Vec3) -> bool {
+        world.is_loaded(position) && world.block_id_at(position) == BlockId::AIR
+    }
+
+    /// Whether a source or a lower level still feeds the block. An unloaded
+    /// neighbour might, so the block is kept until that chunk loads.
+    fn is_fed(world: &World, position: I64Vec3, id: BlockId) -> bool {
+        let level = id.variation();
+        if level == 0 {
+            return true;
+        }
+        let above = position + I64Vec3::Y;
+        if !world.is_loaded(above) || world.block_id_at(above).base_id() == id.base_id() {
+            return true;
+        }
+        NEIGHBOR_OFFSETS
+            .iter()
+            .filter(|offset| offset.y == 0)
+            .map(|offset| position + *offset)
+            .any(|side| {
+                if !world.is_loaded(side) {
+                    return true;
+                }
+                let other = world.block_id_at(side);
+                other.base_id() == id.base_id() && other.variation() < level
+            })
+    }
+}
+
+impl BlockBehavior for FlowingLiquid {
+    fn neighbor_changed(&self, world: &mut World, position: I64Vec3, _source: I64Vec3) {
+        let id = world.block_id_at(position);
+        world.schedule_tick(position, id, Self::FLOW_DELAY);
+    }
+
+    fn scheduled_tick(&self, world: &mut World, position: I64Vec3) {
+        let id = world.block_id_at(position);
+        if !Self::is_fed(world, position, id) {
+            world.set_block_at(position, None);
+            return;
+        }
+
+        let below = position - I64Vec3::Y;
+        if Self::is_open(world, below) {
+            let falling = BlockId::new(id.base_id(), 1, id.color_id());
+            world.set_block_at(below, Some(Block::new(falling)));
+            return;
+        }
+
+        let level = id.variation();
+        if level >= Self::MAX_LEVEL {
+            return;
+        }
+        let spread = BlockId::new(id.base_id(), level + 1, id.color_id());
+        for offset in NEIGHBOR_OFFSETS.iter().filter(|offset| offset.y == 0) {
+            let side = position + *offset;
+            if Self::is_open(world, side) {
+                world.set_block_at(side, Some(Block::new(spread)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::Chunk;
+    use crate::world::chunk_coord::ChunkCoord;
+    use crate::world::generator::terrain::TerrainGenerator;
+
+    #[test]
+    fn test_ticks_run_in_due_then_schedule_order() {
+        let mut scheduler = TickScheduler::new();
+        let a = I64Vec3::new(0, 0, 0);
+        let b = I64Vec3::new(1, 0, 0);
+        let c = I64Vec3::new(2, 0, 0);
+
+        scheduler.schedule(b, BlockId(1), 2);
+        scheduler.schedule(a, BlockId(1), 1);
+        scheduler.schedule(c, BlockId(1), 2);
+        assert!(!scheduler.schedule(c, BlockId(1), 3));
+
+        let first: Vec<_> = scheduler.advance().iter().map(|t| t.position).collect();
+        assert_eq!(first, vec![a]);
+
+        let second: Vec<_> = scheduler.advance().iter().map(|t| t.position).collect();
+        assert_eq!(second, vec![b, c]);
+        assert_eq!(scheduler.pending_ticks(), 0);
+
+        // Once run, the same block can be scheduled again
+        assert!(scheduler.schedule(c, BlockId(1), 1));
+    }
+
+    fn single_chunk_world() -> World {
+        let mut world = World::new(Arc::new(TerrainGenerator::new(
+            Default::default(),
+            Arc::new(BlockRegistry::default()),
+        )));
+        let coord = ChunkCoord::new(0, 0, 0);
+        world.set_chunk(coord, Chunk::new(coord));
+        world
+    }
+
+    #[test]
+    fn test_falling_block_waits_for_unloaded_chunk() {
+        let mut world = single_chunk_world();
+        let sand = world.block_registry().get_by_name("sand").unwrap().id;
+
+        // Bottom layer of the chunk, above the unloaded chunk (0, -1, 0)
+        let position = I64Vec3::new(3, 0, 3);
+        world.set_block_at(position, Some(Block::new(sand)));
+        for _ in 0..5 {
+            world.tick();
+        }
+        assert_eq!(world.block_id_at(position), sand);
+
+        let below = ChunkCoord::new(0, -1, 0);
+        world.set_chunk(below, Chunk::new(below));
+        world.schedule_tick(position, sand, 1);
+        for _ in 0..2 {
+            world.tick();
+        }
+        assert_eq!(world.block_id_at(position), BlockId::AIR);
+        assert_eq!(world.block_id_at(position - I64Vec3::Y), sand);
+    }
+
+    #[test]
+    fn test_liquid_flows_down() {
+        let mut world = single_chunk_world();
+        let water = world.block_registry().get_by_name("water").unwrap().id;
+
+        let source = I64Vec3::new(16, 10, 16);
+        world.set_block_at(source, Some(Block::new(water)));
+        for _ in 0..10 {
+            world.tick();
+        }
+        let below = world.block_id_at(source - I64Vec3::Y);
+        assert_eq!(below.base_id(), water.base_id());
+        assert_eq!(below.variation(), 1);
+    }
+
+    #[test]
+    fn test_liquid_decays_sideways_and_dries_up() {
+        let mut world = single_chunk_world();
+        let water = world.block_registry().get_by_name("water").unwrap().id;
+
+        // On the bottom layer, so it spreads instead of into the unloaded chunk below
+        let source = I64Vec3::new(16, 0, 16);
+        world.set_block_at(source, Some(Block::new(water)));
+        for _ in 0..200 {
+            world.tick();
+        }
+        for distance in 1..=FlowingLiquid::MAX_LEVEL {
+            let id = world.block_id_at(source + I64Vec3::new(distance as i64, 0, 0));
+            assert_eq!(id.base_id(), water.base_id());
+            assert_eq!(id.variation(), distance);
+        }
+        let beyond = source + I64Vec3::new(FlowingLiquid::MAX_LEVEL as i64 + 1, 0, 0);
+        assert_eq!(world.block_id_at(beyond), BlockId::AIR);
+
+        world.set_block_at(source, None);
+        for _ in 0..400 {
+            world.tick();
+        }
+        for distance in 1..=FlowingLiquid::MAX_LEVEL {
+            let side = source + I64Vec3::new(distance as i64, 0, 0);
+            assert_eq!(world.block_id_at(side), BlockId::AIR);
+        }
+    }
+
+    #[test]
+    fn test_liquid_next_to_unloaded_chunk_is_kept() {
+        let mut world = single_chunk_world();
+        let water = world.block_registry().get_by_name("water").unwrap().id;
+
+        // Nothing loaded feeds it, but the unloaded chunk at x < 0 might
+        let edge = I64Vec3::new(0, 0, 16);
+        let flowing = BlockId::new(water.base_id(), 3, water.color_id());
+        world.set_block_at(edge, Some(Block::new(flowing)));
+        for _ in 0..20 {
+            world.tick();
+        }
+        assert_eq!(world.block_id_at(edge), flowing);
+        assert_eq!(world.block_id_at(edge + I64Vec3::X).variation(), 4);
+    }
+
+    #[test]
+    fn test_neighbor_updates_are_fifo() {
+        let mut scheduler = TickScheduler::new();
+        scheduler.block_changed(I64Vec3::ZERO);
+
+        let own = scheduler.pop_neighbor_update().unwrap();
+        assert_eq!(own.position, I64Vec3::ZERO);
+        let below = scheduler.pop_neighbor_update().unwrap();
+        assert_eq!(below.position, I64Vec3::new(0, -1, 0));
+        assert_eq!(below.source, I64Vec3::ZERO);
+        assert_eq!(scheduler.pending_neighbor_updates(), 5);
+    }
+}